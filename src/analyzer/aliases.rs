@@ -0,0 +1,302 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{
+    parser::{ASTNode, AsmOperand, Expression, Node, Program, Type},
+    BuildError, BuildProblem, CompileError,
+};
+
+struct Aliases {
+    definitions: HashMap<String, (Type, PathBuf, usize)>,
+    resolved: HashMap<String, Type>,
+    visiting: Vec<String>,
+}
+impl Aliases {
+    fn alias(&mut self, name: &String) -> Result<Type, CompileError> {
+        match self.resolved.get(name) {
+            Some(t) => return Ok(t.clone()),
+            None => {}
+        }
+        let (mut aliased, path, line) = self.definitions.get(name).unwrap().clone();
+        if self.visiting.contains(name) {
+            let mut cycle = self.visiting.clone();
+            cycle.push(name.clone());
+            return Err(CompileError::BuildProblem(BuildProblem::new(
+                BuildError::CyclicTypeAlias(cycle),
+                path,
+                line,
+            )));
+        }
+
+        self.visiting.push(name.clone());
+        match self.expand(&mut aliased) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+        self.visiting.pop();
+
+        self.resolved.insert(name.clone(), aliased.clone());
+        return Ok(aliased);
+    }
+
+    /// Replaces every alias mentioned by `t` with the type it stands for.
+    fn expand(&mut self, t: &mut Type) -> Result<(), CompileError> {
+        match t {
+            Type::Custom(name) if self.definitions.contains_key(name) => {
+                *t = match self.alias(&name.clone()) {
+                    Ok(aliased) => aliased,
+                    Err(error) => return Err(error),
+                };
+            }
+            Type::Tuple(types) | Type::Generic(_, types) => {
+                for t in types {
+                    match self.expand(t) {
+                        Ok(()) => {}
+                        Err(error) => return Err(error),
+                    }
+                }
+            }
+            Type::Reference(_, inner) | Type::Pointer(_, inner) => return self.expand(inner),
+            Type::Function(parameters, return_type) => {
+                for t in parameters {
+                    match self.expand(t) {
+                        Ok(()) => {}
+                        Err(error) => return Err(error),
+                    }
+                }
+                match return_type {
+                    Some(t) => return self.expand(t),
+                    None => {}
+                }
+            }
+            Type::Base(_) | Type::Custom(_) | Type::Dyn(_) => {}
+        }
+        return Ok(());
+    }
+
+    fn expand_option(&mut self, t: &mut Option<Type>) -> Result<(), CompileError> {
+        return match t {
+            Some(t) => self.expand(t),
+            None => Ok(()),
+        };
+    }
+
+    fn nodes(&mut self, nodes: &mut Vec<ASTNode>) -> Result<(), CompileError> {
+        for ast_node in nodes {
+            let result = match &mut ast_node.node {
+                Node::Function {
+                    parameters,
+                    defaults,
+                    return_type,
+                    body,
+                    ..
+                } => {
+                    for (_, t) in parameters {
+                        match self.expand(t) {
+                            Ok(()) => {}
+                            Err(error) => return Err(error),
+                        }
+                    }
+                    for default in defaults.iter_mut().flatten() {
+                        match self.expression(default) {
+                            Ok(()) => {}
+                            Err(error) => return Err(error),
+                        }
+                    }
+                    match self.expand_option(return_type) {
+                        Ok(()) => match body {
+                            Some(body) => self.nodes(body),
+                            None => Ok(()),
+                        },
+                        Err(error) => Err(error),
+                    }
+                }
+                Node::Struct { body, .. } => {
+                    for (_, _, t) in body {
+                        match self.expand(t) {
+                            Ok(()) => {}
+                            Err(error) => return Err(error),
+                        }
+                    }
+                    Ok(())
+                }
+                Node::Enum { body, .. } => {
+                    for (_, types) in body {
+                        for t in types {
+                            match self.expand(t) {
+                                Ok(()) => {}
+                                Err(error) => return Err(error),
+                            }
+                        }
+                    }
+                    Ok(())
+                }
+                Node::Trait { body, .. } => self.nodes(body),
+                Node::Impl { target, body, .. } => match self.expand(target) {
+                    Ok(()) => self.nodes(body),
+                    Err(error) => Err(error),
+                },
+                Node::Constant {
+                    constant_type: item_type,
+                    expression,
+                    ..
+                }
+                | Node::Static {
+                    static_type: item_type,
+                    expression,
+                    ..
+                } => match self.expand(item_type) {
+                    Ok(()) => self.expression(expression),
+                    Err(error) => Err(error),
+                },
+                Node::TypeAlias { aliased, .. } => self.expand(aliased),
+                Node::Extern { body, .. } => self.nodes(body),
+                Node::ForeignFunction {
+                    parameters,
+                    return_type,
+                    ..
+                } => {
+                    for (_, t) in parameters {
+                        match self.expand(t) {
+                            Ok(()) => {}
+                            Err(error) => return Err(error),
+                        }
+                    }
+                    self.expand_option(return_type)
+                }
+                Node::DefineVariable {
+                    var_type,
+                    expression,
+                    ..
+                } => match self.expand_option(var_type) {
+                    Ok(()) => match expression {
+                        Some(expression) => self.expression(expression),
+                        None => Ok(()),
+                    },
+                    Err(error) => Err(error),
+                },
+                Node::SetVariable(_, expression) | Node::Return(Some(expression)) => {
+                    self.expression(expression)
+                }
+                Node::Call(_, arguments) | Node::MethodCall(_, _, arguments) => {
+                    self.expressions(arguments)
+                }
+                Node::InlineAsm { operands, .. } => {
+                    for operand in operands {
+                        match operand {
+                            AsmOperand::In(_, expression) => match self.expression(expression) {
+                                Ok(()) => {}
+                                Err(error) => return Err(error),
+                            },
+                            _ => {}
+                        }
+                    }
+                    Ok(())
+                }
+                Node::Scope { body, .. } | Node::Loop { body } => self.nodes(body),
+                _ => Ok(()),
+            };
+            match result {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        return Ok(());
+    }
+
+    fn expressions(&mut self, expressions: &mut Vec<Expression>) -> Result<(), CompileError> {
+        for expression in expressions {
+            match self.expression(expression) {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        return Ok(());
+    }
+
+    fn expression(&mut self, expression: &mut Expression) -> Result<(), CompileError> {
+        return match expression {
+            Expression::Value(_) | Expression::GetVariable(_) => Ok(()),
+            Expression::Call(_, arguments) => self.expressions(arguments),
+            Expression::BinaryOperation(a, _, b) => match self.expression(a) {
+                Ok(()) => self.expression(b),
+                Err(error) => Err(error),
+            },
+            Expression::Reference(_, inner)
+            | Expression::Field(inner, _)
+            | Expression::Try(inner)
+            | Expression::NamedArgument(_, inner) => self.expression(inner),
+            Expression::Cast(inner, t) => match self.expand(t) {
+                Ok(()) => self.expression(inner),
+                Err(error) => Err(error),
+            },
+            Expression::MethodCall(receiver, _, arguments) => match self.expression(receiver) {
+                Ok(()) => self.expressions(arguments),
+                Err(error) => Err(error),
+            },
+            Expression::Closure {
+                parameters,
+                return_type,
+                body,
+                ..
+            } => {
+                for (_, t) in parameters {
+                    match self.expand_option(t) {
+                        Ok(()) => {}
+                        Err(error) => return Err(error),
+                    }
+                }
+                match self.expand_option(return_type) {
+                    Ok(()) => self.nodes(body),
+                    Err(error) => Err(error),
+                }
+            }
+        };
+    }
+}
+
+/// Replaces every use of a `type` alias with the aliased type, so the rest of
+/// the compiler never sees alias names.
+pub fn resolve_aliases(program: &mut Program) -> Result<(), CompileError> {
+    let mut aliases = Aliases {
+        definitions: HashMap::new(),
+        resolved: HashMap::new(),
+        visiting: Vec::new(),
+    };
+    let mut types: Vec<String> = Vec::new();
+
+    for (_, module) in &program.modules {
+        for ast_node in &module.body {
+            match &ast_node.node {
+                Node::Struct { name, .. } | Node::Enum { name, .. } => types.push(name.clone()),
+                _ => {}
+            }
+        }
+    }
+    for (_, module) in &program.modules {
+        let path = &module.relative_path;
+        for ast_node in &module.body {
+            let (name, aliased) = match &ast_node.node {
+                Node::TypeAlias { name, aliased, .. } => (name, aliased),
+                _ => continue,
+            };
+            if types.contains(name) || aliases.definitions.contains_key(name) {
+                return Err(CompileError::BuildProblem(BuildProblem::new(
+                    BuildError::AlreadyDefined(name.clone()),
+                    path.clone(),
+                    ast_node.line,
+                )));
+            }
+            aliases
+                .definitions
+                .insert(name.clone(), (aliased.clone(), path.clone(), ast_node.line));
+        }
+    }
+
+    for (_, module) in &mut program.modules {
+        match aliases.nodes(&mut module.body) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+    }
+    return Ok(());
+}
@@ -1,40 +1,173 @@
+use std::path::PathBuf;
+
 use crate::{
-    parser::{ASTNode, Node, Program},
-    CompileError,
+    parser::{ASTNode, BaseType, Node, Program, Type},
+    BuildError, BuildProblem, CompileError,
+};
+
+use super::{
+    closures::check_closures,
+    constants::{check_static_access, evaluate_constants, Constants},
+    names::Symbols,
+    traits::{collect_traits, Traits},
+    typed::TypedFunction,
+    types::check_types,
 };
 
+#[derive(Debug)]
+pub struct Analysis {
+    pub traits: Traits,
+    pub constants: Constants,
+    /// Libraries named by `#[link(name = "...")]`, passed on to the linker.
+    pub libraries: Vec<String>,
+    pub symbols: Symbols,
+    /// Every function body with the types of its expressions.
+    pub functions: Vec<TypedFunction>,
+}
 
-pub fn analyze(program: Program) -> Result<(), CompileError> {
+pub fn analyze(program: &mut Program, symbols: Symbols) -> Result<Analysis, CompileError> {
     // println!("{:#?}", program);
 
-    for (path, mut module) in program.modules {
-        match parse_root(&mut module.body) {
+    for (_, module) in &program.modules {
+        let path = &module.relative_path;
+        match parse_root(path, &module.body) {
             Ok(_) => {},
-            Err(error) => return Err(error) 
+            Err(error) => return Err(error)
+        };
+        match check_closures(path, &module.body) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        };
+    }
+
+    let traits = match collect_traits(program) {
+        Ok(traits) => traits,
+        Err(error) => return Err(error),
+    };
+
+    let constants = match evaluate_constants(program) {
+        Ok(constants) => constants,
+        Err(error) => return Err(error),
+    };
+    for (_, module) in &program.modules {
+        let path = &module.relative_path;
+        match check_static_access(&constants, path, &module.body) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
         };
     }
 
-    todo!()
+    let functions = match check_types(program, &symbols, &traits) {
+        Ok(functions) => functions,
+        Err(error) => return Err(error),
+    };
+
+    let mut libraries = Vec::new();
+    for (_, module) in &program.modules {
+        for ast_node in &module.body {
+            for attribute in &ast_node.attributes {
+                let name = match &attribute.arguments {
+                    Some(arguments) if attribute.name == "link" => &arguments[0].value,
+                    _ => continue,
+                };
+                match name {
+                    Some(name) if !libraries.contains(name) => libraries.push(name.clone()),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    return Ok(Analysis {
+        traits,
+        constants,
+        libraries,
+        symbols,
+        functions,
+    });
 }
 
-pub fn parse_root(nodes: &mut Vec<ASTNode>) -> Result<(), CompileError> {
+pub fn parse_root(relative_path: &PathBuf, nodes: &Vec<ASTNode>) -> Result<(), CompileError> {
     for ast_node in nodes {
         let node = &ast_node.node;
         match node {
-            Node::Function {
-                export,
-                is_unsafe,
+            Node::Function { name, body, .. } => {
+                if body.is_none() {
+                    return Err(CompileError::BuildProblem(BuildProblem::new(
+                        BuildError::MissingFunctionBody(name.clone()),
+                        relative_path.clone(),
+                        ast_node.line,
+                    )));
+                }
+            }
+            Node::Import(_, _) => continue,
+            Node::Struct { .. } | Node::Enum { .. } => continue,
+            Node::Trait { .. } | Node::Impl { .. } => continue,
+            Node::Constant { .. } | Node::Static { .. } => continue,
+            Node::TypeAlias { .. } => continue,
+            Node::Extern { abi, body } => {
+                if abi != "C" {
+                    return Err(CompileError::BuildProblem(BuildProblem::new(
+                        BuildError::UnsupportedAbi(abi.clone()),
+                        relative_path.clone(),
+                        ast_node.line,
+                    )));
+                }
+                match check_foreign_functions(relative_path, body) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            _ => {
+                return Err(CompileError::BuildProblem(BuildProblem::new(
+                    BuildError::StatementOutsideFunction,
+                    relative_path.clone(),
+                    ast_node.line,
+                )))
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+/// Only scalars and pointers cross the C boundary, anything bigger would need
+/// to be passed by hidden reference. That includes 128-bit integers.
+fn is_ffi_safe(t: &Type) -> bool {
+    return match t {
+        Type::Base(BaseType::Str | BaseType::Char) => false,
+        Type::Base(base) => !base.is_wide(),
+        Type::Pointer(_, _) => true,
+        Type::Reference(_, _) => t.trait_object().is_none() && !t.is_str_slice(),
+        _ => false,
+    };
+}
+
+fn check_foreign_functions(relative_path: &PathBuf, nodes: &Vec<ASTNode>) -> Result<(), CompileError> {
+    for ast_node in nodes {
+        let (name, parameters, return_type) = match &ast_node.node {
+            Node::ForeignFunction {
                 name,
                 parameters,
                 return_type,
-                body,
-            } => {
-                
+                ..
+            } => (name, parameters, return_type),
+            _ => continue,
+        };
+        let mut types: Vec<&Type> = parameters.iter().map(|(_, t)| t).collect();
+        match return_type {
+            Some(t) => types.push(t),
+            None => {}
+        }
+        for t in types {
+            if !is_ffi_safe(t) {
+                return Err(CompileError::BuildProblem(BuildProblem::new(
+                    BuildError::NotFfiSafe(t.to_string(), name.clone()),
+                    relative_path.clone(),
+                    ast_node.line,
+                )));
             }
-            Node::Import(_, _) => continue,
-            _ => panic!("Expected function"), // _ => Err(CompileError::BuildProblem(BuildProblem::new(BuildError::, relative_path, line)))
         }
     }
-
     return Ok(());
 }
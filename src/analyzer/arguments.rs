@@ -0,0 +1,347 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{
+    parser::{ASTNode, AsmOperand, DefId, Expression, Node, Path, Program, Type},
+    BuildError, BuildProblem, CompileError,
+};
+
+use super::{closures::mentions, names::display};
+
+/// The parameters of a function that calls can name or leave out.
+struct Parameters {
+    names: Vec<String>,
+    defaults: Vec<Option<Expression>>,
+}
+
+struct Resolver {
+    /// Functions and inherent methods by definition.
+    functions: HashMap<DefId, Parameters>,
+}
+impl Resolver {
+    /// Finds the function a call goes to by what its path resolved to. Calls
+    /// of locals holding functions take all of their arguments, as the type of
+    /// a function value has no defaults.
+    fn find(&self, path: &Path) -> Option<(String, &Parameters)> {
+        return match path.definition.and_then(|id| self.functions.get(&id)) {
+            Some(parameters) => Some((display(path), parameters)),
+            None => None,
+        };
+    }
+
+    /// Orders the arguments of a call to `path` like the parameters of the
+    /// function, filling in defaults for the ones left out.
+    fn call(&mut self, path: &Path, arguments: &mut Vec<Expression>) -> Result<(), BuildError> {
+        let (name, parameters) = match self.find(path) {
+            Some(found) => found,
+            None => match arguments.iter().any(is_named) {
+                true => return Err(BuildError::NamedArgumentsUnsupported),
+                false => return self.expressions(arguments),
+            },
+        };
+
+        let count = arguments.len();
+        let mut slots: Vec<Option<Expression>> = vec![None; parameters.names.len()];
+        let mut named = false;
+        for (index, argument) in arguments.drain(..).enumerate() {
+            match argument {
+                Expression::NamedArgument(argument, value) => {
+                    named = true;
+                    let position = match parameters.names.iter().position(|name| name == &argument) {
+                        Some(position) => position,
+                        None => return Err(BuildError::UnknownArgument(name, argument)),
+                    };
+                    if slots[position].is_some() {
+                        return Err(BuildError::DuplicateArgument(argument));
+                    }
+                    slots[position] = Some(*value);
+                }
+                argument => {
+                    if named {
+                        return Err(BuildError::PositionalAfterNamed);
+                    }
+                    if index >= slots.len() {
+                        return Err(BuildError::TooManyArguments(name, slots.len(), count));
+                    }
+                    slots[index] = Some(argument);
+                }
+            }
+        }
+
+        for (position, slot) in slots.into_iter().enumerate() {
+            arguments.push(match (slot, &parameters.defaults[position]) {
+                (Some(argument), _) => argument,
+                (None, Some(default)) => default.clone(),
+                (None, None) => {
+                    return Err(BuildError::MissingArgument(
+                        name,
+                        parameters.names[position].clone(),
+                    ))
+                }
+            });
+        }
+        return self.expressions(arguments);
+    }
+
+    fn expressions(&mut self, expressions: &mut Vec<Expression>) -> Result<(), BuildError> {
+        for expression in expressions {
+            match self.expression(expression) {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        return Ok(());
+    }
+
+    fn expression(&mut self, expression: &mut Expression) -> Result<(), BuildError> {
+        return match expression {
+            Expression::Value(_) | Expression::GetVariable(_) => Ok(()),
+            Expression::Call(path, arguments) => self.call(path, arguments),
+            Expression::MethodCall(receiver, _, arguments) => {
+                if arguments.iter().any(is_named) {
+                    return Err(BuildError::NamedArgumentsUnsupported);
+                }
+                match self.expression(receiver) {
+                    Ok(()) => self.expressions(arguments),
+                    Err(error) => Err(error),
+                }
+            }
+            Expression::BinaryOperation(a, _, b) => match self.expression(a) {
+                Ok(()) => self.expression(b),
+                Err(error) => Err(error),
+            },
+            Expression::Reference(_, inner)
+            | Expression::Field(inner, _)
+            | Expression::Cast(inner, _)
+            | Expression::Try(inner) => self.expression(inner),
+            Expression::NamedArgument(_, _) => Err(BuildError::NamedArgumentsUnsupported),
+            Expression::Closure { body, .. } => match self.nodes(&PathBuf::new(), body) {
+                Ok(()) => Ok(()),
+                Err(CompileError::BuildProblem(problem)) => Err(problem.error),
+                Err(_) => Ok(()),
+            },
+        };
+    }
+
+    fn nodes(&mut self, relative_path: &PathBuf, nodes: &mut Vec<ASTNode>) -> Result<(), CompileError> {
+        for ast_node in nodes {
+            let result = match &mut ast_node.node {
+                Node::Function { body: Some(body), .. } => match self.nodes(relative_path, body) {
+                    Ok(()) => Ok(()),
+                    Err(error) => return Err(error),
+                },
+                Node::Trait { body, .. } | Node::Impl { body, .. } => {
+                    match self.nodes(relative_path, body) {
+                        Ok(()) => Ok(()),
+                        Err(error) => return Err(error),
+                    }
+                }
+                Node::DefineVariable {
+                    expression: Some(expression),
+                    ..
+                } => self.expression(expression),
+                Node::SetVariable(_, expression) | Node::Return(Some(expression)) => {
+                    self.expression(expression)
+                }
+                Node::Call(path, arguments) => self.call(path, arguments),
+                Node::InlineAsm { operands, .. } => {
+                    for operand in operands {
+                        match operand {
+                            AsmOperand::In(_, expression) => match self.expression(expression) {
+                                Ok(()) => {}
+                                Err(error) => {
+                                    return Err(CompileError::BuildProblem(BuildProblem::new(
+                                        error,
+                                        relative_path.clone(),
+                                        ast_node.line,
+                                    )))
+                                }
+                            },
+                            _ => {}
+                        }
+                    }
+                    Ok(())
+                }
+                Node::MethodCall(_, _, arguments) => match arguments.iter().any(is_named) {
+                    true => Err(BuildError::NamedArgumentsUnsupported),
+                    false => self.expressions(arguments),
+                },
+                Node::Scope { body, .. } | Node::Loop { body } => match self.nodes(relative_path, body) {
+                    Ok(()) => Ok(()),
+                    Err(error) => return Err(error),
+                },
+                _ => Ok(()),
+            };
+            match result {
+                Ok(()) => {}
+                Err(error) => {
+                    return Err(CompileError::BuildProblem(BuildProblem::new(
+                        error,
+                        relative_path.clone(),
+                        ast_node.line,
+                    )))
+                }
+            }
+        }
+        return Ok(());
+    }
+}
+
+fn is_named(expression: &Expression) -> bool {
+    return match expression {
+        Expression::NamedArgument(_, _) => true,
+        _ => false,
+    };
+}
+
+/// Parameters with a default have to come last, and a default may not use the
+/// other parameters since it is evaluated by the caller. Methods are called
+/// through their receiver, which does not say which method is meant until the
+/// type checker runs, so they cannot have defaults.
+fn check_defaults(
+    name: &String,
+    parameters: &Vec<(String, Type)>,
+    defaults: &Vec<Option<Expression>>,
+) -> Result<(), BuildError> {
+    let mut seen_default = false;
+    for ((parameter, _), default) in parameters.iter().zip(defaults) {
+        let default = match default {
+            Some(default) => default,
+            None => match seen_default {
+                true => return Err(BuildError::RequiredAfterDefault(parameter.clone())),
+                false => continue,
+            },
+        };
+        seen_default = true;
+        if parameters.iter().any(|(name, _)| name == "self") {
+            return Err(BuildError::DefaultOnMethod(name.clone()));
+        }
+        match mentions(default)
+            .into_iter()
+            .find(|used| parameters.iter().any(|(name, _)| name == used))
+        {
+            Some(used) => return Err(BuildError::DefaultUsesParameter(parameter.clone(), used)),
+            None => {}
+        }
+    }
+    return Ok(());
+}
+
+fn collect(resolver: &mut Resolver, relative_path: &PathBuf, nodes: &Vec<ASTNode>) -> Result<(), CompileError> {
+    for ast_node in nodes {
+        let (name, parameters, defaults, definition) = match &ast_node.node {
+            Node::Function {
+                name,
+                parameters,
+                defaults,
+                definition,
+                ..
+            } => (name, parameters, defaults, definition),
+            Node::Impl {
+                trait_name: None,
+                body,
+                ..
+            } => match collect(resolver, relative_path, body) {
+                Ok(()) => continue,
+                Err(error) => return Err(error),
+            },
+            Node::Trait { body, .. } | Node::Impl { body, .. } => {
+                for method in body {
+                    match &method.node {
+                        Node::Function {
+                            name,
+                            parameters,
+                            defaults,
+                            ..
+                        } => match check_defaults(name, parameters, defaults) {
+                            Ok(()) => {}
+                            Err(error) => {
+                                return Err(CompileError::BuildProblem(BuildProblem::new(
+                                    error,
+                                    relative_path.clone(),
+                                    method.line,
+                                )))
+                            }
+                        },
+                        _ => continue,
+                    }
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        match check_defaults(name, parameters, defaults) {
+            Ok(()) => {}
+            Err(error) => {
+                return Err(CompileError::BuildProblem(BuildProblem::new(
+                    error,
+                    relative_path.clone(),
+                    ast_node.line,
+                )))
+            }
+        }
+        let definition = match definition {
+            Some(definition) => *definition,
+            None => continue,
+        };
+        resolver.functions.insert(
+            definition,
+            Parameters {
+                names: parameters.iter().map(|(name, _)| name.clone()).collect(),
+                defaults: defaults.clone(),
+            },
+        );
+    }
+    return Ok(());
+}
+
+/// Rewrites every call to a known function into a positional argument list,
+/// placing named arguments at their parameter and filling in defaults.
+pub fn resolve_arguments(program: &mut Program) -> Result<(), CompileError> {
+    let mut resolver = Resolver {
+        functions: HashMap::new(),
+    };
+    for (_, module) in &program.modules {
+        let path = &module.relative_path;
+        match collect(&mut resolver, path, &module.body) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+    }
+
+    for (_, module) in &mut program.modules {
+        let path = &module.relative_path;
+        match resolver.nodes(path, &mut module.body) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        builder::{compile_error, compile_files},
+        BuildError,
+    };
+
+    #[test]
+    fn defaults_keep_the_meaning_they_have_in_the_callee() {
+        let main = "import a;\n\nconst K: i32 = 1;\n\nfn main(): i32 {\n    let y = a::f();\n    return y + K;\n}\n";
+        let a = "const K: i32 = 7;\n\npub fn f(x: i32 = K): i32 {\n    return x;\n}\n";
+        let compiled = compile_files(&[("src/main.eclipse", main), ("src/a.eclipse", a)]).unwrap();
+        assert!(compiled.ir.to_string().contains("item crate::a::K"));
+    }
+
+    #[test]
+    fn defaults_are_checked_where_they_are_written() {
+        let source = "fn f(b: u8 = 300) {}\n\nfn main() {}\n";
+        assert!(matches!(compile_error(source), BuildError::OutOfRange(_, _)));
+    }
+
+    #[test]
+    fn function_values_take_every_argument() {
+        let source = "fn f(a: i32, b: i32 = 2): i32 {\n    return a + b;\n}\n\nfn main() {\n    let g = f;\n    let x = g(1);\n}\n";
+        assert!(matches!(compile_error(source), BuildError::TooFewArguments(_, 2, 1)));
+    }
+}
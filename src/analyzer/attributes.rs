@@ -0,0 +1,583 @@
+use std::path::PathBuf;
+
+use crate::{
+    parser::{ASTNode, AsmOperand, Attribute, DefId, Expression, Node, Program},
+    BuildError, BuildProblem, BuildWarning, CompileError, Warning,
+};
+
+use super::{
+    names::{DefKind, Symbols},
+    typed::{ExpressionKind, Statement, TypedAsmOperand, TypedExpression, TypedFunction, TypedNode},
+};
+
+/// The kinds of nodes an attribute can be placed on.
+#[derive(PartialEq)]
+enum Target {
+    Function,
+    Struct,
+    Enum,
+    Item,
+    Extern,
+    Statement,
+}
+
+/// A known attribute, where it may appear and the form of its input.
+struct Definition {
+    name: &'static str,
+    targets: &'static [Target],
+    template: &'static str,
+    check: fn(&Attribute) -> bool,
+}
+
+const ITEMS: &[Target] = &[Target::Function, Target::Struct, Target::Enum, Target::Item];
+const ANYWHERE: &[Target] = &[
+    Target::Function,
+    Target::Struct,
+    Target::Enum,
+    Target::Item,
+    Target::Statement,
+];
+
+const REGISTRY: &[Definition] = &[
+    Definition {
+        name: "test",
+        targets: &[Target::Function],
+        template: "#[test]",
+        check: is_word,
+    },
+    Definition {
+        name: "inline",
+        targets: &[Target::Function],
+        template: "#[inline]` or `#[inline(always|never)]",
+        check: is_inline,
+    },
+    Definition {
+        name: "repr",
+        targets: &[Target::Struct, Target::Enum],
+        template: "#[repr(C|u8|u16|u32|u64|i8|i16|i32|i64)]",
+        check: is_repr,
+    },
+    Definition {
+        name: "deprecated",
+        targets: ITEMS,
+        template: "#[deprecated]`, `#[deprecated = \"note\"]` or `#[deprecated(since = \"version\", note = \"note\")]",
+        check: is_deprecated,
+    },
+    Definition {
+        name: "allow",
+        targets: ANYWHERE,
+        template: "#[allow(lint, ...)]",
+        check: is_lint_list,
+    },
+    Definition {
+        name: "warn",
+        targets: ANYWHERE,
+        template: "#[warn(lint, ...)]",
+        check: is_lint_list,
+    },
+    Definition {
+        name: "deny",
+        targets: ANYWHERE,
+        template: "#[deny(lint, ...)]",
+        check: is_lint_list,
+    },
+    Definition {
+        name: "link",
+        targets: &[Target::Extern],
+        template: "#[link(name = \"library\")]",
+        check: is_link,
+    },
+    Definition {
+        name: "cfg",
+        targets: ANYWHERE,
+        template: "#[cfg(predicate)]",
+        check: is_cfg,
+    },
+];
+
+fn is_word(attribute: &Attribute) -> bool {
+    return attribute.value.is_none() && attribute.arguments.is_none();
+}
+
+fn is_inline(attribute: &Attribute) -> bool {
+    return match (&attribute.value, &attribute.arguments) {
+        (None, None) => true,
+        (None, Some(arguments)) => {
+            arguments.len() == 1
+                && is_word(&arguments[0])
+                && (arguments[0].name == "always" || arguments[0].name == "never")
+        }
+        _ => false,
+    };
+}
+
+fn is_repr(attribute: &Attribute) -> bool {
+    let arguments = match (&attribute.value, &attribute.arguments) {
+        (None, Some(arguments)) if arguments.len() > 0 => arguments,
+        _ => return false,
+    };
+    return arguments.iter().all(|argument| {
+        is_word(argument)
+            && ["C", "u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64"]
+                .contains(&argument.name.as_str())
+    });
+}
+
+fn is_deprecated(attribute: &Attribute) -> bool {
+    return match (&attribute.value, &attribute.arguments) {
+        (_, None) => true,
+        (None, Some(arguments)) => arguments.iter().all(|argument| {
+            argument.value.is_some()
+                && argument.arguments.is_none()
+                && (argument.name == "since" || argument.name == "note")
+        }),
+        _ => false,
+    };
+}
+
+fn is_lint_list(attribute: &Attribute) -> bool {
+    return match (&attribute.value, &attribute.arguments) {
+        (None, Some(arguments)) => arguments.len() > 0 && arguments.iter().all(is_word),
+        _ => false,
+    };
+}
+
+fn is_link(attribute: &Attribute) -> bool {
+    return match (&attribute.value, &attribute.arguments) {
+        (None, Some(arguments)) => {
+            arguments.len() == 1
+                && arguments[0].name == "name"
+                && arguments[0].value.is_some()
+                && arguments[0].arguments.is_none()
+        }
+        _ => false,
+    };
+}
+
+fn is_cfg(attribute: &Attribute) -> bool {
+    return match (&attribute.value, &attribute.arguments) {
+        (None, Some(arguments)) => arguments.len() == 1 && is_predicate(&arguments[0]),
+        _ => false,
+    };
+}
+
+fn is_predicate(predicate: &Attribute) -> bool {
+    return match (predicate.name.as_str(), &predicate.value, &predicate.arguments) {
+        ("not", None, Some(arguments)) => arguments.len() == 1 && is_predicate(&arguments[0]),
+        ("all" | "any", None, Some(arguments)) => arguments.iter().all(is_predicate),
+        (_, _, None) => true,
+        _ => false,
+    };
+}
+
+/// Evaluates a `cfg` predicate for the target the compiler builds for.
+fn evaluate(predicate: &Attribute) -> bool {
+    return match (predicate.name.as_str(), &predicate.value, &predicate.arguments) {
+        ("not", None, Some(arguments)) => !evaluate(&arguments[0]),
+        ("all", None, Some(arguments)) => arguments.iter().all(evaluate),
+        ("any", None, Some(arguments)) => arguments.iter().any(evaluate),
+        ("windows", None, None) => true,
+        ("target_os", Some(value), None) => value == "windows",
+        ("target_arch", Some(value), None) => value == "x86_64",
+        ("target_pointer_width", Some(value), None) => value == "64",
+        _ => false,
+    };
+}
+
+fn target_of(node: &Node) -> (Target, &'static str) {
+    return match node {
+        Node::Function { .. } => (Target::Function, "function"),
+        Node::Struct { .. } => (Target::Struct, "struct"),
+        Node::Enum { .. } => (Target::Enum, "enum"),
+        Node::Trait { .. } => (Target::Item, "trait"),
+        Node::Impl { .. } => (Target::Item, "impl block"),
+        Node::Constant { .. } => (Target::Item, "constant"),
+        Node::Static { .. } => (Target::Item, "static"),
+        Node::TypeAlias { .. } => (Target::Item, "type alias"),
+        Node::Import(_, _) => (Target::Item, "import"),
+        Node::Extern { .. } => (Target::Extern, "extern block"),
+        _ => (Target::Statement, "statement"),
+    };
+}
+
+fn check_attribute(attribute: &Attribute, node: &Node) -> Result<(), BuildError> {
+    let definition = match REGISTRY.iter().find(|definition| definition.name == attribute.name) {
+        Some(definition) => definition,
+        None => return Err(BuildError::UnknownAttribute(attribute.name.clone())),
+    };
+    let (target, target_name) = target_of(node);
+    if !definition.targets.contains(&target) {
+        return Err(BuildError::MisplacedAttribute(attribute.name.clone(), target_name));
+    }
+    if !(definition.check)(attribute) {
+        return Err(BuildError::MalformedAttribute(
+            attribute.name.clone(),
+            definition.template,
+        ));
+    }
+    return Ok(());
+}
+
+/// Collects the bodies of the closures in `expression`, which can hold
+/// statements and items of their own.
+fn closure_bodies<'a>(expression: &'a mut Expression, bodies: &mut Vec<&'a mut Vec<ASTNode>>) {
+    match expression {
+        Expression::Value(_) | Expression::GetVariable(_) => {}
+        Expression::Call(_, arguments) => {
+            for argument in arguments {
+                closure_bodies(argument, bodies);
+            }
+        }
+        Expression::MethodCall(receiver, _, arguments) => {
+            closure_bodies(receiver, bodies);
+            for argument in arguments {
+                closure_bodies(argument, bodies);
+            }
+        }
+        Expression::BinaryOperation(a, _, b) => {
+            closure_bodies(a, bodies);
+            closure_bodies(b, bodies);
+        }
+        Expression::Reference(_, inner)
+        | Expression::Field(inner, _)
+        | Expression::Cast(inner, _)
+        | Expression::Try(inner)
+        | Expression::NamedArgument(_, inner) => closure_bodies(inner, bodies),
+        Expression::Closure { body, .. } => bodies.push(body),
+    }
+}
+
+/// The statement lists nested in a node: the body of a block and those of
+/// the closures in its expressions.
+fn bodies(node: &mut Node) -> Vec<&mut Vec<ASTNode>> {
+    let mut bodies = Vec::new();
+    match node {
+        Node::Function { body, defaults, .. } => {
+            for default in defaults.iter_mut().flatten() {
+                closure_bodies(default, &mut bodies);
+            }
+            match body {
+                Some(body) => bodies.push(body),
+                None => {}
+            }
+        }
+        Node::Trait { body, .. } | Node::Impl { body, .. } => bodies.push(body),
+        Node::Scope { body, .. } | Node::Loop { body } => bodies.push(body),
+        Node::Call(_, arguments) | Node::MethodCall(_, _, arguments) => {
+            for argument in arguments {
+                closure_bodies(argument, &mut bodies);
+            }
+        }
+        Node::Return(Some(expression))
+        | Node::SetVariable(_, expression)
+        | Node::Constant { expression, .. }
+        | Node::Static { expression, .. }
+        | Node::DefineVariable {
+            expression: Some(expression),
+            ..
+        } => closure_bodies(expression, &mut bodies),
+        Node::InlineAsm { operands, .. } => {
+            for operand in operands {
+                match operand {
+                    AsmOperand::In(_, expression) => closure_bodies(expression, &mut bodies),
+                    _ => continue,
+                }
+            }
+        }
+        _ => {}
+    }
+    return bodies;
+}
+
+/// Validates every attribute in `nodes` against the registry and drops the
+/// nodes whose `cfg` predicate does not hold, down to the items and
+/// statements nested in functions and closures.
+fn apply(relative_path: &PathBuf, nodes: &mut Vec<ASTNode>) -> Result<(), CompileError> {
+    for ast_node in nodes.iter() {
+        for attribute in &ast_node.attributes {
+            match check_attribute(attribute, &ast_node.node) {
+                Ok(()) => {}
+                Err(error) => {
+                    return Err(CompileError::BuildProblem(BuildProblem::new(
+                        error,
+                        relative_path.clone(),
+                        attribute.line,
+                    )))
+                }
+            }
+        }
+    }
+
+    nodes.retain(|ast_node| {
+        ast_node
+            .attributes
+            .iter()
+            .filter(|attribute| attribute.name == "cfg")
+            .all(|attribute| evaluate(&attribute.arguments.as_ref().unwrap()[0]))
+    });
+
+    for ast_node in nodes {
+        for body in bodies(&mut ast_node.node) {
+            match apply(relative_path, body) {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+    return Ok(());
+}
+
+/// The note of a `#[deprecated]` among `attributes`: `None` when the item
+/// is not deprecated and `Some(None)` when it gives no note.
+pub fn deprecation(attributes: &Vec<Attribute>) -> Option<Option<String>> {
+    let attribute = match attributes.iter().find(|attribute| attribute.name == "deprecated") {
+        Some(attribute) => attribute,
+        None => return None,
+    };
+    let note = match &attribute.arguments {
+        Some(arguments) => match arguments.iter().find(|argument| argument.name == "note") {
+            Some(argument) => argument.value.clone(),
+            None => None,
+        },
+        None => attribute.value.clone(),
+    };
+    return Some(note);
+}
+
+/// The methods called in `nodes`, with the line of the statement calling
+/// them. Methods called through a path are already among the uses of their
+/// definition.
+fn method_calls(nodes: &Vec<TypedNode>, calls: &mut Vec<(DefId, usize)>) {
+    for node in nodes {
+        match &node.statement {
+            Statement::DefineVariable {
+                expression: Some(expression),
+                ..
+            }
+            | Statement::SetVariable(_, expression)
+            | Statement::Expression(expression)
+            | Statement::Return(Some(expression)) => expression_method_calls(expression, node.line, calls),
+            Statement::Scope(body) | Statement::Loop(body) => method_calls(body, calls),
+            Statement::InlineAsm { operands, .. } => {
+                for operand in operands {
+                    match operand {
+                        TypedAsmOperand::In(_, expression) => expression_method_calls(expression, node.line, calls),
+                        _ => continue,
+                    }
+                }
+            }
+            Statement::DefineVariable { .. } | Statement::Return(None) => {}
+        }
+    }
+}
+
+fn expression_method_calls(expression: &TypedExpression, line: usize, calls: &mut Vec<(DefId, usize)>) {
+    match &expression.kind {
+        ExpressionKind::Value(_) | ExpressionKind::Local(_) | ExpressionKind::Item(_) => {}
+        ExpressionKind::Call(_, arguments) => {
+            for argument in arguments {
+                expression_method_calls(argument, line, calls);
+            }
+        }
+        ExpressionKind::MethodCall {
+            receiver,
+            definition,
+            arguments,
+            ..
+        } => {
+            match definition {
+                Some(id) => calls.push((*id, line)),
+                None => {}
+            }
+            expression_method_calls(receiver, line, calls);
+            for argument in arguments {
+                expression_method_calls(argument, line, calls);
+            }
+        }
+        ExpressionKind::BinaryOperation(a, _, b) => {
+            expression_method_calls(a, line, calls);
+            expression_method_calls(b, line, calls);
+        }
+        ExpressionKind::Reference(_, inner)
+        | ExpressionKind::Field(inner, _)
+        | ExpressionKind::Cast(inner)
+        | ExpressionKind::Try(inner) => expression_method_calls(inner, line, calls),
+        ExpressionKind::Closure { body, .. } => method_calls(body, calls),
+    }
+}
+
+fn deprecated_use(symbols: &Symbols, id: DefId, relative_path: &PathBuf, line: usize) -> Option<Warning> {
+    let definition = symbols.definition(id);
+    let note = match &definition.deprecated {
+        Some(note) => note.clone(),
+        None => return None,
+    };
+    let kind = match definition.kind {
+        DefKind::Function => "function",
+        DefKind::Method => "method",
+        DefKind::Struct => "struct",
+        DefKind::Enum => "enum",
+        DefKind::Trait => "trait",
+        DefKind::Constant => "constant",
+        DefKind::Static => "static",
+        DefKind::TypeAlias => "type alias",
+        _ => return None,
+    };
+    return Some(Warning::new(
+        BuildWarning::Deprecated(kind, definition.name.clone(), note),
+        relative_path.clone(),
+        line,
+    ));
+}
+
+/// Warns at every place an item marked `#[deprecated]` is named or, for a
+/// method, called. Each item is warned about once per line.
+pub fn check_deprecated(symbols: &Symbols, functions: &Vec<TypedFunction>) -> Vec<Warning> {
+    let mut uses: Vec<(DefId, PathBuf, usize)> = Vec::new();
+    for (index, definition) in symbols.definitions.iter().enumerate() {
+        for (relative_path, line) in &definition.uses {
+            uses.push((DefId(index), relative_path.clone(), *line));
+        }
+    }
+    for function in functions {
+        let mut calls = Vec::new();
+        method_calls(&function.body, &mut calls);
+        for (id, line) in calls {
+            uses.push((id, function.relative_path.clone(), line));
+        }
+    }
+    uses.sort_by(|a, b| (&a.1, a.2, a.0 .0).cmp(&(&b.1, b.2, b.0 .0)));
+    uses.dedup_by(|a, b| a.0 == b.0 && a.1 == b.1 && a.2 == b.2);
+
+    let mut warnings = Vec::new();
+    for (id, relative_path, line) in uses {
+        match deprecated_use(symbols, id, &relative_path, line) {
+            Some(warning) => warnings.push(warning),
+            None => {}
+        }
+    }
+    return warnings;
+}
+
+pub fn apply_attributes(program: &mut Program) -> Result<(), CompileError> {
+    for (_, module) in &mut program.modules {
+        let path = &module.relative_path;
+        match apply(path, &mut module.body) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        builder::{compile_error, compile_source},
+        BuildError, BuildWarning, CompileError,
+    };
+
+    #[test]
+    fn nested_items_are_configured_before_they_are_hoisted() {
+        let source = "fn main(): i32 {\n    #[cfg(windows)]\n    fn pick(): i32 {\n        return 1;\n    }\n    #[cfg(not(windows))]\n    fn pick(): i32 {\n        return 2;\n    }\n    return pick();\n}\n";
+        assert!(compile_source(source).is_ok());
+    }
+
+    #[test]
+    fn closures_are_configured() {
+        let source = "fn main() {\n    let f = || {\n        #[cfg(unix)]\n        let y = missing;\n    };\n}\n";
+        assert!(compile_source(source).is_ok());
+    }
+
+    #[test]
+    fn unknown_attributes_are_rejected() {
+        let error = compile_error("#[inlined]\nfn f() {}\n\nfn main() {}\n");
+        assert!(matches!(error, BuildError::UnknownAttribute(name) if name == "inlined"));
+        let error = compile_error("fn main() {\n    #[unused]\n    let x = 1;\n}\n");
+        assert!(matches!(error, BuildError::UnknownAttribute(name) if name == "unused"));
+    }
+
+    #[test]
+    fn attributes_only_apply_to_their_targets() {
+        let error = compile_error("#[inline]\nstruct S(i32);\n\nfn main() {}\n");
+        assert!(matches!(error, BuildError::MisplacedAttribute(name, target) if name == "inline" && target == "struct"));
+        let error = compile_error("#[repr(C)]\nfn f() {}\n\nfn main() {}\n");
+        assert!(matches!(error, BuildError::MisplacedAttribute(name, target) if name == "repr" && target == "function"));
+        let error = compile_error("#[link(name = \"m\")]\nconst A: i32 = 1;\n\nfn main() {}\n");
+        assert!(matches!(error, BuildError::MisplacedAttribute(name, target) if name == "link" && target == "constant"));
+        let error = compile_error("fn main() {\n    #[test]\n    let x = 1;\n}\n");
+        assert!(matches!(error, BuildError::MisplacedAttribute(name, target) if name == "test" && target == "statement"));
+    }
+
+    #[test]
+    fn deprecated_items_warn_where_they_are_used() {
+        let source = "#[deprecated = \"use `Point` instead\"]\nstruct Old(i32);\n\nimpl Old {\n    #[deprecated(since = \"0.2\", note = \"read the field\")]\n    fn get(&self): i32 {\n        return self.0;\n    }\n}\n\n#[deprecated]\nfn twice(x: i32): i32 {\n    return x * 2;\n}\n\nfn main() {\n    let old = Old(1);\n    let x = old.get() + twice(2) + twice(3);\n    let f = || {\n        return Old::get(&old);\n    };\n}\n";
+        let compiled = match compile_source(source) {
+            Ok(compiled) => compiled,
+            Err(error) => panic!("{:?}", error),
+        };
+        let deprecated: Vec<(String, usize)> = compiled
+            .warnings
+            .iter()
+            .filter_map(|warning| match &warning.warning {
+                BuildWarning::Deprecated(kind, name, note) => {
+                    let note = note.clone().unwrap_or_default();
+                    Some((format!("{} {}: {}", kind, name, note), warning.line))
+                }
+                _ => None,
+            })
+            .collect();
+        // `impl Old` names it, the `Self` of `&self` does not.
+        let expected = [
+            ("struct Old: use `Point` instead", 4),
+            ("struct Old: use `Point` instead", 17),
+            ("function twice: ", 18),
+            ("method Old::get: read the field", 18),
+            ("method Old::get: read the field", 20),
+        ];
+        assert_eq!(deprecated, expected.map(|(warning, line)| (String::from(warning), line)));
+    }
+
+    #[test]
+    fn attribute_errors_point_at_the_attribute() {
+        let source = "#[inline]\n#[repr(u128)]\nstruct S(i32);\n\nfn main() {}\n";
+        let problem = match compile_source(source) {
+            Err(CompileError::BuildProblem(problem)) => problem,
+            _ => panic!("`#[repr(u128)]` should be rejected"),
+        };
+        assert!(matches!(problem.error, BuildError::MisplacedAttribute(name, _) if name == "inline"));
+        assert_eq!(problem.line, 1);
+        let source = "fn main() {\n    let x = 1;\n    #[allow(unused)]\n\n    #[cfg(unix, windows)]\n    let y = 2;\n}\n";
+        let problem = match compile_source(source) {
+            Err(CompileError::BuildProblem(problem)) => problem,
+            _ => panic!("`#[cfg(unix, windows)]` should be rejected"),
+        };
+        assert!(matches!(problem.error, BuildError::MalformedAttribute(name, _) if name == "cfg"));
+        assert_eq!(problem.line, 5);
+    }
+
+    #[test]
+    fn attributes_take_the_input_of_their_template() {
+        let malformed = [
+            ("#[inline(sometimes)]\nfn f() {}", "inline"),
+            ("#[test(x)]\nfn f() {}", "test"),
+            ("#[repr(u128)]\nstruct S(i32);", "repr"),
+            ("#[repr]\nstruct S(i32);", "repr"),
+            ("#[allow]\nfn f() {}", "allow"),
+            ("#[cfg(not(a, b))]\nfn f() {}", "cfg"),
+            ("#[link = \"m\"]\nextern \"C\" {\n    fn sqrt(x: f64): f64;\n}", "link"),
+            ("#[deprecated(reason = \"x\")]\nfn f() {}", "deprecated"),
+        ];
+        for (item, attribute) in malformed {
+            let error = compile_error(&format!("{}\n\nfn main() {{}}\n", item));
+            assert!(
+                matches!(&error, BuildError::MalformedAttribute(name, _) if name == attribute),
+                "{}: {:?}",
+                item,
+                error
+            );
+        }
+        let source = "#[inline(always)]\nfn f() {}\n\n#[repr(C)]\nstruct S(i32);\n\n#[link(name = \"m\")]\nextern \"C\" {\n    fn sqrt(x: f64): f64;\n}\n\n#[cfg(all(windows, target_pointer_width = \"64\"))]\nfn main() {}\n";
+        assert!(compile_source(source).is_ok());
+    }
+}
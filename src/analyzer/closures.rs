@@ -0,0 +1,217 @@
+use std::path::PathBuf;
+
+use crate::{
+    parser::{ASTNode, AsmOperand, Expression, Node, Path, Type},
+    BuildError, BuildProblem, CompileError,
+};
+
+/// Returns the names a closure body uses without binding them itself, in the
+/// order they first appear. These become the closure's captured environment.
+pub fn captures(parameters: &Vec<(String, Option<Type>)>, body: &Vec<ASTNode>) -> Vec<String> {
+    let mut bound: Vec<String> = parameters.iter().map(|(name, _)| name.clone()).collect();
+    let mut found = Vec::new();
+    scope_captures(body, &mut bound, &mut found);
+    return found;
+}
+
+/// Returns the variables and functions an expression names, in the order they
+/// first appear.
+pub fn mentions(expression: &Expression) -> Vec<String> {
+    let mut found = Vec::new();
+    expression_captures(expression, &mut Vec::new(), &mut found);
+    return found;
+}
+
+fn use_path(path: &Path, bound: &Vec<String>, found: &mut Vec<String>) {
+    if path.location.len() > 0 || bound.contains(&path.root) || found.contains(&path.root) {
+        return;
+    }
+    found.push(path.root.clone());
+}
+
+fn scope_captures(body: &Vec<ASTNode>, bound: &mut Vec<String>, found: &mut Vec<String>) {
+    let length = bound.len();
+    for ast_node in body {
+        match &ast_node.node {
+            Node::DefineVariable {
+                name, expression, ..
+            } => {
+                match expression {
+                    Some(expression) => expression_captures(expression, bound, found),
+                    None => {}
+                }
+                bound.push(name.clone());
+            }
+            Node::SetVariable(path, expression) => {
+                use_path(path, bound, found);
+                expression_captures(expression, bound, found);
+            }
+            Node::Call(path, arguments) => {
+                use_path(path, bound, found);
+                for argument in arguments {
+                    expression_captures(argument, bound, found);
+                }
+            }
+            Node::MethodCall(path, _, arguments) => {
+                use_path(path, bound, found);
+                for argument in arguments {
+                    expression_captures(argument, bound, found);
+                }
+            }
+            Node::Return(Some(expression)) => expression_captures(expression, bound, found),
+            Node::InlineAsm { operands, .. } => {
+                for operand in operands {
+                    match operand {
+                        AsmOperand::In(_, expression) => expression_captures(expression, bound, found),
+                        AsmOperand::Out(_, Some(path)) | AsmOperand::InOut(_, path) => {
+                            use_path(path, bound, found)
+                        }
+                        AsmOperand::Out(_, None) | AsmOperand::Clobber(_) => {}
+                    }
+                }
+            }
+            Node::Scope { body, .. } | Node::Loop { body } => scope_captures(body, bound, found),
+            _ => continue,
+        }
+    }
+    bound.truncate(length);
+}
+
+fn expression_captures(expression: &Expression, bound: &mut Vec<String>, found: &mut Vec<String>) {
+    match expression {
+        Expression::Value(_) => {}
+        Expression::GetVariable(path) => use_path(path, bound, found),
+        Expression::Call(path, arguments) => {
+            use_path(path, bound, found);
+            for argument in arguments {
+                expression_captures(argument, bound, found);
+            }
+        }
+        Expression::BinaryOperation(a, _, b) => {
+            expression_captures(a, bound, found);
+            expression_captures(b, bound, found);
+        }
+        Expression::Reference(_, inner)
+        | Expression::Field(inner, _)
+        | Expression::Cast(inner, _)
+        | Expression::Try(inner)
+        | Expression::NamedArgument(_, inner) => expression_captures(inner, bound, found),
+        Expression::MethodCall(receiver, _, arguments) => {
+            expression_captures(receiver, bound, found);
+            for argument in arguments {
+                expression_captures(argument, bound, found);
+            }
+        }
+        Expression::Closure {
+            parameters, body, ..
+        } => {
+            for name in captures(parameters, body) {
+                if !bound.contains(&name) && !found.contains(&name) {
+                    found.push(name);
+                }
+            }
+        }
+    }
+}
+
+/// Rejects closures that borrow locals of a function and are returned from it.
+pub fn check_closures(relative_path: &PathBuf, nodes: &Vec<ASTNode>) -> Result<(), CompileError> {
+    for ast_node in nodes {
+        match &ast_node.node {
+            Node::Function {
+                parameters,
+                body: Some(body),
+                ..
+            } => {
+                let mut locals: Vec<String> = parameters.iter().map(|(name, _)| name.clone()).collect();
+                match check_escaping(relative_path, body, &mut locals) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            Node::Trait { body, .. } | Node::Impl { body, .. } => {
+                match check_closures(relative_path, body) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            _ => continue,
+        }
+    }
+    return Ok(());
+}
+
+fn check_escaping(
+    relative_path: &PathBuf,
+    body: &Vec<ASTNode>,
+    locals: &mut Vec<String>,
+) -> Result<(), CompileError> {
+    let length = locals.len();
+    for ast_node in body {
+        match &ast_node.node {
+            Node::DefineVariable { name, .. } => locals.push(name.clone()),
+            Node::Return(Some(Expression::Closure {
+                is_move: false,
+                parameters,
+                body,
+                ..
+            })) => {
+                for name in captures(parameters, body) {
+                    if locals.contains(&name) {
+                        return Err(CompileError::BuildProblem(BuildProblem::new(
+                            BuildError::ClosureMayOutlive(name),
+                            relative_path.clone(),
+                            ast_node.line,
+                        )));
+                    }
+                }
+            }
+            Node::Scope { body, .. } | Node::Loop { body } => {
+                match check_escaping(relative_path, body, locals) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            _ => continue,
+        }
+    }
+    locals.truncate(length);
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        builder::{compile_error, compile_source},
+        BuildError,
+    };
+
+    #[test]
+    fn closures_capture_what_they_do_not_bind() {
+        let source = "fn main() {\n    let a = 1;\n    let b = 5;\n    let add = |x i32| {\n        let a = 10;\n        return x + a + b;\n    };\n    let y = add(3);\n}\n";
+        let ir = compile_source(source).unwrap().ir.to_string();
+        assert!(ir.contains("fn main::{closure#0}(%0: i32): i32 {\n    capture.0: i32 // b\n  bb0:"));
+        assert!(ir.contains("closure fn#0 [$0]"));
+    }
+
+    #[test]
+    fn returned_closures_move_the_locals_they_capture() {
+        let error = compile_error(
+            "fn make(): fn(i32): i32 {\n    let k = 2;\n    return |x i32| {\n        return x * k;\n    };\n}\n\nfn main() {}\n",
+        );
+        assert!(matches!(error, BuildError::ClosureMayOutlive(name) if name == "k"));
+        let error = compile_error(
+            "fn make(k: i32): fn(i32): i32 {\n    return |x i32| {\n        return x * k;\n    };\n}\n\nfn main() {}\n",
+        );
+        assert!(matches!(error, BuildError::ClosureMayOutlive(name) if name == "k"));
+        let source = "fn make(): fn(i32): i32 {\n    let k = 2;\n    return move |x i32| {\n        return x * k;\n    };\n}\n\nfn main() {\n    let f = make();\n    let y = f(1);\n}\n";
+        let ir = compile_source(source).unwrap().ir.to_string();
+        assert!(ir.contains("move fn make::{closure#0}(%0: i32): i32 {\n    capture.0: i32 // k"));
+    }
+
+    #[test]
+    fn returned_closures_may_name_items() {
+        let source = "fn double(x: i32): i32 {\n    return x * 2;\n}\n\nfn make(): fn(i32): i32 {\n    return |x i32| {\n        return double(x);\n    };\n}\n\nfn main() {\n    let f = make();\n}\n";
+        assert!(compile_source(source).is_ok());
+    }
+}
@@ -0,0 +1,565 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{
+    parser::{ASTNode, AsmOperand, BaseType, Expression, Node, Operator, Path, Program, Type, Value},
+    BuildError, BuildProblem, CompileError,
+};
+
+#[derive(Debug)]
+pub struct Constant {
+    pub value: i128,
+}
+
+#[derive(Debug)]
+pub struct Static {
+    pub mutable: bool,
+    pub static_type: Type,
+    pub value: i128,
+}
+
+/// Every module level `const` and `static` with its value computed at compile time.
+#[derive(Debug)]
+pub struct Constants {
+    pub constants: HashMap<String, Constant>,
+    pub statics: HashMap<String, Static>,
+}
+
+/// The smallest and largest value an integer type can hold.
+pub fn integer_range(base: &BaseType) -> Option<(i128, i128)> {
+    return match base {
+        BaseType::Int128 => Some((i128::MIN, i128::MAX)),
+        // Constants are evaluated as `i128`, which cannot reach the top half of `u128`.
+        BaseType::UInt128 => Some((0, i128::MAX)),
+        BaseType::IntSize | BaseType::UIntSize => integer_range(&base.sized()),
+        BaseType::Int64 => Some((i64::MIN as i128, i64::MAX as i128)),
+        BaseType::UInt64 => Some((0, u64::MAX as i128)),
+        BaseType::Int32 => Some((i32::MIN as i128, i32::MAX as i128)),
+        BaseType::UInt32 => Some((0, u32::MAX as i128)),
+        BaseType::Int16 => Some((i16::MIN as i128, i16::MAX as i128)),
+        BaseType::UInt16 => Some((0, u16::MAX as i128)),
+        BaseType::Int8 => Some((i8::MIN as i128, i8::MAX as i128)),
+        BaseType::UInt8 => Some((0, u8::MAX as i128)),
+        BaseType::Boolean
+        | BaseType::Char
+        | BaseType::Float64
+        | BaseType::Float32
+        | BaseType::Str => None,
+    };
+}
+
+struct Definition<'a> {
+    item_type: &'a Type,
+    expression: &'a Expression,
+    relative_path: &'a PathBuf,
+    line: usize,
+}
+
+struct Evaluator<'a> {
+    constants: HashMap<String, Definition<'a>>,
+    statics: Vec<String>,
+    values: HashMap<String, i128>,
+    visiting: Vec<String>,
+}
+impl<'a> Evaluator<'a> {
+    fn problem(&self, error: BuildError, definition: &Definition) -> CompileError {
+        return CompileError::BuildProblem(BuildProblem::new(
+            error,
+            definition.relative_path.clone(),
+            definition.line,
+        ));
+    }
+
+    fn constant(&mut self, name: &String) -> Result<i128, BuildError> {
+        match self.values.get(name) {
+            Some(value) => return Ok(*value),
+            None => {}
+        }
+        if self.visiting.contains(name) {
+            let mut cycle = self.visiting.clone();
+            cycle.push(name.clone());
+            return Err(BuildError::CyclicConstant(cycle));
+        }
+        let (expression, item_type) = match self.constants.get(name) {
+            Some(definition) => (definition.expression, definition.item_type),
+            None => return Err(BuildError::NotDefined(name.clone())),
+        };
+
+        self.visiting.push(name.clone());
+        let value = match self.evaluate(expression) {
+            Ok(value) => value,
+            Err(error) => return Err(error),
+        };
+        match check_range(value, item_type) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+        self.visiting.pop();
+
+        self.values.insert(name.clone(), value);
+        return Ok(value);
+    }
+
+    fn evaluate(&mut self, expression: &Expression) -> Result<i128, BuildError> {
+        return match expression {
+            Expression::Value(value) => match value {
+                Value::Integer(integer) => Ok(*integer),
+                Value::UInteger(integer) => match i128::try_from(*integer) {
+                    Ok(integer) => Ok(integer),
+                    Err(_) => Err(BuildError::ConstantOverflow),
+                },
+                Value::Boolean(boolean) => Ok(*boolean as i128),
+                Value::Char(char) => Ok(*char as i128),
+                Value::String(_) => Err(BuildError::NotConstant(String::from("a string literal"))),
+            },
+            Expression::GetVariable(Path { root, location, .. }) if location.len() == 0 => {
+                if self.statics.contains(root) {
+                    return Err(BuildError::NotConstant(format!("static `{}`", root)));
+                }
+                self.constant(root)
+            }
+            Expression::BinaryOperation(a, operator, b) => {
+                let a = match self.evaluate(a) {
+                    Ok(a) => a,
+                    Err(error) => return Err(error),
+                };
+                let b = match self.evaluate(b) {
+                    Ok(b) => b,
+                    Err(error) => return Err(error),
+                };
+                let result = match operator {
+                    Operator::Plus => a.checked_add(b),
+                    Operator::Minus => a.checked_sub(b),
+                    Operator::Multiply => a.checked_mul(b),
+                    Operator::Division => match b {
+                        0 => return Err(BuildError::DivisionByZero),
+                        b => a.checked_div(b),
+                    },
+                    Operator::Equal => Some((a == b) as i128),
+                    Operator::NotEqual => Some((a != b) as i128),
+                    Operator::Less => Some((a < b) as i128),
+                    Operator::LessEqual => Some((a <= b) as i128),
+                    Operator::Greater => Some((a > b) as i128),
+                    Operator::GreaterEqual => Some((a >= b) as i128),
+                };
+                match result {
+                    Some(result) => Ok(result),
+                    None => Err(BuildError::ConstantOverflow),
+                }
+            }
+            Expression::GetVariable(_) => Err(BuildError::NotConstant(String::from("path"))),
+            Expression::Call(_, _)
+            | Expression::MethodCall(_, _, _)
+            | Expression::NamedArgument(_, _) => {
+                Err(BuildError::NotConstant(String::from("function call")))
+            }
+            Expression::Reference(_, _) => Err(BuildError::NotConstant(String::from("reference"))),
+            Expression::Field(_, _) => Err(BuildError::NotConstant(String::from("field access"))),
+            Expression::Try(_) => Err(BuildError::NotConstant(String::from("`?` operator"))),
+            Expression::Cast(inner, t) => {
+                let value = match self.evaluate(inner) {
+                    Ok(value) => value,
+                    Err(error) => return Err(error),
+                };
+                let range = match t {
+                    Type::Base(base) => integer_range(base),
+                    _ => None,
+                };
+                // Integer casts keep the low bits, exactly like they do at runtime.
+                match range {
+                    Some((min, max)) => Ok((value - min).rem_euclid(max - min + 1) + min),
+                    None => Err(BuildError::NotConstant(format!("cast to `{}`", t))),
+                }
+            }
+            Expression::Closure { .. } => Err(BuildError::NotConstant(String::from("closure"))),
+        };
+    }
+}
+
+fn check_range(value: i128, item_type: &Type) -> Result<(), BuildError> {
+    let range = match item_type {
+        // Only literals evaluate to a char, so the value is always a scalar value.
+        Type::Base(BaseType::Char) => Some((0, char::MAX as i128)),
+        Type::Base(base) => integer_range(base),
+        _ => None,
+    };
+    return match range {
+        Some((min, max)) if value >= min && value <= max => Ok(()),
+        Some(_) => Err(BuildError::OutOfRange(value.to_string(), item_type.to_string())),
+        None => Err(BuildError::NotConstant(format!("value of type `{}`", item_type))),
+    };
+}
+
+pub fn evaluate_constants(program: &Program) -> Result<Constants, CompileError> {
+    let mut evaluator = Evaluator {
+        constants: HashMap::new(),
+        statics: Vec::new(),
+        values: HashMap::new(),
+        visiting: Vec::new(),
+    };
+    let mut statics = Vec::new();
+
+    for (_, module) in &program.modules {
+        let path = &module.relative_path;
+        for ast_node in &module.body {
+            let name = match &ast_node.node {
+                Node::Constant {
+                    name,
+                    constant_type,
+                    expression,
+                    ..
+                } => {
+                    let definition = Definition {
+                        item_type: constant_type,
+                        expression,
+                        relative_path: path,
+                        line: ast_node.line,
+                    };
+                    match evaluator.constants.insert(name.clone(), definition) {
+                        Some(_) => name,
+                        None => continue,
+                    }
+                }
+                Node::Static { name, .. } => {
+                    statics.push((path, ast_node));
+                    match evaluator.statics.contains(name) {
+                        true => name,
+                        false => {
+                            evaluator.statics.push(name.clone());
+                            continue;
+                        }
+                    }
+                }
+                _ => continue,
+            };
+            return Err(CompileError::BuildProblem(BuildProblem::new(
+                BuildError::AlreadyDefined(name.clone()),
+                path.clone(),
+                ast_node.line,
+            )));
+        }
+    }
+
+    let mut constants = Constants {
+        constants: HashMap::new(),
+        statics: HashMap::new(),
+    };
+
+    let mut names: Vec<String> = evaluator.constants.keys().cloned().collect();
+    names.sort();
+    for name in names {
+        match evaluator.constant(&name) {
+            Ok(_) => {}
+            Err(error) => {
+                let definition = evaluator.constants.get(&name).unwrap();
+                return Err(evaluator.problem(error, definition));
+            }
+        }
+    }
+
+    for (path, ast_node) in statics {
+        let (mutable, name, static_type, expression) = match &ast_node.node {
+            Node::Static {
+                mutable,
+                name,
+                static_type,
+                expression,
+                ..
+            } => (mutable, name, static_type, expression),
+            _ => continue,
+        };
+        let definition = Definition {
+            item_type: static_type,
+            expression,
+            relative_path: path,
+            line: ast_node.line,
+        };
+        let value = match evaluator.evaluate(expression) {
+            Ok(value) => match check_range(value, static_type) {
+                Ok(()) => value,
+                Err(error) => return Err(evaluator.problem(error, &definition)),
+            },
+            Err(error) => return Err(evaluator.problem(error, &definition)),
+        };
+        constants.statics.insert(
+            name.clone(),
+            Static {
+                mutable: *mutable,
+                static_type: static_type.clone(),
+                value,
+            },
+        );
+    }
+
+    for name in evaluator.constants.keys() {
+        constants.constants.insert(
+            name.clone(),
+            Constant {
+                value: *evaluator.values.get(name).unwrap(),
+            },
+        );
+    }
+
+    return Ok(constants);
+}
+
+/// Checks that mutable statics are only touched inside `unsafe` functions or
+/// blocks, and that constants and immutable statics are never assigned to.
+pub fn check_static_access(
+    constants: &Constants,
+    relative_path: &PathBuf,
+    nodes: &Vec<ASTNode>,
+) -> Result<(), CompileError> {
+    for ast_node in nodes {
+        match &ast_node.node {
+            Node::Function {
+                is_unsafe,
+                parameters,
+                body: Some(body),
+                ..
+            } => {
+                let mut locals = parameters.iter().map(|(name, _)| name.clone()).collect();
+                match check_body(constants, relative_path, body, *is_unsafe, &mut locals) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            Node::Trait { body, .. } | Node::Impl { body, .. } => {
+                match check_static_access(constants, relative_path, body) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            _ => continue,
+        }
+    }
+    return Ok(());
+}
+
+fn check_body(
+    constants: &Constants,
+    relative_path: &PathBuf,
+    body: &Vec<ASTNode>,
+    is_unsafe: bool,
+    locals: &mut Vec<String>,
+) -> Result<(), CompileError> {
+    let length = locals.len();
+    for ast_node in body {
+        let result = match &ast_node.node {
+            Node::DefineVariable {
+                name, expression, ..
+            } => {
+                let result = match expression {
+                    Some(expression) => check_expression(constants, expression, is_unsafe, locals),
+                    None => Ok(()),
+                };
+                locals.push(name.clone());
+                result
+            }
+            Node::SetVariable(path, expression) => {
+                match check_assignment(constants, path, is_unsafe, locals) {
+                    Ok(()) => check_expression(constants, expression, is_unsafe, locals),
+                    Err(error) => Err(error),
+                }
+            }
+            Node::Call(_, arguments) => check_arguments(constants, arguments, is_unsafe, locals),
+            Node::MethodCall(path, _, arguments) => {
+                match check_path(constants, path, is_unsafe, locals) {
+                    Ok(()) => check_arguments(constants, arguments, is_unsafe, locals),
+                    Err(error) => Err(error),
+                }
+            }
+            Node::Return(Some(expression)) => check_expression(constants, expression, is_unsafe, locals),
+            Node::InlineAsm { operands, .. } => {
+                for operand in operands {
+                    let result = match operand {
+                        AsmOperand::In(_, expression) => {
+                            check_expression(constants, expression, is_unsafe, locals)
+                        }
+                        AsmOperand::Out(_, Some(path)) | AsmOperand::InOut(_, path) => {
+                            check_assignment(constants, path, is_unsafe, locals)
+                        }
+                        AsmOperand::Out(_, None) | AsmOperand::Clobber(_) => Ok(()),
+                    };
+                    match result {
+                        Ok(()) => {}
+                        Err(error) => return Err(CompileError::BuildProblem(BuildProblem::new(
+                            error,
+                            relative_path.clone(),
+                            ast_node.line,
+                        ))),
+                    }
+                }
+                Ok(())
+            }
+            Node::Scope {
+                is_unsafe: scope_unsafe,
+                body,
+            } => {
+                match check_body(constants, relative_path, body, is_unsafe || *scope_unsafe, locals) {
+                    Ok(()) => Ok(()),
+                    Err(error) => return Err(error),
+                }
+            }
+            Node::Loop { body } => match check_body(constants, relative_path, body, is_unsafe, locals) {
+                Ok(()) => Ok(()),
+                Err(error) => return Err(error),
+            },
+            _ => Ok(()),
+        };
+        match result {
+            Ok(()) => {}
+            Err(error) => {
+                return Err(CompileError::BuildProblem(BuildProblem::new(
+                    error,
+                    relative_path.clone(),
+                    ast_node.line,
+                )))
+            }
+        }
+    }
+    locals.truncate(length);
+    return Ok(());
+}
+
+fn check_path(
+    constants: &Constants,
+    path: &Path,
+    is_unsafe: bool,
+    locals: &Vec<String>,
+) -> Result<(), BuildError> {
+    if path.location.len() > 0 || locals.contains(&path.root) || is_unsafe {
+        return Ok(());
+    }
+    return match constants.statics.get(&path.root) {
+        Some(definition) if definition.mutable => {
+            Err(BuildError::MutableStaticOutsideUnsafe(path.root.clone()))
+        }
+        _ => Ok(()),
+    };
+}
+
+fn check_assignment(
+    constants: &Constants,
+    path: &Path,
+    is_unsafe: bool,
+    locals: &Vec<String>,
+) -> Result<(), BuildError> {
+    if path.location.len() > 0 || locals.contains(&path.root) {
+        return Ok(());
+    }
+    if constants.constants.contains_key(&path.root) {
+        return Err(BuildError::NotMutable(path.root.clone()));
+    }
+    return match constants.statics.get(&path.root) {
+        Some(definition) if !definition.mutable => Err(BuildError::NotMutable(path.root.clone())),
+        _ => check_path(constants, path, is_unsafe, locals),
+    };
+}
+
+fn check_arguments(
+    constants: &Constants,
+    arguments: &Vec<Expression>,
+    is_unsafe: bool,
+    locals: &mut Vec<String>,
+) -> Result<(), BuildError> {
+    for argument in arguments {
+        match check_expression(constants, argument, is_unsafe, locals) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+    }
+    return Ok(());
+}
+
+fn check_expression(
+    constants: &Constants,
+    expression: &Expression,
+    is_unsafe: bool,
+    locals: &mut Vec<String>,
+) -> Result<(), BuildError> {
+    return match expression {
+        Expression::Value(_) => Ok(()),
+        Expression::GetVariable(path) => check_path(constants, path, is_unsafe, locals),
+        Expression::Reference(_, inner)
+        | Expression::Field(inner, _)
+        | Expression::Cast(inner, _)
+        | Expression::Try(inner)
+        | Expression::NamedArgument(_, inner) => check_expression(constants, inner, is_unsafe, locals),
+        Expression::Call(_, arguments) => check_arguments(constants, arguments, is_unsafe, locals),
+        Expression::MethodCall(receiver, _, arguments) => {
+            match check_expression(constants, receiver, is_unsafe, locals) {
+                Ok(()) => check_arguments(constants, arguments, is_unsafe, locals),
+                Err(error) => Err(error),
+            }
+        }
+        Expression::BinaryOperation(a, _, b) => {
+            match check_expression(constants, a, is_unsafe, locals) {
+                Ok(()) => check_expression(constants, b, is_unsafe, locals),
+                Err(error) => Err(error),
+            }
+        }
+        Expression::Closure {
+            parameters, body, ..
+        } => {
+            let length = locals.len();
+            for (name, _) in parameters {
+                locals.push(name.clone());
+            }
+            // Errors inside the closure body are reported on the line of the closure.
+            let result = match check_body(constants, &PathBuf::new(), body, is_unsafe, locals) {
+                Ok(()) => Ok(()),
+                Err(CompileError::BuildProblem(problem)) => Err(problem.error),
+                Err(_) => Ok(()),
+            };
+            locals.truncate(length);
+            result
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        builder::{compile_error, compile_source},
+        BuildError,
+    };
+
+    #[test]
+    fn constants_are_evaluated_at_compile_time() {
+        let source = "const A: i32 = 2 * 3 + 1;\nconst B: u8 = A * 30;\nconst C: i64 = B - 1;\nconst D: u8 = 300 as u8;\n\nfn main() {\n    let x = C;\n    let y = D;\n}\n";
+        let assembly = compile_source(source).unwrap().assembly;
+        assert!(assembly.contains("mov rax, 209\n"));
+        // Casts keep the low bits, as they do at runtime.
+        assert!(assembly.contains("mov rax, 44\n"));
+        let error = compile_error("const A: i32 = 2;\nconst B: u8 = A * 200;\n\nfn main() {}\n");
+        assert!(matches!(error, BuildError::OutOfRange(value, t) if value == "400" && t == "u8"));
+    }
+
+    #[test]
+    fn constants_do_not_divide_by_zero() {
+        let error = compile_error("const A: i32 = 1 / 0;\n\nfn main() {}\n");
+        assert!(matches!(error, BuildError::DivisionByZero));
+        let error = compile_error("const Z: i32 = 0;\nconst A: i32 = 5 / Z;\n\nfn main() {}\n");
+        assert!(matches!(error, BuildError::DivisionByZero));
+    }
+
+    #[test]
+    fn constants_do_not_depend_on_themselves() {
+        let error = compile_error("const A: i32 = B + 1;\nconst B: i32 = A;\n\nfn main() {}\n");
+        assert!(matches!(error, BuildError::CyclicConstant(cycle) if cycle == ["A", "B", "A"]));
+        let error = compile_error("static S: i32 = 1;\nconst A: i32 = S;\n\nfn main() {}\n");
+        assert!(matches!(error, BuildError::NotConstant(what) if what == "static `S`"));
+    }
+
+    #[test]
+    fn mutable_statics_need_unsafe() {
+        let error = compile_error("static mut N: i32 = 0;\n\nfn main() {\n    N = 1;\n}\n");
+        assert!(matches!(error, BuildError::MutableStaticOutsideUnsafe(name) if name == "N"));
+        let error = compile_error("static mut N: i32 = 0;\n\nfn main() {\n    let x = N + 1;\n}\n");
+        assert!(matches!(error, BuildError::MutableStaticOutsideUnsafe(name) if name == "N"));
+        let source = "static mut N: i32 = 0;\n\nfn main() {\n    unsafe {\n        N = N + 1;\n    }\n}\n";
+        assert!(compile_source(source).is_ok());
+        let error = compile_error("static N: i32 = 0;\n\nfn main() {\n    unsafe {\n        N = 1;\n    }\n}\n");
+        assert!(matches!(error, BuildError::NotMutable(name) if name == "N"));
+    }
+}
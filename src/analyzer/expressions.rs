@@ -0,0 +1,257 @@
+use crate::{
+    parser::{BaseType, Operator, Type, Value},
+    BuildError,
+};
+
+use super::traits::Traits;
+
+/// Stands in for type arguments that cannot be known here, like the `T` of `None`.
+pub fn unknown() -> Type {
+    return Type::Custom(String::from("_"));
+}
+
+pub fn is_unknown(t: &Type) -> bool {
+    return match t {
+        Type::Custom(name) => name == "_",
+        _ => false,
+    };
+}
+
+pub fn str_slice() -> Type {
+    return Type::Reference(false, Box::new(Type::Base(BaseType::Str)));
+}
+
+pub fn is_integer(t: &Type) -> bool {
+    return match t {
+        Type::Base(
+            BaseType::Float64 | BaseType::Float32 | BaseType::Boolean | BaseType::Char | BaseType::Str,
+        ) => false,
+        Type::Base(_) => true,
+        _ => false,
+    };
+}
+
+fn is_numeric(t: &Type) -> bool {
+    return match t {
+        Type::Base(BaseType::Boolean | BaseType::Char | BaseType::Str) => false,
+        Type::Base(_) => true,
+        _ => false,
+    };
+}
+
+/// Integers wide enough to hold an address.
+fn is_address_sized(t: &Type) -> bool {
+    return match t {
+        Type::Base(base) => {
+            base.sized() == BaseType::IntSize.sized() || base.sized() == BaseType::UIntSize.sized()
+        }
+        _ => false,
+    };
+}
+
+/// 128-bit integers convert to and from other integers, but not to floats.
+fn is_float_and_wide(from: &Type, to: &Type) -> bool {
+    let is_float = |t: &Type| matches!(t, Type::Base(BaseType::Float64 | BaseType::Float32));
+    let is_wide = |t: &Type| match t {
+        Type::Base(base) => base.is_wide(),
+        _ => false,
+    };
+    return (is_float(from) && is_wide(to)) || (is_wide(from) && is_float(to));
+}
+
+pub fn check_cast(from: &Type, to: &Type, is_unsafe: bool) -> Result<(), BuildError> {
+    if from == to {
+        return Ok(());
+    }
+    let valid = match (from, to) {
+        (Type::Base(BaseType::Boolean), to) => is_integer(to),
+        // Every char is a valid integer, but only bytes are always valid chars.
+        (Type::Base(BaseType::Char), to) => is_integer(to),
+        (Type::Base(BaseType::UInt8), Type::Base(BaseType::Char)) => true,
+        (from, to) if is_float_and_wide(from, to) => false,
+        (from, to) if is_numeric(from) && is_numeric(to) => true,
+        (Type::Pointer(_, _), Type::Pointer(_, _)) => true,
+        // A `&str` gives up its length to become a pointer to its first byte.
+        (from, Type::Pointer(false, pointee)) if from.is_str_slice() => {
+            pointee.as_ref() == &Type::Base(BaseType::UInt8)
+        }
+        (Type::Reference(_, inner), Type::Pointer(mutable, pointee)) => {
+            inner == pointee && (!mutable || matches!(from, Type::Reference(true, _)))
+        }
+        (Type::Pointer(_, _), to) if is_address_sized(to) => match is_unsafe {
+            true => true,
+            false => return Err(BuildError::UnsafeCast(from.to_string(), to.to_string())),
+        },
+        (from, Type::Pointer(_, _)) if is_address_sized(from) => match is_unsafe {
+            true => true,
+            false => return Err(BuildError::UnsafeCast(from.to_string(), to.to_string())),
+        },
+        _ => false,
+    };
+    return match valid {
+        true => Ok(()),
+        false => Err(BuildError::InvalidCast(from.to_string(), to.to_string())),
+    };
+}
+
+/// Integer literals can become any number, a char if they fit in a byte, or
+/// a pointer like any other integer.
+pub fn check_literal_cast(value: &Value, to: &Type, is_unsafe: bool) -> Result<(), BuildError> {
+    return match to {
+        Type::Pointer(_, _) => check_cast(&Type::Base(BaseType::UInt64), to, is_unsafe),
+        Type::Base(BaseType::Char) if matches!(value, Value::UInteger(0..=0xff)) => Ok(()),
+        to if is_numeric(to) => Ok(()),
+        to => Err(BuildError::InvalidCast(String::from("{integer}"), to.to_string())),
+    };
+}
+
+/// Numbers have the operators built in, bools and chars only compare, and
+/// pointers compare and move by an integer with `+` and `-`. Structs and
+/// enums get them from an implementation of the prelude trait behind the
+/// operator.
+pub fn check_operator(traits: &Traits, t: &Type, operator: Operator) -> Result<(), BuildError> {
+    return match t {
+        Type::Custom(_) | Type::Generic(_, _) => {
+            let (trait_name, _) = operator.overload();
+            match traits.find_implementation(&String::from(trait_name), t) {
+                Some(_) => Ok(()),
+                None => Err(BuildError::MissingOperatorImpl(
+                    operator.symbol(),
+                    t.to_string(),
+                    trait_name,
+                )),
+            }
+        }
+        t if is_numeric(t) => Ok(()),
+        Type::Base(BaseType::Boolean | BaseType::Char) if operator.is_comparison() => Ok(()),
+        Type::Pointer(_, _) if operator.is_comparison() => Ok(()),
+        Type::Pointer(_, _) if matches!(operator, Operator::Plus | Operator::Minus) => Ok(()),
+        _ => Err(BuildError::InvalidOperands(operator.symbol(), t.to_string())),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{builder::compile_error, builder::compile_source, BuildError};
+
+    #[test]
+    fn bool_has_no_arithmetic() {
+        let error = compile_error("fn main() {\n    let a = true;\n    let b = a + a;\n}\n");
+        assert!(matches!(error, BuildError::InvalidOperands("+", t) if t == "bool"));
+    }
+
+    #[test]
+    fn bool_compares() {
+        assert!(compile_source("fn main() {\n    let a = true;\n    let b = a == false;\n}\n").is_ok());
+    }
+
+    #[test]
+    fn pointers_only_move_by_integers() {
+        let error = compile_error(
+            "fn main() {\n    let x = 1;\n    let p = &x as *const i32;\n    let q = p * 2;\n}\n",
+        );
+        assert!(matches!(error, BuildError::InvalidOperands("*", _)));
+        let error = compile_error(
+            "fn main() {\n    let x = 1;\n    let p = &x as *const i32;\n    let q = p + true;\n}\n",
+        );
+        assert!(matches!(error, BuildError::MismatchedTypes(_, _)));
+        assert!(compile_source(
+            "fn main() {\n    let x = 1;\n    let p = &x as *const i32;\n    let q = p + 1;\n    let r = q - 1;\n}\n"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn chars_have_no_arithmetic() {
+        let error = compile_error("fn main() {\n    let c = 'a';\n    let d = c * c;\n}\n");
+        assert!(matches!(error, BuildError::InvalidOperands("*", _)));
+    }
+
+    #[test]
+    fn negative_literals_stop_at_i128() {
+        assert!(compile_source("fn main() {\n    let x: i128 = -170141183460469231731687303715884105728;\n}\n").is_ok());
+        let error = compile_error("fn main() {\n    let x: i128 = -170141183460469231731687303715884105729;\n}\n");
+        assert!(matches!(error, BuildError::OutOfRange(value, _) if value == "-170141183460469231731687303715884105729"));
+    }
+
+    #[test]
+    fn literals_stop_at_u128() {
+        let error = compile_error("fn main() {\n    let x = 340282366920938463463374607431768211456;\n}\n");
+        assert!(matches!(error, BuildError::Tokenize(message) if message.starts_with("integer literal is too large")));
+    }
+
+    /// A body casting `value` of type `from` as `to`, with `x` and `s` to
+    /// take references of.
+    fn cast(from: &str, value: &str, to: &str) -> String {
+        return format!(
+            "fn main() {{\n    let mut x: i32 = 1;\n    let s = \"s\";\n    let v: {} = {};\n    let c = v as {};\n}}\n",
+            from, value, to
+        );
+    }
+
+    #[test]
+    fn casts_convert_between_scalars() {
+        let valid = [
+            ("bool", "true", "i32"),
+            ("char", "'a'", "u32"),
+            ("u8", "97", "char"),
+            ("i32", "1", "f64"),
+            ("f64", "x as f64", "i8"),
+            ("i64", "1", "u128"),
+            ("u128", "1", "i8"),
+            ("*const i32", "&x as *const i32", "*mut u8"),
+            ("&str", "s", "*const u8"),
+            ("&i32", "&x", "*const i32"),
+            ("&mut i32", "&mut x", "*mut i32"),
+        ];
+        for (from, value, to) in valid {
+            assert!(compile_source(&cast(from, value, to)).is_ok(), "{} as {}", from, to);
+        }
+        let invalid = [
+            ("i32", "1", "bool"),
+            ("i32", "1", "char"),
+            ("bool", "true", "f64"),
+            ("char", "'a'", "f32"),
+            ("f64", "x as f64", "i128"),
+            ("i128", "1", "f32"),
+            ("&str", "s", "*const i32"),
+            ("&i32", "&x", "*mut i32"),
+            ("&i32", "&x", "*const u8"),
+            ("i32", "1", "&i32"),
+        ];
+        for (from, value, to) in invalid {
+            let error = compile_error(&cast(from, value, to));
+            assert!(
+                matches!(&error, BuildError::InvalidCast(f, t) if f == from && t == to),
+                "{} as {}: {:?}",
+                from,
+                to,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn pointers_and_integers_convert_in_unsafe() {
+        for (from, value, to) in [("*const i32", "&x as *const i32", "usize"), ("u64", "1", "*const u8")] {
+            let error = compile_error(&cast(from, value, to));
+            assert!(matches!(&error, BuildError::UnsafeCast(f, t) if f == from && t == to), "{:?}", error);
+        }
+        let source = "fn main() {\n    let x = 1;\n    let p = &x as *const i32;\n    unsafe {\n        let a = p as usize;\n        let b = a as *const i32;\n        let c = 0 as *mut u8;\n    }\n}\n";
+        assert!(compile_source(source).is_ok());
+        // Only integers as wide as an address hold one.
+        let error = compile_error("fn main() {\n    let x = 1;\n    let p = &x as *const i32;\n    unsafe {\n        let a = p as i32;\n    }\n}\n");
+        assert!(matches!(error, BuildError::InvalidCast(from, to) if from == "*const i32" && to == "i32"));
+    }
+
+    #[test]
+    fn literals_cast_to_what_they_fit() {
+        assert!(compile_source("fn main() {\n    let a = 97 as char;\n    let b = 3 as f32;\n    let c = 300 as u8;\n}\n").is_ok());
+        let error = compile_error("fn main() {\n    let a = 300 as char;\n}\n");
+        assert!(matches!(error, BuildError::InvalidCast(from, to) if from == "{integer}" && to == "char"));
+        let error = compile_error("fn main() {\n    let a = 1 as bool;\n}\n");
+        assert!(matches!(error, BuildError::InvalidCast(from, to) if from == "{integer}" && to == "bool"));
+        let error = compile_error("fn main() {\n    let a = 0 as *const u8;\n}\n");
+        assert!(matches!(error, BuildError::UnsafeCast(from, to) if from == "u64" && to == "*const u8"));
+    }
+}
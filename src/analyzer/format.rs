@@ -0,0 +1,153 @@
+use crate::BuildError;
+
+/// The builtins that take a format string followed by its arguments.
+pub const BUILTINS: [&str; 3] = ["print", "println", "format"];
+
+/// How a placeholder formats its argument.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Spec {
+    Display,
+    LowerHex,
+    UpperHex,
+    Binary,
+    Octal,
+}
+impl Spec {
+    pub fn placeholder(&self) -> &'static str {
+        return match self {
+            Spec::Display => "{}",
+            Spec::LowerHex => "{:x}",
+            Spec::UpperHex => "{:X}",
+            Spec::Binary => "{:b}",
+            Spec::Octal => "{:o}",
+        };
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Piece {
+    Literal(String),
+    Placeholder(Spec),
+}
+
+/// Splits a format string into literal text and placeholders. `{{` and `}}`
+/// stand for literal braces.
+pub fn parse_format(format: &String) -> Result<Vec<Piece>, BuildError> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut chars = format.chars().peekable();
+
+    loop {
+        let ch = match chars.next() {
+            Some(ch) => ch,
+            None => break,
+        };
+        match ch {
+            '{' => {
+                match chars.peek() {
+                    Some('{') => {
+                        chars.next();
+                        literal.push('{');
+                        continue;
+                    }
+                    _ => {}
+                }
+                let mut content = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => content.push(ch),
+                        None => {
+                            return Err(BuildError::InvalidFormatString(String::from(
+                                "expected `}` but the string was terminated",
+                            )))
+                        }
+                    }
+                }
+                let spec = match content.as_str() {
+                    "" => Spec::Display,
+                    ":x" => Spec::LowerHex,
+                    ":X" => Spec::UpperHex,
+                    ":b" => Spec::Binary,
+                    ":o" => Spec::Octal,
+                    _ => {
+                        return Err(BuildError::InvalidFormatString(format!(
+                            "unknown placeholder `{{{}}}`",
+                            content
+                        )))
+                    }
+                };
+                if literal.len() > 0 {
+                    pieces.push(Piece::Literal(literal));
+                    literal = String::new();
+                }
+                pieces.push(Piece::Placeholder(spec));
+            }
+            '}' => match chars.next() {
+                Some('}') => literal.push('}'),
+                _ => {
+                    return Err(BuildError::InvalidFormatString(String::from(
+                        "unmatched `}`, use `}}` for a literal brace",
+                    )))
+                }
+            },
+            ch => literal.push(ch),
+        }
+    }
+    if literal.len() > 0 {
+        pieces.push(Piece::Literal(literal));
+    }
+    return Ok(pieces);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_format, Piece, Spec};
+    use crate::BuildError;
+
+    fn parse(format: &str) -> Result<Vec<Piece>, BuildError> {
+        return parse_format(&String::from(format));
+    }
+
+    #[test]
+    fn format_strings_split_into_text_and_placeholders() {
+        let pieces = parse("x = {}, {:x}{:X} {:b} {:o}!").unwrap();
+        let expected = [
+            "Literal(\"x = \")",
+            "Placeholder(Display)",
+            "Literal(\", \")",
+            "Placeholder(LowerHex)",
+            "Placeholder(UpperHex)",
+            "Literal(\" \")",
+            "Placeholder(Binary)",
+            "Literal(\" \")",
+            "Placeholder(Octal)",
+            "Literal(\"!\")",
+        ];
+        assert_eq!(pieces.iter().map(|piece| format!("{:?}", piece)).collect::<Vec<String>>(), expected);
+        assert!(matches!(parse("{{}}").unwrap().as_slice(), [Piece::Literal(text)] if text == "{}"));
+        assert!(matches!(parse("{{{}}}").unwrap().as_slice(), [
+            Piece::Literal(open),
+            Piece::Placeholder(Spec::Display),
+            Piece::Literal(close),
+        ] if open == "{" && close == "}"));
+        assert!(parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_format_strings_are_rejected() {
+        for (format, reason) in [
+            ("{", "expected `}` but the string was terminated"),
+            ("a {:x", "expected `}` but the string was terminated"),
+            ("}", "unmatched `}`, use `}}` for a literal brace"),
+            ("{} }", "unmatched `}`, use `}}` for a literal brace"),
+            ("{0}", "unknown placeholder `{0}`"),
+            ("{:?}", "unknown placeholder `{:?}`"),
+        ] {
+            match parse(format) {
+                Err(BuildError::InvalidFormatString(error)) => assert_eq!(error, reason, "{}", format),
+                _ => panic!("`{}` should be invalid", format),
+            }
+        }
+    }
+}
@@ -0,0 +1,494 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{
+    parser::{ASTNode, AsmOperand, Expression, Node, Path, Program, Type},
+    BuildError, BuildProblem, CompileError,
+};
+
+/// The names one block adds: the items declared anywhere in it, under their
+/// mangled names, and the locals declared so far.
+struct Frame {
+    items: HashMap<String, String>,
+    locals: Vec<String>,
+    /// The frame of a function, constant or static body, which cannot see the
+    /// locals of the frames around it.
+    boundary: bool,
+}
+impl Frame {
+    fn new(locals: Vec<String>, boundary: bool) -> Self {
+        Self {
+            items: HashMap::new(),
+            locals,
+            boundary,
+        }
+    }
+}
+
+fn is_local_item(node: &Node) -> bool {
+    return match node {
+        Node::Function { .. }
+        | Node::Struct { .. }
+        | Node::Enum { .. }
+        | Node::Trait { .. }
+        | Node::Impl { .. }
+        | Node::Constant { .. }
+        | Node::Static { .. }
+        | Node::TypeAlias { .. }
+        | Node::Extern { .. } => true,
+        _ => false,
+    };
+}
+
+fn item_name(node: &mut Node) -> Option<&mut String> {
+    return match node {
+        Node::Function { name, .. }
+        | Node::Struct { name, .. }
+        | Node::Enum { name, .. }
+        | Node::Trait { name, .. }
+        | Node::Constant { name, .. }
+        | Node::Static { name, .. }
+        | Node::TypeAlias { name, .. } => Some(name),
+        _ => None,
+    };
+}
+
+struct Hoister<'a> {
+    relative_path: &'a PathBuf,
+    frames: Vec<Frame>,
+    /// Items taken out of function bodies, ready to join the module items.
+    hoisted: Vec<ASTNode>,
+    /// Every mangled name handed out so far, in the whole program.
+    names: &'a mut Vec<String>,
+    /// The function whose body is being walked, which names its items.
+    prefix: String,
+    line: usize,
+}
+impl<'a> Hoister<'a> {
+    fn problem(&self, error: BuildError) -> CompileError {
+        return CompileError::BuildProblem(BuildProblem::new(
+            error,
+            self.relative_path.clone(),
+            self.line,
+        ));
+    }
+
+    /// `main::helper` for an item `helper` declared in `main`, with a number
+    /// added when another block of `main` declares a `helper` too.
+    fn mangle(&mut self, prefix: &String, name: &String) -> String {
+        let mut mangled = format!("{}::{}", prefix, name);
+        let mut index = 1;
+        loop {
+            if !self.names.contains(&mangled) {
+                break;
+            }
+            index += 1;
+            mangled = format!("{}::{}#{}", prefix, name, index);
+        }
+        self.names.push(mangled.clone());
+        return mangled;
+    }
+
+    fn local_item(&self, name: &String) -> Option<&String> {
+        for frame in self.frames.iter().rev() {
+            match frame.items.get(name) {
+                Some(mangled) => return Some(mangled),
+                None => {}
+            }
+        }
+        return None;
+    }
+
+    /// Points `path` at the local item it names. Locals shadow items, but only
+    /// within their own function.
+    fn value(&self, path: &mut Path) -> Result<(), CompileError> {
+        let mut crossed = false;
+        for frame in self.frames.iter().rev() {
+            if path.location.len() == 0 && frame.locals.contains(&path.root) {
+                return match crossed {
+                    true => Err(self.problem(BuildError::CaptureInItem(path.root.clone()))),
+                    false => Ok(()),
+                };
+            }
+            match frame.items.get(&path.root) {
+                Some(mangled) => {
+                    path.root = mangled.clone();
+                    return Ok(());
+                }
+                None => {}
+            }
+            if frame.boundary {
+                crossed = true;
+            }
+        }
+        return Ok(());
+    }
+
+    fn rename(&self, t: &mut Type) {
+        match t {
+            Type::Custom(name) | Type::Dyn(name) => match self.local_item(name) {
+                Some(mangled) => *name = mangled.clone(),
+                None => {}
+            },
+            Type::Generic(name, types) => {
+                match self.local_item(name) {
+                    Some(mangled) => *name = mangled.clone(),
+                    None => {}
+                }
+                for t in types {
+                    self.rename(t);
+                }
+            }
+            Type::Tuple(types) => {
+                for t in types {
+                    self.rename(t);
+                }
+            }
+            Type::Reference(_, inner) | Type::Pointer(_, inner) => self.rename(inner),
+            Type::Function(parameters, return_type) => {
+                for t in parameters {
+                    self.rename(t);
+                }
+                match return_type {
+                    Some(t) => self.rename(t),
+                    None => {}
+                }
+            }
+            Type::Base(_) => {}
+        }
+    }
+
+    fn rename_option(&self, t: &mut Option<Type>) {
+        match t {
+            Some(t) => self.rename(t),
+            None => {}
+        }
+    }
+
+    /// Walks an item, which is either a module item or one already taken out
+    /// of a block.
+    fn item(&mut self, ast_node: &mut ASTNode) -> Result<(), CompileError> {
+        self.line = ast_node.line;
+        let prefix = match &ast_node.node {
+            Node::Function { name, .. } => name.clone(),
+            _ => String::new(),
+        };
+        return match &mut ast_node.node {
+            node @ Node::Function { .. } => self.function(node, prefix),
+            Node::Struct { body, .. } => {
+                for (_, _, t) in body {
+                    self.rename(t);
+                }
+                Ok(())
+            }
+            Node::Enum { body, .. } => {
+                for (_, types) in body {
+                    for t in types {
+                        self.rename(t);
+                    }
+                }
+                Ok(())
+            }
+            Node::Trait { name, body, .. } => {
+                let trait_name = name.clone();
+                for method in body {
+                    self.line = method.line;
+                    let prefix = match &method.node {
+                        Node::Function { name, .. } => format!("{}::{}", trait_name, name),
+                        _ => continue,
+                    };
+                    match self.function(&mut method.node, prefix) {
+                        Ok(()) => {}
+                        Err(error) => return Err(error),
+                    }
+                }
+                Ok(())
+            }
+            Node::Impl {
+                trait_name,
+                target,
+                body,
+                ..
+            } => {
+                self.rename(target);
+                match trait_name {
+                    Some(name) => match self.local_item(name) {
+                        Some(mangled) => *name = mangled.clone(),
+                        None => {}
+                    },
+                    None => {}
+                }
+                for method in body {
+                    self.line = method.line;
+                    let prefix = match &method.node {
+                        Node::Function { name, .. } => format!("{}::{}", target, name),
+                        _ => continue,
+                    };
+                    match self.function(&mut method.node, prefix) {
+                        Ok(()) => {}
+                        Err(error) => return Err(error),
+                    }
+                }
+                Ok(())
+            }
+            Node::Constant {
+                constant_type: item_type,
+                expression,
+                ..
+            }
+            | Node::Static {
+                static_type: item_type,
+                expression,
+                ..
+            } => {
+                self.rename(item_type);
+                self.frames.push(Frame::new(Vec::new(), true));
+                let result = self.expression(expression);
+                self.frames.pop();
+                result
+            }
+            Node::TypeAlias { aliased, .. } => {
+                self.rename(aliased);
+                Ok(())
+            }
+            Node::Extern { body, .. } => {
+                for foreign in body {
+                    match &mut foreign.node {
+                        Node::ForeignFunction {
+                            parameters,
+                            return_type,
+                            ..
+                        } => {
+                            for (_, t) in parameters {
+                                self.rename(t);
+                            }
+                            self.rename_option(return_type);
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+    }
+
+    /// Walks a function with `prefix`, its own mangled name, naming the items
+    /// declared in its body.
+    fn function(&mut self, node: &mut Node, prefix: String) -> Result<(), CompileError> {
+        let (parameters, defaults, return_type, body) = match node {
+            Node::Function {
+                parameters,
+                defaults,
+                return_type,
+                body,
+                ..
+            } => (parameters, defaults, return_type, body),
+            _ => return Ok(()),
+        };
+        for (_, t) in parameters.iter_mut() {
+            self.rename(t);
+        }
+        self.rename_option(return_type);
+
+        let names = parameters.iter().map(|(name, _)| name.clone()).collect();
+        self.frames.push(Frame::new(names, true));
+        let outer = std::mem::replace(&mut self.prefix, prefix);
+        let mut result = Ok(());
+        for default in defaults.iter_mut().flatten() {
+            result = self.expression(default);
+            if result.is_err() {
+                break;
+            }
+        }
+        if result.is_ok() {
+            result = match body {
+                Some(body) => self.block(body),
+                None => Ok(()),
+            };
+        }
+        self.prefix = outer;
+        self.frames.pop();
+        return result;
+    }
+
+    /// Takes the items out of a block and walks what is left with them in
+    /// scope, which includes the statements before their declaration.
+    fn block(&mut self, body: &mut Vec<ASTNode>) -> Result<(), CompileError> {
+        let mut items = Vec::new();
+        for ast_node in std::mem::take(body) {
+            match is_local_item(&ast_node.node) {
+                true => items.push(ast_node),
+                false => body.push(ast_node),
+            }
+        }
+
+        let mut frame = Frame::new(Vec::new(), false);
+        for item in &mut items {
+            self.line = item.line;
+            let name = match item_name(&mut item.node) {
+                Some(name) => name,
+                None => continue,
+            };
+            if frame.items.contains_key(name) {
+                return Err(self.problem(BuildError::AlreadyDefined(name.clone())));
+            }
+            let prefix = self.prefix.clone();
+            let mangled = self.mangle(&prefix, name);
+            frame.items.insert(name.clone(), mangled.clone());
+            *name = mangled;
+        }
+        self.frames.push(frame);
+
+        let mut result = self.nodes(body);
+        if result.is_ok() {
+            for item in &mut items {
+                result = self.item(item);
+                if result.is_err() {
+                    break;
+                }
+            }
+        }
+        self.frames.pop();
+        self.hoisted.append(&mut items);
+        return result;
+    }
+
+    fn nodes(&mut self, body: &mut Vec<ASTNode>) -> Result<(), CompileError> {
+        for ast_node in body {
+            self.line = ast_node.line;
+            let result = match &mut ast_node.node {
+                Node::DefineVariable {
+                    name,
+                    var_type,
+                    expression,
+                    ..
+                } => {
+                    self.rename_option(var_type);
+                    let result = match expression {
+                        Some(expression) => self.expression(expression),
+                        None => Ok(()),
+                    };
+                    self.frames.last_mut().unwrap().locals.push(name.clone());
+                    result
+                }
+                Node::SetVariable(path, expression) => match self.value(path) {
+                    Ok(()) => self.expression(expression),
+                    Err(error) => Err(error),
+                },
+                Node::Call(path, arguments) | Node::MethodCall(path, _, arguments) => {
+                    match self.value(path) {
+                        Ok(()) => self.expressions(arguments),
+                        Err(error) => Err(error),
+                    }
+                }
+                Node::Return(Some(expression)) => self.expression(expression),
+                Node::InlineAsm { operands, .. } => {
+                    for operand in operands {
+                        let result = match operand {
+                            AsmOperand::In(_, expression) => self.expression(expression),
+                            AsmOperand::Out(_, Some(path)) | AsmOperand::InOut(_, path) => {
+                                self.value(path)
+                            }
+                            _ => Ok(()),
+                        };
+                        match result {
+                            Ok(()) => {}
+                            Err(error) => return Err(error),
+                        }
+                    }
+                    Ok(())
+                }
+                Node::Scope { body, .. } | Node::Loop { body } => self.block(body),
+                _ => Ok(()),
+            };
+            match result {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        return Ok(());
+    }
+
+    fn expressions(&mut self, expressions: &mut Vec<Expression>) -> Result<(), CompileError> {
+        for expression in expressions {
+            match self.expression(expression) {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        return Ok(());
+    }
+
+    fn expression(&mut self, expression: &mut Expression) -> Result<(), CompileError> {
+        return match expression {
+            Expression::Value(_) => Ok(()),
+            Expression::GetVariable(path) => self.value(path),
+            Expression::Call(path, arguments) => match self.value(path) {
+                Ok(()) => self.expressions(arguments),
+                Err(error) => Err(error),
+            },
+            Expression::BinaryOperation(a, _, b) => match self.expression(a) {
+                Ok(()) => self.expression(b),
+                Err(error) => Err(error),
+            },
+            Expression::Reference(_, inner)
+            | Expression::Field(inner, _)
+            | Expression::Try(inner)
+            | Expression::NamedArgument(_, inner) => self.expression(inner),
+            Expression::Cast(inner, t) => {
+                self.rename(t);
+                self.expression(inner)
+            }
+            Expression::MethodCall(receiver, _, arguments) => match self.expression(receiver) {
+                Ok(()) => self.expressions(arguments),
+                Err(error) => Err(error),
+            },
+            // Closures see the locals around them, so their parameters are no boundary.
+            Expression::Closure {
+                parameters,
+                return_type,
+                body,
+                ..
+            } => {
+                for (_, t) in parameters.iter_mut() {
+                    self.rename_option(t);
+                }
+                self.rename_option(return_type);
+                let names = parameters.iter().map(|(name, _)| name.clone()).collect();
+                self.frames.push(Frame::new(names, false));
+                let result = self.block(body);
+                self.frames.pop();
+                result
+            }
+        };
+    }
+}
+
+/// Gives items declared inside function bodies a meaning: each one is visible
+/// in its block, cannot use the locals of the functions around it and is
+/// moved next to the module items under a name mangled after the function,
+/// so `helper` declared in `main` becomes `main::helper` for the rest of the
+/// compiler.
+pub fn hoist_items(program: &mut Program) -> Result<(), CompileError> {
+    let mut names = Vec::new();
+    for (_, module) in &mut program.modules {
+        let path = &module.relative_path;
+        let mut hoister = Hoister {
+            relative_path: path,
+            frames: Vec::new(),
+            hoisted: Vec::new(),
+            names: &mut names,
+            prefix: String::new(),
+            line: 0,
+        };
+        for ast_node in &mut module.body {
+            match hoister.item(ast_node) {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        let mut hoisted = hoister.hoisted;
+        module.body.append(&mut hoisted);
+    }
+    return Ok(());
+}
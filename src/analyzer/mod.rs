@@ -1,5 +1,28 @@
+mod aliases;
+mod arguments;
 mod analyzer;
+mod attributes;
+mod expressions;
+mod closures;
+mod constants;
+mod format;
 mod functions;
-mod node;
+mod items;
+mod names;
+mod traits;
+mod typed;
+mod types;
+mod visibility;
 
-pub use analyzer::analyze;
\ No newline at end of file
+pub use aliases::resolve_aliases;
+pub use arguments::resolve_arguments;
+pub use analyzer::{analyze, Analysis};
+pub use attributes::{apply_attributes, check_deprecated};
+pub use expressions::{is_unknown, unknown};
+pub use format::{parse_format, Piece, Spec};
+pub use items::hoist_items;
+pub use names::{resolve_names, target_name, DefKind, Symbols};
+pub use typed::{
+    Callee, ExpressionKind, Statement, TypedAsmOperand, TypedExpression, TypedFunction, TypedNode,
+};
+pub use visibility::check_visibility;
\ No newline at end of file
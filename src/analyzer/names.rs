@@ -0,0 +1,1095 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{
+    builder::ModulePath,
+    parser::{ASTNode, AsmOperand, DefId, Expression, Node, Path, Program, Type},
+    BuildError, BuildProblem, CompileError,
+};
+
+use super::{attributes::deprecation, format::BUILTINS};
+
+/// The prelude variants that can be named without their enum, like `Some(1)`.
+const PRELUDE_VARIANTS: [&str; 4] = ["Some", "None", "Ok", "Err"];
+
+/// Types the compiler knows without a definition, like `Box<dyn Trait>`.
+const BUILTIN_TYPES: [&str; 1] = ["Box"];
+
+/// Functions of the builtin types.
+const BUILTIN_FUNCTIONS: [&str; 1] = ["Box::new"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefKind {
+    Function,
+    ForeignFunction,
+    Struct,
+    Enum,
+    Variant,
+    Trait,
+    Constant,
+    Static,
+    TypeAlias,
+    Method,
+    GenericParameter,
+    Parameter,
+    Local,
+}
+
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub name: String,
+    pub kind: DefKind,
+    pub module: ModulePath,
+    pub relative_path: PathBuf,
+    pub line: usize,
+    /// Whether the item is `pub`. Variants and trait methods are as visible
+    /// as their enum or trait.
+    pub export: bool,
+    /// Every place the definition is named, by file and line.
+    pub uses: Vec<(PathBuf, usize)>,
+    /// `Some` for an item marked `#[deprecated]`, with the note it gives.
+    pub deprecated: Option<Option<String>>,
+}
+
+/// The items a module declares, by the names it declares them with.
+#[derive(Debug, Default)]
+pub struct Namespace {
+    /// Structs, enums, traits and type aliases.
+    pub types: HashMap<String, DefId>,
+    /// Functions, constants, statics and tuple struct constructors.
+    pub values: HashMap<String, DefId>,
+}
+
+/// Every definition of the program. The prelude is walked first, then the
+/// modules in the order of their paths and each module in source order, so a
+/// build hands out the same ids as the build before it.
+#[derive(Debug)]
+pub struct Symbols {
+    /// Definitions at the index of their `DefId`.
+    pub definitions: Vec<Definition>,
+    /// What each module declares, by module path.
+    pub namespaces: HashMap<ModulePath, Namespace>,
+    /// Structs, enums, traits and type aliases by the name they go by in the
+    /// whole program. That is the name they are declared with, unless an item
+    /// of a module walked before took it, in which case their module path is
+    /// put in front, like `crate::shapes::Ordering`. Later passes know types by
+    /// this name only.
+    pub types: HashMap<String, DefId>,
+    /// Functions, constants and statics by the name they go by in the whole
+    /// program, like types.
+    pub values: HashMap<String, DefId>,
+    /// Variants and methods by `Enum::Variant` and `Target::method`, after the
+    /// name their enum or target goes by.
+    pub members: HashMap<String, DefId>,
+}
+impl Symbols {
+    pub fn definition(&self, id: DefId) -> &Definition {
+        return &self.definitions[id.0];
+    }
+    /// The value a module declares under `name`.
+    pub fn value(&self, module: &ModulePath, name: &str) -> Option<DefId> {
+        return match self.namespaces.get(module) {
+            Some(namespace) => namespace.values.get(name).copied(),
+            None => None,
+        };
+    }
+    /// The `Chars` of the prelude that `chars` on a `&str` returns, by the
+    /// name it goes by, so a struct of the program called `Chars` is never
+    /// taken for it.
+    pub fn chars(&self) -> Type {
+        let id = self.namespaces[&ModulePath::prelude()].types["Chars"];
+        return Type::Custom(self.definition(id).name.clone());
+    }
+}
+
+/// The name impl methods are kept under, like `Wrapper` for `impl<T> Wrapper<T>`.
+pub fn target_name(target: &Type) -> String {
+    return match target {
+        Type::Custom(name) | Type::Generic(name, _) => name.clone(),
+        t => t.to_string(),
+    };
+}
+
+pub fn display(path: &Path) -> String {
+    let mut name = path.root.clone();
+    for segment in &path.location {
+        name.push_str("::");
+        name.push_str(segment);
+    }
+    return name;
+}
+
+struct Resolver {
+    symbols: Symbols,
+    /// The modules each module imports, by name.
+    imports: HashMap<ModulePath, Vec<(String, ModulePath)>>,
+
+    module: ModulePath,
+    relative_path: PathBuf,
+    line: usize,
+    /// What `Self` stands for inside a trait or impl, when it is an item.
+    self_type: Option<(String, Option<DefId>)>,
+    generics: Vec<(String, DefId)>,
+    /// The locals of each block around the current node, innermost last.
+    scopes: Vec<Vec<(String, DefId)>>,
+}
+impl Resolver {
+    fn problem(&self, error: BuildError) -> CompileError {
+        return CompileError::BuildProblem(BuildProblem::new(
+            error,
+            self.relative_path.clone(),
+            self.line,
+        ));
+    }
+
+    fn define(&mut self, name: &String, kind: DefKind) -> DefId {
+        let id = DefId(self.symbols.definitions.len());
+        self.symbols.definitions.push(Definition {
+            name: name.clone(),
+            kind,
+            module: self.module.clone(),
+            relative_path: self.relative_path.clone(),
+            line: self.line,
+            export: false,
+            uses: Vec::new(),
+            deprecated: None,
+        });
+        return id;
+    }
+
+    fn redefined(&self, name: &String, previous: DefId) -> CompileError {
+        let previous = self.symbols.definition(previous);
+        return self.problem(BuildError::Redefined(
+            name.clone(),
+            previous.relative_path.clone(),
+            previous.line,
+        ));
+    }
+
+    fn namespace(&mut self, table: Table) -> &mut HashMap<String, DefId> {
+        let namespace = self.symbols.namespaces.entry(self.module.clone()).or_default();
+        return match table {
+            Table::Types => &mut namespace.types,
+            Table::Values => &mut namespace.values,
+        };
+    }
+
+    /// Defines an item in the namespace of the current module, which only
+    /// takes one definition of each name, and renames it to the name it goes
+    /// by in the whole program.
+    fn define_item(
+        &mut self,
+        table: Table,
+        name: &mut String,
+        kind: DefKind,
+        export: bool,
+    ) -> Result<DefId, CompileError> {
+        match self.namespace(table).get(name).copied() {
+            Some(previous) => return Err(self.redefined(name, previous)),
+            None => {}
+        }
+        let taken = match table {
+            Table::Types => self.symbols.types.contains_key(name),
+            Table::Values => self.symbols.values.contains_key(name),
+        };
+        let item_name = match taken {
+            true => format!("{}::{}", self.module, name),
+            false => name.clone(),
+        };
+        let id = self.define(&item_name, kind);
+        self.symbols.definitions[id.0].export = export;
+        self.namespace(table).insert(name.clone(), id);
+        match table {
+            Table::Types => self.symbols.types.insert(item_name.clone(), id),
+            Table::Values => self.symbols.values.insert(item_name.clone(), id),
+        };
+        *name = item_name;
+        return Ok(id);
+    }
+
+    /// Defines a variant or method under `Owner::member`.
+    fn define_member(&mut self, key: &String, kind: DefKind, export: bool) -> Result<DefId, CompileError> {
+        match self.symbols.members.get(key).copied() {
+            Some(previous) => return Err(self.redefined(key, previous)),
+            None => {}
+        }
+        let id = self.define(key, kind);
+        self.symbols.definitions[id.0].export = export;
+        self.symbols.members.insert(key.clone(), id);
+        return Ok(id);
+    }
+
+    /// The item `name` stands for in the current module, or in the prelude
+    /// when the module declares no such item.
+    fn lookup(&self, table: Table, name: &String) -> Option<DefId> {
+        for module in [&self.module, &ModulePath::prelude()] {
+            let namespace = match self.symbols.namespaces.get(module) {
+                Some(namespace) => namespace,
+                None => continue,
+            };
+            let found = match table {
+                Table::Types => namespace.types.get(name),
+                Table::Values => namespace.values.get(name),
+            };
+            match found {
+                Some(id) => return Some(*id),
+                None => continue,
+            }
+        }
+        return None;
+    }
+
+    fn record(&mut self, id: DefId) {
+        let site = (self.relative_path.clone(), self.line);
+        self.symbols.definitions[id.0].uses.push(site);
+    }
+
+    fn local(&self, name: &String) -> Option<DefId> {
+        for scope in self.scopes.iter().rev() {
+            match scope.iter().rev().find(|(local, _)| local == name) {
+                Some((_, id)) => return Some(*id),
+                None => continue,
+            }
+        }
+        return None;
+    }
+
+    fn declare(&mut self, name: &String, kind: DefKind) -> DefId {
+        let id = self.define(name, kind);
+        self.scopes.last_mut().unwrap().push((name.clone(), id));
+        return id;
+    }
+
+    fn parameters(&mut self, parameters: Vec<&String>) -> Result<Vec<DefId>, CompileError> {
+        let mut definitions = Vec::new();
+        for (index, name) in parameters.iter().enumerate() {
+            if parameters[..index].contains(name) {
+                return Err(self.problem(BuildError::AlreadyDefined((*name).clone())));
+            }
+            definitions.push(self.declare(name, DefKind::Parameter));
+        }
+        return Ok(definitions);
+    }
+
+    fn push_generics(&mut self, generics: &Vec<String>) {
+        for name in generics {
+            let id = self.define(name, DefKind::GenericParameter);
+            self.generics.push((name.clone(), id));
+        }
+    }
+
+    fn pop_generics(&mut self, generics: &Vec<String>) {
+        let length = self.generics.len() - generics.len();
+        self.generics.truncate(length);
+    }
+
+    /// The item `name` stands for in type position, or `None` for a builtin
+    /// type or a `Self` that is not an item, like in `impl Add for i32`.
+    fn type_name(&self, name: &String) -> Result<Option<DefId>, BuildError> {
+        match self.generics.iter().rev().find(|(generic, _)| generic == name) {
+            Some((_, id)) => return Ok(Some(*id)),
+            None => {}
+        }
+        if name == "Self" {
+            return match &self.self_type {
+                Some((_, id)) => Ok(*id),
+                None => Err(BuildError::UndefinedType(name.clone())),
+            };
+        }
+        return match self.lookup(Table::Types, name) {
+            Some(id) => Ok(Some(id)),
+            None if BUILTIN_TYPES.contains(&name.as_str()) => Ok(None),
+            None => Err(BuildError::UndefinedType(name.clone())),
+        };
+    }
+
+    /// Resolves the items a type names, renaming each to the name it goes by
+    /// in the whole program.
+    fn resolve_type(&mut self, t: &mut Type) -> Result<(), CompileError> {
+        let name = match t {
+            Type::Custom(name) | Type::Dyn(name) => name,
+            Type::Generic(name, types) => {
+                for t in types {
+                    match self.resolve_type(t) {
+                        Ok(()) => {}
+                        Err(error) => return Err(error),
+                    }
+                }
+                name
+            }
+            Type::Tuple(types) => {
+                for t in types {
+                    match self.resolve_type(t) {
+                        Ok(()) => {}
+                        Err(error) => return Err(error),
+                    }
+                }
+                return Ok(());
+            }
+            Type::Reference(_, inner) | Type::Pointer(_, inner) => return self.resolve_type(inner),
+            Type::Function(parameters, return_type) => {
+                for t in parameters {
+                    match self.resolve_type(t) {
+                        Ok(()) => {}
+                        Err(error) => return Err(error),
+                    }
+                }
+                return match return_type {
+                    Some(t) => self.resolve_type(t),
+                    None => Ok(()),
+                };
+            }
+            Type::Base(_) => return Ok(()),
+        };
+        let id = match self.type_name(name) {
+            Ok(Some(id)) => id,
+            Ok(None) => return Ok(()),
+            Err(error) => return Err(self.problem(error)),
+        };
+        // `Self` names the item the impl or trait already named.
+        if name != "Self" {
+            self.record(id);
+        }
+        let definition = self.symbols.definition(id);
+        match definition.kind {
+            DefKind::Struct | DefKind::Enum | DefKind::Trait | DefKind::TypeAlias if name != "Self" => {
+                *name = definition.name.clone();
+            }
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    fn resolve_types(&mut self, types: Vec<&mut Type>) -> Result<(), CompileError> {
+        for t in types {
+            match self.resolve_type(t) {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        return Ok(());
+    }
+
+    /// Finds what a path names. A single name is a local, an item or a
+    /// builtin. Longer paths go through imported modules, starting from the
+    /// current module or from `crate`, and can end in `Type::member`.
+    fn find(&self, path: &Path) -> Result<Option<DefId>, BuildError> {
+        if path.location.len() == 0 {
+            match self.local(&path.root) {
+                Some(id) => return Ok(Some(id)),
+                None => {}
+            }
+            if path.root == "Self" {
+                match &self.self_type {
+                    Some((_, Some(id))) => return Ok(Some(*id)),
+                    _ => {}
+                }
+            }
+            match self.lookup(Table::Values, &path.root) {
+                Some(id) => return Ok(Some(id)),
+                None => {}
+            }
+            if BUILTINS.contains(&path.root.as_str()) {
+                return Ok(None);
+            }
+            return Err(BuildError::NotDefined(path.root.clone()));
+        }
+
+        let mut segments = vec![&path.root];
+        segments.extend(path.location.iter());
+        let mut module = None;
+        let mut index = 0;
+        if path.root == "crate" {
+            module = Some(ModulePath::root());
+            index = 1;
+        }
+        while index < segments.len() - 1 {
+            let current = match &module {
+                Some(module) => module,
+                None => &self.module,
+            };
+            let imports = match self.imports.get(current) {
+                Some(imports) => imports,
+                None => break,
+            };
+            match imports.iter().find(|(name, _)| name == segments[index]) {
+                Some((_, child)) => {
+                    module = Some(child.clone());
+                    index += 1;
+                }
+                None => break,
+            }
+        }
+
+        let not_defined = BuildError::NotDefined(display(path));
+        return match (&segments[index..], &module) {
+            ([name], Some(module)) => match self.symbols.value(module, name) {
+                Some(id) => Ok(Some(id)),
+                None => Err(not_defined),
+            },
+            ([target, member], module) => {
+                let owner = match (module, target.as_str(), &self.self_type) {
+                    (Some(module), _, _) => match self.symbols.namespaces.get(module) {
+                        Some(namespace) => namespace.types.get(*target).copied(),
+                        None => None,
+                    },
+                    (None, "Self", Some((name, _))) => {
+                        let key = format!("{}::{}", name, member);
+                        return match self.symbols.members.get(&key) {
+                            Some(id) => Ok(Some(*id)),
+                            None => Err(not_defined),
+                        };
+                    }
+                    (None, _, _) => self.lookup(Table::Types, target),
+                };
+                // Methods of builtin types are kept under the name of the type.
+                let target = match owner {
+                    Some(owner) => self.symbols.definition(owner).name.clone(),
+                    None if module.is_none() => (*target).clone(),
+                    None => return Err(not_defined),
+                };
+                let key = format!("{}::{}", target, member);
+                match self.symbols.members.get(&key) {
+                    Some(id) => Ok(Some(*id)),
+                    None if owner.is_none() && BUILTIN_FUNCTIONS.contains(&key.as_str()) => Ok(None),
+                    None => Err(not_defined),
+                }
+            }
+            _ => Err(not_defined),
+        };
+    }
+
+    /// Spells a path to an item the way later passes, which know items by
+    /// name, look it up: `a::helper` becomes `helper`, or `crate::a::helper`
+    /// when that is the name the function goes by, and `a::Shape::new`
+    /// becomes `Shape::new`.
+    fn spell(&self, path: &mut Path, id: DefId) {
+        let definition = self.symbols.definition(id);
+        match definition.kind {
+            DefKind::Function | DefKind::ForeignFunction | DefKind::Struct | DefKind::Constant | DefKind::Static => {
+                path.root = definition.name.clone();
+                path.location = Vec::new();
+            }
+            // A variant named on its own, like `Some`, stays that way.
+            DefKind::Variant | DefKind::Method if path.location.len() > 0 => {
+                match definition.name.rsplit_once("::") {
+                    Some((owner, member)) => {
+                        path.root = String::from(owner);
+                        path.location = vec![String::from(member)];
+                    }
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn resolve_path(&mut self, path: &mut Path) -> Result<(), CompileError> {
+        return match self.find(path) {
+            Ok(Some(id)) => {
+                path.definition = Some(id);
+                self.spell(path, id);
+                self.record(id);
+                Ok(())
+            }
+            Ok(None) => Ok(()),
+            Err(error) => Err(self.problem(error)),
+        };
+    }
+
+    fn expressions(&mut self, expressions: &mut Vec<Expression>) -> Result<(), CompileError> {
+        for expression in expressions {
+            match self.expression(expression) {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        return Ok(());
+    }
+
+    fn expression(&mut self, expression: &mut Expression) -> Result<(), CompileError> {
+        return match expression {
+            Expression::Value(_) => Ok(()),
+            Expression::GetVariable(path) => self.resolve_path(path),
+            Expression::Call(path, arguments) => match self.resolve_path(path) {
+                Ok(()) => self.expressions(arguments),
+                Err(error) => Err(error),
+            },
+            Expression::BinaryOperation(a, _, b) => match self.expression(a) {
+                Ok(()) => self.expression(b),
+                Err(error) => Err(error),
+            },
+            Expression::Reference(_, inner)
+            | Expression::Field(inner, _)
+            | Expression::Try(inner)
+            | Expression::NamedArgument(_, inner) => self.expression(inner),
+            Expression::Cast(inner, t) => match self.expression(inner) {
+                Ok(()) => self.resolve_type(t),
+                Err(error) => Err(error),
+            },
+            Expression::MethodCall(receiver, _, arguments) => match self.expression(receiver) {
+                Ok(()) => self.expressions(arguments),
+                Err(error) => Err(error),
+            },
+            Expression::Closure {
+                parameters,
+                return_type,
+                body,
+                parameter_definitions,
+                ..
+            } => {
+                let types: Vec<&mut Type> = parameters.iter_mut().filter_map(|(_, t)| t.as_mut()).collect();
+                match self.resolve_types(types) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+                match return_type {
+                    Some(t) => match self.resolve_type(t) {
+                        Ok(()) => {}
+                        Err(error) => return Err(error),
+                    },
+                    None => {}
+                }
+                self.scopes.push(Vec::new());
+                let result = match self.parameters(parameters.iter().map(|(name, _)| name).collect()) {
+                    Ok(definitions) => {
+                        *parameter_definitions = definitions;
+                        self.block(body)
+                    }
+                    Err(error) => Err(error),
+                };
+                self.scopes.pop();
+                result
+            }
+        };
+    }
+
+    /// Resolves the statements of a block, whose locals go out of scope at
+    /// its end.
+    fn block(&mut self, body: &mut Vec<ASTNode>) -> Result<(), CompileError> {
+        let line = self.line;
+        self.scopes.push(Vec::new());
+        for ast_node in body {
+            self.line = ast_node.line;
+            let result = match &mut ast_node.node {
+                Node::DefineVariable {
+                    name,
+                    var_type,
+                    expression,
+                    definition,
+                    ..
+                } => {
+                    let result = match var_type {
+                        Some(t) => self.resolve_type(t),
+                        None => Ok(()),
+                    };
+                    // The expression can't see the local it initializes.
+                    let result = match (result, expression) {
+                        (Ok(()), Some(expression)) => self.expression(expression),
+                        (result, _) => result,
+                    };
+                    *definition = Some(self.declare(name, DefKind::Local));
+                    result
+                }
+                Node::SetVariable(path, expression) => match self.resolve_path(path) {
+                    Ok(()) => self.expression(expression),
+                    Err(error) => Err(error),
+                },
+                Node::Return(Some(expression)) => self.expression(expression),
+                Node::Call(path, arguments) | Node::MethodCall(path, _, arguments) => {
+                    match self.resolve_path(path) {
+                        Ok(()) => self.expressions(arguments),
+                        Err(error) => Err(error),
+                    }
+                }
+                Node::InlineAsm { operands, .. } => {
+                    for operand in operands {
+                        let result = match operand {
+                            AsmOperand::In(_, expression) => self.expression(expression),
+                            AsmOperand::Out(_, Some(path)) | AsmOperand::InOut(_, path) => {
+                                self.resolve_path(path)
+                            }
+                            AsmOperand::Out(_, None) | AsmOperand::Clobber(_) => Ok(()),
+                        };
+                        match result {
+                            Ok(()) => {}
+                            Err(error) => return Err(error),
+                        }
+                    }
+                    Ok(())
+                }
+                Node::Scope { body, .. } | Node::Loop { body } => self.block(body),
+                _ => Ok(()),
+            };
+            match result {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        self.scopes.pop();
+        self.line = line;
+        return Ok(());
+    }
+
+    /// Resolves the items of a module, or the methods of a trait or impl.
+    fn items(&mut self, nodes: &mut Vec<ASTNode>) -> Result<(), CompileError> {
+        for ast_node in nodes {
+            self.line = ast_node.line;
+            let result = match &mut ast_node.node {
+                Node::Function {
+                    generics,
+                    parameters,
+                    defaults,
+                    return_type,
+                    body,
+                    parameter_definitions,
+                    ..
+                } => {
+                    self.push_generics(generics);
+                    let mut types: Vec<&mut Type> = parameters.iter_mut().map(|(_, t)| t).collect();
+                    match return_type {
+                        Some(t) => types.push(t),
+                        None => {}
+                    }
+                    let result = self.resolve_types(types);
+
+                    // Defaults are resolved with the parameters in scope, so naming
+                    // one is reported as such when arguments are resolved.
+                    self.scopes.push(Vec::new());
+                    let result = match result {
+                        Ok(()) => match self.parameters(parameters.iter().map(|(name, _)| name).collect()) {
+                            Ok(definitions) => {
+                                *parameter_definitions = definitions;
+                                Ok(())
+                            }
+                            Err(error) => Err(error),
+                        },
+                        Err(error) => Err(error),
+                    };
+                    let result = match result {
+                        Ok(()) => {
+                            let mut result = Ok(());
+                            for default in defaults.iter_mut().flatten() {
+                                result = self.expression(default);
+                                if result.is_err() {
+                                    break;
+                                }
+                            }
+                            result
+                        }
+                        Err(error) => Err(error),
+                    };
+                    let result = match (result, body) {
+                        (Ok(()), Some(body)) => self.block(body),
+                        (result, _) => result,
+                    };
+                    self.scopes.pop();
+                    self.pop_generics(generics);
+                    result
+                }
+                Node::ForeignFunction {
+                    parameters,
+                    return_type,
+                    ..
+                } => {
+                    let mut types: Vec<&mut Type> = parameters.iter_mut().map(|(_, t)| t).collect();
+                    match return_type {
+                        Some(t) => types.push(t),
+                        None => {}
+                    }
+                    self.resolve_types(types)
+                }
+                Node::Struct { generics, body, .. } => {
+                    self.push_generics(generics);
+                    let result = self.resolve_types(body.iter_mut().map(|(_, _, t)| t).collect());
+                    self.pop_generics(generics);
+                    result
+                }
+                Node::Enum { generics, body, .. } => {
+                    self.push_generics(generics);
+                    let result = self.resolve_types(body.iter_mut().flat_map(|(_, types)| types).collect());
+                    self.pop_generics(generics);
+                    result
+                }
+                Node::Constant {
+                    constant_type: item_type,
+                    expression,
+                    ..
+                }
+                | Node::Static {
+                    static_type: item_type,
+                    expression,
+                    ..
+                } => match self.resolve_type(item_type) {
+                    Ok(()) => {
+                        self.scopes.push(Vec::new());
+                        let result = self.expression(expression);
+                        self.scopes.pop();
+                        result
+                    }
+                    Err(error) => Err(error),
+                },
+                Node::TypeAlias { aliased, .. } => self.resolve_type(aliased),
+                Node::Trait {
+                    name,
+                    generics,
+                    body,
+                    ..
+                } => {
+                    let id = self.symbols.types.get(name).copied();
+                    self.self_type = Some((name.clone(), id));
+                    self.push_generics(generics);
+                    let result = self.items(body);
+                    self.pop_generics(generics);
+                    self.self_type = None;
+                    result
+                }
+                // The trait and target were resolved with the methods.
+                Node::Impl {
+                    generics, target, body, ..
+                } => {
+                    self.push_generics(generics);
+                    let name = target_name(target);
+                    let id = self.symbols.types.get(&name).copied();
+                    self.self_type = Some((name, id));
+                    let result = self.items(body);
+                    self.self_type = None;
+                    self.pop_generics(generics);
+                    result
+                }
+                Node::Extern { body, .. } => self.items(body),
+                _ => Ok(()),
+            };
+            match result {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        return Ok(());
+    }
+
+    /// Defines the items of a module in its namespace, renaming the ones
+    /// whose name an earlier module took.
+    fn collect(&mut self, nodes: &mut Vec<ASTNode>) -> Result<(), CompileError> {
+        for ast_node in nodes {
+            self.line = ast_node.line;
+            let deprecated = deprecation(&ast_node.attributes);
+            let result = match &mut ast_node.node {
+                Node::Function {
+                    export,
+                    name,
+                    definition,
+                    ..
+                } => {
+                    match self.define_item(Table::Values, name, DefKind::Function, *export) {
+                        Ok(id) => {
+                            *definition = Some(id);
+                            Ok(id)
+                        }
+                        Err(error) => Err(error),
+                    }
+                }
+                Node::Extern { body, .. } => {
+                    for foreign in body {
+                        self.line = foreign.line;
+                        match &foreign.node {
+                            Node::ForeignFunction { export, name, .. } => match self.define_foreign(name, *export) {
+                                Ok(_) => {}
+                                Err(error) => return Err(error),
+                            },
+                            _ => continue,
+                        }
+                    }
+                    continue;
+                }
+                Node::Struct {
+                    export, name, tuple, ..
+                } => {
+                    let declared = name.clone();
+                    match self.define_item(Table::Types, name, DefKind::Struct, *export) {
+                        // A tuple struct names its constructor too.
+                        Ok(id) if *tuple => match self.namespace(Table::Values).get(&declared).copied() {
+                            Some(previous) => Err(self.redefined(&declared, previous)),
+                            None => {
+                                self.namespace(Table::Values).insert(declared, id);
+                                Ok(id)
+                            }
+                        },
+                        result => result,
+                    }
+                }
+                Node::Enum {
+                    export, name, body, ..
+                } => {
+                    match self.define_item(Table::Types, name, DefKind::Enum, *export) {
+                        Ok(id) => self.symbols.definitions[id.0].deprecated = deprecated,
+                        Err(error) => return Err(error),
+                    }
+                    for (variant, _) in body {
+                        let key = format!("{}::{}", name, variant);
+                        let id = match self.define_member(&key, DefKind::Variant, *export) {
+                            Ok(id) => id,
+                            Err(error) => return Err(error),
+                        };
+                        if self.module == ModulePath::prelude() && PRELUDE_VARIANTS.contains(&variant.as_str()) {
+                            self.namespace(Table::Values).insert(variant.clone(), id);
+                        }
+                    }
+                    continue;
+                }
+                Node::Trait {
+                    export, name, body, ..
+                } => {
+                    match self.define_item(Table::Types, name, DefKind::Trait, *export) {
+                        Ok(id) => self.symbols.definitions[id.0].deprecated = deprecated,
+                        Err(error) => return Err(error),
+                    }
+                    for method in body {
+                        self.line = method.line;
+                        let deprecated = deprecation(&method.attributes);
+                        match &mut method.node {
+                            Node::Function {
+                                name: method,
+                                definition,
+                                ..
+                            } => {
+                                let key = format!("{}::{}", name, method);
+                                match self.define_member(&key, DefKind::Method, *export) {
+                                    Ok(id) => {
+                                        self.symbols.definitions[id.0].deprecated = deprecated;
+                                        *definition = Some(id);
+                                    }
+                                    Err(error) => return Err(error),
+                                }
+                            }
+                            _ => continue,
+                        }
+                    }
+                    continue;
+                }
+                Node::Constant { export, name, .. } => {
+                    self.define_item(Table::Values, name, DefKind::Constant, *export)
+                }
+                Node::Static { export, name, .. } => self.define_item(Table::Values, name, DefKind::Static, *export),
+                Node::TypeAlias { export, name, .. } => {
+                    self.define_item(Table::Types, name, DefKind::TypeAlias, *export)
+                }
+                _ => continue,
+            };
+            match result {
+                Ok(id) => self.symbols.definitions[id.0].deprecated = deprecated,
+                Err(error) => return Err(error),
+            }
+        }
+        return Ok(());
+    }
+
+    /// Defines a function of an `extern` block, which keeps its name as the
+    /// linker knows it by. Modules declaring the same function share it.
+    fn define_foreign(&mut self, name: &String, export: bool) -> Result<DefId, CompileError> {
+        match self.namespace(Table::Values).get(name).copied() {
+            Some(previous) => return Err(self.redefined(name, previous)),
+            None => {}
+        }
+        let id = match self.symbols.values.get(name) {
+            Some(id) if self.symbols.definition(*id).kind == DefKind::ForeignFunction => *id,
+            _ => {
+                let id = self.define(name, DefKind::ForeignFunction);
+                self.symbols.definitions[id.0].export = export;
+                self.symbols.values.entry(name.clone()).or_insert(id);
+                id
+            }
+        };
+        self.namespace(Table::Values).insert(name.clone(), id);
+        return Ok(id);
+    }
+
+    /// Resolves the trait and target of the impls of a module and defines
+    /// their methods, once the types of every module are known. Returns the
+    /// methods of trait impls, which are only reached through their type
+    /// when no inherent method takes the name.
+    fn collect_impls(&mut self, nodes: &mut Vec<ASTNode>) -> Result<Vec<(String, DefId)>, CompileError> {
+        let mut trait_methods = Vec::new();
+        for ast_node in nodes {
+            self.line = ast_node.line;
+            let (generics, trait_name, target, body) = match &mut ast_node.node {
+                Node::Impl {
+                    generics,
+                    trait_name,
+                    target,
+                    body,
+                } => (generics, trait_name, target, body),
+                _ => continue,
+            };
+            self.push_generics(generics);
+            let result = match trait_name {
+                Some(trait_name) => match self.lookup(Table::Types, trait_name) {
+                    Some(id) if self.symbols.definition(id).kind == DefKind::Trait => {
+                        self.record(id);
+                        *trait_name = self.symbols.definition(id).name.clone();
+                        Ok(())
+                    }
+                    _ => Err(self.problem(BuildError::UndefinedTrait(trait_name.clone()))),
+                },
+                None => Ok(()),
+            };
+            let result = match result {
+                Ok(()) => self.resolve_type(target),
+                Err(error) => Err(error),
+            };
+            self.pop_generics(generics);
+            match result {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+
+            let target = target_name(target);
+            for method in body {
+                self.line = method.line;
+                let deprecated = deprecation(&method.attributes);
+                let (export, name, definition) = match &mut method.node {
+                    Node::Function {
+                        export,
+                        name,
+                        definition,
+                        ..
+                    } => (*export, name, definition),
+                    _ => continue,
+                };
+                let key = format!("{}::{}", target, name);
+                let id = match trait_name {
+                    // Trait methods are as visible as their trait.
+                    Some(_) => {
+                        let id = self.define(&key, DefKind::Method);
+                        self.symbols.definitions[id.0].export = true;
+                        trait_methods.push((key, id));
+                        id
+                    }
+                    None => match self.define_member(&key, DefKind::Method, export) {
+                        Ok(id) => id,
+                        Err(error) => return Err(error),
+                    },
+                };
+                self.symbols.definitions[id.0].deprecated = deprecated;
+                *definition = Some(id);
+            }
+        }
+        return Ok(trait_methods);
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Table {
+    Types,
+    Values,
+}
+
+/// Gives every item, parameter and local a `DefId` and points every path at
+/// the definition it names. Each module has a namespace of its own: a name
+/// is looked up in the current module and then in the prelude, whose items
+/// the module's shadow, and other modules are reached through the paths of
+/// imports. Locals are scoped to their block and shadow what was defined
+/// before them.
+pub fn resolve_names(program: &mut Program) -> Result<Symbols, CompileError> {
+    let mut order: Vec<ModulePath> = program.modules.keys().cloned().collect();
+    // The prelude goes first, so its items keep the names the compiler knows them by.
+    order.sort_by_key(|module| (module != &ModulePath::prelude(), module.clone()));
+
+    let mut resolver = Resolver {
+        symbols: Symbols {
+            definitions: Vec::new(),
+            namespaces: HashMap::new(),
+            types: HashMap::new(),
+            values: HashMap::new(),
+            members: HashMap::new(),
+        },
+        imports: HashMap::new(),
+        module: ModulePath::root(),
+        relative_path: PathBuf::new(),
+        line: 0,
+        self_type: None,
+        generics: Vec::new(),
+        scopes: Vec::new(),
+    };
+
+    for module_path in &order {
+        let module = program.modules.get_mut(module_path).unwrap();
+        let imports = module
+            .imports
+            .iter()
+            .map(|import| (import.name.clone(), import.module.clone()))
+            .collect();
+        resolver.imports.insert(module_path.clone(), imports);
+
+        resolver.module = module_path.clone();
+        resolver.relative_path = module.relative_path.clone();
+        match resolver.collect(&mut module.body) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+    }
+
+    let mut trait_methods = Vec::new();
+    for module_path in &order {
+        let module = program.modules.get_mut(module_path).unwrap();
+        resolver.module = module_path.clone();
+        resolver.relative_path = module.relative_path.clone();
+        match resolver.collect_impls(&mut module.body) {
+            Ok(methods) => trait_methods.extend(methods),
+            Err(error) => return Err(error),
+        }
+    }
+    for (key, id) in trait_methods {
+        resolver.symbols.members.entry(key).or_insert(id);
+    }
+
+    for module_path in &order {
+        let module = program.modules.get_mut(module_path).unwrap();
+        resolver.module = module_path.clone();
+        resolver.relative_path = module.relative_path.clone();
+        match resolver.items(&mut module.body) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+    }
+    return Ok(resolver.symbols);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        builder::{compile_error, compile_files, compile_source},
+        BuildError, CompileError,
+    };
+
+    #[test]
+    fn items_shadow_the_prelude() {
+        let source = "struct Ordering(i32);\n\nfn main() {\n    let o = Ordering(1);\n    let x = o.0 + 1;\n}\n";
+        assert!(compile_source(source).is_ok());
+    }
+
+    #[test]
+    fn modules_have_namespaces_of_their_own() {
+        let main = "import a;\n\nstruct S(i32, i32);\n\nfn helper(): i32 {\n    return 1;\n}\n\nfn main() {\n    let s = S(1, 2);\n    let t = a::S(3);\n    let x = helper() + a::helper() + s.1;\n}\n";
+        let a = "pub struct S(pub i64);\n\npub fn helper(): i32 {\n    return 2;\n}\n";
+        let compiled = compile_files(&[("src/main.eclipse", main), ("src/a.eclipse", a)]);
+        assert!(compiled.is_ok());
+    }
+
+    #[test]
+    fn unqualified_names_stay_in_their_module() {
+        let main = "import a;\n\nfn main() {\n    helper();\n}\n";
+        let a = "pub fn helper() {}\n";
+        match compile_files(&[("src/main.eclipse", main), ("src/a.eclipse", a)]) {
+            Err(CompileError::BuildProblem(problem)) => {
+                assert!(matches!(problem.error, BuildError::NotDefined(name) if name == "helper"))
+            }
+            _ => panic!("expected `helper` to be undefined in `main`"),
+        }
+    }
+
+    #[test]
+    fn redefinitions_point_at_the_first() {
+        let error = compile_error("fn f() {}\n\nfn f() {}\n\nfn main() {}\n");
+        assert!(matches!(error, BuildError::Redefined(name, _, 1) if name == "f"));
+    }
+}
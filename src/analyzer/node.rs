@@ -1,3 +0,0 @@
-pub enum IRNode {
-    
-}
\ No newline at end of file
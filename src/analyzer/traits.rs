@@ -0,0 +1,472 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{
+    parser::{ASTNode, Node, Program, Type},
+    BuildError, BuildProblem, CompileError,
+};
+
+use super::types::substitute;
+
+#[derive(Debug)]
+pub struct TraitMethod {
+    pub name: String,
+    pub generics: Vec<String>,
+    pub parameters: Vec<(String, Type)>,
+    pub return_type: Option<Type>,
+    pub has_default: bool,
+}
+
+#[derive(Debug)]
+pub struct Trait {
+    pub name: String,
+    pub methods: Vec<TraitMethod>,
+    relative_path: PathBuf,
+    line: usize,
+}
+impl Trait {
+    /// Returns the first method that prevents the trait from being used as
+    /// `dyn Trait`, together with the reason.
+    pub fn object_safety_violation(&self) -> Option<(&TraitMethod, &'static str)> {
+        for method in &self.methods {
+            if method.generics.len() > 0 {
+                return Some((method, "has generic parameters"));
+            }
+            match method.parameters.first() {
+                Some((name, t)) if name == "self" => match t {
+                    Type::Reference(_, _) => {}
+                    _ => return Some((method, "takes `self` by value")),
+                },
+                _ => return Some((method, "has no `self` receiver")),
+            }
+            for (_, t) in method.parameters.iter().skip(1) {
+                if mentions_self(t) {
+                    return Some((method, "uses `Self` as a parameter type"));
+                }
+            }
+            match &method.return_type {
+                Some(t) if mentions_self(t) => {
+                    return Some((method, "returns `Self`"));
+                }
+                _ => {}
+            }
+        }
+        return None;
+    }
+}
+
+#[derive(Debug)]
+pub struct Implementation {
+    pub trait_name: String,
+    pub target: Type,
+}
+
+/// Every trait and trait implementation in the program, keyed by trait name.
+#[derive(Debug)]
+pub struct Traits {
+    pub traits: HashMap<String, Trait>,
+    pub implementations: Vec<Implementation>,
+}
+impl Traits {
+    pub fn find_implementation(&self, trait_name: &String, target: &Type) -> Option<&Implementation> {
+        return self
+            .implementations
+            .iter()
+            .find(|implementation| &implementation.trait_name == trait_name && &implementation.target == target);
+    }
+}
+
+fn mentions_self(t: &Type) -> bool {
+    return match t {
+        Type::Custom(name) => name == "Self",
+        Type::Base(_) | Type::Dyn(_) => false,
+        Type::Tuple(types) | Type::Generic(_, types) => types.iter().any(mentions_self),
+        Type::Reference(_, inner) | Type::Pointer(_, inner) => mentions_self(inner),
+        Type::Function(parameters, return_type) => {
+            parameters.iter().any(mentions_self)
+                || match return_type {
+                    Some(t) => mentions_self(t),
+                    None => false,
+                }
+        }
+    };
+}
+
+/// A method signature the way errors show it, like `fn(&self, i32) -> bool`.
+fn show_signature(names: &Vec<(String, Type)>, parameters: &Vec<Type>, return_type: &Option<Type>) -> String {
+    let parameters: Vec<String> = names
+        .iter()
+        .zip(parameters)
+        .map(|((name, _), t)| match (name.as_str(), t) {
+            ("self", Type::Reference(false, _)) => String::from("&self"),
+            ("self", Type::Reference(true, _)) => String::from("&mut self"),
+            ("self", _) => String::from("self"),
+            _ => t.to_string(),
+        })
+        .collect();
+    return match return_type {
+        Some(t) => format!("fn({}) -> {}", parameters.join(", "), t),
+        None => format!("fn({})", parameters.join(", ")),
+    };
+}
+
+/// Checks that a method of an impl has the signature the trait declares,
+/// with `Self` standing for the target and generics matched by position.
+fn check_signature(
+    trait_name: &String,
+    method: &TraitMethod,
+    target: &Type,
+    generics: &Vec<String>,
+    parameters: &Vec<(String, Type)>,
+    return_type: &Option<Type>,
+) -> Result<(), BuildError> {
+    let mut expected = HashMap::from([(String::from("Self"), target.clone())]);
+    let mut found = expected.clone();
+    for (index, (name, generic)) in method.generics.iter().zip(generics).enumerate() {
+        let placeholder = Type::Custom(format!("T{}", index));
+        expected.insert(name.clone(), placeholder.clone());
+        found.insert(generic.clone(), placeholder);
+    }
+    let signature = |parameters: &Vec<(String, Type)>, return_type: &Option<Type>, bindings| {
+        let types: Vec<Type> = parameters.iter().map(|(_, t)| substitute(t, bindings)).collect();
+        return (types, return_type.as_ref().map(|t| substitute(t, bindings)));
+    };
+    let expected = signature(&method.parameters, &method.return_type, &expected);
+    let found = signature(parameters, return_type, &found);
+    let receiver = |parameters: &Vec<(String, Type)>| match parameters.first() {
+        Some((name, _)) => name == "self",
+        None => false,
+    };
+    let same_receiver = receiver(&method.parameters) == receiver(parameters);
+    if expected == found && same_receiver && method.generics.len() == generics.len() {
+        return Ok(());
+    }
+    return Err(BuildError::IncompatibleTraitMethod(
+        trait_name.clone(),
+        method.name.clone(),
+        show_signature(&method.parameters, &expected.0, &expected.1),
+        show_signature(parameters, &found.0, &found.1),
+    ));
+}
+
+fn problem(error: BuildError, relative_path: &PathBuf, line: usize) -> CompileError {
+    return CompileError::BuildProblem(BuildProblem::new(error, relative_path.clone(), line));
+}
+
+pub fn collect_traits(program: &Program) -> Result<Traits, CompileError> {
+    let mut traits = Traits {
+        traits: HashMap::new(),
+        implementations: Vec::new(),
+    };
+
+    for (_, module) in &program.modules {
+        let path = &module.relative_path;
+        for ast_node in &module.body {
+            match &ast_node.node {
+                Node::Trait { name, body, .. } => {
+                    let mut methods = Vec::new();
+                    for method in body {
+                        match &method.node {
+                            Node::Function {
+                                name,
+                                generics,
+                                parameters,
+                                return_type,
+                                body,
+                                ..
+                            } => methods.push(TraitMethod {
+                                name: name.clone(),
+                                generics: generics.clone(),
+                                parameters: parameters.clone(),
+                                return_type: return_type.clone(),
+                                has_default: body.is_some(),
+                            }),
+                            _ => continue,
+                        }
+                    }
+
+                    let definition = Trait {
+                        name: name.clone(),
+                        methods,
+                        relative_path: path.clone(),
+                        line: ast_node.line,
+                    };
+                    match traits.traits.insert(name.clone(), definition) {
+                        Some(_) => {
+                            return Err(problem(
+                                BuildError::AlreadyDefined(name.clone()),
+                                path,
+                                ast_node.line,
+                            ))
+                        }
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    for (_, module) in &program.modules {
+        let path = &module.relative_path;
+        for ast_node in &module.body {
+            let (trait_name, target, body) = match &ast_node.node {
+                Node::Impl {
+                    trait_name: Some(trait_name),
+                    target,
+                    body,
+                    ..
+                } => (trait_name, target, body),
+                _ => continue,
+            };
+
+            let definition = match traits.traits.get(trait_name) {
+                Some(definition) => definition,
+                None => {
+                    return Err(problem(
+                        BuildError::UndefinedTrait(trait_name.clone()),
+                        path,
+                        ast_node.line,
+                    ))
+                }
+            };
+
+            let mut methods = Vec::new();
+            for method in body {
+                match &method.node {
+                    Node::Function {
+                        name,
+                        generics,
+                        parameters,
+                        return_type,
+                        body,
+                        ..
+                    } => {
+                        if body.is_none() {
+                            return Err(problem(
+                                BuildError::MissingFunctionBody(name.clone()),
+                                path,
+                                method.line,
+                            ));
+                        }
+                        let declared = match definition.methods.iter().find(|m| &m.name == name) {
+                            Some(declared) => declared,
+                            None => {
+                                return Err(problem(
+                                    BuildError::NotATraitMethod(trait_name.clone(), name.clone()),
+                                    path,
+                                    method.line,
+                                ))
+                            }
+                        };
+                        match check_signature(trait_name, declared, target, generics, parameters, return_type) {
+                            Ok(()) => {}
+                            Err(error) => return Err(problem(error, path, method.line)),
+                        }
+                        methods.push(name.clone());
+                    }
+                    _ => continue,
+                }
+            }
+            for method in &definition.methods {
+                if !method.has_default && !methods.contains(&method.name) {
+                    return Err(problem(
+                        BuildError::MissingTraitMethod(
+                            trait_name.clone(),
+                            method.name.clone(),
+                            target.to_string(),
+                        ),
+                        path,
+                        ast_node.line,
+                    ));
+                }
+            }
+
+            if traits.find_implementation(trait_name, target).is_some() {
+                return Err(problem(
+                    BuildError::AlreadyDefined(format!("impl {} for {}", trait_name, target)),
+                    path,
+                    ast_node.line,
+                ));
+            }
+            traits.implementations.push(Implementation {
+                trait_name: trait_name.clone(),
+                target: target.clone(),
+            });
+        }
+    }
+
+    for (_, module) in &program.modules {
+        let path = &module.relative_path;
+        match check_trait_objects(&traits, path, &module.body) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+    }
+
+    return Ok(traits);
+}
+
+/// Checks that every `dyn Trait` written in the module names an object safe trait.
+fn check_trait_objects(
+    traits: &Traits,
+    relative_path: &PathBuf,
+    nodes: &Vec<ASTNode>,
+) -> Result<(), CompileError> {
+    for ast_node in nodes {
+        let mut types: Vec<&Type> = Vec::new();
+        match &ast_node.node {
+            Node::Function {
+                parameters,
+                return_type,
+                body,
+                ..
+            } => {
+                for (_, t) in parameters {
+                    types.push(t);
+                }
+                match return_type {
+                    Some(t) => types.push(t),
+                    None => {}
+                }
+                match body {
+                    Some(body) => match check_trait_objects(traits, relative_path, body) {
+                        Ok(()) => {}
+                        Err(error) => return Err(error),
+                    },
+                    None => {}
+                }
+            }
+            Node::Struct { body, .. } => {
+                for (_, _, t) in body {
+                    types.push(t);
+                }
+            }
+            Node::Enum { body, .. } => {
+                for (_, variant) in body {
+                    for t in variant {
+                        types.push(t);
+                    }
+                }
+            }
+            Node::DefineVariable {
+                var_type: Some(t), ..
+            } => types.push(t),
+            Node::Trait { body, .. } => match check_trait_objects(traits, relative_path, body) {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            },
+            Node::Impl { target, body, .. } => {
+                types.push(target);
+                match check_trait_objects(traits, relative_path, body) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            Node::Loop { body } | Node::Scope { body, .. } => {
+                match check_trait_objects(traits, relative_path, body) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            _ => continue,
+        }
+
+        for t in types {
+            match check_type(traits, t) {
+                Ok(()) => {}
+                Err(error) => return Err(problem(error, relative_path, ast_node.line)),
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+fn check_type(traits: &Traits, t: &Type) -> Result<(), BuildError> {
+    return match t {
+        Type::Dyn(name) => match traits.traits.get(name) {
+            Some(definition) => match definition.object_safety_violation() {
+                Some((method, reason)) => Err(BuildError::NotObjectSafe(
+                    definition.name.clone(),
+                    method.name.clone(),
+                    String::from(reason),
+                    definition.relative_path.clone(),
+                    definition.line,
+                )),
+                None => Ok(()),
+            },
+            None => Err(BuildError::UndefinedTrait(name.clone())),
+        },
+        Type::Tuple(types) | Type::Generic(_, types) => {
+            for t in types {
+                match check_type(traits, t) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            Ok(())
+        }
+        Type::Reference(_, inner) | Type::Pointer(_, inner) => check_type(traits, inner),
+        Type::Function(parameters, return_type) => {
+            for t in parameters {
+                match check_type(traits, t) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            match return_type {
+                Some(t) => check_type(traits, t),
+                None => Ok(()),
+            }
+        }
+        Type::Custom(_) | Type::Base(_) => Ok(()),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        builder::{compile_error, compile_source},
+        BuildError,
+    };
+
+    const SHAPE: &str = "trait Shape {\n    fn area(&self): i32;\n    fn keep<U>(&self, u: U): U;\n}\n\nstruct Square(i32);\n\n";
+
+    fn implement(methods: &str) -> String {
+        return format!("{}impl Shape for Square {{\n{}}}\n\nfn main() {{}}\n", SHAPE, methods);
+    }
+
+    const KEEP: &str = "    fn keep<V>(&self, v: V): V {\n        return v;\n    }\n";
+
+    #[test]
+    fn impl_methods_match_the_trait() {
+        let area = "    fn area(&self): i32 {\n        return self.0;\n    }\n";
+        assert!(compile_source(&implement(&format!("{}{}", area, KEEP))).is_ok());
+    }
+
+    #[test]
+    fn impl_methods_keep_the_parameters_and_return_type() {
+        let area = "    fn area(&self, x: i32): bool {\n        return true;\n    }\n";
+        let error = compile_error(&implement(&format!("{}{}", area, KEEP)));
+        assert!(matches!(error, BuildError::IncompatibleTraitMethod(_, method, expected, found)
+            if method == "area" && expected == "fn(&self) -> i32" && found == "fn(&self, i32) -> bool"));
+    }
+
+    #[test]
+    fn impl_methods_keep_the_receiver() {
+        let area = "    fn area(&mut self): i32 {\n        return 1;\n    }\n";
+        let error = compile_error(&implement(&format!("{}{}", area, KEEP)));
+        assert!(matches!(error, BuildError::IncompatibleTraitMethod(_, _, _, found) if found == "fn(&mut self) -> i32"));
+        let area = "    fn area(square: &Square): i32 {\n        return 1;\n    }\n";
+        let error = compile_error(&implement(&format!("{}{}", area, KEEP)));
+        assert!(matches!(error, BuildError::IncompatibleTraitMethod(_, _, _, found) if found == "fn(&Square) -> i32"));
+    }
+
+    #[test]
+    fn operator_impls_keep_their_signature() {
+        let source = "struct V(i32);\n\nimpl Add for V {\n    fn add(self, other: i32): bool {\n        return true;\n    }\n}\n\nfn main() {}\n";
+        let error = compile_error(source);
+        assert!(matches!(error, BuildError::IncompatibleTraitMethod(trait_name, _, expected, _)
+            if trait_name == "Add" && expected == "fn(self, V) -> V"));
+    }
+}
@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+
+use crate::parser::{DefId, Operator, Type, Value};
+
+/// A function body with the type of every expression in it, as the type
+/// checker leaves it for the stages after it.
+#[derive(Debug, Clone)]
+pub struct TypedFunction {
+    /// The name of a free function, or `Target::method` for a method.
+    pub name: String,
+    pub definition: DefId,
+    pub relative_path: PathBuf,
+    pub line: usize,
+    pub parameters: Vec<(DefId, Type)>,
+    /// Which parameters are declared `mut`, in the order of `parameters`.
+    pub mutable_parameters: Vec<bool>,
+    pub return_type: Option<Type>,
+    pub body: Vec<TypedNode>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypedNode {
+    pub line: usize,
+    pub statement: Statement,
+}
+
+#[derive(Debug, Clone)]
+pub enum Statement {
+    /// A `let`, whose type is the annotation or else the type of its value.
+    DefineVariable {
+        local: DefId,
+        mutable: bool,
+        local_type: Type,
+        expression: Option<TypedExpression>,
+    },
+    /// An assignment to a local, a parameter or a static.
+    SetVariable(DefId, TypedExpression),
+    Expression(TypedExpression),
+    Return(Option<TypedExpression>),
+    Scope(Vec<TypedNode>),
+    Loop(Vec<TypedNode>),
+    InlineAsm {
+        template: Vec<String>,
+        operands: Vec<TypedAsmOperand>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum TypedAsmOperand {
+    In(String, TypedExpression),
+    Out(String, Option<DefId>),
+    InOut(String, DefId),
+    Clobber(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct TypedExpression {
+    pub kind: ExpressionKind,
+    /// `None` for calls of functions that return nothing. Types that could
+    /// not be known, like the `T` of a `None` nothing constrains, are `_`.
+    pub t: Option<Type>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExpressionKind {
+    Value(Value),
+    /// A local or a parameter.
+    Local(DefId),
+    /// A function, constant, static or unit variant named as a value.
+    Item(DefId),
+    Call(Callee, Vec<TypedExpression>),
+    MethodCall {
+        receiver: Box<TypedExpression>,
+        method: String,
+        /// The method called, or `None` for the builtin methods of `&str` and `Chars`.
+        definition: Option<DefId>,
+        arguments: Vec<TypedExpression>,
+    },
+    BinaryOperation(Box<TypedExpression>, Operator, Box<TypedExpression>),
+    Reference(bool, Box<TypedExpression>),
+    Field(Box<TypedExpression>, String),
+    Cast(Box<TypedExpression>),
+    Try(Box<TypedExpression>),
+    Closure {
+        is_move: bool,
+        parameters: Vec<(DefId, Type)>,
+        body: Vec<TypedNode>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum Callee {
+    /// A function, a method, a tuple struct or an enum variant.
+    Item(DefId),
+    /// A closure or function pointer held by a local or parameter.
+    Local(DefId),
+    /// `print`, `println` or `format`.
+    Builtin(String),
+}
@@ -0,0 +1,2296 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{
+    builder::ModulePath,
+    parser::{ASTNode, AsmOperand, BaseType, DefId, Expression, Node, Path, Program, Type, Value},
+    BuildError, BuildProblem, CompileError,
+};
+
+use super::{
+    constants::integer_range,
+    expressions::{check_cast, check_literal_cast, check_operator, is_integer, is_unknown, str_slice, unknown},
+    format::{parse_format, Piece, Spec, BUILTINS},
+    names::{display, target_name, DefKind, Symbols},
+    traits::Traits,
+    typed::{Callee, ExpressionKind, Statement, TypedAsmOperand, TypedExpression, TypedFunction, TypedNode},
+};
+
+/// What a call needs to know about a function or method.
+#[derive(Clone)]
+struct Signature {
+    name: String,
+    /// The generics of the function, and of the impl it is in. Trait methods
+    /// take `Self` as one more, bound by their receiver.
+    generics: Vec<String>,
+    /// Including `self` for methods.
+    parameters: Vec<(String, Type)>,
+    return_type: Option<Type>,
+    variadic: bool,
+    /// The target of the impl a method is in.
+    self_type: Option<Type>,
+}
+
+/// An `as` cast, checked once the type of what is cast is known.
+struct Cast {
+    from: Type,
+    to: Type,
+    /// The integer literal that is cast, which can become types other integers cannot.
+    literal: Option<Value>,
+    is_unsafe: bool,
+    line: usize,
+}
+
+/// The registers an `asm!` operand can name. `rsp` and `rbp` hold the stack
+/// frame the locals live in, so they are kept out of the list.
+const ASM_REGISTERS: [&str; 14] = [
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+];
+
+/// Whether a value of the type fits in one general purpose register.
+fn fits_register(t: &Type) -> bool {
+    return match t {
+        Type::Base(BaseType::Str) => false,
+        Type::Base(base) => !base.is_wide(),
+        Type::Pointer(_, _) => true,
+        Type::Reference(_, inner) => !matches!(inner.as_ref(), Type::Base(BaseType::Str)),
+        _ => false,
+    };
+}
+
+/// Rejects operands that name a register that cannot be used or that is
+/// already read or written by another operand.
+fn check_registers(operands: &Vec<AsmOperand>) -> Result<(), BuildError> {
+    let mut inputs: Vec<&String> = Vec::new();
+    let mut outputs: Vec<&String> = Vec::new();
+    for operand in operands {
+        let register = match operand {
+            AsmOperand::In(register, _)
+            | AsmOperand::Out(register, _)
+            | AsmOperand::InOut(register, _)
+            | AsmOperand::Clobber(register) => register,
+        };
+        if register == "rsp" || register == "rbp" {
+            return Err(BuildError::ReservedRegister(register.clone()));
+        }
+        if !ASM_REGISTERS.contains(&register.as_str()) {
+            return Err(BuildError::UnknownRegister(register.clone()));
+        }
+        let (is_input, is_output) = match operand {
+            AsmOperand::In(_, _) => (true, false),
+            AsmOperand::InOut(_, _) => (true, true),
+            AsmOperand::Out(_, _) | AsmOperand::Clobber(_) => (false, true),
+        };
+        if (is_input && inputs.contains(&register)) || (is_output && outputs.contains(&register)) {
+            return Err(BuildError::DuplicateAsmRegister(register.clone()));
+        }
+        if is_input {
+            inputs.push(register);
+        }
+        if is_output {
+            outputs.push(register);
+        }
+    }
+    return Ok(());
+}
+
+/// Every base type and string can be displayed, only integers have a hex,
+/// binary or octal form. References are formatted as what they point to.
+fn is_formattable(t: &Type, spec: Spec) -> bool {
+    if t.is_str_slice() {
+        return spec == Spec::Display;
+    }
+    return match (t, spec) {
+        (Type::Reference(_, inner), spec) => is_formattable(inner, spec),
+        (Type::Base(BaseType::Str), _) => false,
+        (Type::Base(_), Spec::Display) => true,
+        (t, _) => is_integer(t),
+    };
+}
+
+/// The placeholders of the format string a call of `print`, `println` or
+/// `format` starts with, which has to be a string literal.
+fn format_specs(name: &String, arguments: &Vec<Expression>) -> Result<Vec<Spec>, BuildError> {
+    let format = match arguments.first() {
+        Some(Expression::Value(Value::String(format))) => format,
+        // `println()` only prints a newline.
+        None if name == "println" => return Ok(Vec::new()),
+        _ => return Err(BuildError::FormatStringExpected(name.clone())),
+    };
+    let pieces = match parse_format(format) {
+        Ok(pieces) => pieces,
+        Err(error) => return Err(error),
+    };
+    let specs: Vec<Spec> = pieces
+        .iter()
+        .filter_map(|piece| match piece {
+            Piece::Placeholder(spec) => Some(*spec),
+            Piece::Literal(_) => None,
+        })
+        .collect();
+    if specs.len() != arguments.len() - 1 {
+        return Err(BuildError::FormatArgumentCount(specs.len(), arguments.len() - 1));
+    }
+    return Ok(specs);
+}
+
+/// A struct, or an enum variant with the enum it belongs to.
+#[derive(Clone)]
+struct Shape {
+    name: String,
+    generics: Vec<String>,
+    fields: Vec<(String, Type)>,
+}
+
+/// A type that is found from how a value is used later in the function, like
+/// the `T` of `None` in `let x = None;`. Variables of integer literals can
+/// only become integers and are `i32` when nothing else says so.
+struct Variable {
+    integer: bool,
+    bound: Option<Type>,
+}
+
+fn unit() -> Type {
+    return Type::Tuple(Vec::new());
+}
+
+/// Variables are named `?0`, `?1`, ..., which no type written in source can be.
+fn variable_type(index: usize) -> Type {
+    return Type::Custom(format!("?{}", index));
+}
+
+fn variable_index(t: &Type) -> Option<usize> {
+    return match t {
+        Type::Custom(name) => match name.strip_prefix('?') {
+            Some(index) => index.parse().ok(),
+            None => None,
+        },
+        _ => None,
+    };
+}
+
+pub fn substitute(t: &Type, bindings: &HashMap<String, Type>) -> Type {
+    let all = |types: &Vec<Type>| types.iter().map(|t| substitute(t, bindings)).collect();
+    return match t {
+        Type::Custom(name) => match bindings.get(name) {
+            Some(bound) => bound.clone(),
+            None => t.clone(),
+        },
+        Type::Generic(name, types) => Type::Generic(name.clone(), all(types)),
+        Type::Tuple(types) => Type::Tuple(all(types)),
+        Type::Reference(mutable, inner) => Type::Reference(*mutable, Box::new(substitute(inner, bindings))),
+        Type::Pointer(mutable, inner) => Type::Pointer(*mutable, Box::new(substitute(inner, bindings))),
+        Type::Function(parameters, return_type) => Type::Function(
+            all(parameters),
+            return_type.as_ref().map(|t| Box::new(substitute(t, bindings))),
+        ),
+        Type::Base(_) | Type::Dyn(_) => t.clone(),
+    };
+}
+
+/// Applies `change` to every type inside `t`, innermost first.
+fn map_type(t: &Type, change: &mut dyn FnMut(Type) -> Type) -> Type {
+    let t = match t {
+        Type::Generic(name, types) => Type::Generic(name.clone(), types.iter().map(|t| map_type(t, change)).collect()),
+        Type::Tuple(types) => Type::Tuple(types.iter().map(|t| map_type(t, change)).collect()),
+        Type::Reference(mutable, inner) => Type::Reference(*mutable, Box::new(map_type(inner, change))),
+        Type::Pointer(mutable, inner) => Type::Pointer(*mutable, Box::new(map_type(inner, change))),
+        Type::Function(parameters, return_type) => Type::Function(
+            parameters.iter().map(|t| map_type(t, change)).collect(),
+            return_type.as_ref().map(|t| Box::new(map_type(t, change))),
+        ),
+        t => t.clone(),
+    };
+    return change(t);
+}
+
+fn is_integer_literal(expression: &Expression) -> bool {
+    return matches!(expression, Expression::Value(Value::Integer(_) | Value::UInteger(_)));
+}
+
+/// Rejects integer literals that do not fit the type they were given.
+fn check_literal(value: &Value, t: &Type) -> Result<(), BuildError> {
+    let base = match t {
+        Type::Base(base) => base,
+        _ => return Ok(()),
+    };
+    let fits = match (value, base) {
+        (Value::UInteger(_), BaseType::UInt128) => true,
+        (Value::UInteger(value), base) => match (i128::try_from(*value), integer_range(base)) {
+            (Ok(value), Some((min, max))) => value >= min && value <= max,
+            (_, range) => range.is_none(),
+        },
+        (Value::Integer(value), base) => match integer_range(base) {
+            Some((min, max)) => *value >= min && *value <= max,
+            None => true,
+        },
+        _ => true,
+    };
+    let shown = match value {
+        Value::Integer(value) => value.to_string(),
+        Value::UInteger(value) => value.to_string(),
+        _ => return Ok(()),
+    };
+    return match fits {
+        true => Ok(()),
+        false => Err(BuildError::OutOfRange(shown, t.to_string())),
+    };
+}
+
+/// The methods `&str` and the prelude's `Chars` have built in.
+fn string_method(target: &Type, method: &str, chars: Type) -> Option<Type> {
+    return match (target, method) {
+        (Type::Base(BaseType::Str), "len") => Some(Type::Base(BaseType::UIntSize)),
+        (Type::Base(BaseType::Str), "chars") => Some(chars),
+        (t, "count") if t == &chars => Some(Type::Base(BaseType::UIntSize)),
+        (t, "next") if t == &chars => Some(Type::Generic(
+            String::from("Option"),
+            vec![Type::Base(BaseType::Char)],
+        )),
+        _ => None,
+    };
+}
+
+struct Checker<'a> {
+    symbols: &'a Symbols,
+    traits: &'a Traits,
+    signatures: HashMap<DefId, Signature>,
+    /// The types of constants and statics.
+    globals: HashMap<DefId, Type>,
+    /// Structs by name, with their fields.
+    structs: HashMap<String, Shape>,
+    /// Variants by their definition, with their payload as fields.
+    variants: HashMap<DefId, Shape>,
+
+    relative_path: PathBuf,
+    line: usize,
+    locals: HashMap<DefId, Type>,
+    /// The type variables of the current function, at the index of their name.
+    variables: Vec<Variable>,
+    /// What `Self` stands for in the current method.
+    self_type: Option<Type>,
+    /// `None` when the return type is not known, as for unannotated closures.
+    return_type: Option<Option<Type>>,
+    /// What the first `return` of an unannotated closure returned.
+    inferred: Option<Option<Type>>,
+    /// Whether the code being checked is in an `unsafe` function or block.
+    is_unsafe: bool,
+    /// The casts of the current function.
+    casts: Vec<Cast>,
+}
+impl<'a> Checker<'a> {
+    fn problem(&self, error: BuildError) -> CompileError {
+        return CompileError::BuildProblem(BuildProblem::new(
+            error,
+            self.relative_path.clone(),
+            self.line,
+        ));
+    }
+
+    fn mismatch(&self, expected: &Type, found: String) -> CompileError {
+        return self.problem(BuildError::MismatchedTypes(self.show(expected), found));
+    }
+
+    fn resolve_self(&self, t: &Type) -> Type {
+        return match &self.self_type {
+            Some(self_type) => substitute(t, &HashMap::from([(String::from("Self"), self_type.clone())])),
+            None => t.clone(),
+        };
+    }
+
+    fn fresh(&mut self, integer: bool) -> Type {
+        self.variables.push(Variable { integer, bound: None });
+        return variable_type(self.variables.len() - 1);
+    }
+
+    /// One fresh variable for each generic, so every call finds its own types.
+    fn instantiate(&mut self, generics: &Vec<String>) -> HashMap<String, Type> {
+        let mut bindings = HashMap::new();
+        for name in generics {
+            let t = self.fresh(false);
+            bindings.insert(name.clone(), t);
+        }
+        return bindings;
+    }
+
+    /// Follows the bindings of a variable to the type it stands for, which
+    /// is a variable again if it is still open.
+    fn shallow(&self, t: &Type) -> Type {
+        let mut t = t.clone();
+        loop {
+            match variable_index(&t).and_then(|index| self.variables[index].bound.as_ref()) {
+                Some(bound) => t = bound.clone(),
+                None => return t,
+            }
+        }
+    }
+
+    /// Replaces every bound variable in `t` by what it stands for.
+    fn resolve(&self, t: &Type) -> Type {
+        return map_type(t, &mut |t| match variable_index(&t) {
+            Some(_) => {
+                let shallow = self.shallow(&t);
+                match variable_index(&shallow) {
+                    Some(_) => shallow,
+                    None => self.resolve(&shallow),
+                }
+            }
+            None => t,
+        });
+    }
+
+    /// Whether a variable nothing could be learnt about is left in `t`.
+    fn is_open(&self, t: &Type) -> bool {
+        let mut open = false;
+        map_type(&self.resolve(t), &mut |t| {
+            match variable_index(&t) {
+                Some(index) if !self.variables[index].integer => open = true,
+                _ => {}
+            }
+            t
+        });
+        return open;
+    }
+
+    /// How a type is named in errors, with `{integer}` for the type of a
+    /// literal that is not known yet and `_` for other open variables.
+    fn show(&self, t: &Type) -> String {
+        let t = map_type(&self.resolve(t), &mut |t| match variable_index(&t) {
+            Some(index) if self.variables[index].integer => Type::Custom(String::from("{integer}")),
+            Some(_) => unknown(),
+            None => t,
+        });
+        return t.to_string();
+    }
+
+    fn found(&self, expression: &TypedExpression) -> String {
+        return match &expression.t {
+            Some(t) => self.show(t),
+            None => unit().to_string(),
+        };
+    }
+
+    /// The final type of a value once the function is checked. Integer
+    /// literals nothing constrained are `i32`.
+    fn finish(&mut self, t: &Type) -> Type {
+        let t = self.resolve(t);
+        return map_type(&t, &mut |t| match variable_index(&t) {
+            Some(index) if self.variables[index].integer => {
+                self.variables[index].bound = Some(Type::Base(BaseType::Int32));
+                Type::Base(BaseType::Int32)
+            }
+            Some(_) => unknown(),
+            None => t,
+        });
+    }
+
+    /// Binds an open variable, keeping integer variables to integers.
+    fn bind(&mut self, index: usize, t: &Type) -> bool {
+        match variable_index(t) {
+            Some(other) if other == index => return true,
+            Some(other) if self.variables[index].integer && !self.variables[other].integer => {
+                self.variables[other].bound = Some(variable_type(index));
+                return true;
+            }
+            Some(_) => {}
+            None if self.variables[index].integer && !is_integer(t) => return false,
+            None => {}
+        }
+        self.variables[index].bound = Some(t.clone());
+        return true;
+    }
+
+    /// Whether a value of type `found` can be used where `expected` is,
+    /// binding the variables on either side on the way. A `&mut T` is also a
+    /// `&T`, and a `&T` or `Box<T>` becomes a `&dyn` or `Box<dyn>` of any
+    /// trait `T` implements.
+    fn fits(&mut self, expected: &Type, found: &Type) -> bool {
+        let expected = self.shallow(expected);
+        let found = self.shallow(found);
+        if is_unknown(&expected) || is_unknown(&found) {
+            return true;
+        }
+        match (variable_index(&expected), variable_index(&found)) {
+            (Some(index), _) => return self.bind(index, &found),
+            (None, Some(index)) => return self.bind(index, &expected),
+            (None, None) => {}
+        }
+        if expected == found {
+            return true;
+        }
+        match expected.trait_object() {
+            Some(trait_name) => {
+                // A reference only becomes a `&dyn`, and a box a `Box<dyn>`.
+                let pointee = match (&expected, &found) {
+                    (Type::Reference(mutable, _), Type::Reference(found_mutable, inner)) if !mutable || *found_mutable => {
+                        self.shallow(inner)
+                    }
+                    (Type::Generic(_, _), Type::Generic(name, types)) if name == "Box" && types.len() == 1 => {
+                        self.shallow(&types[0])
+                    }
+                    _ => return false,
+                };
+                return match &pointee {
+                    Type::Dyn(name) => name == trait_name,
+                    pointee => is_unknown(pointee) || self.traits.find_implementation(trait_name, pointee).is_some(),
+                };
+            }
+            None => {}
+        }
+        return match (&expected, &found) {
+            (Type::Reference(mutable, e), Type::Reference(found_mutable, f)) => {
+                (!mutable || *found_mutable) && self.fits(e, f)
+            }
+            (Type::Pointer(mutable, e), Type::Pointer(found_mutable, f)) => {
+                mutable == found_mutable && self.fits(e, f)
+            }
+            (Type::Generic(name, e), Type::Generic(found_name, f)) => name == found_name && self.fits_all(e, f),
+            (Type::Tuple(e), Type::Tuple(f)) => self.fits_all(e, f),
+            (Type::Function(e, e_return), Type::Function(f, f_return)) => {
+                self.fits_all(e, f)
+                    && match (e_return, f_return) {
+                        (Some(e), Some(f)) => self.fits(e, f),
+                        (None, None) => true,
+                        _ => false,
+                    }
+            }
+            _ => false,
+        };
+    }
+
+    fn fits_all(&mut self, expected: &Vec<Type>, found: &Vec<Type>) -> bool {
+        if expected.len() != found.len() {
+            return false;
+        }
+        for (e, f) in expected.iter().zip(found) {
+            if !self.fits(e, f) {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    fn expect(&mut self, expected: &Type, expression: &TypedExpression) -> Result<(), CompileError> {
+        return match &expression.t {
+            Some(t) if self.fits(expected, t) => Ok(()),
+            _ => Err(self.mismatch(expected, self.found(expression))),
+        };
+    }
+
+    /// Lets the type a value is expected to have bind the variables of the
+    /// type it is going to have, so `let x: Option<u8> = None;` knows its
+    /// `T` before the arguments are looked at. Whether they really fit is
+    /// checked where the value is used.
+    fn expect_early(&mut self, expected: Option<&Type>, t: &Type) {
+        match expected {
+            Some(expected) if expected.trait_object().is_none() => {
+                self.fits(expected, t);
+            }
+            _ => {}
+        }
+    }
+
+    /// The type of a struct or enum, with its generics as they are bound.
+    fn shape_type(&self, shape: &Shape, bindings: &HashMap<String, Type>) -> Type {
+        if shape.generics.len() == 0 {
+            return Type::Custom(shape.name.clone());
+        }
+        let types = shape
+            .generics
+            .iter()
+            .map(|name| bindings.get(name).cloned().unwrap_or_else(unknown))
+            .collect();
+        return Type::Generic(shape.name.clone(), types);
+    }
+
+    fn expressions(&mut self, expressions: &Vec<Expression>) -> Result<Vec<TypedExpression>, CompileError> {
+        let mut typed = Vec::new();
+        for expression in expressions {
+            match self.expression(expression, None) {
+                Ok(expression) => typed.push(expression),
+                Err(error) => return Err(error),
+            }
+        }
+        return Ok(typed);
+    }
+
+    /// Checks the arguments of a call against the parameters of what is
+    /// called, with the generics already replaced by variables. Extra
+    /// arguments of variadic functions have no type to match.
+    fn arguments(
+        &mut self,
+        name: &String,
+        parameters: &[(String, Type)],
+        variadic: bool,
+        arguments: &Vec<Expression>,
+    ) -> Result<Vec<TypedExpression>, CompileError> {
+        if arguments.len() > parameters.len() && !variadic {
+            return Err(self.problem(BuildError::TooManyArguments(
+                name.clone(),
+                parameters.len(),
+                arguments.len(),
+            )));
+        }
+        if arguments.len() < parameters.len() {
+            let (parameter, _) = &parameters[arguments.len()];
+            // Function values have no parameter names to point at.
+            return Err(self.problem(match parameter.as_str() {
+                "_" => BuildError::TooFewArguments(name.clone(), parameters.len(), arguments.len()),
+                _ => BuildError::MissingArgument(name.clone(), parameter.clone()),
+            }));
+        }
+
+        let mut typed = Vec::new();
+        for (index, argument) in arguments.iter().enumerate() {
+            let expected = parameters.get(index).map(|(_, t)| t);
+            let argument = match self.expression(argument, expected) {
+                Ok(argument) => argument,
+                Err(error) => return Err(error),
+            };
+            match expected {
+                Some(expected) => match self.expect(expected, &argument) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                },
+                None => {}
+            }
+            typed.push(argument);
+        }
+        return Ok(typed);
+    }
+
+    /// The parameters and return type of a signature for one use of it.
+    fn instance(&mut self, signature: &Signature) -> (Vec<(String, Type)>, Option<Type>) {
+        let mut bindings = self.instantiate(&signature.generics);
+        match &signature.self_type {
+            Some(self_type) => {
+                let self_type = substitute(self_type, &bindings);
+                bindings.insert(String::from("Self"), self_type);
+            }
+            None => {}
+        }
+        let parameters = signature
+            .parameters
+            .iter()
+            .map(|(name, t)| (name.clone(), substitute(t, &bindings)))
+            .collect();
+        let return_type = signature.return_type.as_ref().map(|t| substitute(t, &bindings));
+        return (parameters, return_type);
+    }
+
+    fn call(
+        &mut self,
+        path: &Path,
+        arguments: &Vec<Expression>,
+        expected: Option<&Type>,
+    ) -> Result<TypedExpression, CompileError> {
+        let id = match path.definition {
+            Some(id) => id,
+            None if display(path) == "Box::new" => return self.box_new(arguments, expected),
+            None if BUILTINS.contains(&path.root.as_str()) => return self.format(&path.root, arguments),
+            None => return Err(self.problem(BuildError::NotDefined(display(path)))),
+        };
+        let definition = self.symbols.definition(id);
+        let name = definition.name.clone();
+
+        let (callee, name, parameters, return_type, variadic) = match definition.kind {
+            DefKind::Local | DefKind::Parameter => {
+                let t = match self.locals.get(&id) {
+                    Some(t) => self.shallow(t),
+                    None => unknown(),
+                };
+                let (parameters, return_type) = match t {
+                    Type::Function(parameters, return_type) => (parameters, return_type),
+                    t if is_unknown(&t) || variable_index(&t).is_some() => {
+                        return match self.expressions(arguments) {
+                            Ok(arguments) => Ok(TypedExpression {
+                                kind: ExpressionKind::Call(Callee::Local(id), arguments),
+                                t: Some(unknown()),
+                            }),
+                            Err(error) => Err(error),
+                        }
+                    }
+                    t => return Err(self.problem(BuildError::NotAFunction(name, self.show(&t)))),
+                };
+                let parameters = parameters.into_iter().map(|t| (String::from("_"), t)).collect();
+                (Callee::Local(id), name, parameters, return_type.map(|t| *t), false)
+            }
+            DefKind::Struct | DefKind::Variant => {
+                let shape = match definition.kind {
+                    DefKind::Struct => self.structs.get(&name),
+                    _ => self.variants.get(&id),
+                };
+                let shape = match shape {
+                    Some(shape) => shape.clone(),
+                    None => return Err(self.problem(BuildError::NotAFunction(name, String::from("_")))),
+                };
+                let bindings = self.instantiate(&shape.generics);
+                let parameters = shape
+                    .fields
+                    .iter()
+                    .map(|(name, t)| (name.clone(), substitute(t, &bindings)))
+                    .collect();
+                (Callee::Item(id), display(path), parameters, Some(self.shape_type(&shape, &bindings)), false)
+            }
+            // The compiler cannot check what foreign functions do.
+            DefKind::ForeignFunction if !self.is_unsafe => {
+                return Err(self.problem(BuildError::ForeignCallOutsideUnsafe(name)))
+            }
+            _ => match self.signatures.get(&id) {
+                Some(signature) => {
+                    let signature = signature.clone();
+                    let (parameters, return_type) = self.instance(&signature);
+                    (Callee::Item(id), signature.name, parameters, return_type, signature.variadic)
+                }
+                None => return Err(self.problem(BuildError::NotAFunction(name, String::from("_")))),
+            },
+        };
+
+        match &return_type {
+            Some(t) => self.expect_early(expected, t),
+            None => {}
+        }
+        return match self.arguments(&name, &parameters, variadic, arguments) {
+            Ok(arguments) => Ok(TypedExpression {
+                kind: ExpressionKind::Call(callee, arguments),
+                t: return_type,
+            }),
+            Err(error) => Err(error),
+        };
+    }
+
+    /// `print`, `println` and `format`, whose format string has to match the
+    /// arguments after it.
+    fn format(&mut self, name: &String, arguments: &Vec<Expression>) -> Result<TypedExpression, CompileError> {
+        let specs = match format_specs(name, arguments) {
+            Ok(specs) => specs,
+            Err(error) => return Err(self.problem(error)),
+        };
+        let arguments = match self.expressions(arguments) {
+            Ok(arguments) => arguments,
+            Err(error) => return Err(error),
+        };
+        for (spec, argument) in specs.iter().zip(arguments.iter().skip(1)) {
+            let t = match &argument.t {
+                Some(t) => self.resolve(t),
+                None => unit(),
+            };
+            // Integer literals are formattable whatever integer they become.
+            let known = match variable_index(&t) {
+                Some(_) => false,
+                None => !is_unknown(&t),
+            };
+            if known && !is_formattable(&t, *spec) {
+                return Err(self.problem(BuildError::NotFormattable(self.show(&t), spec.placeholder())));
+            }
+        }
+        let t = match name.as_str() {
+            "format" => Some(str_slice()),
+            _ => None,
+        };
+        return Ok(TypedExpression {
+            kind: ExpressionKind::Call(Callee::Builtin(name.clone()), arguments),
+            t,
+        });
+    }
+
+    /// `Box::new(value)`, which moves a value of any type to the heap.
+    fn box_new(&mut self, arguments: &Vec<Expression>, expected: Option<&Type>) -> Result<TypedExpression, CompileError> {
+        let name = String::from("Box::new");
+        let t = self.fresh(false);
+        let return_type = Type::Generic(String::from("Box"), vec![t.clone()]);
+        self.expect_early(expected, &return_type);
+        return match self.arguments(&name, &[(String::from("x"), t)], false, arguments) {
+            Ok(arguments) => Ok(TypedExpression {
+                kind: ExpressionKind::Call(Callee::Builtin(name), arguments),
+                t: Some(return_type),
+            }),
+            Err(error) => Err(error),
+        };
+    }
+
+    /// Finds the method a call on a value of type `receiver` goes to: an
+    /// inherent method, a method of a trait the type implements, a method of
+    /// the trait behind a trait object. Returns it with the type `Self`
+    /// stands for.
+    fn find_method(&self, receiver: &Type, method: &String) -> Option<(DefId, Type)> {
+        match receiver.trait_object() {
+            Some(trait_name) => {
+                return match self.symbols.members.get(&format!("{}::{}", trait_name, method)) {
+                    Some(id) => Some((*id, Type::Dyn(trait_name.clone()))),
+                    None => None,
+                }
+            }
+            None => {}
+        }
+        let target = match receiver {
+            Type::Reference(_, inner) => self.resolve(inner),
+            t => self.resolve(t),
+        };
+        match self.symbols.members.get(&format!("{}::{}", target_name(&target), method)) {
+            Some(id) => return Some((*id, target)),
+            None => {}
+        }
+        // Default methods of implemented traits are only kept under the trait.
+        let implementation = self.traits.implementations.iter().find(|implementation| {
+            implementation.target == target
+                && match self.traits.traits.get(&implementation.trait_name) {
+                    Some(definition) => definition.methods.iter().any(|m| &m.name == method),
+                    None => false,
+                }
+        });
+        return match implementation {
+            Some(implementation) => {
+                match self.symbols.members.get(&format!("{}::{}", implementation.trait_name, method)) {
+                    Some(id) => Some((*id, target)),
+                    None => None,
+                }
+            }
+            None => None,
+        };
+    }
+
+    fn method_call(
+        &mut self,
+        receiver: &Expression,
+        method: &String,
+        arguments: &Vec<Expression>,
+    ) -> Result<TypedExpression, CompileError> {
+        let receiver = match self.expression(receiver, None) {
+            Ok(receiver) => receiver,
+            Err(error) => return Err(error),
+        };
+        let receiver_type = match &receiver.t {
+            Some(t) => self.shallow(t),
+            None => unit(),
+        };
+        let target = match &receiver_type {
+            Type::Reference(_, inner) => self.shallow(inner),
+            t => t.clone(),
+        };
+        match variable_index(&target) {
+            Some(index) if self.variables[index].integer => {
+                return Err(self.problem(BuildError::AmbiguousNumeric(method.clone())))
+            }
+            Some(_) => return Err(self.problem(BuildError::TypeAnnotationsNeeded(self.show(&target), None))),
+            None => {}
+        }
+        if is_unknown(&target) {
+            return match self.expressions(arguments) {
+                Ok(arguments) => Ok(TypedExpression {
+                    kind: ExpressionKind::MethodCall {
+                        receiver: Box::new(receiver),
+                        method: method.clone(),
+                        definition: None,
+                        arguments,
+                    },
+                    t: Some(unknown()),
+                }),
+                Err(error) => Err(error),
+            };
+        }
+
+        let no_method = BuildError::NoMethod(method.clone(), self.show(&receiver_type));
+        let (id, self_type) = match self.find_method(&receiver_type, method) {
+            Some(found) => found,
+            None => {
+                return match (string_method(&target, method, self.symbols.chars()), arguments.len()) {
+                    (Some(t), 0) => Ok(TypedExpression {
+                        kind: ExpressionKind::MethodCall {
+                            receiver: Box::new(receiver),
+                            method: method.clone(),
+                            definition: None,
+                            arguments: Vec::new(),
+                        },
+                        t: Some(t),
+                    }),
+                    (Some(_), found) => Err(self.problem(BuildError::TooManyArguments(
+                        method.clone(),
+                        0,
+                        found,
+                    ))),
+                    (None, _) => Err(self.problem(no_method)),
+                };
+            }
+        };
+        let signature = match self.signatures.get(&id) {
+            Some(signature) => signature.clone(),
+            None => return Err(self.problem(no_method)),
+        };
+        match signature.parameters.first() {
+            Some((name, _)) if name == "self" => {}
+            _ => return Err(self.problem(no_method)),
+        }
+        // A `&mut self` method cannot reach a value through a `&`.
+        match (&signature.parameters[0].1, &receiver_type) {
+            (Type::Reference(true, _), Type::Reference(false, _)) => {
+                return Err(self.problem(BuildError::BorrowBehindReference(
+                    self.place_name(&receiver).map(|name| format!("*{}", name)),
+                )))
+            }
+            _ => {}
+        }
+
+        let mut bindings = self.instantiate(&signature.generics);
+        match &signature.self_type {
+            Some(impl_target) => {
+                let impl_target = substitute(impl_target, &bindings);
+                self.fits(&impl_target, &self_type);
+            }
+            None => {}
+        }
+        bindings.insert(String::from("Self"), self_type);
+        let parameters: Vec<(String, Type)> = signature
+            .parameters
+            .iter()
+            .skip(1)
+            .map(|(name, t)| (name.clone(), substitute(t, &bindings)))
+            .collect();
+        let arguments = match self.arguments(&signature.name, &parameters, false, arguments) {
+            Ok(arguments) => arguments,
+            Err(error) => return Err(error),
+        };
+        return Ok(TypedExpression {
+            kind: ExpressionKind::MethodCall {
+                receiver: Box::new(receiver),
+                method: method.clone(),
+                definition: Some(id),
+                arguments,
+            },
+            t: signature.return_type.map(|t| substitute(&t, &bindings)),
+        });
+    }
+
+    /// How a place is named in errors, if it is a local, an item or a field of one.
+    fn place_name(&self, expression: &TypedExpression) -> Option<String> {
+        return match &expression.kind {
+            ExpressionKind::Local(id) | ExpressionKind::Item(id) => Some(self.symbols.definition(*id).name.clone()),
+            ExpressionKind::Field(inner, field) => self.place_name(inner).map(|name| format!("{}.{}", name, field)),
+            _ => None,
+        };
+    }
+
+    fn variable(&mut self, path: &Path, expected: Option<&Type>) -> Result<TypedExpression, CompileError> {
+        let id = match path.definition {
+            Some(id) => id,
+            None => return Err(self.problem(BuildError::NotDefined(display(path)))),
+        };
+        let definition = self.symbols.definition(id);
+        let (kind, t) = match definition.kind {
+            DefKind::Local | DefKind::Parameter => (
+                ExpressionKind::Local(id),
+                self.locals.get(&id).cloned().unwrap_or_else(unknown),
+            ),
+            DefKind::Constant | DefKind::Static => (
+                ExpressionKind::Item(id),
+                self.globals.get(&id).cloned().unwrap_or_else(unknown),
+            ),
+            DefKind::Function | DefKind::ForeignFunction | DefKind::Method => {
+                let t = match self.signatures.get(&id) {
+                    Some(signature) => {
+                        let signature = signature.clone();
+                        let (parameters, return_type) = self.instance(&signature);
+                        Type::Function(
+                            parameters.into_iter().map(|(_, t)| t).collect(),
+                            return_type.map(Box::new),
+                        )
+                    }
+                    None => unknown(),
+                };
+                (ExpressionKind::Item(id), t)
+            }
+            DefKind::Variant => {
+                let t = match self.variants.get(&id) {
+                    Some(shape) => {
+                        let shape = shape.clone();
+                        let bindings = self.instantiate(&shape.generics);
+                        let t = self.shape_type(&shape, &bindings);
+                        match shape.fields.len() {
+                            0 => {
+                                self.expect_early(expected, &t);
+                                t
+                            }
+                            _ => Type::Function(
+                                shape.fields.iter().map(|(_, t)| substitute(t, &bindings)).collect(),
+                                Some(Box::new(t)),
+                            ),
+                        }
+                    }
+                    None => unknown(),
+                };
+                (ExpressionKind::Item(id), t)
+            }
+            DefKind::Struct => (ExpressionKind::Item(id), Type::Custom(definition.name.clone())),
+            _ => (ExpressionKind::Item(id), unknown()),
+        };
+        return Ok(TypedExpression { kind, t: Some(t) });
+    }
+
+    fn expression(&mut self, expression: &Expression, expected: Option<&Type>) -> Result<TypedExpression, CompileError> {
+        let (kind, t) = match expression {
+            Expression::Value(value) => {
+                let t = match value {
+                    Value::String(_) => str_slice(),
+                    Value::Boolean(_) => Type::Base(BaseType::Boolean),
+                    Value::Char(_) => Type::Base(BaseType::Char),
+                    // Integer literals take the integer type they are expected to have.
+                    Value::Integer(_) | Value::UInteger(_) => match expected.map(|t| self.shallow(t)) {
+                        Some(t) if is_integer(&t) => t,
+                        _ => self.fresh(true),
+                    },
+                };
+                (ExpressionKind::Value(value.clone()), Some(t))
+            }
+            Expression::GetVariable(path) => return self.variable(path, expected),
+            Expression::Call(path, arguments) => return self.call(path, arguments, expected),
+            Expression::MethodCall(receiver, method, arguments) => {
+                return self.method_call(receiver, method, arguments)
+            }
+            Expression::BinaryOperation(a, operator, b) => {
+                let expected = match operator.is_comparison() {
+                    true => None,
+                    false => expected,
+                };
+                // A literal takes the type of the other operand, whichever side it is on.
+                let (a, b) = match is_integer_literal(a) && !is_integer_literal(b) {
+                    true => {
+                        let b = match self.expression(b, expected) {
+                            Ok(b) => b,
+                            Err(error) => return Err(error),
+                        };
+                        match self.expression(a, b.t.as_ref()) {
+                            Ok(a) => (a, b),
+                            Err(error) => return Err(error),
+                        }
+                    }
+                    false => {
+                        let a = match self.expression(a, expected) {
+                            Ok(a) => a,
+                            Err(error) => return Err(error),
+                        };
+                        match self.expression(b, a.t.as_ref()) {
+                            Ok(b) => (a, b),
+                            Err(error) => return Err(error),
+                        }
+                    }
+                };
+                let (x, y) = match (&a.t, &b.t) {
+                    (Some(x), Some(y)) => (self.shallow(x), self.shallow(y)),
+                    (Some(x), None) => return Err(self.mismatch(x, unit().to_string())),
+                    (None, _) => return Err(self.mismatch(&unit(), self.found(&b))),
+                };
+                let t = match &x {
+                    // Pointer arithmetic moves the pointer by a number of elements.
+                    Type::Pointer(_, _) if !operator.is_comparison() => {
+                        let is_integer = match variable_index(&y) {
+                            Some(index) => self.variables[index].integer,
+                            None => is_integer(&y) || is_unknown(&y),
+                        };
+                        match is_integer {
+                            true => x.clone(),
+                            false => {
+                                return Err(self.problem(BuildError::MismatchedTypes(
+                                    String::from("{integer}"),
+                                    self.found(&b),
+                                )))
+                            }
+                        }
+                    }
+                    x if self.fits(x, &y) => match is_unknown(x) {
+                        true => y.clone(),
+                        false => x.clone(),
+                    },
+                    x if self.fits(&y, x) => y.clone(),
+                    x => return Err(self.mismatch(x, self.found(&b))),
+                };
+                let t = match operator.is_comparison() {
+                    true => Type::Base(BaseType::Boolean),
+                    false => t,
+                };
+                (ExpressionKind::BinaryOperation(Box::new(a), *operator, Box::new(b)), Some(t))
+            }
+            Expression::Reference(mutable, inner) => {
+                let expected = match expected.map(|t| self.shallow(t)) {
+                    Some(Type::Reference(_, inner)) => Some(*inner),
+                    _ => None,
+                };
+                let inner = match self.expression(inner, expected.as_ref()) {
+                    Ok(inner) => inner,
+                    Err(error) => return Err(error),
+                };
+                let t = Type::Reference(*mutable, Box::new(inner.t.clone().unwrap_or_else(unit)));
+                (ExpressionKind::Reference(*mutable, Box::new(inner)), Some(t))
+            }
+            Expression::Field(inner, field) => {
+                let inner = match self.expression(inner, None) {
+                    Ok(inner) => inner,
+                    Err(error) => return Err(error),
+                };
+                let mut base = self.shallow(&inner.t.clone().unwrap_or_else(unit));
+                loop {
+                    match base {
+                        Type::Reference(_, pointee) => base = self.shallow(&pointee),
+                        _ => break,
+                    }
+                }
+                match variable_index(&base) {
+                    Some(index) if !self.variables[index].integer => {
+                        return Err(self.problem(BuildError::TypeAnnotationsNeeded(self.show(&base), None)))
+                    }
+                    _ => {}
+                }
+                let no_field = BuildError::NoField(field.clone(), self.show(&base));
+                let t = match &base {
+                    t if is_unknown(t) => unknown(),
+                    Type::Custom(name) | Type::Generic(name, _) => {
+                        let shape = match self.structs.get(name) {
+                            Some(shape) => shape,
+                            None => return Err(self.problem(no_field)),
+                        };
+                        let mut bindings = HashMap::new();
+                        match &base {
+                            Type::Generic(_, types) => {
+                                for (generic, t) in shape.generics.iter().zip(types) {
+                                    bindings.insert(generic.clone(), t.clone());
+                                }
+                            }
+                            _ => {}
+                        }
+                        match shape.fields.iter().find(|(name, _)| name == field) {
+                            Some((_, t)) => substitute(t, &bindings),
+                            None => return Err(self.problem(no_field)),
+                        }
+                    }
+                    Type::Tuple(types) => match field.parse::<usize>().ok().and_then(|index| types.get(index)) {
+                        Some(t) => t.clone(),
+                        None => return Err(self.problem(no_field)),
+                    },
+                    _ => return Err(self.problem(no_field)),
+                };
+                (ExpressionKind::Field(Box::new(inner), field.clone()), Some(t))
+            }
+            Expression::Cast(source, t) => {
+                let inner = match self.expression(source, None) {
+                    Ok(inner) => inner,
+                    Err(error) => return Err(error),
+                };
+                let t = self.resolve_self(t);
+                let literal = match source.as_ref() {
+                    Expression::Value(value @ (Value::Integer(_) | Value::UInteger(_))) => Some(value.clone()),
+                    _ => None,
+                };
+                self.casts.push(Cast {
+                    from: inner.t.clone().unwrap_or_else(unit),
+                    to: t.clone(),
+                    literal,
+                    is_unsafe: self.is_unsafe,
+                    line: self.line,
+                });
+                (ExpressionKind::Cast(Box::new(inner)), Some(t))
+            }
+            Expression::Try(inner) => {
+                let inner = match self.expression(inner, None) {
+                    Ok(inner) => inner,
+                    Err(error) => return Err(error),
+                };
+                let t = match self.try_value(&inner) {
+                    Ok(t) => t,
+                    Err(error) => return Err(error),
+                };
+                (ExpressionKind::Try(Box::new(inner)), Some(t))
+            }
+            Expression::NamedArgument(_, inner) => return self.expression(inner, expected),
+            Expression::Closure {
+                is_move,
+                parameters,
+                return_type,
+                body,
+                parameter_definitions,
+            } => {
+                let (expected_parameters, expected_return) = match expected.map(|t| self.shallow(t)) {
+                    Some(Type::Function(parameters, return_type)) => (Some(parameters), Some(return_type)),
+                    _ => (None, None),
+                };
+                let mut typed_parameters = Vec::new();
+                for (index, ((_, t), id)) in parameters.iter().zip(parameter_definitions).enumerate() {
+                    let t = match (t, expected_parameters.as_ref().and_then(|expected| expected.get(index))) {
+                        (Some(t), _) => t.clone(),
+                        (None, Some(t)) if !is_unknown(t) => t.clone(),
+                        (None, _) => self.fresh(false),
+                    };
+                    self.locals.insert(*id, t.clone());
+                    typed_parameters.push((*id, t));
+                }
+
+                let outer = (self.return_type.take(), self.inferred.take());
+                self.return_type = match (return_type, expected_return) {
+                    (Some(t), _) => Some(Some(t.clone())),
+                    (None, Some(Some(t))) if is_unknown(&t) => None,
+                    (None, Some(t)) => Some(t.map(|t| *t)),
+                    (None, None) => None,
+                };
+                let result = self.block(body);
+                let returned = match self.return_type.take() {
+                    Some(t) => t,
+                    None => self.inferred.take().unwrap_or(None),
+                };
+                (self.return_type, self.inferred) = outer;
+                let body = match result {
+                    Ok(body) => body,
+                    Err(error) => return Err(error),
+                };
+
+                let t = Type::Function(
+                    typed_parameters.iter().map(|(_, t)| t.clone()).collect(),
+                    returned.map(Box::new),
+                );
+                let kind = ExpressionKind::Closure {
+                    is_move: *is_move,
+                    parameters: typed_parameters,
+                    body,
+                };
+                (kind, Some(t))
+            }
+        };
+        return Ok(TypedExpression { kind, t });
+    }
+
+    /// `?` on an `Option` needs the function to return an `Option`, and on a
+    /// `Result` it needs a `Result` with the same error type. Gives the type
+    /// of the value `?` takes out.
+    fn try_value(&mut self, operand: &TypedExpression) -> Result<Type, CompileError> {
+        let operand_type = match &operand.t {
+            Some(t) => self.shallow(t),
+            None => unit(),
+        };
+        match variable_index(&operand_type) {
+            Some(index) if !self.variables[index].integer => {
+                return Err(self.problem(BuildError::TypeAnnotationsNeeded(self.show(&operand_type), None)))
+            }
+            Some(_) => {}
+            None if is_unknown(&operand_type) => return Ok(unknown()),
+            None => {}
+        }
+        let is_try_type = |t: &Type| match t {
+            Type::Generic(name, _) => name == "Option" || name == "Result",
+            _ => false,
+        };
+        // Unannotated closures return whatever `?` needs them to.
+        let return_type = match &self.return_type {
+            Some(Some(t)) if is_try_type(t) => Some(t.clone()),
+            Some(Some(t)) => return Err(self.problem(BuildError::TryInIncompatibleFunction(self.show(t)))),
+            Some(None) => return Err(self.problem(BuildError::TryInIncompatibleFunction(unit().to_string()))),
+            None => None,
+        };
+        let (name, types) = match &operand_type {
+            Type::Generic(name, types) if is_try_type(&operand_type) => (name, types),
+            t => return Err(self.problem(BuildError::TryOnIncompatibleType(self.show(t)))),
+        };
+        let compatible = match &return_type {
+            Some(Type::Generic(to, to_types)) => {
+                to == name
+                    && match (to_types.get(1), types.get(1)) {
+                        (Some(to), Some(from)) => self.fits(to, from),
+                        _ => true,
+                    }
+            }
+            _ => true,
+        };
+        return match (compatible, &return_type) {
+            (false, Some(return_type)) => Err(self.problem(BuildError::TryMismatch(
+                self.show(&operand_type),
+                self.show(return_type),
+            ))),
+            _ => Ok(types[0].clone()),
+        };
+    }
+
+    fn ret(&mut self, expression: &Option<Expression>) -> Result<Statement, CompileError> {
+        let expected = match &self.return_type {
+            Some(t) => Some(t.clone()),
+            None => self.inferred.clone(),
+        };
+        let expression = match (expression, &expected) {
+            (Some(expression), Some(Some(t))) => match self.expression(expression, Some(t)) {
+                Ok(expression) => match self.expect(t, &expression) {
+                    Ok(()) => Some(expression),
+                    Err(error) => return Err(error),
+                },
+                Err(error) => return Err(error),
+            },
+            (Some(expression), Some(None)) => match self.expression(expression, None) {
+                Ok(expression) => return Err(self.mismatch(&unit(), self.found(&expression))),
+                Err(error) => return Err(error),
+            },
+            (Some(expression), None) => match self.expression(expression, None) {
+                Ok(expression) => {
+                    self.inferred = Some(expression.t.clone());
+                    Some(expression)
+                }
+                Err(error) => return Err(error),
+            },
+            (None, Some(Some(t))) => return Err(self.mismatch(t, unit().to_string())),
+            (None, _) => {
+                if self.return_type.is_none() {
+                    self.inferred = Some(None);
+                }
+                None
+            }
+        };
+        return Ok(Statement::Return(expression));
+    }
+
+    fn definition(&self, path: &Path) -> Result<DefId, CompileError> {
+        return match path.definition {
+            Some(id) => Ok(id),
+            None => Err(self.problem(BuildError::NotDefined(display(path)))),
+        };
+    }
+
+    /// The operands of an `asm!`, which is only allowed in `unsafe` code and
+    /// passes values that fit in one register.
+    fn asm(&mut self, operands: &Vec<AsmOperand>) -> Result<Vec<TypedAsmOperand>, CompileError> {
+        if !self.is_unsafe {
+            return Err(self.problem(BuildError::AsmOutsideUnsafe));
+        }
+        match check_registers(operands) {
+            Ok(()) => {}
+            Err(error) => return Err(self.problem(error)),
+        }
+        let mut typed = Vec::new();
+        for operand in operands {
+            let (operand, t) = match operand {
+                AsmOperand::In(register, expression) => match self.expression(expression, None) {
+                    Ok(expression) => {
+                        let t = expression.t.clone();
+                        (TypedAsmOperand::In(register.clone(), expression), t)
+                    }
+                    Err(error) => return Err(error),
+                },
+                AsmOperand::Out(register, Some(path)) => match self.definition(path) {
+                    Ok(id) => (TypedAsmOperand::Out(register.clone(), Some(id)), self.place_type(id)),
+                    Err(error) => return Err(error),
+                },
+                AsmOperand::Out(register, None) => (TypedAsmOperand::Out(register.clone(), None), None),
+                AsmOperand::InOut(register, path) => match self.definition(path) {
+                    Ok(id) => (TypedAsmOperand::InOut(register.clone(), id), self.place_type(id)),
+                    Err(error) => return Err(error),
+                },
+                AsmOperand::Clobber(register) => (TypedAsmOperand::Clobber(register.clone()), None),
+            };
+            let t = t.map(|t| self.resolve(&t));
+            match t {
+                Some(t) if variable_index(&t).is_none() && !is_unknown(&t) && !fits_register(&t) => {
+                    return Err(self.problem(BuildError::AsmOperandType(self.show(&t))))
+                }
+                _ => {}
+            }
+            typed.push(operand);
+        }
+        return Ok(typed);
+    }
+
+    /// The type of the local or static an `asm!` output is written to.
+    fn place_type(&self, id: DefId) -> Option<Type> {
+        return match self.locals.get(&id) {
+            Some(t) => Some(t.clone()),
+            None => self.globals.get(&id).cloned(),
+        };
+    }
+
+    fn block(&mut self, body: &Vec<ASTNode>) -> Result<Vec<TypedNode>, CompileError> {
+        let line = self.line;
+        let mut typed = Vec::new();
+        for ast_node in body {
+            self.line = ast_node.line;
+            let statement = match &ast_node.node {
+                Node::DefineVariable {
+                    mutable,
+                    var_type,
+                    expression,
+                    definition,
+                    ..
+                } => {
+                    let expression = match expression {
+                        Some(expression) => match self.expression(expression, var_type.as_ref()) {
+                            Ok(expression) => Some(expression),
+                            Err(error) => return Err(error),
+                        },
+                        None => None,
+                    };
+                    let local_type = match (var_type, &expression) {
+                        (Some(t), Some(expression)) => match self.expect(t, expression) {
+                            Ok(()) => t.clone(),
+                            Err(error) => return Err(error),
+                        },
+                        (Some(t), None) => t.clone(),
+                        (None, Some(expression)) => expression.t.clone().unwrap_or_else(unit),
+                        // Takes the type of the first value assigned to it.
+                        (None, None) => self.fresh(false),
+                    };
+                    let local = match definition {
+                        Some(id) => *id,
+                        None => continue,
+                    };
+                    self.locals.insert(local, local_type.clone());
+                    Statement::DefineVariable {
+                        local,
+                        mutable: *mutable,
+                        local_type,
+                        expression,
+                    }
+                }
+                Node::SetVariable(path, expression) => {
+                    let id = match self.definition(path) {
+                        Ok(id) => id,
+                        Err(error) => return Err(error),
+                    };
+                    let target = match self.locals.get(&id) {
+                        Some(t) => Some(t.clone()),
+                        None => self.globals.get(&id).cloned(),
+                    };
+                    let target = target.filter(|t| !is_unknown(t));
+                    let expression = match self.expression(expression, target.as_ref()) {
+                        Ok(expression) => expression,
+                        Err(error) => return Err(error),
+                    };
+                    match &target {
+                        Some(t) => match self.expect(t, &expression) {
+                            Ok(()) => {}
+                            Err(error) => return Err(error),
+                        },
+                        None => {
+                            self.locals.insert(id, expression.t.clone().unwrap_or_else(unit));
+                        }
+                    }
+                    Statement::SetVariable(id, expression)
+                }
+                Node::Call(path, arguments) => match self.call(path, arguments, None) {
+                    Ok(expression) => Statement::Expression(expression),
+                    Err(error) => return Err(error),
+                },
+                Node::MethodCall(path, method, arguments) => {
+                    let receiver = Expression::GetVariable(path.clone());
+                    match self.method_call(&receiver, method, arguments) {
+                        Ok(expression) => Statement::Expression(expression),
+                        Err(error) => return Err(error),
+                    }
+                }
+                Node::Return(expression) => match self.ret(expression) {
+                    Ok(statement) => statement,
+                    Err(error) => return Err(error),
+                },
+                Node::Scope { is_unsafe, body } => {
+                    let outer = self.is_unsafe;
+                    self.is_unsafe = outer || *is_unsafe;
+                    let body = self.block(body);
+                    self.is_unsafe = outer;
+                    match body {
+                        Ok(body) => Statement::Scope(body),
+                        Err(error) => return Err(error),
+                    }
+                }
+                Node::Loop { body } => match self.block(body) {
+                    Ok(body) => Statement::Loop(body),
+                    Err(error) => return Err(error),
+                },
+                Node::InlineAsm { template, operands } => match self.asm(operands) {
+                    Ok(operands) => Statement::InlineAsm {
+                        template: template.clone(),
+                        operands,
+                    },
+                    Err(error) => return Err(error),
+                },
+                _ => continue,
+            };
+            typed.push(TypedNode {
+                line: ast_node.line,
+                statement,
+            });
+        }
+        self.line = line;
+        return Ok(typed);
+    }
+
+
+    fn function(
+        &mut self,
+        name: String,
+        ast_node: &ASTNode,
+        self_type: Option<Type>,
+    ) -> Result<Option<TypedFunction>, CompileError> {
+        let (is_unsafe, parameters, mutable_parameters, return_type, body, definition, parameter_definitions, defaults) =
+            match &ast_node.node {
+                Node::Function {
+                    is_unsafe,
+                    parameters,
+                    mutable_parameters,
+                    return_type,
+                    body: Some(body),
+                    definition: Some(definition),
+                    parameter_definitions,
+                    defaults,
+                    ..
+                } => (
+                    *is_unsafe,
+                    parameters,
+                    mutable_parameters,
+                    return_type,
+                    body,
+                    definition,
+                    parameter_definitions,
+                    defaults,
+                ),
+                _ => return Ok(None),
+            };
+        self.line = ast_node.line;
+        self.self_type = self_type;
+        self.locals.clear();
+        self.variables.clear();
+        self.casts.clear();
+        self.is_unsafe = is_unsafe;
+
+        let mut typed_parameters = Vec::new();
+        for ((_, t), id) in parameters.iter().zip(parameter_definitions) {
+            let t = self.resolve_self(t);
+            self.locals.insert(*id, t.clone());
+            typed_parameters.push((*id, t));
+        }
+        let return_type = return_type.as_ref().map(|t| self.resolve_self(t));
+        self.return_type = Some(return_type.clone());
+        self.inferred = None;
+
+        // Defaults are copied into every call that leaves them out, so they
+        // are checked here, where a mistake in one belongs.
+        for ((_, t), default) in typed_parameters.iter().zip(defaults) {
+            let default = match default {
+                Some(default) => default,
+                None => continue,
+            };
+            let mut typed = match self.expression(default, Some(t)) {
+                Ok(typed) => typed,
+                Err(error) => return Err(error),
+            };
+            match self.expect(t, &typed) {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+            match self.finish_expression(&mut typed) {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        let mut body = match self.block(body) {
+            Ok(body) => body,
+            Err(error) => return Err(error),
+        };
+        match self.finish_nodes(&mut body) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+        match self.check_casts() {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+        self.self_type = None;
+        return Ok(Some(TypedFunction {
+            name,
+            definition: *definition,
+            relative_path: self.relative_path.clone(),
+            line: ast_node.line,
+            parameters: typed_parameters,
+            mutable_parameters: mutable_parameters.clone(),
+            return_type,
+            body,
+        }));
+    }
+
+    /// Replaces the variables of a checked body by the types they stand for,
+    /// now that every use of every value has been seen.
+    fn finish_nodes(&mut self, nodes: &mut Vec<TypedNode>) -> Result<(), CompileError> {
+        for node in nodes {
+            self.line = node.line;
+            let result = match &mut node.statement {
+                Statement::DefineVariable {
+                    local,
+                    local_type,
+                    expression,
+                    ..
+                } => {
+                    if self.is_open(local_type) {
+                        let name = self.symbols.definition(*local).name.clone();
+                        return Err(self.problem(BuildError::TypeAnnotationsNeeded(
+                            self.show(local_type),
+                            Some(name),
+                        )));
+                    }
+                    *local_type = self.finish(local_type);
+                    match expression {
+                        Some(expression) => self.finish_expression(expression),
+                        None => Ok(()),
+                    }
+                }
+                Statement::SetVariable(_, expression) | Statement::Expression(expression) => {
+                    self.finish_expression(expression)
+                }
+                Statement::Return(Some(expression)) => self.finish_expression(expression),
+                Statement::Return(None) => Ok(()),
+                Statement::Scope(body) | Statement::Loop(body) => self.finish_nodes(body),
+                Statement::InlineAsm { operands, .. } => {
+                    for operand in operands {
+                        match operand {
+                            TypedAsmOperand::In(_, expression) => match self.finish_expression(expression) {
+                                Ok(()) => {}
+                                Err(error) => return Err(error),
+                            },
+                            _ => continue,
+                        }
+                    }
+                    Ok(())
+                }
+            };
+            match result {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        return Ok(());
+    }
+
+    fn finish_expression(&mut self, expression: &mut TypedExpression) -> Result<(), CompileError> {
+        expression.t = expression.t.as_ref().map(|t| self.finish(t));
+        let mut children = Vec::new();
+        match &mut expression.kind {
+            ExpressionKind::Value(value) => match &expression.t {
+                Some(t) => match check_literal(value, t) {
+                    Ok(()) => {}
+                    Err(error) => return Err(self.problem(error)),
+                },
+                None => {}
+            },
+            ExpressionKind::Local(_) | ExpressionKind::Item(_) => {}
+            ExpressionKind::Call(_, arguments) => children.extend(arguments.iter_mut()),
+            ExpressionKind::MethodCall {
+                receiver, arguments, ..
+            } => {
+                children.push(receiver.as_mut());
+                children.extend(arguments.iter_mut());
+            }
+            ExpressionKind::BinaryOperation(a, _, b) => {
+                children.push(a.as_mut());
+                children.push(b.as_mut());
+            }
+            ExpressionKind::Reference(_, inner)
+            | ExpressionKind::Field(inner, _)
+            | ExpressionKind::Cast(inner)
+            | ExpressionKind::Try(inner) => children.push(inner.as_mut()),
+            ExpressionKind::Closure { parameters, body, .. } => {
+                for (_, t) in parameters.iter_mut() {
+                    *t = self.finish(t);
+                }
+                return self.finish_nodes(body);
+            }
+        }
+        for child in children {
+            match self.finish_expression(child) {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        // Operators are checked once the operand types are known, which for
+        // integer literals is only after the whole function has been seen.
+        return match &expression.kind {
+            ExpressionKind::BinaryOperation(a, operator, _) => match &a.t {
+                Some(t) if !is_unknown(t) => match check_operator(self.traits, t, *operator) {
+                    Ok(()) => Ok(()),
+                    Err(error) => Err(self.problem(error)),
+                },
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        };
+    }
+
+    /// Casts are checked after the function, when integer literals nothing
+    /// else constrained have become `i32`. Pointers and integers only
+    /// convert in `unsafe` code.
+    fn check_casts(&mut self) -> Result<(), CompileError> {
+        for cast in std::mem::take(&mut self.casts) {
+            self.line = cast.line;
+            let from = self.finish(&cast.from);
+            let mut known = true;
+            map_type(&from, &mut |t| {
+                known = known && !is_unknown(&t);
+                t
+            });
+            let result = match &cast.literal {
+                Some(value) => check_literal_cast(value, &cast.to, cast.is_unsafe),
+                None if known => check_cast(&from, &cast.to, cast.is_unsafe),
+                None => Ok(()),
+            };
+            match result {
+                Ok(()) => {}
+                Err(error) => return Err(self.problem(error)),
+            }
+        }
+        return Ok(());
+    }
+
+    fn collect(&mut self, nodes: &Vec<ASTNode>) {
+        for ast_node in nodes {
+            match &ast_node.node {
+                Node::Function {
+                    name,
+                    generics,
+                    parameters,
+                    return_type,
+                    definition: Some(id),
+                    ..
+                } => {
+                    self.signatures.insert(
+                        *id,
+                        Signature {
+                            name: name.clone(),
+                            generics: generics.clone(),
+                            parameters: parameters.clone(),
+                            return_type: return_type.clone(),
+                            variadic: false,
+                            self_type: None,
+                        },
+                    );
+                }
+                Node::Extern { body, .. } => {
+                    for foreign in body {
+                        match &foreign.node {
+                            Node::ForeignFunction {
+                                name,
+                                parameters,
+                                variadic,
+                                return_type,
+                                ..
+                            } => match self.symbols.values.get(name) {
+                                Some(id) => {
+                                    self.signatures.insert(
+                                        *id,
+                                        Signature {
+                                            name: name.clone(),
+                                            generics: Vec::new(),
+                                            parameters: parameters.clone(),
+                                            return_type: return_type.clone(),
+                                            variadic: *variadic,
+                                            self_type: None,
+                                        },
+                                    );
+                                }
+                                None => {}
+                            },
+                            _ => continue,
+                        }
+                    }
+                }
+                Node::Impl {
+                    generics: impl_generics,
+                    target,
+                    body,
+                    ..
+                } => {
+                    for method in body {
+                        match &method.node {
+                            Node::Function {
+                                name,
+                                generics,
+                                parameters,
+                                return_type,
+                                definition: Some(id),
+                                ..
+                            } => {
+                                let mut all = impl_generics.clone();
+                                all.extend(generics.iter().cloned());
+                                self.signatures.insert(
+                                    *id,
+                                    Signature {
+                                        name: format!("{}::{}", target_name(target), name),
+                                        generics: all,
+                                        parameters: parameters.clone(),
+                                        return_type: return_type.clone(),
+                                        variadic: false,
+                                        self_type: Some(target.clone()),
+                                    },
+                                );
+                            }
+                            _ => continue,
+                        }
+                    }
+                }
+                Node::Trait { name: trait_name, body, .. } => {
+                    for method in body {
+                        match &method.node {
+                            Node::Function {
+                                name,
+                                generics,
+                                parameters,
+                                return_type,
+                                definition: Some(id),
+                                ..
+                            } => {
+                                let mut all = vec![String::from("Self")];
+                                all.extend(generics.iter().cloned());
+                                self.signatures.insert(
+                                    *id,
+                                    Signature {
+                                        name: format!("{}::{}", trait_name, name),
+                                        generics: all,
+                                        parameters: parameters.clone(),
+                                        return_type: return_type.clone(),
+                                        variadic: false,
+                                        self_type: None,
+                                    },
+                                );
+                            }
+                            _ => continue,
+                        }
+                    }
+                }
+                Node::Constant {
+                    name, constant_type, ..
+                } => match self.symbols.values.get(name) {
+                    Some(id) => {
+                        self.globals.insert(*id, constant_type.clone());
+                    }
+                    None => {}
+                },
+                Node::Static {
+                    name, static_type, ..
+                } => match self.symbols.values.get(name) {
+                    Some(id) => {
+                        self.globals.insert(*id, static_type.clone());
+                    }
+                    None => {}
+                },
+                Node::Struct {
+                    name, generics, body, ..
+                } => {
+                    let shape = Shape {
+                        name: name.clone(),
+                        generics: generics.clone(),
+                        fields: body.iter().map(|(_, name, t)| (name.clone(), t.clone())).collect(),
+                    };
+                    self.structs.insert(name.clone(), shape);
+                }
+                Node::Enum {
+                    name, generics, body, ..
+                } => {
+                    for (variant, payload) in body {
+                        let id = match self.symbols.members.get(&format!("{}::{}", name, variant)) {
+                            Some(id) => *id,
+                            None => continue,
+                        };
+                        let shape = Shape {
+                            name: name.clone(),
+                            generics: generics.clone(),
+                            fields: payload
+                                .iter()
+                                .enumerate()
+                                .map(|(index, t)| (index.to_string(), t.clone()))
+                                .collect(),
+                        };
+                        self.variants.insert(id, shape);
+                    }
+                }
+                _ => continue,
+            }
+        }
+    }
+}
+
+/// The types found for unannotated locals and closure parameters, by their
+/// definition.
+fn inferred_types(nodes: &Vec<TypedNode>, types: &mut HashMap<DefId, Type>) {
+    let mut expressions = Vec::new();
+    for node in nodes {
+        match &node.statement {
+            Statement::DefineVariable {
+                local,
+                local_type,
+                expression,
+                ..
+            } => {
+                types.insert(*local, local_type.clone());
+                expressions.extend(expression.iter());
+            }
+            Statement::SetVariable(_, expression) | Statement::Expression(expression) => {
+                expressions.push(expression)
+            }
+            Statement::Return(expression) => expressions.extend(expression.iter()),
+            Statement::Scope(body) | Statement::Loop(body) => inferred_types(body, types),
+            Statement::InlineAsm { .. } => {}
+        }
+    }
+    while let Some(expression) = expressions.pop() {
+        match &expression.kind {
+            ExpressionKind::Call(_, arguments) => expressions.extend(arguments.iter()),
+            ExpressionKind::MethodCall {
+                receiver, arguments, ..
+            } => {
+                expressions.push(receiver);
+                expressions.extend(arguments.iter());
+            }
+            ExpressionKind::BinaryOperation(a, _, b) => {
+                expressions.push(a);
+                expressions.push(b);
+            }
+            ExpressionKind::Reference(_, inner)
+            | ExpressionKind::Field(inner, _)
+            | ExpressionKind::Cast(inner)
+            | ExpressionKind::Try(inner) => expressions.push(inner),
+            ExpressionKind::Closure { parameters, body, .. } => {
+                for (id, t) in parameters {
+                    types.insert(*id, t.clone());
+                }
+                inferred_types(body, types);
+            }
+            ExpressionKind::Value(_) | ExpressionKind::Local(_) | ExpressionKind::Item(_) => {}
+        }
+    }
+}
+
+/// Types that code generation can allocate without knowing more than the
+/// program: no generics, no `_` and no closures, which it types itself.
+fn is_concrete(t: &Type, symbols: &Symbols) -> bool {
+    return match t {
+        Type::Base(_) | Type::Dyn(_) => true,
+        Type::Custom(name) => symbols.types.contains_key(name),
+        Type::Generic(name, types) => {
+            (symbols.types.contains_key(name) || name == "Box") && types.iter().all(|t| is_concrete(t, symbols))
+        }
+        Type::Tuple(types) => types.len() > 0 && types.iter().all(|t| is_concrete(t, symbols)),
+        Type::Reference(_, inner) | Type::Pointer(_, inner) => is_concrete(inner, symbols),
+        Type::Function(_, _) => false,
+    };
+}
+
+struct Annotator<'a> {
+    symbols: &'a Symbols,
+    types: HashMap<DefId, Type>,
+}
+impl<'a> Annotator<'a> {
+    fn inferred(&self, id: &Option<DefId>) -> Option<Type> {
+        return match id.and_then(|id| self.types.get(&id)) {
+            Some(t) if is_concrete(t, self.symbols) => Some(t.clone()),
+            _ => None,
+        };
+    }
+
+    fn nodes(&self, nodes: &mut Vec<ASTNode>) {
+        for ast_node in nodes {
+            match &mut ast_node.node {
+                Node::DefineVariable {
+                    var_type,
+                    expression,
+                    definition,
+                    ..
+                } => {
+                    if var_type.is_none() {
+                        *var_type = self.inferred(definition);
+                    }
+                    match expression {
+                        Some(expression) => self.expression(expression),
+                        None => {}
+                    }
+                }
+                Node::SetVariable(_, expression) | Node::Return(Some(expression)) => self.expression(expression),
+                Node::Call(_, arguments) | Node::MethodCall(_, _, arguments) => {
+                    for argument in arguments {
+                        self.expression(argument);
+                    }
+                }
+                Node::InlineAsm { operands, .. } => {
+                    for operand in operands {
+                        match operand {
+                            AsmOperand::In(_, expression) => self.expression(expression),
+                            _ => continue,
+                        }
+                    }
+                }
+                Node::Scope { body, .. } | Node::Loop { body } => self.nodes(body),
+                Node::Function { body: Some(body), .. } => self.nodes(body),
+                Node::Trait { body, .. } | Node::Impl { body, .. } => self.nodes(body),
+                _ => continue,
+            }
+        }
+    }
+
+    fn expression(&self, expression: &mut Expression) {
+        match expression {
+            Expression::Value(_) | Expression::GetVariable(_) => {}
+            Expression::Call(_, arguments) => {
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            Expression::MethodCall(receiver, _, arguments) => {
+                self.expression(receiver);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            Expression::BinaryOperation(a, _, b) => {
+                self.expression(a);
+                self.expression(b);
+            }
+            Expression::Reference(_, inner)
+            | Expression::Field(inner, _)
+            | Expression::Cast(inner, _)
+            | Expression::Try(inner)
+            | Expression::NamedArgument(_, inner) => self.expression(inner),
+            Expression::Closure {
+                parameters,
+                body,
+                parameter_definitions,
+                ..
+            } => {
+                for ((_, t), id) in parameters.iter_mut().zip(parameter_definitions.iter()) {
+                    if t.is_none() {
+                        *t = self.inferred(&Some(*id));
+                    }
+                }
+                self.nodes(body);
+            }
+        }
+    }
+}
+
+/// Gives every expression in a function body a type and checks that values
+/// fit where they are used: in `let` with an annotation, in assignments, as
+/// arguments of calls, as return values and as operands of each other.
+/// Types that are not written down are found from how values are used in
+/// the rest of the function, and then written into the program so code
+/// generation gives locals the same types.
+/// Returns the bodies with their types for the stages after it.
+pub fn check_types(
+    program: &mut Program,
+    symbols: &Symbols,
+    traits: &Traits,
+) -> Result<Vec<TypedFunction>, CompileError> {
+    let mut order: Vec<ModulePath> = program.modules.keys().cloned().collect();
+    order.sort();
+
+    let mut checker = Checker {
+        symbols,
+        traits,
+        signatures: HashMap::new(),
+        globals: HashMap::new(),
+        structs: HashMap::new(),
+        variants: HashMap::new(),
+        relative_path: PathBuf::new(),
+        line: 0,
+        locals: HashMap::new(),
+        variables: Vec::new(),
+        self_type: None,
+        return_type: None,
+        inferred: None,
+        is_unsafe: false,
+        casts: Vec::new(),
+    };
+    for module_path in &order {
+        checker.collect(&program.modules[module_path].body);
+    }
+
+    let mut functions = Vec::new();
+    for module_path in &order {
+        let module = &program.modules[module_path];
+        checker.relative_path = module.relative_path.clone();
+        for ast_node in &module.body {
+            let mut methods = Vec::new();
+            match &ast_node.node {
+                Node::Function { name, .. } => methods.push((name.clone(), ast_node, None)),
+                Node::Impl { target, body, .. } => {
+                    for method in body {
+                        match &method.node {
+                            Node::Function { name, .. } => methods.push((
+                                format!("{}::{}", target_name(target), name),
+                                method,
+                                Some(target.clone()),
+                            )),
+                            _ => continue,
+                        }
+                    }
+                }
+                // Default methods see `self` as a trait object of their trait.
+                Node::Trait { name: trait_name, body, .. } => {
+                    for method in body {
+                        match &method.node {
+                            Node::Function { name, .. } => methods.push((
+                                format!("{}::{}", trait_name, name),
+                                method,
+                                Some(Type::Dyn(trait_name.clone())),
+                            )),
+                            _ => continue,
+                        }
+                    }
+                }
+                _ => continue,
+            }
+            for (name, method, self_type) in methods {
+                match checker.function(name, method, self_type) {
+                    Ok(Some(function)) => functions.push(function),
+                    Ok(None) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+        }
+    }
+
+    let mut annotator = Annotator {
+        symbols,
+        types: HashMap::new(),
+    };
+    for function in &functions {
+        inferred_types(&function.body, &mut annotator.types);
+    }
+    for (_, module) in program.modules.iter_mut() {
+        annotator.nodes(&mut module.body);
+    }
+    return Ok(functions);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        builder::{compile_error, compile_source},
+        BuildError,
+    };
+
+    const SHAPE: &str = "trait Shape {\n    fn area(&self): i32;\n}\n\nstruct Square(i32);\n\nimpl Shape for Square {\n    fn area(&self): i32 {\n        return self.0 * self.0;\n    }\n}\n\n";
+
+    #[test]
+    fn references_do_not_become_boxes() {
+        let error = compile_error(&format!(
+            "{}fn main() {{\n    let square = Square(2);\n    let shape: Box<dyn Shape> = &square;\n}}\n",
+            SHAPE
+        ));
+        assert!(matches!(error, BuildError::MismatchedTypes(expected, _) if expected == "Box<dyn Shape>"));
+    }
+
+    #[test]
+    fn boxes_do_not_become_references() {
+        let error = compile_error(&format!(
+            "{}fn main() {{\n    let square = Box::new(Square(2));\n    let shape: &dyn Shape = square;\n}}\n",
+            SHAPE
+        ));
+        assert!(matches!(error, BuildError::MismatchedTypes(expected, _) if expected == "&dyn Shape"));
+    }
+
+    #[test]
+    fn literals_take_the_width_their_use_needs() {
+        let source = "fn f(x: u8) {}\n\nfn main() {\n    let a = 200;\n    f(a);\n}\n";
+        let compiled = compile_source(source).unwrap();
+        assert!(compiled.ir.to_string().contains("%0: u8 = const 200"));
+        let error = compile_error("fn f(x: u8) {}\n\nfn main() {\n    let a = 300;\n    f(a);\n}\n");
+        assert!(matches!(error, BuildError::OutOfRange(value, t) if value == "300" && t == "u8"));
+    }
+
+    #[test]
+    fn literals_default_to_i32() {
+        let error = compile_error("fn main() {\n    let x = 3000000000;\n}\n");
+        assert!(matches!(error, BuildError::OutOfRange(_, t) if t == "i32"));
+    }
+
+    #[test]
+    fn open_types_need_annotations() {
+        let error = compile_error("fn main() {\n    let x = None;\n}\n");
+        assert!(matches!(error, BuildError::TypeAnnotationsNeeded(t, Some(name)) if t == "Option<_>" && name == "x"));
+    }
+
+    const CASTS: &str = "struct W(i32);\n\nstruct P(*const i32);\n\nimpl W {\n    fn me(&self): W {\n        return W(self.0);\n    }\n}\n\nfn f(x: i32): i32 {\n    return x;\n}\n\n";
+
+    #[test]
+    fn casts_check_the_type_of_any_operand() {
+        let invalid = [
+            ("let a = f(3) as bool;", "i32", "bool"),
+            ("let a = f as i64;", "fn(i32): i32", "i64"),
+            ("let w = W(1);\n    let a = w.0 as bool;", "i32", "bool"),
+            ("let w = W(1);\n    let a = w.me() as i32;", "W", "i32"),
+        ];
+        for (body, from, to) in invalid {
+            let error = compile_error(&format!("{}fn main() {{\n    {}\n}}\n", CASTS, body));
+            assert!(
+                matches!(&error, BuildError::InvalidCast(f, t) if f == from && t == to),
+                "{}: {:?}",
+                body,
+                error
+            );
+        }
+        let source = format!(
+            "{}fn main() {{\n    let w = W(1);\n    let a = f(3) as i64;\n    let b = w.0 as u8;\n    let c = w.me().0 as f64;\n}}\n",
+            CASTS
+        );
+        assert!(compile_source(&source).is_ok());
+    }
+
+    #[test]
+    fn pointer_fields_become_integers_only_in_unsafe() {
+        let error = compile_error(&format!(
+            "{}fn main() {{\n    let x = 1;\n    let p = P(&x as *const i32);\n    let a = p.0 as usize;\n}}\n",
+            CASTS
+        ));
+        assert!(matches!(error, BuildError::UnsafeCast(from, to) if from == "*const i32" && to == "usize"));
+        let source = format!(
+            "{}fn main() {{\n    let x = 1;\n    let p = P(&x as *const i32);\n    unsafe {{\n        let a = p.0 as usize;\n    }}\n}}\n",
+            CASTS
+        );
+        assert!(compile_source(&source).is_ok());
+    }
+
+    #[test]
+    fn literals_are_cast_once_their_type_is_known() {
+        let error = compile_error("fn main() {\n    let x = 65;\n    let c = x as char;\n}\n");
+        assert!(matches!(error, BuildError::InvalidCast(from, to) if from == "i32" && to == "char"));
+        assert!(compile_source("fn main() {\n    let x: u8 = 65;\n    let c = x as char;\n    let d = 66 as char;\n}\n").is_ok());
+    }
+
+    const TRY: &str = "struct W(i32);\n\nimpl W {\n    fn get(&self): i32 {\n        return self.0;\n    }\n\n    fn find(&self): Option<i32> {\n        return Some(self.0);\n    }\n}\n\n";
+
+    #[test]
+    fn try_needs_an_option_or_result() {
+        let operands = [("w.get()", "i32"), ("w.0", "i32"), ("w", "W"), ("5", "{integer}")];
+        for (operand, found) in operands {
+            let error = compile_error(&format!(
+                "{}fn f(w: W): Option<i32> {{\n    let x = {}?;\n    return Some(1);\n}}\n",
+                TRY, operand
+            ));
+            assert!(
+                matches!(&error, BuildError::TryOnIncompatibleType(t) if t == found),
+                "{}: {:?}",
+                operand,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn try_needs_a_matching_return_type() {
+        let error = compile_error(&format!(
+            "{}fn f(w: W): Result<i32, bool> {{\n    let x = w.find()?;\n    return Ok(x);\n}}\n",
+            TRY
+        ));
+        assert!(matches!(error, BuildError::TryMismatch(from, to) if from == "Option<i32>" && to == "Result<i32, bool>"));
+        let error = compile_error(&format!("{}fn f(w: W): i32 {{\n    let x = w.find()?;\n    return x;\n}}\n", TRY));
+        assert!(matches!(error, BuildError::TryInIncompatibleFunction(t) if t == "i32"));
+        let error = compile_error(
+            "fn g(): Result<i32, u8> {\n    return Ok(1);\n}\n\nfn f(): Result<i32, bool> {\n    let x = g()?;\n    return Ok(x);\n}\n",
+        );
+        assert!(matches!(error, BuildError::TryMismatch(from, to) if from == "Result<i32, u8>" && to == "Result<i32, bool>"));
+    }
+
+    #[test]
+    fn try_gives_the_value_inside() {
+        let source = format!(
+            "{}fn f(w: W): Option<i32> {{\n    let x: i32 = w.find()?;\n    return Some(x + 1);\n}}\n\nfn g(): Result<u8, bool> {{\n    let e: Result<u8, bool> = Ok(2);\n    let x = e?;\n    return Ok(x);\n}}\n\nfn main() {{}}\n",
+            TRY
+        );
+        assert!(compile_source(&source).is_ok());
+    }
+
+    #[test]
+    fn try_returns_the_error_early() {
+        let source = "fn half(x: i32): Result<i32, bool> {\n    return Ok(x / 2);\n}\n\nfn f(x: i32): Result<i32, bool> {\n    let y = half(x)?;\n    return Ok(y + 1);\n}\n\nfn main() {\n    let r = f(4);\n}\n";
+        let ir = compile_source(source).unwrap().ir.to_string();
+        assert!(ir.contains("%3: bool = is %2 Result::Ok // line 6\n    branch %3, bb1, bb2\n"));
+        assert!(ir.contains("%5: bool = (%2 as Result::Err).0 // line 6\n    %4: Result<i32, bool> = Result::Err(%5) // line 6\n    return %4\n"));
+    }
+
+    #[test]
+    fn try_needs_a_function_that_can_return_early() {
+        let error = compile_error("fn main() {\n    let v = Some(1);\n    let x = v?;\n}\n");
+        assert!(matches!(error, BuildError::TryInIncompatibleFunction(t) if t == "()"));
+        // Closures return from themselves, not from the function around them.
+        let error = compile_error("fn f(): Option<i32> {\n    let g = |v Option<i32>|: i32 {\n        let x = v?;\n        return x;\n    };\n    return Some(1);\n}\n\nfn main() {}\n");
+        assert!(matches!(error, BuildError::TryInIncompatibleFunction(t) if t == "i32"));
+        let source = "fn main() {\n    let g = |v Option<i32>|: Option<i32> {\n        let x = v?;\n        return Some(x + 1);\n    };\n    let y = g(Some(1));\n}\n";
+        assert!(compile_source(source).is_ok());
+    }
+
+    #[test]
+    fn try_keeps_option_and_result_apart() {
+        let error = compile_error("fn f(r: Result<i32, bool>): Option<i32> {\n    let x = r?;\n    return Some(x);\n}\n\nfn main() {}\n");
+        assert!(matches!(error, BuildError::TryMismatch(from, to) if from == "Result<i32, bool>" && to == "Option<i32>"));
+        // The error type of a `None` or `Ok` is learnt from the function it returns from.
+        let source = "fn f(): Result<i32, bool> {\n    let r = Ok(1);\n    let x = r?;\n    return Ok(x);\n}\n\nfn main() {\n    let r = f();\n}\n";
+        assert!(compile_source(source).is_ok());
+    }
+
+    #[test]
+    fn format_strings_match_their_arguments() {
+        let source = "struct Point(i32, i32);\n\nfn main() {\n    let p = Point(1, 2);\n    let x: u8 = 3;\n    let s = format(\"{}-{:x}\", x, 255);\n    print(\"{} {:b} {:o} {:X} {}\\n\", s, x, &x, p.0, true);\n    println();\n}\n";
+        assert!(compile_source(source).is_ok());
+        let error = compile_error("fn main() {\n    let s = \"{}\";\n    println(s, 1);\n}\n");
+        assert!(matches!(error, BuildError::FormatStringExpected(name) if name == "println"));
+        let error = compile_error("fn main() {\n    print();\n}\n");
+        assert!(matches!(error, BuildError::FormatStringExpected(name) if name == "print"));
+        let error = compile_error("fn main() {\n    println(\"{:?}\", 1);\n}\n");
+        assert!(matches!(error, BuildError::InvalidFormatString(_)));
+        let error = compile_error("fn main() {\n    println(\"{} {}\", 1);\n}\n");
+        assert!(matches!(error, BuildError::FormatArgumentCount(2, 1)));
+        let error = compile_error("fn main() {\n    let s = format(\"{{}}\", 1);\n}\n");
+        assert!(matches!(error, BuildError::FormatArgumentCount(0, 1)));
+    }
+
+    #[test]
+    fn placeholders_need_a_formattable_argument() {
+        let error = compile_error("struct Point(i32, i32);\n\nfn main() {\n    println(\"{}\", Point(1, 2));\n}\n");
+        assert!(matches!(error, BuildError::NotFormattable(t, "{}") if t == "Point"));
+        let error = compile_error("fn main() {\n    println(\"{:x}\", true);\n}\n");
+        assert!(matches!(error, BuildError::NotFormattable(t, "{:x}") if t == "bool"));
+        let error = compile_error("fn main() {\n    println(\"{:b}\", \"text\");\n}\n");
+        assert!(matches!(error, BuildError::NotFormattable(t, "{:b}") if t == "&str"));
+    }
+
+    fn asm(operands: &str) -> String {
+        return format!(
+            "fn main() {{\n    let x: i64 = 5;\n    let mut y: i64 = 0;\n    unsafe {{\n        asm!(\"nop\", {});\n    }}\n}}\n",
+            operands
+        );
+    }
+
+    #[test]
+    fn asm_operands_bind_registers() {
+        let source = "fn main() {\n    let x: i64 = 5;\n    let mut y: i64 = 0;\n    unsafe {\n        asm!(\"mov rbx, rax\", \"add rbx, 1\", in(\"rax\") x, out(\"rbx\") y, clobber(\"rcx\", \"rdx\"));\n    }\n    println(\"{}\", y);\n}\n";
+        let assembly = compile_source(source).unwrap().assembly;
+        // `rbx` is callee saved, so it is restored once its output is stored.
+        assert!(assembly.contains("\tmov rbx, rax\n\tadd rbx, 1\n\tmov [rbp-72], rbx\n\tmov rbx, [rbp-64]\n"));
+        assert!(compile_source(&asm("inout(\"rax\") y, out(\"rcx\") _")).is_ok());
+        // A register may be both read by one operand and written by another.
+        assert!(compile_source(&asm("in(\"rax\") x, out(\"rax\") y")).is_ok());
+    }
+
+    #[test]
+    fn asm_needs_unsafe() {
+        let error = compile_error("fn main() {\n    asm!(\"nop\");\n}\n");
+        assert!(matches!(error, BuildError::AsmOutsideUnsafe));
+        assert!(compile_source("unsafe fn f() {\n    asm!(\"nop\");\n}\n\nfn main() {\n    unsafe {\n        f();\n    }\n}\n").is_ok());
+    }
+
+    #[test]
+    fn asm_registers_are_checked() {
+        let error = compile_error(&asm("in(\"rsp\") x"));
+        assert!(matches!(error, BuildError::ReservedRegister(register) if register == "rsp"));
+        let error = compile_error(&asm("clobber(\"rbp\")"));
+        assert!(matches!(error, BuildError::ReservedRegister(register) if register == "rbp"));
+        let error = compile_error(&asm("in(\"eax\") x"));
+        assert!(matches!(error, BuildError::UnknownRegister(register) if register == "eax"));
+        let error = compile_error(&asm("in(\"rax\") x, inout(\"rax\") y"));
+        assert!(matches!(error, BuildError::DuplicateAsmRegister(register) if register == "rax"));
+        let error = compile_error(&asm("out(\"rcx\") y, clobber(\"rcx\")"));
+        assert!(matches!(error, BuildError::DuplicateAsmRegister(register) if register == "rcx"));
+    }
+
+    #[test]
+    fn asm_operands_fit_a_register() {
+        let error = compile_error(&asm("in(\"rax\") \"text\""));
+        assert!(matches!(error, BuildError::AsmOperandType(t) if t == "&str"));
+        let error = compile_error("struct Point(i32, i32);\n\nfn main() {\n    let mut p = Point(1, 2);\n    unsafe {\n        asm!(\"nop\", out(\"rax\") p);\n    }\n}\n");
+        assert!(matches!(error, BuildError::AsmOperandType(t) if t == "Point"));
+        assert!(compile_source(&asm("in(\"rax\") &x, in(\"rbx\") true, in(\"rcx\") 7")).is_ok());
+    }
+}
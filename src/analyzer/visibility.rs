@@ -0,0 +1,695 @@
+use std::collections::HashMap;
+
+use crate::{
+    builder::{Module, ModulePath},
+    parser::{ASTNode, AsmOperand, DefId, Expression, Node, Path, Program, Type},
+    BuildError, BuildProblem, CompileError,
+};
+
+use super::names::{DefKind, Definition, Symbols};
+
+struct Visibility<'a> {
+    modules: &'a HashMap<ModulePath, Module>,
+    symbols: &'a Symbols,
+    /// The fields of each struct, with whether they are `pub`.
+    fields: HashMap<String, Vec<(bool, String)>>,
+    /// What functions and methods return, to know the type of a receiver.
+    returns: HashMap<DefId, Option<Type>>,
+
+    /// The module being checked and the type `Self` stands for in it.
+    module: ModulePath,
+    self_type: Option<String>,
+    /// The generic parameters in scope, which shadow items of the same name.
+    generics: Vec<String>,
+}
+impl<'a> Visibility<'a> {
+    fn private(&self, what: String, definition: &Definition) -> BuildError {
+        return BuildError::Private(what, definition.relative_path.clone(), definition.line);
+    }
+
+    /// Checks that every module on the way to `module` is reachable, which
+    /// takes a `pub import` unless the current module is inside the importer.
+    fn reach(&self, module: &ModulePath) -> Result<(), BuildError> {
+        for depth in 1..module.0.len() {
+            let parent = ModulePath(module.0[..depth].to_vec());
+            if self.module.is_within(&parent) {
+                continue;
+            }
+            let importer = match self.modules.get(&parent) {
+                Some(importer) => importer,
+                None => continue,
+            };
+            let child = ModulePath(module.0[..depth + 1].to_vec());
+            match importer.imports.iter().find(|import| import.module == child) {
+                Some(import) if !import.export => {
+                    return Err(BuildError::Private(
+                        format!("module `{}`", import.name),
+                        importer.relative_path.clone(),
+                        import.line,
+                    ))
+                }
+                _ => {}
+            }
+        }
+        return Ok(());
+    }
+
+    fn access(&self, what: String, definition: &Definition) -> Result<(), BuildError> {
+        if !definition.export && !self.module.is_within(&definition.module) {
+            return Err(self.private(what, definition));
+        }
+        return self.reach(&definition.module);
+    }
+
+    fn item(&self, id: DefId) -> Result<(), BuildError> {
+        let definition = self.symbols.definition(id);
+        let kind = match definition.kind {
+            DefKind::Function | DefKind::ForeignFunction => "function",
+            DefKind::Struct => "struct",
+            DefKind::Enum => "enum",
+            DefKind::Trait => "trait",
+            DefKind::Constant => "constant",
+            DefKind::Static => "static",
+            DefKind::TypeAlias => "type alias",
+            _ => return Ok(()),
+        };
+        return self.access(format!("{} `{}`", kind, definition.name), definition);
+    }
+
+    /// Checks a struct, enum, trait or type alias named in type position.
+    fn type_item(&self, name: &String) -> Result<(), BuildError> {
+        if self.generics.contains(name) {
+            return Ok(());
+        }
+        return match self.symbols.types.get(name) {
+            Some(id) => self.item(*id),
+            None => Ok(()),
+        };
+    }
+
+    /// The type a variant or method is kept under, like `Shape` for
+    /// `Shape::area`.
+    fn owner(&self, definition: &Definition) -> Option<String> {
+        return match definition.name.rsplit_once("::") {
+            Some((owner, _)) => Some(String::from(owner)),
+            None => None,
+        };
+    }
+
+    fn field(&self, struct_name: &String, field: &String) -> Result<(), BuildError> {
+        let definition = match self.symbols.types.get(struct_name) {
+            Some(id) => self.symbols.definition(*id),
+            None => return Ok(()),
+        };
+        let export = match self.fields.get(struct_name) {
+            Some(fields) => match fields.iter().find(|(_, name)| name == field) {
+                Some((export, _)) => *export,
+                None => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+        if !export && !self.module.is_within(&definition.module) {
+            return Err(self.private(
+                format!("field `{}` of struct `{}`", field, struct_name),
+                definition,
+            ));
+        }
+        return Ok(());
+    }
+
+    /// Checks a method and the type it belongs to. Methods of trait impls
+    /// are as visible as the trait.
+    fn method(&self, id: DefId) -> Result<(), BuildError> {
+        let definition = self.symbols.definition(id);
+        let owner = match self.owner(definition) {
+            Some(owner) => owner,
+            None => return Ok(()),
+        };
+        match self.type_item(&owner) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+        let method = &definition.name[owner.len() + 2..];
+        return self.access(format!("method `{}` of `{}`", method, owner), definition);
+    }
+
+    /// Checks what a path resolved to, however it was spelled. Constructing a
+    /// tuple struct also takes all of its fields to be visible.
+    fn path(&self, path: &Path) -> Result<(), BuildError> {
+        let id = match path.definition {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let definition = self.symbols.definition(id);
+        return match definition.kind {
+            DefKind::Variant => match self.owner(definition) {
+                Some(owner) => self.type_item(&owner),
+                None => Ok(()),
+            },
+            DefKind::Method => self.method(id),
+            DefKind::Struct => {
+                match self.item(id) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+                match self.fields.get(&definition.name) {
+                    Some(fields) => {
+                        for (_, field) in fields {
+                            match self.field(&definition.name, field) {
+                                Ok(()) => {}
+                                Err(error) => return Err(error),
+                            }
+                        }
+                    }
+                    None => {}
+                }
+                Ok(())
+            }
+            _ => self.item(id),
+        };
+    }
+
+    fn check_type(&self, t: &Type) -> Result<(), BuildError> {
+        return match t {
+            Type::Custom(name) | Type::Dyn(name) => self.type_item(name),
+            Type::Generic(name, types) => match self.type_item(name) {
+                Ok(()) => self.check_types(types),
+                Err(error) => Err(error),
+            },
+            Type::Tuple(types) => self.check_types(types),
+            Type::Reference(_, inner) | Type::Pointer(_, inner) => self.check_type(inner),
+            Type::Function(parameters, return_type) => match self.check_types(parameters) {
+                Ok(()) => match return_type {
+                    Some(t) => self.check_type(t),
+                    None => Ok(()),
+                },
+                Err(error) => Err(error),
+            },
+            Type::Base(_) => Ok(()),
+        };
+    }
+
+    fn check_types(&self, types: &Vec<Type>) -> Result<(), BuildError> {
+        for t in types {
+            match self.check_type(t) {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        return Ok(());
+    }
+
+    /// The struct a value of type `t` is, seen through references and `Self`.
+    fn struct_name(&self, t: &Option<Type>) -> Option<String> {
+        return match t {
+            Some(Type::Reference(_, inner)) => self.struct_name(&Some(*inner.clone())),
+            Some(Type::Custom(name)) if name == "Self" => self.self_type.clone(),
+            Some(Type::Custom(name)) => Some(name.clone()),
+            _ => None,
+        };
+    }
+
+    /// What the function or method returns, with `Self` standing for `target`.
+    fn returns(&self, id: DefId, target: Option<String>) -> Option<Type> {
+        return match (self.returns.get(&id), target) {
+            (Some(Some(Type::Custom(name))), Some(target)) if name == "Self" => Some(Type::Custom(target)),
+            (Some(t), _) => t.clone(),
+            (None, _) => None,
+        };
+    }
+
+    /// The type of an expression as far as it is needed to find the struct
+    /// behind a field access or a method call.
+    fn type_of(&self, locals: &HashMap<DefId, Option<Type>>, expression: &Expression) -> Option<Type> {
+        return match expression {
+            Expression::GetVariable(path) => match path.definition.and_then(|id| locals.get(&id)) {
+                Some(t) => t.clone(),
+                None => None,
+            },
+            Expression::Call(path, _) => {
+                let id = match path.definition {
+                    Some(id) => id,
+                    None => return None,
+                };
+                let definition = self.symbols.definition(id);
+                match definition.kind {
+                    DefKind::Struct => Some(Type::Custom(definition.name.clone())),
+                    _ => self.returns(id, self.owner(definition)),
+                }
+            }
+            Expression::MethodCall(receiver, method, _) => {
+                let target = match self.struct_name(&self.type_of(locals, receiver)) {
+                    Some(target) => target,
+                    None => return None,
+                };
+                match self.symbols.members.get(&format!("{}::{}", target, method)) {
+                    Some(id) => self.returns(*id, Some(target)),
+                    None => None,
+                }
+            }
+            Expression::Reference(mutable, inner) => match self.type_of(locals, inner) {
+                Some(t) => Some(Type::Reference(*mutable, Box::new(t))),
+                None => None,
+            },
+            Expression::Cast(_, t) => Some(t.clone()),
+            _ => None,
+        };
+    }
+
+    fn expressions(
+        &self,
+        locals: &mut HashMap<DefId, Option<Type>>,
+        expressions: &Vec<Expression>,
+    ) -> Result<(), BuildError> {
+        for expression in expressions {
+            match self.expression(locals, expression) {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        return Ok(());
+    }
+
+    fn expression(
+        &self,
+        locals: &mut HashMap<DefId, Option<Type>>,
+        expression: &Expression,
+    ) -> Result<(), BuildError> {
+        return match expression {
+            Expression::Value(_) => Ok(()),
+            Expression::GetVariable(path) => self.path(path),
+            Expression::Call(path, arguments) => match self.path(path) {
+                Ok(()) => self.expressions(locals, arguments),
+                Err(error) => Err(error),
+            },
+            Expression::BinaryOperation(a, _, b) => match self.expression(locals, a) {
+                Ok(()) => self.expression(locals, b),
+                Err(error) => Err(error),
+            },
+            Expression::Reference(_, inner)
+            | Expression::Try(inner)
+            | Expression::NamedArgument(_, inner) => self.expression(locals, inner),
+            Expression::Field(inner, field) => match self.expression(locals, inner) {
+                Ok(()) => match self.struct_name(&self.type_of(locals, inner)) {
+                    Some(struct_name) => self.field(&struct_name, field),
+                    None => Ok(()),
+                },
+                Err(error) => Err(error),
+            },
+            Expression::Cast(inner, t) => match self.check_type(t) {
+                Ok(()) => self.expression(locals, inner),
+                Err(error) => Err(error),
+            },
+            Expression::MethodCall(receiver, method, arguments) => {
+                match self.expression(locals, receiver) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+                let target = self.struct_name(&self.type_of(locals, receiver));
+                match target.and_then(|target| self.symbols.members.get(&format!("{}::{}", target, method))) {
+                    Some(id) => match self.method(*id) {
+                        Ok(()) => {}
+                        Err(error) => return Err(error),
+                    },
+                    None => {}
+                }
+                self.expressions(locals, arguments)
+            }
+            Expression::Closure {
+                parameters,
+                return_type,
+                body,
+                parameter_definitions,
+                ..
+            } => {
+                for ((_, t), id) in parameters.iter().zip(parameter_definitions) {
+                    match t {
+                        Some(t) => match self.check_type(t) {
+                            Ok(()) => {}
+                            Err(error) => return Err(error),
+                        },
+                        None => {}
+                    }
+                    locals.insert(*id, t.clone());
+                }
+                match return_type {
+                    Some(t) => match self.check_type(t) {
+                        Ok(()) => {}
+                        Err(error) => return Err(error),
+                    },
+                    None => {}
+                }
+                match self.body(locals, body) {
+                    Ok(()) => Ok(()),
+                    Err(CompileError::BuildProblem(problem)) => Err(problem.error),
+                    Err(_) => Ok(()),
+                }
+            }
+        };
+    }
+
+    fn body(&self, locals: &mut HashMap<DefId, Option<Type>>, body: &Vec<ASTNode>) -> Result<(), CompileError> {
+        for ast_node in body {
+            let result = match &ast_node.node {
+                Node::DefineVariable {
+                    var_type,
+                    expression,
+                    definition,
+                    ..
+                } => {
+                    let result = match var_type {
+                        Some(t) => self.check_type(t),
+                        None => Ok(()),
+                    };
+                    let result = match (result, expression) {
+                        (Ok(()), Some(expression)) => self.expression(locals, expression),
+                        (result, _) => result,
+                    };
+                    let t = match (var_type, expression) {
+                        (Some(t), _) => Some(t.clone()),
+                        (None, Some(expression)) => self.type_of(locals, expression),
+                        (None, None) => None,
+                    };
+                    match definition {
+                        Some(id) => {
+                            locals.insert(*id, t);
+                        }
+                        None => {}
+                    }
+                    result
+                }
+                Node::SetVariable(_, expression) | Node::Return(Some(expression)) => {
+                    self.expression(locals, expression)
+                }
+                Node::Call(path, arguments) => match self.path(path) {
+                    Ok(()) => self.expressions(locals, arguments),
+                    Err(error) => Err(error),
+                },
+                Node::MethodCall(path, method, arguments) => {
+                    let receiver = Expression::GetVariable(path.clone());
+                    self.expression(
+                        locals,
+                        &Expression::MethodCall(Box::new(receiver), method.clone(), arguments.clone()),
+                    )
+                }
+                Node::InlineAsm { operands, .. } => {
+                    for operand in operands {
+                        match operand {
+                            AsmOperand::In(_, expression) => match self.expression(locals, expression) {
+                                Ok(()) => {}
+                                Err(error) => return Err(self.problem(error, ast_node.line)),
+                            },
+                            _ => {}
+                        }
+                    }
+                    Ok(())
+                }
+                Node::Scope { body, .. } | Node::Loop { body } => match self.body(locals, body) {
+                    Ok(()) => Ok(()),
+                    Err(error) => return Err(error),
+                },
+                _ => Ok(()),
+            };
+            match result {
+                Ok(()) => {}
+                Err(error) => return Err(self.problem(error, ast_node.line)),
+            }
+        }
+        return Ok(());
+    }
+
+    fn problem(&self, error: BuildError, line: usize) -> CompileError {
+        let relative_path = self.modules.get(&self.module).unwrap().relative_path.clone();
+        return CompileError::BuildProblem(BuildProblem::new(error, relative_path, line));
+    }
+
+    /// Checks the items of a module, and the methods of a trait or impl.
+    fn nodes(&mut self, nodes: &Vec<ASTNode>) -> Result<(), CompileError> {
+        for ast_node in nodes {
+            let length = self.generics.len();
+            match &ast_node.node {
+                Node::Function { generics, .. }
+                | Node::Struct { generics, .. }
+                | Node::Enum { generics, .. }
+                | Node::Trait { generics, .. }
+                | Node::Impl { generics, .. } => self.generics.extend(generics.iter().cloned()),
+                _ => {}
+            }
+            let result = match &ast_node.node {
+                Node::Function {
+                    parameters,
+                    defaults,
+                    return_type,
+                    body,
+                    parameter_definitions,
+                    ..
+                } => {
+                    let mut types: Vec<Type> = parameters.iter().map(|(_, t)| t.clone()).collect();
+                    match return_type {
+                        Some(t) => types.push(t.clone()),
+                        None => {}
+                    }
+                    let mut locals = parameter_definitions
+                        .iter()
+                        .zip(parameters)
+                        .map(|(id, (_, t))| (*id, Some(t.clone())))
+                        .collect();
+                    let result = match self.check_types(&types) {
+                        Ok(()) => {
+                            let defaults: Vec<Expression> = defaults.iter().flatten().cloned().collect();
+                            self.expressions(&mut locals, &defaults)
+                        }
+                        Err(error) => Err(error),
+                    };
+                    match (result, body) {
+                        (Ok(()), Some(body)) => match self.body(&mut locals, body) {
+                            Ok(()) => Ok(()),
+                            Err(error) => return Err(error),
+                        },
+                        (result, _) => result,
+                    }
+                }
+                Node::ForeignFunction {
+                    parameters,
+                    return_type,
+                    ..
+                } => {
+                    let mut types: Vec<Type> = parameters.iter().map(|(_, t)| t.clone()).collect();
+                    match return_type {
+                        Some(t) => types.push(t.clone()),
+                        None => {}
+                    }
+                    self.check_types(&types)
+                }
+                Node::Struct { body, .. } => {
+                    let types = body.iter().map(|(_, _, t)| t.clone()).collect();
+                    self.check_types(&types)
+                }
+                Node::Enum { body, .. } => {
+                    let types = body.iter().map(|(_, types)| types.clone()).flatten().collect();
+                    self.check_types(&types)
+                }
+                Node::Constant {
+                    constant_type: item_type,
+                    expression,
+                    ..
+                }
+                | Node::Static {
+                    static_type: item_type,
+                    expression,
+                    ..
+                } => match self.check_type(item_type) {
+                    Ok(()) => self.expression(&mut HashMap::new(), expression),
+                    Err(error) => Err(error),
+                },
+                Node::TypeAlias { aliased, .. } => self.check_type(aliased),
+                Node::Trait { name, body, .. } => {
+                    self.self_type = Some(name.clone());
+                    let result = self.nodes(body);
+                    self.self_type = None;
+                    match result {
+                        Ok(()) => Ok(()),
+                        Err(error) => return Err(error),
+                    }
+                }
+                Node::Impl {
+                    trait_name,
+                    target,
+                    body,
+                    ..
+                } => {
+                    let result = match trait_name {
+                        Some(trait_name) => self.type_item(trait_name),
+                        None => Ok(()),
+                    };
+                    match result {
+                        Ok(()) => match self.check_type(target) {
+                            Ok(()) => {
+                                self.self_type = self.struct_name(&Some(target.clone()));
+                                let result = self.nodes(body);
+                                self.self_type = None;
+                                match result {
+                                    Ok(()) => Ok(()),
+                                    Err(error) => return Err(error),
+                                }
+                            }
+                            Err(error) => Err(error),
+                        },
+                        Err(error) => Err(error),
+                    }
+                }
+                Node::Extern { body, .. } => match self.nodes(body) {
+                    Ok(()) => Ok(()),
+                    Err(error) => return Err(error),
+                },
+                _ => Ok(()),
+            };
+            self.generics.truncate(length);
+            match result {
+                Ok(()) => {}
+                Err(error) => return Err(self.problem(error, ast_node.line)),
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Records what functions and methods return, declared anywhere among `nodes`.
+fn collect_returns(symbols: &Symbols, nodes: &Vec<ASTNode>, returns: &mut HashMap<DefId, Option<Type>>) {
+    for ast_node in nodes {
+        match &ast_node.node {
+            Node::Function {
+                return_type,
+                definition: Some(id),
+                ..
+            } => {
+                returns.insert(*id, return_type.clone());
+            }
+            Node::ForeignFunction { name, return_type, .. } => match symbols.values.get(name) {
+                Some(id) => {
+                    returns.insert(*id, return_type.clone());
+                }
+                None => {}
+            },
+            Node::Trait { body, .. } | Node::Impl { body, .. } | Node::Extern { body, .. } => {
+                collect_returns(symbols, body, returns)
+            }
+            _ => continue,
+        }
+    }
+}
+
+/// Rejects uses of items, fields, methods and modules that are not `pub`
+/// from outside the module that defines them. A private item is visible in
+/// its own module and the modules below it, and an item is only reachable
+/// through modules imported with `pub import` from outside their importer.
+/// Paths are checked by what they resolved to, so `a::helper` is checked
+/// the same as `helper`.
+pub fn check_visibility(program: &Program, symbols: &Symbols) -> Result<(), CompileError> {
+    let mut visibility = Visibility {
+        modules: &program.modules,
+        symbols,
+        fields: HashMap::new(),
+        returns: HashMap::new(),
+        module: ModulePath::root(),
+        self_type: None,
+        generics: Vec::new(),
+    };
+
+    for (_, module) in &program.modules {
+        collect_returns(symbols, &module.body, &mut visibility.returns);
+        for ast_node in &module.body {
+            match &ast_node.node {
+                Node::Struct { name, body, .. } => {
+                    let fields = body.iter().map(|(export, field, _)| (*export, field.clone())).collect();
+                    visibility.fields.insert(name.clone(), fields);
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    for (module_path, module) in &program.modules {
+        visibility.module = module_path.clone();
+        match visibility.nodes(&module.body) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{builder::compile_files, BuildError, CompileError};
+
+    const MODULE: &str = "pub struct S(pub i32, i32);
+
+impl S {
+    pub fn value(&self): i32 {
+        return self.0;
+    }
+    fn secret(&self): i32 {
+        return self.1;
+    }
+}
+
+pub fn make(): S {
+    return S(1, 2);
+}
+
+fn helper(): i32 {
+    return 3;
+}
+";
+
+    /// Compiles `main` next to the module `a`, returning the error it stops at.
+    fn check(main: &str) -> Result<(), BuildError> {
+        let main = format!("import a;\n\nfn main() {{\n    {}\n}}\n", main);
+        return match compile_files(&[("src/main.eclipse", &main), ("src/a.eclipse", MODULE)]) {
+            Ok(_) => Ok(()),
+            Err(CompileError::BuildProblem(problem)) => Err(problem.error),
+            Err(error) => panic!("{:?}", error),
+        };
+    }
+
+    fn private(result: Result<(), BuildError>) -> String {
+        return match result {
+            Err(BuildError::Private(what, _, _)) => what,
+            result => panic!("expected a privacy error, got {:?}", result),
+        };
+    }
+
+    #[test]
+    fn qualified_private_function() {
+        assert_eq!(private(check("let x = a::helper();")), "function `helper`");
+    }
+
+    #[test]
+    fn private_field_of_returned_struct() {
+        assert_eq!(private(check("let x = a::make().1;")), "field `1` of struct `S`");
+        assert_eq!(private(check("let s = a::make();\n    let x = s.1;")), "field `1` of struct `S`");
+    }
+
+    #[test]
+    fn private_method_of_returned_struct() {
+        assert_eq!(private(check("let x = a::make().secret();")), "method `secret` of `S`");
+    }
+
+    #[test]
+    fn private_fields_keep_constructor_out() {
+        assert_eq!(private(check("let s = a::S(1, 2);")), "field `1` of struct `S`");
+    }
+
+    #[test]
+    fn public_items_are_reachable() {
+        assert!(check("let x = a::make().0;\n    let y = a::make().value();").is_ok());
+    }
+
+    #[test]
+    fn generic_parameters_are_not_items() {
+        let main = "import a;\n\nenum E {\n    A,\n}\n\nstruct T(i32);\n\nfn main() {\n    let e = E::A;\n    let x = Some(1);\n}\n";
+        let a = "struct T(i32);\n\nenum E {\n    B,\n}\n";
+        assert!(compile_files(&[("src/main.eclipse", main), ("src/a.eclipse", a)]).is_ok());
+    }
+}
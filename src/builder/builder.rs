@@ -1,20 +1,239 @@
 use std::path::PathBuf;
 
-use crate::analyzer::analyze;
+use crate::analyzer::{
+    analyze, apply_attributes, check_deprecated, check_visibility, hoist_items, resolve_aliases,
+    resolve_arguments, resolve_names,
+};
+use crate::codegen::generate;
+use crate::ir::{cfg_dot, check_assignments, check_flow, lower, to_ssa, verify, IrProgram};
 use crate::parser::Program;
-use crate::CompileError;
+use crate::{execute, CompileError, Warning};
+#[cfg(test)]
+use crate::BuildError;
 
 use crate::FILE_EXTENSION;
 
-pub fn build(project_path: PathBuf) -> Result<String, CompileError> {
-    let mut program = Program::new(project_path);
+/// What `--emit=...` asks to be written to the build directory next to the
+/// assembly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Emit {
+    /// The IR of every function, as `app.ir`.
+    Ir,
+    /// The control-flow graph of every function for Graphviz, as
+    /// `cfg/<index>_<function>.dot`.
+    CfgDot,
+}
+
+/// What a project compiles to before the assembler and linker run.
+pub struct Compiled {
+    pub ir: IrProgram,
+    pub assembly: String,
+    pub warnings: Vec<Warning>,
+    /// The libraries the `extern` blocks link against.
+    pub libraries: Vec<String>,
+}
+
+pub fn build(project_path: PathBuf, emit: &Vec<Emit>) -> Result<String, CompileError> {
+    let name = "app";
+    let compiled = match compile(&project_path, emit) {
+        Ok(compiled) => compiled,
+        Err(error) => return Err(error),
+    };
+    let build_path = project_path.join("build");
+
+    let assembly_file = build_path.join(format!("{}.s", name));
+    match std::fs::write(&assembly_file, compiled.assembly) {
+        Ok(()) => {}
+        Err(error) => return Err(CompileError::OpenFile(error)),
+    };
+
+    match execute(format!(
+        "nasm -f win64 {}",
+        String::from(assembly_file.to_str().unwrap())
+    )) {
+        Ok(_out) => {}
+        Err(error) => return Err(CompileError::NASM(error)),
+    }
+
+    let object_file = String::from(build_path.join(format!("{}.obj", name)).to_str().unwrap());
+    let executable = String::from(build_path.join(name).to_str().unwrap());
+
+    let mut libraries = String::new();
+    for library in &compiled.libraries {
+        libraries.push_str(&format!(" -l{}", library));
+    }
+
+    match execute(format!(
+        "gcc -o {}.exe {} -m64{}",
+        executable, object_file, libraries
+    )) {
+        Ok(_out) => {}
+        Err(error) => return Err(CompileError::GCC(error)),
+    }
+
+    return Ok(executable);
+}
+
+/// Compiles the project to assembly, printing its warnings and writing what
+/// `emit` asks for to the build directory.
+pub fn compile(project_path: &PathBuf, emit: &Vec<Emit>) -> Result<Compiled, CompileError> {
+    let name = "app";
+
+    let mut program = Program::new(project_path.clone());
     match program.parse(PathBuf::from(format!("src/main.{}", FILE_EXTENSION))) {
         Ok(()) => {}
         Err(error) => return Err(error),
     };
+    match program.parse_prelude() {
+        Ok(()) => {}
+        Err(error) => return Err(error),
+    };
+
+    // Before items are hoisted, so items configured away never take a name.
+    match apply_attributes(&mut program) {
+        Ok(()) => {}
+        Err(error) => return Err(error),
+    };
+
+    match hoist_items(&mut program) {
+        Ok(()) => {}
+        Err(error) => return Err(error),
+    };
+
+    // Before aliases are resolved, so uses of an alias are recorded.
+    let symbols = match resolve_names(&mut program) {
+        Ok(symbols) => symbols,
+        Err(error) => return Err(error),
+    };
+
+    // Before aliases are resolved too, so a private alias can't hide behind its target.
+    match check_visibility(&program, &symbols) {
+        Ok(()) => {}
+        Err(error) => return Err(error),
+    };
+
+    match resolve_aliases(&mut program) {
+        Ok(()) => {}
+        Err(error) => return Err(error),
+    };
+
+    match resolve_arguments(&mut program) {
+        Ok(()) => {}
+        Err(error) => return Err(error),
+    };
+
+    let analysis = match analyze(&mut program, symbols) {
+        Ok(analysis) => analysis,
+        Err(error) => return Err(error),
+    };
+
+    let build_path = project_path.join("build");
+    std::fs::create_dir(&build_path).unwrap_or_default();
+
+    // Lowering only sees checked programs, so invalid IR is a compiler bug.
+    let mut ir = lower(&program, &analysis);
+    match verify(&ir) {
+        Ok(()) => {}
+        Err(error) => panic!("invalid IR: {}\n{}", error, ir),
+    }
+    let mut warnings = check_deprecated(&analysis.symbols, &analysis.functions);
+    // Before SSA construction drops the blocks that never run.
+    match check_flow(&ir) {
+        Ok(found) => warnings.extend(found),
+        Err(error) => return Err(error),
+    };
+    for warning in &warnings {
+        warning.print();
+    }
+    match check_assignments(&ir) {
+        Ok(found) => {
+            for warning in &found {
+                warning.print();
+            }
+            warnings.extend(found);
+        }
+        Err(error) => return Err(error),
+    };
+    to_ssa(&mut ir);
+    match verify(&ir) {
+        Ok(()) => {}
+        Err(error) => panic!("invalid IR after SSA construction: {}\n{}", error, ir),
+    }
+    if emit.contains(&Emit::Ir) {
+        match std::fs::write(build_path.join(format!("{}.ir", name)), ir.to_string()) {
+            Ok(()) => {}
+            Err(error) => return Err(CompileError::OpenFile(error)),
+        };
+    }
+    if emit.contains(&Emit::CfgDot) {
+        let cfg_path = build_path.join("cfg");
+        std::fs::create_dir(&cfg_path).unwrap_or_default();
+        for (index, function) in ir.functions.iter().enumerate() {
+            // Closures are named like `main::{closure#0}`, which is no file name.
+            let file_name: String = function
+                .name
+                .chars()
+                .map(|ch| match ch.is_alphanumeric() {
+                    true => ch,
+                    false => '_',
+                })
+                .collect();
+            let file = cfg_path.join(format!("{}_{}.dot", index, file_name));
+            match std::fs::write(file, cfg_dot(function)) {
+                Ok(()) => {}
+                Err(error) => return Err(CompileError::OpenFile(error)),
+            };
+        }
+    }
+
+    let assembly = match generate(&program, &analysis, &ir) {
+        Ok(assembly) => assembly,
+        Err(error) => return Err(error),
+    };
+
+    return Ok(Compiled {
+        ir,
+        assembly,
+        warnings,
+        libraries: analysis.libraries,
+    });
+}
+
 
-    analyze(program).unwrap();
+/// Compiles a project made of `files`, each given by its path in the project,
+/// in a directory of its own.
+#[cfg(test)]
+pub fn compile_files(files: &[(&str, &str)]) -> Result<Compiled, CompileError> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
+    static PROJECTS: AtomicUsize = AtomicUsize::new(0);
+    let project = std::env::temp_dir().join(format!(
+        "eclipse-{}-{}",
+        std::process::id(),
+        PROJECTS.fetch_add(1, Ordering::Relaxed)
+    ));
+    for (path, source) in files {
+        let path = project.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, source).unwrap();
+    }
+    let compiled = compile(&project, &Vec::new());
+    std::fs::remove_dir_all(&project).unwrap_or_default();
+    return compiled;
+}
+
+/// Compiles a project whose only module is `main`.
+#[cfg(test)]
+pub fn compile_source(source: &str) -> Result<Compiled, CompileError> {
+    return compile_files(&[(&format!("src/main.{}", FILE_EXTENSION), source)]);
+}
 
-    todo!()
+/// The error compiling `source` stops at.
+#[cfg(test)]
+pub fn compile_error(source: &str) -> BuildError {
+    return match compile_source(source) {
+        Ok(_) => panic!("expected an error compiling:\n{}", source),
+        Err(CompileError::BuildProblem(problem)) => problem.error,
+        Err(error) => panic!("{:?}", error),
+    };
 }
@@ -2,4 +2,6 @@ mod builder;
 mod module;
 
 pub use module::*;
-pub use builder::build;
+pub use builder::{build, compile, Compiled, Emit};
+#[cfg(test)]
+pub use builder::{compile_error, compile_files, compile_source};
@@ -1,9 +1,53 @@
+use std::{fmt, path::PathBuf};
+
 use crate::parser::ASTNode;
 
 
+/// Where a module sits in the module tree: the crate it belongs to followed by
+/// the names of the imports leading to it, like `crate::network::client`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ModulePath(pub Vec<String>);
+impl ModulePath {
+    /// The root of the user's crate, parsed from `main`.
+    pub fn root() -> Self {
+        Self(vec![String::from("crate")])
+    }
+    pub fn prelude() -> Self {
+        Self(vec![String::from("prelude")])
+    }
+    pub fn child(&self, name: &str) -> Self {
+        let mut path = self.0.clone();
+        path.push(String::from(name));
+        return Self(path);
+    }
+    /// Whether this is `ancestor` or one of the modules below it.
+    pub fn is_within(&self, ancestor: &ModulePath) -> bool {
+        return self.0.starts_with(&ancestor.0);
+    }
+}
+impl fmt::Display for ModulePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join("::"))
+    }
+}
+
 #[derive(Debug)]
 pub struct Module {
-    // name: String,
+    /// The file the module was parsed from, relative to the project.
+    pub relative_path: PathBuf,
     pub body: Vec<ASTNode>,
+    pub imports: Vec<Import>,
 }
 
+/// An `import name;` of a module.
+#[derive(Debug)]
+pub struct Import {
+    pub name: String,
+    /// The path the module is kept under. A file reached through several
+    /// imports is kept under the first one.
+    pub module: ModulePath,
+    /// Whether the import is `pub`, making the module reachable from outside
+    /// the importing one.
+    pub export: bool,
+    pub line: usize,
+}
@@ -1,3 +1,288 @@
-pub fn generate() {
-    todo!()
-}
\ No newline at end of file
+use std::{cell::Cell, collections::HashMap, path::PathBuf};
+
+use crate::{
+    analyzer::{target_name, Analysis, DefKind},
+    builder::ModulePath,
+    ir::IrProgram,
+    parser::{DefId, Node, Program, Type},
+    BuildError, BuildProblem, CompileError, FILE_EXTENSION,
+};
+
+use super::{
+    function::FunctionWriter,
+    labels::Labels,
+    layout::{EnumLayout, Layouts, StructLayout},
+    runtime::{write_runtime, RUNTIME_EXTERNS},
+    statics::{static_key, write_statics},
+    vtable::write_vtables,
+    writer::Writer,
+};
+
+/// Every label the compiler makes up starts with it, as do the routines of
+/// the runtime. Foreign functions cannot be named with it, so the names they
+/// are linked by never clash with one.
+pub const RESERVED_PREFIX: &str = "eclipse_";
+
+pub struct Context<'a> {
+    pub analysis: &'a Analysis,
+    pub ir: &'a IrProgram,
+    pub labels: Labels,
+    pub layouts: Layouts,
+    /// The index of the function of the IR compiled from each definition.
+    pub bodies: HashMap<DefId, usize>,
+    /// Default trait methods taking `&self`, with the trait they belong to.
+    /// They are compiled once and find the methods of `self` through the
+    /// vtable their callers pass in `r11`.
+    pub defaults: HashMap<DefId, String>,
+    /// Set once a function formats values, so the runtime is only linked in
+    /// when it is needed.
+    pub runtime: Cell<bool>,
+}
+impl<'a> Context<'a> {
+    pub fn function_label(&self, index: usize) -> String {
+        return match self.labels.peek(&function_key(index)) {
+            Some(label) => format!("{}_{}", RESERVED_PREFIX, label),
+            None => format!("{}function{}", RESERVED_PREFIX, index),
+        };
+    }
+    pub fn body_label(&self, definition: DefId) -> Option<String> {
+        return match self.bodies.get(&definition) {
+            Some(index) => Some(self.function_label(*index)),
+            None => None,
+        };
+    }
+    pub fn vtable_label(&self, target: &Type, trait_name: &String) -> Option<String> {
+        return match self.labels.peek(&vtable_key(target, trait_name)) {
+            Some(label) => Some(format!("{}_{}", RESERVED_PREFIX, label)),
+            None => None,
+        };
+    }
+    pub fn static_label(&self, name: &String) -> Option<String> {
+        return match self.labels.peek(&static_key(name)) {
+            Some(label) => Some(format!("{}_{}", RESERVED_PREFIX, label)),
+            None => None,
+        };
+    }
+    /// The label of the code that enters a default method with the vtable of
+    /// `target`.
+    pub fn thunk_label(&self, target: &Type, trait_name: &String, method: &String) -> Option<String> {
+        return match self.labels.peek(&method_key(target, trait_name, method)) {
+            Some(label) => Some(format!("{}_{}", RESERVED_PREFIX, label)),
+            None => None,
+        };
+    }
+    /// The method `target` runs for a method of the trait: its own, or the
+    /// default of the trait.
+    pub fn method(&self, target: &Type, trait_name: &String, method: &String) -> Option<DefId> {
+        let members = &self.analysis.symbols.members;
+        return match members.get(&format!("{}::{}", target_name(target), method)) {
+            Some(id) => Some(*id),
+            None => members.get(&format!("{}::{}", trait_name, method)).copied(),
+        };
+    }
+}
+
+pub fn method_key(target: &Type, trait_name: &String, method: &String) -> String {
+    return format!("<{} as {}>::{}", target, trait_name, method);
+}
+
+pub fn vtable_key(target: &Type, trait_name: &String) -> String {
+    return format!("vtable <{} as {}>", target, trait_name);
+}
+
+fn function_key(index: usize) -> String {
+    return format!("function {}", index);
+}
+
+/// Translates the IR of the program into `nasm` assembly for Win64.
+pub fn generate(program: &Program, analysis: &Analysis, ir: &IrProgram) -> Result<String, CompileError> {
+    let mut context = Context {
+        analysis,
+        ir,
+        labels: Labels::new(),
+        layouts: Layouts::new(),
+        bodies: HashMap::new(),
+        defaults: HashMap::new(),
+        runtime: Cell::new(false),
+    };
+    let mut externs = vec![String::from("exit"), String::from("malloc")];
+
+    let mut paths: Vec<_> = program.modules.keys().collect();
+    paths.sort();
+    for path in paths {
+        for ast_node in &program.modules[path].body {
+            match &ast_node.node {
+                Node::Struct {
+                    name, generics, body, ..
+                } => {
+                    let fields = body.iter().map(|(_, name, t)| (name.clone(), t.clone())).collect();
+                    context.layouts.structs.insert(
+                        name.clone(),
+                        StructLayout {
+                            generics: generics.clone(),
+                            fields,
+                        },
+                    );
+                }
+                Node::Enum {
+                    name, generics, body, ..
+                } => {
+                    context.layouts.enums.insert(
+                        name.clone(),
+                        EnumLayout {
+                            generics: generics.clone(),
+                            variants: body.clone(),
+                        },
+                    );
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    // Default methods taking `self` by value would need a copy for every
+    // type, so only the ones taking a reference get a body.
+    let mut skipped = Vec::new();
+    let mut trait_names: Vec<&String> = analysis.traits.traits.keys().collect();
+    trait_names.sort();
+    for trait_name in &trait_names {
+        for method in &analysis.traits.traits[*trait_name].methods {
+            let id = match analysis
+                .symbols
+                .members
+                .get(&format!("{}::{}", trait_name, method.name))
+            {
+                Some(id) if method.has_default => *id,
+                _ => continue,
+            };
+            match method.parameters.first() {
+                Some((name, Type::Reference(_, _))) if name == "self" => {
+                    context.defaults.insert(id, (*trait_name).clone());
+                }
+                _ => skipped.push(id),
+            }
+        }
+    }
+
+    let mut compiled = Vec::new();
+    for (index, function) in ir.functions.iter().enumerate() {
+        match function.definition {
+            Some(id) if skipped.contains(&id) => continue,
+            Some(id) => {
+                context.bodies.insert(id, index);
+            }
+            None => {}
+        }
+        context.labels.generate(&function_key(index));
+        compiled.push(index);
+    }
+
+    let mut implementations: Vec<_> = analysis.traits.implementations.iter().collect();
+    implementations.sort_by_key(|implementation| vtable_key(&implementation.target, &implementation.trait_name));
+    for implementation in &implementations {
+        context
+            .labels
+            .generate(&vtable_key(&implementation.target, &implementation.trait_name));
+    }
+    let mut statics: Vec<&String> = analysis.constants.statics.keys().collect();
+    statics.sort();
+    for name in statics {
+        context.labels.generate(&static_key(name));
+    }
+    let mut thunks = Vec::new();
+    for implementation in &implementations {
+        let definition = match analysis.traits.traits.get(&implementation.trait_name) {
+            Some(definition) => definition,
+            None => continue,
+        };
+        for method in &definition.methods {
+            let id = match context.method(&implementation.target, &implementation.trait_name, &method.name) {
+                Some(id) => id,
+                None => continue,
+            };
+            if context.defaults.contains_key(&id) && context.bodies.contains_key(&id) {
+                let (target, trait_name) = (&implementation.target, &implementation.trait_name);
+                context.labels.generate(&method_key(target, trait_name, &method.name));
+                thunks.push((target, trait_name, id, &method.name));
+            }
+        }
+    }
+    for definition in &analysis.symbols.definitions {
+        if definition.kind == DefKind::ForeignFunction {
+            externs.push(definition.name.clone());
+        }
+    }
+
+    let mut text = Writer::new();
+    for index in compiled {
+        match FunctionWriter::compile(&context, index) {
+            Ok(function) => text.writer(function),
+            Err(error) => return Err(error),
+        }
+    }
+    // A default method reached through a vtable gets the vtable it came from.
+    for (target, trait_name, id, method) in thunks {
+        text.label(&context.thunk_label(target, trait_name, method).unwrap());
+        text.add_operation(format!(
+            "lea r11, [rel {}]",
+            context.vtable_label(target, trait_name).unwrap()
+        ));
+        text.add_operation(format!("jmp {}", context.body_label(id).unwrap()));
+    }
+    if context.runtime.get() {
+        externs.extend(RUNTIME_EXTERNS.iter().map(|name| name.to_string()));
+    }
+
+    let mut writer = Writer::new();
+    writer.push_str("bits 64\n");
+    writer.push_str("default rel\n");
+    writer.push_str("global main\n");
+    externs.sort();
+    externs.dedup();
+    writer.push_str(&format!("extern {}\n\n", externs.join(", ")));
+
+    writer.push_str("section .data\n");
+    write_vtables(&context, &mut writer);
+    let mut bss = Writer::new();
+    write_statics(&context, &mut writer, &mut bss);
+
+    writer.push_str("\nsection .bss\n");
+    writer.writer(bss);
+
+    writer.push_str("\nsection .text\n");
+    writer.writer(text);
+
+    writer.push_str("main:\n");
+    writer.add_operation_str("push rbp");
+    writer.add_operation_str("mov rbp, rsp");
+    writer.add_operation_str("sub rsp, 32");
+
+    let main = match analysis
+        .symbols
+        .value(&ModulePath::root(), "main")
+        .and_then(|id| context.bodies.get(&id))
+    {
+        Some(main) => &ir.functions[*main],
+        None => {
+            return Err(CompileError::BuildProblem(BuildProblem::new(
+                BuildError::NotDefined(String::from("main")),
+                PathBuf::from(format!("src/main.{}", FILE_EXTENSION)),
+                0,
+            )))
+        }
+    };
+    writer.add_operation(format!(
+        "call {}",
+        context.body_label(main.definition.unwrap()).unwrap()
+    ));
+    match main.return_type {
+        Some(_) => writer.add_operation_str("mov rcx, rax"),
+        None => writer.add_operation_str("mov rcx, 0"),
+    }
+    writer.add_operation_str("call exit");
+
+    if context.runtime.get() {
+        write_runtime(&mut writer);
+    }
+    return Ok(writer.body);
+}
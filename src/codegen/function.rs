@@ -0,0 +1,1717 @@
+use std::collections::HashMap;
+
+use crate::{
+    analyzer::{parse_format, DefKind, Piece, Spec},
+    ir::{AsmOperand, BlockId, Callee, Cfg, Function, Instruction, Item, Place, Register, Terminator},
+    parser::{BaseType, Operator, Type, Value},
+    BuildError, BuildProblem, CompileError,
+};
+
+use super::{codegen::Context, layout::align_to, vtable::VTABLE_HEADER, writer::Writer};
+
+const ARGUMENT_REGISTERS: [&str; 4] = ["rcx", "rdx", "r8", "r9"];
+const SHADOW_SPACE: usize = 32;
+/// Registers the Win64 ABI makes the callee preserve, besides `rsp` and `rbp`.
+const CALLEE_SAVED: [&str; 7] = ["rbx", "rsi", "rdi", "r12", "r13", "r14", "r15"];
+
+/// A memory operand, `offset` bytes past a register or a label.
+#[derive(Debug, Clone)]
+struct Memory {
+    base: String,
+    offset: isize,
+}
+impl Memory {
+    fn frame(offset: isize) -> Self {
+        Self {
+            base: String::from("rbp"),
+            offset,
+        }
+    }
+    fn register(register: &str) -> Self {
+        Self {
+            base: String::from(register),
+            offset: 0,
+        }
+    }
+    fn label(label: &String) -> Self {
+        Self {
+            base: format!("rel {}", label),
+            offset: 0,
+        }
+    }
+    fn at(&self, offset: usize) -> Self {
+        Self {
+            base: self.base.clone(),
+            offset: self.offset + offset as isize,
+        }
+    }
+    fn operand(&self) -> String {
+        return match self.offset {
+            0 => format!("[{}]", self.base),
+            offset => format!("[{}{:+}]", self.base, offset),
+        };
+    }
+}
+
+/// One 8 byte slot of the arguments of a call.
+enum Slot {
+    Value(Memory),
+    /// The address of the memory, like the hidden pointer large values are
+    /// returned through.
+    Address(Memory),
+}
+
+fn width(size: usize) -> &'static str {
+    return match size {
+        8 => "qword",
+        4 => "dword",
+        2 => "word",
+        _ => "byte",
+    };
+}
+
+/// The low 32 bits of a 64-bit register, like `ecx` for `rcx` and `r8d` for `r8`.
+fn low_dword(register: &str) -> String {
+    return match register.strip_prefix('r') {
+        Some(rest) if rest.starts_with(|ch: char| ch.is_ascii_digit()) => format!("{}d", register),
+        Some(rest) => format!("e{}", rest),
+        None => String::from(register),
+    };
+}
+
+fn is_float(t: &Type) -> bool {
+    return matches!(t, Type::Base(BaseType::Float64 | BaseType::Float32));
+}
+
+/// The SSE suffix of the float type: `sd` for `f64`, `ss` for `f32`.
+fn precision(t: &Type) -> &'static str {
+    return match t {
+        Type::Base(BaseType::Float32) => "ss",
+        _ => "sd",
+    };
+}
+
+/// What a reference or pointer of the type points to.
+fn pointee(t: &Type) -> Option<&Type> {
+    return match t {
+        Type::Reference(_, inner) | Type::Pointer(_, inner) => Some(inner),
+        _ => None,
+    };
+}
+
+/// Compiles one function of the IR.
+///
+/// Every register, slot and phi has a home in the frame below `rbp`, padded
+/// to a multiple of 8 bytes. Arguments take one 8 byte slot for every 8 bytes
+/// of their value and values up to 16 bytes are returned in `rax:rdx`,
+/// larger ones through a hidden pointer in the first slot. The first four
+/// slots are passed in `rcx`, `rdx`, `r8` and `r9`, the others on the stack
+/// above the shadow space, like Win64 does for C. Closures get their
+/// environment in `r10` and default trait methods the vtable of `self` in
+/// `r11`, as they are compiled once for every type that uses them.
+pub struct FunctionWriter<'a> {
+    context: &'a Context<'a>,
+    function: &'a Function,
+    registers: Vec<isize>,
+    slots: Vec<isize>,
+    /// Where the edges into a block leave the incoming value of each of its
+    /// phis, so no phi is overwritten before every other phi has been read.
+    phis: HashMap<Register, isize>,
+    /// String literals by their register, as `print`, `println` and
+    /// `format` read their format string at compile time.
+    strings: HashMap<Register, String>,
+    /// The home of `r10`, for closures.
+    environment: isize,
+    /// The home of the hidden pointer large values are returned through.
+    return_pointer: Option<isize>,
+    stack_size: usize,
+    /// The most argument slots any call of the function passes.
+    outgoing: usize,
+    label_count: usize,
+    line: usize,
+    writer: Writer,
+}
+impl<'a> FunctionWriter<'a> {
+    pub fn compile(context: &'a Context<'a>, index: usize) -> Result<Writer, CompileError> {
+        let function = &context.ir.functions[index];
+        let mut writer = Self {
+            context,
+            function,
+            registers: Vec::new(),
+            slots: Vec::new(),
+            phis: HashMap::new(),
+            strings: HashMap::new(),
+            environment: 0,
+            return_pointer: None,
+            stack_size: 0,
+            outgoing: 0,
+            label_count: 0,
+            line: function.line,
+            writer: Writer::new(),
+        };
+        return match writer.body() {
+            Ok(()) => Ok(writer.finish(&context.function_label(index))),
+            Err(error) => Err(CompileError::BuildProblem(BuildProblem::new(
+                error,
+                function.relative_path.clone(),
+                writer.line,
+            ))),
+        };
+    }
+
+    fn body(&mut self) -> Result<(), BuildError> {
+        let function = self.function;
+        for t in &function.registers {
+            let home = self.allocate(self.size(t));
+            self.registers.push(home);
+        }
+        for slot in &function.slots {
+            let home = self.allocate(self.size(&slot.t));
+            self.slots.push(home);
+        }
+        for block in &function.blocks {
+            for node in &block.nodes {
+                match &node.instruction {
+                    Instruction::Phi(dest, _) => {
+                        let home = self.allocate(self.register_size(*dest));
+                        self.phis.insert(*dest, home);
+                    }
+                    Instruction::Value(dest, Value::String(string)) => {
+                        self.strings.insert(*dest, string.clone());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        match self.prologue() {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+
+        let cfg = Cfg::new(function);
+        for (position, block) in cfg.order.iter().enumerate() {
+            let next = cfg.order.get(position + 1).copied();
+            self.writer.push_str(&format!(".b{}:\n", block.0));
+            // Phis take the values the edge into the block left for them.
+            for node in &function.blocks[block.0].nodes {
+                match &node.instruction {
+                    Instruction::Phi(dest, _) => {
+                        let size = self.register_size(*dest);
+                        self.copy(&self.home(*dest), &Memory::frame(self.phis[dest]), size);
+                    }
+                    _ => {}
+                }
+            }
+            for node in &function.blocks[block.0].nodes {
+                self.line = node.line;
+                match self.instruction(&node.instruction) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            self.line = function.blocks[block.0].line;
+            self.terminator(*block, next);
+        }
+        return Ok(());
+    }
+
+    /// Spills the argument registers into their shadow space home and copies
+    /// every parameter into the home of its register.
+    fn prologue(&mut self) -> Result<(), BuildError> {
+        let function = self.function;
+        let mut slot = 0;
+        if self.register_size_of(&function.return_type) > 16 {
+            self.return_pointer = Some(16);
+            slot += 1;
+        }
+        let is_default = match function.definition {
+            Some(id) => self.context.defaults.contains_key(&id),
+            None => false,
+        };
+        let mut copies = Vec::new();
+        for (index, parameter) in function.parameters.iter().enumerate() {
+            let incoming = Memory::frame(16 + 8 * slot as isize);
+            // The caller of a default method passes a thin `self` and its
+            // vtable, which make up the `&dyn Trait` the body sees.
+            if is_default && index == 0 {
+                copies.push((self.home(*parameter), incoming, 8));
+                slot += 1;
+                continue;
+            }
+            let size = self.register_size(*parameter);
+            copies.push((self.home(*parameter), incoming, size));
+            slot += size.div_ceil(8);
+        }
+
+        for (index, register) in ARGUMENT_REGISTERS.iter().enumerate().take(slot) {
+            self.operation(format!("mov [rbp+{}], {}", 16 + 8 * index, register));
+        }
+        if function.captures.len() > 0 {
+            self.environment = self.allocate(8);
+            self.operation(format!("mov {}, r10", Memory::frame(self.environment).operand()));
+        }
+        if is_default && function.parameters.len() > 0 {
+            let home = self.home(function.parameters[0]);
+            self.operation(format!("mov {}, r11", home.at(8).operand()));
+        }
+        for (home, incoming, size) in copies {
+            self.copy(&home, &incoming, size);
+        }
+        return Ok(());
+    }
+
+    /// Puts the frame around the body once its size is known.
+    fn finish(self, label: &String) -> Writer {
+        let outgoing = align_to((8 * self.outgoing).max(SHADOW_SPACE), 16);
+        let frame = align_to(self.stack_size, 16) + outgoing;
+        let mut writer = Writer::new();
+        writer.push_str(&format!("; {}\n", self.function.name));
+        writer.label(label);
+        writer.add_operation_str("push rbp");
+        writer.add_operation_str("mov rbp, rsp");
+        writer.add_operation(format!("sub rsp, {}", frame));
+        writer.writer(self.writer);
+        writer.push_str(".return:\n");
+        writer.add_operation_str("leave");
+        writer.add_operation_str("ret");
+        return writer;
+    }
+
+    fn operation(&mut self, operation: String) {
+        self.writer.add_operation(operation);
+    }
+
+    fn label(&mut self) -> String {
+        self.label_count += 1;
+        return format!(".L{}", self.label_count);
+    }
+
+    fn allocate(&mut self, size: usize) -> isize {
+        self.stack_size += align_to(size.max(8), 8);
+        return -(self.stack_size as isize);
+    }
+
+    fn size(&self, t: &Type) -> usize {
+        return self.context.layouts.size_of(t);
+    }
+
+    fn register_size(&self, register: Register) -> usize {
+        return self.size(self.function.register_type(register));
+    }
+
+    fn register_size_of(&self, t: &Option<Type>) -> usize {
+        return match t {
+            Some(t) => self.size(t),
+            None => 0,
+        };
+    }
+
+    fn home(&self, register: Register) -> Memory {
+        return Memory::frame(self.registers[register.0]);
+    }
+
+    fn unsupported(&self, what: String) -> BuildError {
+        return BuildError::Unsupported(what);
+    }
+
+    /// Copies `size` bytes, 8 at a time and then in smaller pieces, through `rax`.
+    fn copy(&mut self, dest: &Memory, src: &Memory, size: usize) {
+        let mut offset = 0;
+        for (piece, register) in [(8, "rax"), (4, "eax"), (2, "ax"), (1, "al")] {
+            while size - offset >= piece {
+                self.operation(format!(
+                    "mov {}, {} {}",
+                    register,
+                    width(piece),
+                    src.at(offset).operand()
+                ));
+                self.operation(format!(
+                    "mov {} {}, {}",
+                    width(piece),
+                    dest.at(offset).operand(),
+                    register
+                ));
+                offset += piece;
+            }
+        }
+    }
+
+    /// Loads a value of type `t` into the 64-bit register, sign or zero
+    /// extending values narrower than it.
+    fn load(&mut self, register: &str, memory: &Memory, t: &Type) {
+        let signed = match t {
+            Type::Base(base) => base.is_signed(),
+            _ => false,
+        };
+        let operand = memory.operand();
+        let operation = match (self.size(t), signed) {
+            (0, _) => return,
+            (4, true) => format!("movsxd {}, dword {}", register, operand),
+            (4, false) => format!("mov {}, dword {}", low_dword(register), operand),
+            (size @ (1 | 2), true) => format!("movsx {}, {} {}", register, width(size), operand),
+            (size @ (1 | 2), false) => format!("movzx {}, {} {}", low_dword(register), width(size), operand),
+            _ => format!("mov {}, qword {}", register, operand),
+        };
+        self.operation(operation);
+    }
+
+    /// Where the value of the place is. Captures are reached through `r11`.
+    fn place(&mut self, place: Place) -> Result<Memory, BuildError> {
+        return match place {
+            Place::Slot(slot) => Ok(Memory::frame(self.slots[slot.0])),
+            Place::Static(id) => {
+                let name = &self.context.analysis.symbols.definition(id).name;
+                match self.context.static_label(name) {
+                    Some(label) => Ok(Memory::label(&label)),
+                    None => Err(self.unsupported(format!("static `{}`", name))),
+                }
+            }
+            Place::Capture(index) => {
+                self.operation(format!("mov r11, {}", Memory::frame(self.environment).operand()));
+                match self.function.is_move {
+                    // Move closures keep copies of what they capture.
+                    true => Ok(Memory::register("r11").at(capture_offsets(self.context, self.function)[index])),
+                    false => {
+                        self.operation(format!("mov r11, [r11+{}]", 8 * index));
+                        Ok(Memory::register("r11"))
+                    }
+                }
+            }
+        };
+    }
+
+    fn place_type(&self, place: Place) -> Type {
+        return match place {
+            Place::Slot(slot) => self.function.slots[slot.0].t.clone(),
+            Place::Static(id) => match self.context.ir.statics.get(&id) {
+                Some(t) => t.clone(),
+                None => Type::Tuple(Vec::new()),
+            },
+            Place::Capture(index) => self.function.captures[index].t.clone(),
+        };
+    }
+
+    /// Places the bytes of `string` in the read-only data section and returns
+    /// their label. A 0 follows them, which is not part of the `&str` but
+    /// ends the C string a literal cast to `*const u8` is passed as.
+    fn string(&mut self, string: &String) -> String {
+        let label = self.label();
+        self.writer.push_str("section .rdata\n");
+        self.writer.label(&label);
+        let mut bytes: Vec<String> = string.bytes().map(|byte| byte.to_string()).collect();
+        bytes.push(String::from("0"));
+        self.operation(format!("db {}", bytes.join(", ")));
+        self.writer.push_str("section .text\n");
+        return label;
+    }
+
+    /// Stores an integer as a value of type `t`, converting it for floats.
+    fn integer(&mut self, dest: &Memory, t: &Type, value: i128) {
+        let bits = match t {
+            Type::Base(BaseType::Float64) => (value as f64).to_bits(),
+            Type::Base(BaseType::Float32) => (value as f32).to_bits() as u64,
+            _ => value as u64,
+        };
+        self.operation(format!("mov rax, {}", bits as i64));
+        self.operation(format!("mov {}, rax", dest.operand()));
+        if self.size(t) == 16 {
+            self.operation(format!("mov rax, {}", (value >> 64) as i64));
+            self.operation(format!("mov {}, rax", dest.at(8).operand()));
+        }
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) -> Result<(), BuildError> {
+        let function = self.function;
+        match instruction {
+            Instruction::Value(dest, value) => {
+                let home = self.home(*dest);
+                match value {
+                    Value::Integer(integer) => self.integer(&home, function.register_type(*dest), *integer),
+                    Value::UInteger(integer) => self.integer(&home, function.register_type(*dest), *integer as i128),
+                    Value::Boolean(boolean) => {
+                        self.operation(format!("mov qword {}, {}", home.operand(), *boolean as u8))
+                    }
+                    Value::Char(char) => self.operation(format!("mov qword {}, {}", home.operand(), *char as u32)),
+                    Value::String(string) => {
+                        let label = self.string(string);
+                        self.operation(format!("lea rax, [rel {}]", label));
+                        self.operation(format!("mov {}, rax", home.operand()));
+                        self.operation(format!("mov qword {}, {}", home.at(8).operand(), string.len()));
+                    }
+                }
+            }
+            Instruction::Item(dest, item) => return self.item(*dest, item),
+            Instruction::Declare(_) | Instruction::Undefined(_) | Instruction::Phi(_, _) => {}
+            Instruction::Load(dest, place) => {
+                let memory = match self.place(*place) {
+                    Ok(memory) => memory,
+                    Err(error) => return Err(error),
+                };
+                let size = self.register_size(*dest);
+                self.copy(&self.home(*dest), &memory, size);
+            }
+            Instruction::Store(place, src) => {
+                let memory = match self.place(*place) {
+                    Ok(memory) => memory,
+                    Err(error) => return Err(error),
+                };
+                let size = self.size(&self.place_type(*place));
+                self.copy(&memory, &self.home(*src), size);
+            }
+            Instruction::Address(dest, _, place) => {
+                let memory = match self.place(*place) {
+                    Ok(memory) => memory,
+                    Err(error) => return Err(error),
+                };
+                self.operation(format!("lea rax, {}", memory.operand()));
+                self.operation(format!("mov {}, rax", self.home(*dest).operand()));
+            }
+            Instruction::Deref(dest, src) => {
+                self.operation(format!("mov r11, {}", self.home(*src).operand()));
+                let size = self.register_size(*dest);
+                self.copy(&self.home(*dest), &Memory::register("r11"), size);
+            }
+            Instruction::FieldAddress(dest, base, field) => {
+                let t = match pointee(function.register_type(*base)) {
+                    Some(t) => t,
+                    None => return Err(self.unsupported(format!("field `{}` of a value", field))),
+                };
+                let (offset, _) = match self.context.layouts.field(t, field) {
+                    Some(field) => field,
+                    None => return Err(self.unsupported(format!("field `{}` of `{}`", field, t))),
+                };
+                self.operation(format!("mov rax, {}", self.home(*base).operand()));
+                self.operation(format!("add rax, {}", offset));
+                self.operation(format!("mov {}, rax", self.home(*dest).operand()));
+            }
+            Instruction::Field(dest, src, field) => {
+                let t = function.register_type(*src);
+                let (offset, _) = match self.context.layouts.field(t, field) {
+                    Some(field) => field,
+                    None => return Err(self.unsupported(format!("field `{}` of `{}`", field, t))),
+                };
+                let size = self.register_size(*dest);
+                self.copy(&self.home(*dest), &self.home(*src).at(offset), size);
+            }
+            Instruction::Binary(dest, operator, a, b) => return self.binary(*dest, *operator, *a, *b),
+            Instruction::Call(dest, callee, arguments) => return self.call(*dest, callee, arguments),
+            Instruction::Construct(dest, item, fields) => return self.construct(*dest, item, fields),
+            Instruction::Cast(dest, src) => return self.cast(*dest, *src),
+            Instruction::Coerce(dest, src) => return self.coerce(*dest, *src),
+            Instruction::IsVariant(dest, src, item) => {
+                let t = function.register_type(*src);
+                let (index, _) = match self.context.layouts.variant(t, &member(&item.name)) {
+                    Some(variant) => variant,
+                    None => return Err(self.unsupported(format!("variant `{}` of `{}`", item.name, t))),
+                };
+                self.operation(format!("cmp qword {}, {}", self.home(*src).operand(), index));
+                self.operation(String::from("sete al"));
+                self.operation(String::from("movzx eax, al"));
+                self.operation(format!("mov {}, rax", self.home(*dest).operand()));
+            }
+            Instruction::Payload(dest, src, item, index) => {
+                let t = function.register_type(*src);
+                let (_, fields) = match self.context.layouts.variant(t, &member(&item.name)) {
+                    Some(variant) => variant,
+                    None => return Err(self.unsupported(format!("variant `{}` of `{}`", item.name, t))),
+                };
+                let offset = 8 + self.context.layouts.field_offsets(&fields)[*index];
+                let size = self.register_size(*dest);
+                self.copy(&self.home(*dest), &self.home(*src).at(offset), size);
+            }
+            Instruction::Closure(dest, index, places) => return self.closure(*dest, *index, places),
+            Instruction::Asm(template, operands) => return self.inline_asm(template, operands),
+        }
+        return Ok(());
+    }
+
+    fn terminator(&mut self, block: BlockId, next: Option<BlockId>) {
+        match &self.function.blocks[block.0].terminator {
+            Terminator::Jump(target) => {
+                self.edge(block, *target);
+                if next != Some(*target) {
+                    self.operation(format!("jmp .b{}", target.0));
+                }
+            }
+            Terminator::Branch(condition, then, otherwise) => {
+                self.operation(format!("cmp byte {}, 0", self.home(*condition).operand()));
+                self.operation(format!("je .e{}", block.0));
+                self.edge(block, *then);
+                self.operation(format!("jmp .b{}", then.0));
+                self.writer.push_str(&format!(".e{}:\n", block.0));
+                self.edge(block, *otherwise);
+                if next != Some(*otherwise) {
+                    self.operation(format!("jmp .b{}", otherwise.0));
+                }
+            }
+            Terminator::Return(value) => {
+                match value {
+                    Some(value) => {
+                        let size = self.register_size(*value);
+                        let home = self.home(*value);
+                        match self.return_pointer {
+                            Some(pointer) => {
+                                self.operation(format!("mov rcx, {}", Memory::frame(pointer).operand()));
+                                self.copy(&Memory::register("rcx"), &home, size);
+                                self.operation(String::from("mov rax, rcx"));
+                            }
+                            None => {
+                                if size > 0 {
+                                    self.operation(format!("mov rax, {}", home.operand()));
+                                }
+                                if size > 8 {
+                                    self.operation(format!("mov rdx, {}", home.at(8).operand()));
+                                }
+                            }
+                        }
+                    }
+                    None => {}
+                }
+                self.operation(String::from("jmp .return"));
+            }
+            Terminator::Unreachable => self.operation(String::from("ud2")),
+        }
+    }
+
+    /// Leaves the values the phis of `to` take when control comes from `from`.
+    fn edge(&mut self, from: BlockId, to: BlockId) {
+        for node in &self.function.blocks[to.0].nodes {
+            let (dest, incoming) = match &node.instruction {
+                Instruction::Phi(dest, incoming) => (dest, incoming),
+                _ => continue,
+            };
+            match incoming.iter().find(|(block, _)| *block == from) {
+                Some((_, value)) => {
+                    let size = self.register_size(*dest);
+                    self.copy(&Memory::frame(self.phis[dest]), &self.home(*value), size);
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// A function named as a value, paired with an empty environment, or the
+    /// value of a constant.
+    fn item(&mut self, dest: Register, item: &Item) -> Result<(), BuildError> {
+        let home = self.home(dest);
+        let definition = self.context.analysis.symbols.definition(item.definition);
+        match definition.kind {
+            DefKind::Constant => {
+                let value = match self.context.analysis.constants.constants.get(&definition.name) {
+                    Some(constant) => constant.value,
+                    None => return Err(self.unsupported(format!("constant `{}`", item.name))),
+                };
+                self.integer(&home, self.function.register_type(dest), value);
+            }
+            DefKind::Function | DefKind::Method if !self.context.defaults.contains_key(&item.definition) => {
+                let label = match self.context.body_label(item.definition) {
+                    Some(label) => label,
+                    None => return Err(self.unsupported(format!("`{}` as a value", item.name))),
+                };
+                self.operation(format!("lea rax, [rel {}]", label));
+                self.operation(format!("mov {}, rax", home.operand()));
+                self.operation(format!("mov qword {}, 0", home.at(8).operand()));
+            }
+            _ => return Err(self.unsupported(format!("`{}` as a value", item.name))),
+        }
+        return Ok(());
+    }
+
+    fn binary(&mut self, dest: Register, operator: Operator, a: Register, b: Register) -> Result<(), BuildError> {
+        let t = self.function.register_type(a).clone();
+        match &t {
+            Type::Custom(_) | Type::Generic(_, _) => return self.overloaded(dest, operator, a, b),
+            Type::Base(base) if base.is_wide() => {
+                self.wide(dest, base, operator, a, b);
+                return Ok(());
+            }
+            t if is_float(t) => {
+                self.float(dest, t, operator, a, b);
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let b_type = self.function.register_type(b).clone();
+        self.load("rax", &self.home(a), &t);
+        self.load("rcx", &self.home(b), &b_type);
+        // Pointer arithmetic moves by whole values of what the pointer points to.
+        match pointee(&t) {
+            Some(inner) if !operator.is_comparison() => {
+                let size = self.size(inner);
+                if size != 1 {
+                    self.operation(format!("imul rcx, rcx, {}", size));
+                }
+            }
+            _ => {}
+        }
+        let signed = match &t {
+            Type::Base(base) => base.is_signed(),
+            _ => false,
+        };
+        let home = self.home(dest);
+        let condition = match (operator, signed) {
+            (Operator::Plus, _) => "add rax, rcx",
+            (Operator::Minus, _) => "sub rax, rcx",
+            (Operator::Multiply, _) => "imul rax, rcx",
+            (Operator::Division, true) => {
+                self.operation(String::from("cqo"));
+                "idiv rcx"
+            }
+            (Operator::Division, false) => {
+                self.operation(String::from("xor edx, edx"));
+                "div rcx"
+            }
+            (Operator::Equal, _) => "sete al",
+            (Operator::NotEqual, _) => "setne al",
+            (Operator::Less, true) => "setl al",
+            (Operator::Less, false) => "setb al",
+            (Operator::LessEqual, true) => "setle al",
+            (Operator::LessEqual, false) => "setbe al",
+            (Operator::Greater, true) => "setg al",
+            (Operator::Greater, false) => "seta al",
+            (Operator::GreaterEqual, true) => "setge al",
+            (Operator::GreaterEqual, false) => "setae al",
+        };
+        if operator.is_comparison() {
+            self.operation(String::from("cmp rax, rcx"));
+            self.operation(String::from(condition));
+            self.operation(String::from("movzx eax, al"));
+        } else {
+            self.operation(String::from(condition));
+        }
+        self.operation(format!("mov {}, rax", home.operand()));
+        return Ok(());
+    }
+
+    /// Float arithmetic and comparisons on SSE registers. Comparisons with
+    /// NaN are false, except `!=`, which `ucomisd` reports through the parity
+    /// flag.
+    fn float(&mut self, dest: Register, t: &Type, operator: Operator, a: Register, b: Register) {
+        let suffix = precision(t);
+        let home = self.home(dest);
+        self.operation(format!("mov{} xmm0, {}", suffix, self.home(a).operand()));
+        self.operation(format!("mov{} xmm1, {}", suffix, self.home(b).operand()));
+        let arithmetic = match operator {
+            Operator::Plus => Some("add"),
+            Operator::Minus => Some("sub"),
+            Operator::Multiply => Some("mul"),
+            Operator::Division => Some("div"),
+            _ => None,
+        };
+        match arithmetic {
+            Some(operation) => {
+                self.operation(format!("{}{} xmm0, xmm1", operation, suffix));
+                self.operation(format!("mov{} {}, xmm0", suffix, home.operand()));
+                return;
+            }
+            None => {}
+        }
+        // `a < b` is `b > a`, so that unordered operands give false.
+        let (first, second) = match operator {
+            Operator::Less | Operator::LessEqual => ("xmm1", "xmm0"),
+            _ => ("xmm0", "xmm1"),
+        };
+        self.operation(format!("ucomi{} {}, {}", suffix, first, second));
+        match operator {
+            Operator::Equal => {
+                self.operation(String::from("sete al"));
+                self.operation(String::from("setnp cl"));
+                self.operation(String::from("and al, cl"));
+            }
+            Operator::NotEqual => {
+                self.operation(String::from("setne al"));
+                self.operation(String::from("setp cl"));
+                self.operation(String::from("or al, cl"));
+            }
+            Operator::Less | Operator::Greater => self.operation(String::from("seta al")),
+            _ => self.operation(String::from("setae al")),
+        }
+        self.operation(String::from("movzx eax, al"));
+        self.operation(format!("mov {}, rax", home.operand()));
+    }
+
+    /// Operators on 128-bit integers, done on both halves. Division is left
+    /// to the runtime.
+    fn wide(&mut self, dest: Register, base: &BaseType, operator: Operator, a: Register, b: Register) {
+        let (left, right) = (self.home(a), self.home(b));
+        let (low, high) = (left.operand(), left.at(8).operand());
+        let (other_low, other_high) = (right.operand(), right.at(8).operand());
+
+        let operations = match operator {
+            Operator::Plus | Operator::Minus | Operator::Equal | Operator::NotEqual => {
+                let (first, second) = match operator {
+                    Operator::Plus => ("add", "adc"),
+                    Operator::Minus => ("sub", "sbb"),
+                    _ => ("xor", "xor"),
+                };
+                vec![
+                    format!("mov rax, {}", low),
+                    format!("mov rdx, {}", high),
+                    format!("{} rax, {}", first, other_low),
+                    format!("{} rdx, {}", second, other_high),
+                ]
+            }
+            Operator::Multiply => vec![
+                format!("mov rax, {}", low),
+                format!("mul qword {}", other_low),
+                format!("mov rcx, {}", low),
+                format!("imul rcx, {}", other_high),
+                String::from("add rdx, rcx"),
+                format!("mov rcx, {}", high),
+                format!("imul rcx, {}", other_low),
+                String::from("add rdx, rcx"),
+            ],
+            Operator::Division => {
+                self.context.runtime.set(true);
+                vec![
+                    format!("mov rcx, {}", low),
+                    format!("mov rdx, {}", high),
+                    format!("mov r8, {}", other_low),
+                    format!("mov r9, {}", other_high),
+                    match base.is_signed() {
+                        true => String::from("call eclipse_div128"),
+                        false => String::from("call eclipse_udiv128"),
+                    },
+                ]
+            }
+            // `cmp` and `sbb` leave the flags of a full 128-bit subtraction,
+            // which answer `<` and `>=`. The other two swap their operands.
+            _ => {
+                let (first, second) = match operator {
+                    Operator::Less | Operator::GreaterEqual => (&left, &right),
+                    _ => (&right, &left),
+                };
+                vec![
+                    format!("mov rax, {}", first.operand()),
+                    format!("mov rdx, {}", first.at(8).operand()),
+                    format!("cmp rax, {}", second.operand()),
+                    format!("sbb rdx, {}", second.at(8).operand()),
+                ]
+            }
+        };
+        for operation in operations {
+            self.operation(operation);
+        }
+
+        let home = self.home(dest);
+        let condition = match (operator, base.is_signed()) {
+            (Operator::Plus | Operator::Minus | Operator::Multiply | Operator::Division, _) => {
+                self.operation(format!("mov {}, rax", home.operand()));
+                self.operation(format!("mov {}, rdx", home.at(8).operand()));
+                return;
+            }
+            (Operator::Equal, _) => {
+                self.operation(String::from("or rax, rdx"));
+                "e"
+            }
+            (Operator::NotEqual, _) => {
+                self.operation(String::from("or rax, rdx"));
+                "ne"
+            }
+            (Operator::Less | Operator::Greater, true) => "l",
+            (Operator::Less | Operator::Greater, false) => "b",
+            (Operator::LessEqual | Operator::GreaterEqual, true) => "ge",
+            (Operator::LessEqual | Operator::GreaterEqual, false) => "ae",
+        };
+        self.operation(format!("set{} al", condition));
+        self.operation(String::from("movzx eax, al"));
+        self.operation(format!("mov {}, rax", home.operand()));
+    }
+
+    /// Calls the prelude trait method behind an operator on a struct or an
+    /// enum. `eq` and `cmp` take their operands by reference.
+    fn overloaded(&mut self, dest: Register, operator: Operator, a: Register, b: Register) -> Result<(), BuildError> {
+        let t = self.function.register_type(a).clone();
+        let (trait_name, method) = operator.overload();
+        let label = match self
+            .context
+            .method(&t, &String::from(trait_name), &String::from(method))
+            .and_then(|id| self.context.body_label(id))
+        {
+            Some(label) => label,
+            None => return Err(self.unsupported(format!("`{}` on `{}`", operator.symbol(), t))),
+        };
+
+        let size = self.size(&t);
+        let arguments = match operator.is_comparison() {
+            true => {
+                let mut slots = Vec::new();
+                for operand in [a, b] {
+                    let pointer = self.allocate(8);
+                    self.operation(format!("lea rax, {}", self.home(operand).operand()));
+                    self.operation(format!("mov {}, rax", Memory::frame(pointer).operand()));
+                    slots.push((Memory::frame(pointer), 8));
+                }
+                slots
+            }
+            false => vec![(self.home(a), size), (self.home(b), size)],
+        };
+        let result = match operator.is_comparison() {
+            true => None,
+            false => Some(dest),
+        };
+        self.internal_call(arguments, result, Vec::new(), format!("call {}", label));
+        if !operator.is_comparison() {
+            return Ok(());
+        }
+
+        let ordering = |variant: &str| -> Option<usize> {
+            return match self.context.layouts.enums.get("Ordering") {
+                Some(layout) => layout.variants.iter().position(|(name, _)| name == variant),
+                None => None,
+            };
+        };
+        let compared = match operator {
+            Operator::Equal => None,
+            Operator::NotEqual => {
+                self.operation(String::from("xor eax, 1"));
+                None
+            }
+            Operator::Less => Some(("e", ordering("Less"))),
+            Operator::LessEqual => Some(("ne", ordering("Greater"))),
+            Operator::Greater => Some(("e", ordering("Greater"))),
+            _ => Some(("ne", ordering("Less"))),
+        };
+        match compared {
+            Some((condition, Some(index))) => {
+                self.operation(format!("cmp rax, {}", index));
+                self.operation(format!("set{} al", condition));
+                self.operation(String::from("movzx eax, al"));
+            }
+            Some((_, None)) => return Err(self.unsupported(String::from("`Ordering` without its variants"))),
+            None => {}
+        }
+        self.operation(format!("mov {}, rax", self.home(dest).operand()));
+        return Ok(());
+    }
+
+    /// Passes the arguments in the slots of the calling convention, runs the
+    /// `setup` instructions and the call, and stores what it returns.
+    fn internal_call(
+        &mut self,
+        arguments: Vec<(Memory, usize)>,
+        dest: Option<Register>,
+        setup: Vec<String>,
+        call: String,
+    ) {
+        let return_size = match dest {
+            Some(dest) => self.register_size(dest),
+            None => 0,
+        };
+        let mut slots = Vec::new();
+        if return_size > 16 {
+            slots.push(Slot::Address(self.home(dest.unwrap())));
+        }
+        for (memory, size) in arguments {
+            for index in 0..size.div_ceil(8) {
+                slots.push(Slot::Value(memory.at(8 * index)));
+            }
+        }
+        self.outgoing = self.outgoing.max(slots.len());
+
+        for (index, slot) in slots.iter().enumerate().skip(ARGUMENT_REGISTERS.len()) {
+            match slot {
+                Slot::Value(memory) => self.operation(format!("mov rax, {}", memory.operand())),
+                Slot::Address(memory) => self.operation(format!("lea rax, {}", memory.operand())),
+            }
+            self.operation(format!("mov [rsp+{}], rax", 8 * index));
+        }
+        for (register, slot) in ARGUMENT_REGISTERS.iter().zip(&slots) {
+            match slot {
+                Slot::Value(memory) => self.operation(format!("mov {}, {}", register, memory.operand())),
+                Slot::Address(memory) => self.operation(format!("lea {}, {}", register, memory.operand())),
+            }
+        }
+        for operation in setup {
+            self.operation(operation);
+        }
+        self.operation(call);
+
+        match dest {
+            Some(dest) if return_size > 0 && return_size <= 16 => {
+                let home = self.home(dest);
+                self.operation(format!("mov {}, rax", home.operand()));
+                if return_size > 8 {
+                    self.operation(format!("mov {}, rdx", home.at(8).operand()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Calls a function declared in an `extern "C"` block. Every argument
+    /// takes one slot, larger ones are passed by address. Floats go in the
+    /// `xmm` register of their slot and, for variadic functions, in the
+    /// general purpose one too, and come back in `xmm0`.
+    fn foreign_call(
+        &mut self,
+        name: &String,
+        arguments: &Vec<Register>,
+        dest: Option<Register>,
+    ) -> Result<(), BuildError> {
+        self.outgoing = self.outgoing.max(arguments.len());
+        let mut order: Vec<usize> = (ARGUMENT_REGISTERS.len()..arguments.len()).collect();
+        order.extend(0..arguments.len().min(ARGUMENT_REGISTERS.len()));
+        for index in order {
+            let argument = arguments[index];
+            let t = self.function.register_type(argument).clone();
+            let home = self.home(argument);
+            let register = match ARGUMENT_REGISTERS.get(index) {
+                Some(register) => register,
+                None => "rax",
+            };
+            match (&t, self.size(&t)) {
+                (Type::Base(BaseType::Float32), _) if index < ARGUMENT_REGISTERS.len() => {
+                    self.operation(format!("movss xmm{}, {}", index, home.operand()));
+                    self.operation(format!("mov {}, dword {}", low_dword(register), home.operand()));
+                }
+                (Type::Base(BaseType::Float64), _) if index < ARGUMENT_REGISTERS.len() => {
+                    self.operation(format!("movsd xmm{}, {}", index, home.operand()));
+                    self.operation(format!("mov {}, {}", register, home.operand()));
+                }
+                (_, size) if size > 8 => self.operation(format!("lea {}, {}", register, home.operand())),
+                _ => self.load(register, &home, &t),
+            }
+            if index >= ARGUMENT_REGISTERS.len() {
+                self.operation(format!("mov [rsp+{}], rax", 8 * index));
+            }
+        }
+        self.operation(format!("call {}", name));
+
+        let dest = match dest {
+            Some(dest) => dest,
+            None => return Ok(()),
+        };
+        let t = self.function.register_type(dest);
+        let home = self.home(dest);
+        match (t, self.size(t)) {
+            (t, _) if is_float(t) => self.operation(format!("mov{} {}, xmm0", precision(t), home.operand())),
+            (_, 0) => {}
+            (_, size) if size <= 8 => self.operation(format!("mov {}, rax", home.operand())),
+            (t, _) => return Err(self.unsupported(format!("returning `{}` from `{}`", t, name))),
+        }
+        return Ok(());
+    }
+
+    fn call(&mut self, dest: Option<Register>, callee: &Callee, arguments: &Vec<Register>) -> Result<(), BuildError> {
+        let values = |writer: &Self, arguments: &[Register]| -> Vec<(Memory, usize)> {
+            return arguments
+                .iter()
+                .map(|argument| (writer.home(*argument), writer.register_size(*argument)))
+                .collect();
+        };
+        match callee {
+            Callee::Function(item) => {
+                let definition = self.context.analysis.symbols.definition(item.definition);
+                if definition.kind == DefKind::ForeignFunction {
+                    return self.foreign_call(&definition.name, arguments, dest);
+                }
+                let label = match self.context.body_label(item.definition) {
+                    Some(label) => label,
+                    None => return Err(self.unsupported(format!("calling `{}`", item.name))),
+                };
+                let mut setup = Vec::new();
+                match self.context.defaults.get(&item.definition) {
+                    Some(trait_name) => {
+                        let receiver = match arguments.first() {
+                            Some(receiver) => self.function.register_type(*receiver),
+                            None => return Err(self.unsupported(format!("calling `{}`", item.name))),
+                        };
+                        let vtable = match pointee(receiver).and_then(|t| self.context.vtable_label(t, trait_name)) {
+                            Some(vtable) => vtable,
+                            None => return Err(self.unsupported(format!("calling `{}` on `{}`", item.name, receiver))),
+                        };
+                        setup.push(format!("lea r11, [rel {}]", vtable));
+                    }
+                    None => {}
+                }
+                self.internal_call(values(self, arguments), dest, setup, format!("call {}", label));
+            }
+            Callee::Virtual(item) => {
+                let receiver = match arguments.first() {
+                    Some(receiver) => *receiver,
+                    None => return Err(self.unsupported(format!("calling `{}`", item.name))),
+                };
+                let receiver_type = self.function.register_type(receiver);
+                let index = match receiver_type
+                    .trait_object()
+                    .and_then(|trait_name| self.context.analysis.traits.traits.get(trait_name))
+                    .and_then(|definition| {
+                        definition
+                            .methods
+                            .iter()
+                            .position(|method| method.name == member(&item.name))
+                    }) {
+                    Some(index) => index,
+                    None => return Err(self.unsupported(format!("calling `{}` on `{}`", item.name, receiver_type))),
+                };
+                // Only the data pointer is passed, the method knows its type.
+                let mut passed = vec![(self.home(receiver), 8)];
+                passed.extend(values(self, &arguments[1..]));
+                let setup = vec![format!("mov rax, {}", self.home(receiver).at(8).operand())];
+                self.internal_call(
+                    passed,
+                    dest,
+                    setup,
+                    format!("call [rax+{}]", 8 * (VTABLE_HEADER + index)),
+                );
+            }
+            Callee::Register(function) => {
+                let home = self.home(*function);
+                let setup = vec![
+                    format!("mov r10, {}", home.at(8).operand()),
+                    format!("mov rax, {}", home.operand()),
+                ];
+                self.internal_call(values(self, arguments), dest, setup, String::from("call rax"));
+            }
+            Callee::Builtin(name) => return self.builtin(name, dest, arguments),
+        }
+        return Ok(());
+    }
+
+    fn construct(&mut self, dest: Register, item: &Item, fields: &Vec<Register>) -> Result<(), BuildError> {
+        let t = self.function.register_type(dest);
+        let home = self.home(dest);
+        let (start, types) = match self.context.analysis.symbols.definition(item.definition).kind {
+            DefKind::Variant => match self.context.layouts.variant(t, &member(&item.name)) {
+                Some((index, types)) => {
+                    self.operation(format!("mov qword {}, {}", home.operand(), index));
+                    // The payload follows the tag.
+                    (8, types)
+                }
+                None => return Err(self.unsupported(format!("variant `{}` of `{}`", item.name, t))),
+            },
+            _ => match self.context.layouts.struct_fields(t) {
+                Some(types) => (0, types),
+                None => return Err(self.unsupported(format!("constructing `{}`", t))),
+            },
+        };
+        let offsets = self.context.layouts.field_offsets(&types);
+        for ((field, t), offset) in fields.iter().zip(&types).zip(offsets) {
+            let size = self.size(t);
+            self.copy(&home.at(start + offset), &self.home(*field), size);
+        }
+        return Ok(());
+    }
+
+    /// Integer casts truncate to the target width and then sign or zero
+    /// extend it, float to integer casts round toward zero and integer to
+    /// float casts round to the nearest representable value. Pointers and
+    /// references keep their address.
+    fn cast(&mut self, dest: Register, src: Register) -> Result<(), BuildError> {
+        let (from, to) = (
+            self.function.register_type(src).clone(),
+            self.function.register_type(dest).clone(),
+        );
+        let (source, home) = (self.home(src), self.home(dest));
+        let (from, to) = match (&from, &to) {
+            (Type::Base(from), Type::Base(to)) => (from.sized(), to.sized()),
+            // Addresses, and the data pointer of a `&str`.
+            _ => {
+                self.load("rax", &source, &from);
+                self.operation(format!("mov {}, rax", home.operand()));
+                return Ok(());
+            }
+        };
+        let float = |base: &BaseType| matches!(base, BaseType::Float64 | BaseType::Float32);
+        let (from_type, to_type) = (Type::Base(from.clone()), Type::Base(to.clone()));
+
+        match (float(&from), float(&to)) {
+            // Widening to 128 bits fills the high half with the sign or
+            // zeros, narrowing from it keeps the low half.
+            (false, false) => match (from.is_wide(), to.is_wide()) {
+                (true, true) => self.copy(&home, &source, 16),
+                (true, false) => self.copy(&home, &source, 8),
+                (false, is_wide) => {
+                    self.load("rax", &source, &from_type);
+                    self.operation(format!("mov {}, rax", home.operand()));
+                    if is_wide {
+                        match from.is_signed() {
+                            true => self.operation(String::from("cqo")),
+                            false => self.operation(String::from("xor edx, edx")),
+                        }
+                        self.operation(format!("mov {}, rdx", home.at(8).operand()));
+                    }
+                }
+            },
+            _ if from.is_wide() || to.is_wide() => {
+                return Err(self.unsupported(format!("casting `{}` to `{}`", from, to)))
+            }
+            (false, true) => {
+                let suffix = precision(&to_type);
+                self.load("rax", &source, &from_type);
+                match from {
+                    BaseType::UInt64 => {
+                        // Values with the top bit set do not fit a signed conversion,
+                        // so halve them (keeping the low bit for rounding) and double.
+                        let large = self.label();
+                        let done = self.label();
+                        self.operation(String::from("test rax, rax"));
+                        self.operation(format!("js {}", large));
+                        self.operation(format!("cvtsi2{} xmm0, rax", suffix));
+                        self.operation(format!("jmp {}", done));
+                        self.writer.label(&large);
+                        self.operation(String::from("mov rcx, rax"));
+                        self.operation(String::from("shr rcx, 1"));
+                        self.operation(String::from("and eax, 1"));
+                        self.operation(String::from("or rcx, rax"));
+                        self.operation(format!("cvtsi2{} xmm0, rcx", suffix));
+                        self.operation(format!("add{} xmm0, xmm0", suffix));
+                        self.writer.label(&done);
+                    }
+                    _ => self.operation(format!("cvtsi2{} xmm0, rax", suffix)),
+                }
+                self.operation(format!("mov{} {}, xmm0", suffix, home.operand()));
+            }
+            (true, false) => {
+                self.operation(format!("cvtt{}2si rax, {}", precision(&from_type), source.operand()));
+                self.operation(format!("mov {}, rax", home.operand()));
+            }
+            (true, true) => match (from, to) {
+                (BaseType::Float64, BaseType::Float32) => {
+                    self.operation(format!("cvtsd2ss xmm0, {}", source.operand()));
+                    self.operation(format!("movss {}, xmm0", home.operand()));
+                }
+                (BaseType::Float32, BaseType::Float64) => {
+                    self.operation(format!("cvtss2sd xmm0, {}", source.operand()));
+                    self.operation(format!("movsd {}, xmm0", home.operand()));
+                }
+                _ => self.copy(&home, &source, 8),
+            },
+        }
+        return Ok(());
+    }
+
+    /// Turns a thin `&T` or `Box<T>` into a `dyn Trait` fat pointer by pairing
+    /// it with the vtable of `T`. Other coercions keep the value as it is.
+    fn coerce(&mut self, dest: Register, src: Register) -> Result<(), BuildError> {
+        let (from, to) = (self.function.register_type(src), self.function.register_type(dest));
+        let (source, home) = (self.home(src), self.home(dest));
+        let trait_name = match (to.trait_object(), from.trait_object()) {
+            (Some(trait_name), None) => trait_name,
+            _ => {
+                let size = self.size(to).min(self.size(from));
+                self.copy(&home, &source, size);
+                return Ok(());
+            }
+        };
+        let target = match from {
+            Type::Reference(_, inner) => inner.as_ref(),
+            Type::Generic(name, types) if name == "Box" && types.len() == 1 => &types[0],
+            _ => return Err(self.unsupported(format!("coercing `{}` to `{}`", from, to))),
+        };
+        let vtable = match self.context.vtable_label(target, trait_name) {
+            Some(vtable) => vtable,
+            None => return Err(self.unsupported(format!("coercing `{}` to `{}`", from, to))),
+        };
+        self.copy(&home, &source, 8);
+        self.operation(format!("lea rax, [rel {}]", vtable));
+        self.operation(format!("mov {}, rax", home.at(8).operand()));
+        return Ok(());
+    }
+
+    /// Builds a closure value from the address of its code and the address of
+    /// its environment.
+    ///
+    /// The environment holds the address of every captured local, or a copy
+    /// of its value for `move` closures. `move` environments live on the heap
+    /// so the closure can outlive the current frame.
+    fn closure(&mut self, dest: Register, index: usize, places: &Vec<Place>) -> Result<(), BuildError> {
+        let closure = &self.context.ir.functions[index];
+        let home = self.home(dest);
+        if places.is_empty() {
+            self.operation(format!("mov qword {}, 0", home.at(8).operand()));
+        } else if closure.is_move {
+            let offsets = capture_offsets(self.context, closure);
+            let size =
+                offsets[offsets.len() - 1] + align_to(self.size(&closure.captures[offsets.len() - 1].t).max(8), 8);
+            self.outgoing = self.outgoing.max(1);
+            self.operation(format!("mov rcx, {}", size));
+            self.operation(String::from("call malloc"));
+            self.operation(format!("mov {}, rax", home.at(8).operand()));
+            for ((place, offset), capture) in places.iter().zip(offsets).zip(&closure.captures) {
+                let memory = match self.place(*place) {
+                    Ok(memory) => memory,
+                    Err(error) => return Err(error),
+                };
+                self.operation(format!("mov rcx, {}", home.at(8).operand()));
+                self.copy(&Memory::register("rcx").at(offset), &memory, self.size(&capture.t));
+            }
+        } else {
+            let environment = Memory::frame(self.allocate(8 * places.len()));
+            for (index, place) in places.iter().enumerate() {
+                let memory = match self.place(*place) {
+                    Ok(memory) => memory,
+                    Err(error) => return Err(error),
+                };
+                self.operation(format!("lea rax, {}", memory.operand()));
+                self.operation(format!("mov {}, rax", environment.at(8 * index).operand()));
+            }
+            self.operation(format!("lea rax, {}", environment.operand()));
+            self.operation(format!("mov {}, rax", home.at(8).operand()));
+        }
+        self.operation(format!("lea rax, [rel {}]", self.context.function_label(index)));
+        self.operation(format!("mov {}, rax", home.operand()));
+        return Ok(());
+    }
+
+    /// Moves the inputs into their registers after every one is known, and
+    /// the outputs out of them before any is stored, so no operand overwrites
+    /// another. Callee saved registers the operands name are preserved around
+    /// the template, since the function itself has to keep them for its caller.
+    fn inline_asm(&mut self, template: &Vec<String>, operands: &Vec<AsmOperand>) -> Result<(), BuildError> {
+        let mut inputs = Vec::new();
+        for operand in operands {
+            match operand {
+                AsmOperand::In(register, value) => {
+                    let t = self.function.register_type(*value).clone();
+                    inputs.push((register.clone(), self.home(*value), t));
+                }
+                AsmOperand::InOut(register, place) => {
+                    let t = self.place_type(*place);
+                    let memory = match self.place(*place) {
+                        Ok(memory) => memory,
+                        Err(error) => return Err(error),
+                    };
+                    let temporary = Memory::frame(self.allocate(8));
+                    self.copy(&temporary, &memory, self.size(&t));
+                    inputs.push((register.clone(), temporary, t));
+                }
+                AsmOperand::Out(_, _) | AsmOperand::Clobber(_) => {}
+            }
+        }
+
+        let mut saved = Vec::new();
+        for register in CALLEE_SAVED {
+            let named = operands.iter().any(|operand| match operand {
+                AsmOperand::In(name, _)
+                | AsmOperand::Out(name, _)
+                | AsmOperand::InOut(name, _)
+                | AsmOperand::Clobber(name) => name == register,
+            });
+            if named {
+                let home = Memory::frame(self.allocate(8));
+                self.operation(format!("mov {}, {}", home.operand(), register));
+                saved.push((register, home));
+            }
+        }
+
+        for (register, memory, t) in &inputs {
+            self.load(register, memory, t);
+        }
+        for line in template.iter().flat_map(|part| part.split('\n')) {
+            self.operation(String::from(line.trim()));
+        }
+
+        let mut outputs = Vec::new();
+        for operand in operands {
+            let (register, place) = match operand {
+                AsmOperand::Out(register, Some(place)) | AsmOperand::InOut(register, place) => (register, place),
+                _ => continue,
+            };
+            let temporary = Memory::frame(self.allocate(8));
+            self.operation(format!("mov {}, {}", temporary.operand(), register));
+            outputs.push((temporary, *place));
+        }
+        for (register, home) in saved {
+            self.operation(format!("mov {}, {}", register, home.operand()));
+        }
+        for (temporary, place) in outputs {
+            let size = self.size(&self.place_type(place));
+            let memory = match self.place(place) {
+                Ok(memory) => memory,
+                Err(error) => return Err(error),
+            };
+            self.copy(&memory, &temporary, size);
+        }
+        return Ok(());
+    }
+
+    /// Reads through references until `arrived` holds for the type, returning
+    /// where that value is. Goes through `r11`.
+    fn through_references(&mut self, register: Register, arrived: impl Fn(&Type) -> bool) -> Option<Memory> {
+        let mut memory = self.home(register);
+        let mut t = self.function.register_type(register);
+        loop {
+            if arrived(t) {
+                return Some(memory);
+            }
+            t = match t {
+                Type::Reference(_, inner) => inner,
+                _ => return None,
+            };
+            self.operation(format!("mov r11, {}", memory.operand()));
+            memory = Memory::register("r11");
+        }
+    }
+
+    /// `print`, `println` and `format`, and `len` and `chars` on a `&str`
+    /// and `next` and `count` on the `Chars` that `chars` returns, which are
+    /// built into the runtime. `Chars` is the part of the string not decoded
+    /// yet.
+    fn builtin(&mut self, name: &String, dest: Option<Register>, arguments: &Vec<Register>) -> Result<(), BuildError> {
+        let chars = self.context.analysis.symbols.chars();
+        let receiver = match (name.as_str(), arguments.first()) {
+            ("print" | "println" | "format", _) => return self.format(name, dest, arguments),
+            ("Box::new", Some(value)) => return self.box_new(dest, *value),
+            (_, Some(receiver)) => *receiver,
+            (_, None) => return Err(self.unsupported(format!("`{}`", name))),
+        };
+        let receiver = match name.as_str() {
+            "len" | "chars" => self.through_references(receiver, |t| t.is_str_slice()),
+            "count" => self.through_references(receiver, |t| t == &chars),
+            "next" => self.through_references(receiver, |t| pointee(t) == Some(&chars)),
+            _ => None,
+        };
+        let (receiver, dest) = match (receiver, dest) {
+            (Some(receiver), Some(dest)) => (receiver, self.home(dest)),
+            _ => return Err(self.unsupported(format!("`{}`", name))),
+        };
+        match name.as_str() {
+            "len" => {
+                self.operation(format!("mov rax, {}", receiver.at(8).operand()));
+                self.operation(format!("mov {}, rax", dest.operand()));
+            }
+            "chars" => self.copy(&dest, &receiver, 16),
+            "count" => {
+                self.context.runtime.set(true);
+                self.operation(format!("mov rcx, {}", receiver.operand()));
+                self.operation(format!("mov rdx, {}", receiver.at(8).operand()));
+                self.operation(String::from("call eclipse_count_chars"));
+                self.operation(format!("mov {}, rax", dest.operand()));
+            }
+            _ => {
+                self.context.runtime.set(true);
+                // `next` advances the `Chars` in place, through its address.
+                self.operation(format!("mov rcx, {}", receiver.operand()));
+                self.operation(String::from("call eclipse_next_char"));
+                let variants = match self.context.layouts.enums.get("Option") {
+                    Some(layout) => &layout.variants,
+                    None => return Err(self.unsupported(String::from("`Option` without its variants"))),
+                };
+                let some = variants.iter().position(|(name, _)| name == "Some");
+                let none = variants.iter().position(|(name, _)| name == "None");
+                let (some, none) = match (some, none) {
+                    (Some(some), Some(none)) => (some, none),
+                    _ => return Err(self.unsupported(String::from("`Option` without its variants"))),
+                };
+                self.operation(String::from("test rax, rax"));
+                self.operation(format!("mov eax, {}", none));
+                self.operation(format!("mov ecx, {}", some));
+                self.operation(String::from("cmovnz eax, ecx"));
+                self.operation(format!("mov {}, rax", dest.operand()));
+                self.operation(format!("mov {}, rdx", dest.at(8).operand()));
+            }
+        }
+        return Ok(());
+    }
+
+    /// Moves the value into memory of its own on the heap and keeps its
+    /// address.
+    fn box_new(&mut self, dest: Option<Register>, value: Register) -> Result<(), BuildError> {
+        let size = self.size(self.function.register_type(value));
+        let source = self.home(value);
+        self.outgoing = self.outgoing.max(1);
+        self.operation(format!("mov rcx, {}", size.max(1)));
+        self.operation(String::from("call malloc"));
+        match dest {
+            Some(dest) => {
+                let home = self.home(dest);
+                self.operation(format!("mov {}, rax", home.operand()));
+            }
+            None => {}
+        }
+        self.operation(String::from("mov rcx, rax"));
+        self.copy(&Memory::register("rcx"), &source, size);
+        return Ok(());
+    }
+
+    /// Formats the arguments of `print`, `println` or `format` through the
+    /// runtime. Every argument was evaluated before the call, so a nested
+    /// `format` has the buffer to itself.
+    fn format(&mut self, name: &String, dest: Option<Register>, arguments: &Vec<Register>) -> Result<(), BuildError> {
+        self.context.runtime.set(true);
+        let pieces = match arguments.first().and_then(|format| self.strings.get(format)) {
+            Some(format) => match parse_format(format) {
+                Ok(pieces) => pieces,
+                Err(error) => return Err(error),
+            },
+            None => Vec::new(),
+        };
+
+        let mut values = arguments.iter().skip(1);
+        for piece in pieces {
+            match piece {
+                Piece::Literal(text) => {
+                    let label = self.string(&text);
+                    self.operation(format!("lea rcx, [rel {}]", label));
+                    self.operation(format!("mov rdx, {}", text.len()));
+                    self.operation(String::from("call eclipse_write_str"));
+                }
+                Piece::Placeholder(spec) => {
+                    let value = match values.next() {
+                        Some(value) => *value,
+                        None => return Err(BuildError::FormatArgumentCount(arguments.len() - 1, arguments.len())),
+                    };
+                    let t = self.function.register_type(value).clone();
+                    match self.write_value(&self.home(value), &t, spec) {
+                        Ok(()) => {}
+                        Err(error) => return Err(error),
+                    }
+                }
+            }
+        }
+
+        match name.as_str() {
+            "format" => {
+                self.operation(String::from("call eclipse_take_string"));
+                match dest {
+                    Some(dest) => {
+                        let home = self.home(dest);
+                        self.operation(format!("mov {}, rax", home.operand()));
+                        self.operation(format!("mov {}, rdx", home.at(8).operand()));
+                    }
+                    None => {}
+                }
+                return Ok(());
+            }
+            "println" => self.operation(String::from("mov ecx, 1")),
+            _ => self.operation(String::from("xor ecx, ecx")),
+        }
+        self.operation(String::from("call eclipse_flush"));
+        return Ok(());
+    }
+
+    /// Appends the value to the format buffer. References are formatted as
+    /// the value they point to.
+    fn write_value(&mut self, memory: &Memory, t: &Type, spec: Spec) -> Result<(), BuildError> {
+        if t.is_str_slice() {
+            self.operation(format!("mov rcx, {}", memory.operand()));
+            self.operation(format!("mov rdx, {}", memory.at(8).operand()));
+            self.operation(String::from("call eclipse_write_str"));
+            return Ok(());
+        }
+        let base = match t {
+            Type::Reference(_, inner) => {
+                self.operation(format!("mov r11, {}", memory.operand()));
+                return self.write_value(&Memory::register("r11"), inner, spec);
+            }
+            Type::Base(base) => base.sized(),
+            t => return Err(self.unsupported(format!("formatting `{}`", t))),
+        };
+        match base {
+            BaseType::Int128 | BaseType::UInt128 => {
+                let signed = match (spec, &base) {
+                    (Spec::Display, BaseType::Int128) => 1,
+                    _ => 0,
+                };
+                let (radix, upper) = match spec {
+                    Spec::Display => (10, 0),
+                    Spec::LowerHex => (16, 0),
+                    Spec::UpperHex => (16, 2),
+                    Spec::Binary => (2, 0),
+                    Spec::Octal => (8, 0),
+                };
+                self.operation(format!("mov rcx, {}", memory.operand()));
+                self.operation(format!("mov rdx, {}", memory.at(8).operand()));
+                self.operation(format!("mov r8d, {}", radix));
+                self.operation(format!("mov r9d, {}", signed | upper));
+                self.operation(String::from("call eclipse_write_int128"));
+            }
+            BaseType::Boolean => {
+                self.operation(format!("movzx ecx, byte {}", memory.operand()));
+                self.operation(String::from("call eclipse_write_bool"));
+            }
+            BaseType::Char => {
+                self.operation(format!("mov ecx, dword {}", memory.operand()));
+                self.operation(String::from("call eclipse_write_char"));
+            }
+            BaseType::Float64 => {
+                self.operation(format!("mov rcx, {}", memory.operand()));
+                self.operation(String::from("call eclipse_write_float"));
+            }
+            BaseType::Float32 => {
+                self.operation(format!("cvtss2sd xmm0, dword {}", memory.operand()));
+                self.operation(String::from("movq rcx, xmm0"));
+                self.operation(String::from("call eclipse_write_float"));
+            }
+            BaseType::Str => return Err(self.unsupported(String::from("formatting `str`"))),
+            base => {
+                // Hex, binary and octal show the bits of the value at its own width.
+                let base = match (spec, base) {
+                    (Spec::Display, base) => base,
+                    (_, BaseType::Int64) => BaseType::UInt64,
+                    (_, BaseType::Int32) => BaseType::UInt32,
+                    (_, BaseType::Int16) => BaseType::UInt16,
+                    (_, BaseType::Int8) => BaseType::UInt8,
+                    (_, base) => base,
+                };
+                let signed = base.is_signed() as u8;
+                let (radix, upper) = match spec {
+                    Spec::Display => (10, 0),
+                    Spec::LowerHex => (16, 0),
+                    Spec::UpperHex => (16, 1),
+                    Spec::Binary => (2, 0),
+                    Spec::Octal => (8, 0),
+                };
+                self.load("rcx", memory, &Type::Base(base));
+                self.operation(format!("mov edx, {}", radix));
+                self.operation(format!("mov r8d, {}", signed));
+                self.operation(format!("mov r9d, {}", upper));
+                self.operation(String::from("call eclipse_write_int"));
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// The name of a variant or method without the enum or type before it.
+fn member(name: &String) -> String {
+    return match name.rsplit_once("::") {
+        Some((_, member)) => String::from(member),
+        None => name.clone(),
+    };
+}
+
+/// Where every capture of a `move` closure starts in its environment.
+fn capture_offsets(context: &Context, closure: &Function) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut size = 0;
+    for capture in &closure.captures {
+        offsets.push(size);
+        size += align_to(context.layouts.size_of(&capture.t).max(8), 8);
+    }
+    return offsets;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        builder::{compile_error, compile_files, compile_source},
+        BuildError,
+    };
+
+    fn assembly(source: &str) -> String {
+        return match compile_source(source) {
+            Ok(compiled) => compiled.assembly,
+            Err(error) => panic!("{:?}", error),
+        };
+    }
+
+    #[test]
+    fn float_arithmetic_uses_sse() {
+        let assembly = assembly(
+            "fn main() {\n    let a = 3 as f64;\n    let b = 2 as f64;\n    let c = a + b;\n    let d = a - b;\n    let e = a * b;\n    let f = a / b;\n}\n",
+        );
+        for operation in ["addsd xmm0, xmm1", "subsd xmm0, xmm1", "mulsd xmm0, xmm1", "divsd xmm0, xmm1"] {
+            assert!(assembly.contains(operation), "no `{}` in\n{}", operation, assembly);
+        }
+        assert!(!assembly.contains("imul rax, rcx"));
+    }
+
+    #[test]
+    fn f32_uses_single_precision() {
+        let assembly = assembly("fn main() {\n    let a = 3 as f32;\n    let b = a * a;\n    let c = a < b;\n}\n");
+        assert!(assembly.contains("mulss xmm0, xmm1"));
+        assert!(assembly.contains("ucomiss xmm1, xmm0"));
+    }
+
+    #[test]
+    fn float_equality_is_false_for_nan() {
+        let assembly = assembly("fn main() {\n    let a = 3 as f64;\n    let b = a == a;\n}\n");
+        assert!(assembly.contains("ucomisd xmm0, xmm1\n\tsete al\n\tsetnp cl\n\tand al, cl"));
+    }
+
+    #[test]
+    fn foreign_floats_go_in_xmm_registers() {
+        let assembly = assembly(
+            "extern \"C\" {\n    fn pow(x: f64, y: f64): f64;\n}\n\nfn main() {\n    let a = 3 as f64;\n    unsafe {\n        let b = pow(a, a);\n    }\n}\n",
+        );
+        assert!(assembly.contains("movsd xmm0, "));
+        assert!(assembly.contains("movsd xmm1, "));
+        assert!(assembly.contains("call pow\n\tmovsd [rbp"));
+    }
+
+    #[test]
+    fn newtypes_construct_through_module_paths() {
+        let main = "import a;\n\nfn main() {\n    let q = a::Q(3);\n    let x = q.0 + 1;\n}\n";
+        let a = "pub struct Q(pub i32);\n";
+        let assembly = match compile_files(&[("src/main.eclipse", main), ("src/a.eclipse", a)]) {
+            Ok(compiled) => compiled.assembly,
+            Err(error) => panic!("{:?}", error),
+        };
+        assert!(assembly.contains("mov rax, 3"));
+    }
+
+    #[test]
+    fn boxed_trait_objects_call_through_their_vtable() {
+        let assembly = assembly(
+            "trait Shape {\n    fn area(&self): i32;\n}\n\nstruct Square(i32);\n\nimpl Shape for Square {\n    fn area(&self): i32 {\n        return self.0 * self.0;\n    }\n}\n\nfn main(): i32 {\n    let shape: Box<dyn Shape> = Box::new(Square(3));\n    return shape.area();\n}\n",
+        );
+        assert!(assembly.contains("mov rcx, 4\n\tcall malloc"));
+        assert!(assembly.contains("; <Square as Shape>"));
+        assert!(assembly.contains("call [rax+16]"));
+    }
+
+    #[test]
+    fn string_literals_end_with_a_zero() {
+        let assembly = assembly(
+            "extern \"C\" {\n    fn puts(s: *const u8): i32;\n}\n\nfn main() {\n    unsafe {\n        puts(\"hi\" as *const u8);\n    }\n}\n",
+        );
+        assert!(assembly.contains("db 104, 105, 0\n"));
+    }
+
+    #[test]
+    fn foreign_names_never_clash_with_labels() {
+        let assembly = assembly("extern \"C\" {\n    fn _b();\n    fn _a();\n}\n\nfn f() {}\n\nfn main() {\n    f();\n}\n");
+        assert!(assembly.contains("extern _a, _b,"));
+        assert!(!assembly.contains("\n_a:") && !assembly.contains("\n_b:"));
+        assert!(assembly.contains("\neclipse__a:") && assembly.contains("\neclipse__b:"));
+        let error = compile_error("extern \"C\" {\n    fn eclipse_flush();\n}\n\nfn main() {}\n");
+        assert!(matches!(error, BuildError::ReservedName(name) if name == "eclipse_flush"));
+    }
+
+    #[test]
+    fn strings_are_walked_char_by_char() {
+        let compiled = match compile_source(
+            "fn main() {\n    let s = \"h\u{e9}llo\";\n    let n: usize = s.len();\n    let mut chars = s.chars();\n    let first: Option<char> = chars.next();\n    let rest: usize = chars.count();\n}\n",
+        ) {
+            Ok(compiled) => compiled,
+            Err(error) => panic!("{:?}", error),
+        };
+        let ir = compiled.ir.to_string();
+        assert!(ir.contains("%5: &mut Chars = &mut $0 // line 5\n    %6: Option<char> = call builtin next(%5) // line 5\n"));
+        assert!(ir.contains("%8: usize = call builtin count(%7) // line 6\n"));
+        // `len` counts the bytes, `é` takes two of them.
+        assert!(compiled.assembly.contains("db 104, 195, 169, 108, 108, 111, 0\n"));
+        assert!(compiled.assembly.contains("\tmov qword [rbp-8], 6\n\tmov rax, [rbp-8]\n"));
+        // `next` gets the address of the `Chars` it advances, `count` a copy.
+        assert!(compiled.assembly.contains("\tlea rax, [rbp-136]\n\tmov [rbp-80], rax\n\tmov rcx, [rbp-80]\n\tcall eclipse_next_char\n"));
+        assert!(compiled.assembly.contains("\tmov rcx, [rbp-112]\n\tmov rdx, [rbp-104]\n\tcall eclipse_count_chars\n"));
+        assert!(compiled.assembly.contains("\neclipse_next_char:\n") && compiled.assembly.contains("\neclipse_count_chars:\n"));
+    }
+
+    #[test]
+    fn structs_called_chars_are_not_the_builtin_one() {
+        let source = "struct Chars(i32);\n\nimpl Chars {\n    fn count(&self): i32 {\n        return self.0;\n    }\n}\n\nfn main() {\n    let mine = Chars(3);\n    let n: i32 = mine.count();\n    let m: usize = \"abc\".chars().count();\n}\n";
+        let ir = match compile_source(source) {
+            Ok(compiled) => compiled.ir.to_string(),
+            Err(error) => panic!("{:?}", error),
+        };
+        assert!(ir.contains("%3: i32 = call crate::Chars::count(%2) // line 11\n"));
+        assert!(ir.contains("%5: Chars = call builtin chars(%4) // line 12\n    %6: usize = call builtin count(%5) // line 12\n"));
+        let error = compile_error("struct Chars(i32);\n\nfn main() {\n    let mine = Chars(3);\n    let n = mine.count();\n}\n");
+        assert!(matches!(error, BuildError::NoMethod(method, t) if method == "count" && t == "crate::Chars"));
+    }
+
+    #[test]
+    fn chars_methods_have_fixed_types() {
+        let error = compile_error("fn main() {\n    let n: i32 = \"abc\".chars().count();\n}\n");
+        assert!(matches!(error, BuildError::MismatchedTypes(expected, found) if expected == "i32" && found == "usize"));
+        let error = compile_error("fn main() {\n    let mut chars = \"abc\".chars();\n    let c: char = chars.next();\n}\n");
+        assert!(matches!(error, BuildError::MismatchedTypes(expected, found) if expected == "char" && found == "Option<char>"));
+        let error = compile_error("fn main() {\n    let x: i32 = 1;\n    let n = x.chars();\n}\n");
+        assert!(matches!(error, BuildError::NoMethod(method, t) if method == "chars" && t == "i32"));
+    }
+}
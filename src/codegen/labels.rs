@@ -35,12 +35,8 @@ impl Labels {
             labels: HashMap::new(),
         }
     }
-    pub fn increment(&mut self) -> String {
-        self.offset += 1;
-        number_to_string(self.offset)
-    }
-    pub fn get(&mut self, key: &String) -> String {
-        self.labels.get(key).unwrap().to_owned()
+    pub fn peek(&self, key: &String) -> Option<&String> {
+        self.labels.get(key)
     }
     pub fn generate(&mut self, key: &String) -> String {
         match self.labels.get(key) {
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use crate::{
+    parser::{BaseType, Type},
+    POINTER_SIZE,
+};
+
+/// The generic parameters and named fields of a struct, tuple structs naming
+/// theirs `0`, `1` and so on.
+pub struct StructLayout {
+    pub generics: Vec<String>,
+    pub fields: Vec<(String, Type)>,
+}
+impl StructLayout {
+    /// The field types with the generic parameters replaced by `arguments`.
+    pub fn instantiate(&self, arguments: &[Type]) -> Vec<Type> {
+        return self
+            .fields
+            .iter()
+            .map(|(_, t)| substitute(t, &self.generics, arguments))
+            .collect();
+    }
+}
+
+/// The generic parameters and named variants of an enum. Enum values are a
+/// tag holding the variant index followed by the payload.
+pub struct EnumLayout {
+    pub generics: Vec<String>,
+    pub variants: Vec<(String, Vec<Type>)>,
+}
+impl EnumLayout {
+    /// The payload types of every variant with the generic parameters
+    /// replaced by `arguments`.
+    pub fn instantiate(&self, arguments: &[Type]) -> Vec<Vec<Type>> {
+        return self
+            .variants
+            .iter()
+            .map(|(_, fields)| {
+                fields
+                    .iter()
+                    .map(|t| substitute(t, &self.generics, arguments))
+                    .collect()
+            })
+            .collect();
+    }
+}
+
+/// Replaces the generic parameters in `t` with their arguments.
+pub fn substitute(t: &Type, generics: &Vec<String>, arguments: &[Type]) -> Type {
+    return match t {
+        Type::Custom(name) => match generics.iter().position(|generic| generic == name) {
+            Some(index) => match arguments.get(index) {
+                Some(argument) => argument.clone(),
+                None => t.clone(),
+            },
+            None => t.clone(),
+        },
+        Type::Tuple(types) => Type::Tuple(
+            types
+                .iter()
+                .map(|t| substitute(t, generics, arguments))
+                .collect(),
+        ),
+        Type::Generic(name, types) => Type::Generic(
+            name.clone(),
+            types
+                .iter()
+                .map(|t| substitute(t, generics, arguments))
+                .collect(),
+        ),
+        Type::Reference(mutable, inner) => {
+            Type::Reference(*mutable, Box::new(substitute(inner, generics, arguments)))
+        }
+        Type::Pointer(mutable, inner) => {
+            Type::Pointer(*mutable, Box::new(substitute(inner, generics, arguments)))
+        }
+        Type::Function(parameters, return_type) => Type::Function(
+            parameters
+                .iter()
+                .map(|t| substitute(t, generics, arguments))
+                .collect(),
+            match return_type {
+                Some(t) => Some(Box::new(substitute(t, generics, arguments))),
+                None => None,
+            },
+        ),
+        Type::Base(_) | Type::Dyn(_) => t.clone(),
+    };
+}
+
+/// Field lists of every struct and variant lists of every enum, used to size
+/// values the same way a C compiler would.
+pub struct Layouts {
+    pub structs: HashMap<String, StructLayout>,
+    pub enums: HashMap<String, EnumLayout>,
+}
+impl Layouts {
+    pub fn new() -> Self {
+        Self {
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+        }
+    }
+    pub fn size_of(&self, t: &Type) -> usize {
+        return match t {
+            Type::Base(base) => match base {
+                BaseType::Int128 | BaseType::UInt128 => 16,
+                BaseType::Int64 | BaseType::UInt64 | BaseType::Float64 => 8,
+                BaseType::Int32 | BaseType::UInt32 | BaseType::Float32 | BaseType::Char => 4,
+                BaseType::Int16 | BaseType::UInt16 => 2,
+                BaseType::Int8 | BaseType::UInt8 | BaseType::Boolean => 1,
+                // Only ever used behind a reference.
+                BaseType::Str => 0,
+                BaseType::IntSize | BaseType::UIntSize => POINTER_SIZE,
+            },
+            // Function values are a code address paired with an environment pointer.
+            Type::Function(_, _) => 16,
+            Type::Pointer(_, _) => 8,
+            Type::Reference(_, _) if t.is_str_slice() => 16,
+            Type::Reference(_, _) | Type::Generic(_, _) | Type::Custom(_) => match t.trait_object() {
+                Some(_) => 16,
+                None => match (self.struct_fields(t), self.enum_variants(t)) {
+                    (Some(fields), _) => self.fields_size(&fields),
+                    (None, Some(variants)) => self.enum_size(&variants),
+                    // References, boxes and the generic parameters of a
+                    // function, which are passed as one word.
+                    (None, None) => 8,
+                },
+            },
+            Type::Tuple(types) => self.fields_size(types),
+            // An unsized `dyn Trait` is only ever reached through a pointer.
+            Type::Dyn(_) => 0,
+        };
+    }
+    pub fn align_of(&self, t: &Type) -> usize {
+        return match t {
+            Type::Tuple(types) => types.iter().map(|t| self.align_of(t)).max().unwrap_or(1),
+            Type::Custom(_) | Type::Generic(_, _) => match self.struct_fields(t) {
+                Some(fields) => fields.iter().map(|t| self.align_of(t)).max().unwrap_or(1),
+                None => 8,
+            },
+            Type::Dyn(_) => 1,
+            t => self.size_of(t).min(8),
+        };
+    }
+    /// The field types when `t` is a struct or a tuple.
+    pub fn struct_fields(&self, t: &Type) -> Option<Vec<Type>> {
+        return match t {
+            Type::Custom(name) => match self.structs.get(name) {
+                Some(layout) => Some(layout.instantiate(&[])),
+                None => None,
+            },
+            Type::Generic(name, arguments) => match self.structs.get(name) {
+                Some(layout) => Some(layout.instantiate(arguments)),
+                None => None,
+            },
+            Type::Tuple(types) => Some(types.clone()),
+            _ => None,
+        };
+    }
+    /// Where the field named `field` of the struct or tuple `t` starts, and
+    /// its type.
+    pub fn field(&self, t: &Type, field: &String) -> Option<(usize, Type)> {
+        let fields = match self.struct_fields(t) {
+            Some(fields) => fields,
+            None => return None,
+        };
+        let index = match t {
+            Type::Custom(name) | Type::Generic(name, _) => {
+                match self.structs[name].fields.iter().position(|(name, _)| name == field) {
+                    Some(index) => index,
+                    None => return None,
+                }
+            }
+            _ => match field.parse::<usize>() {
+                Ok(index) if index < fields.len() => index,
+                _ => return None,
+            },
+        };
+        return Some((self.field_offsets(&fields)[index], fields[index].clone()));
+    }
+    /// Where every field starts, each aligned like a C compiler would.
+    pub fn field_offsets(&self, fields: &Vec<Type>) -> Vec<usize> {
+        let mut offsets = Vec::new();
+        let mut size = 0;
+        for field in fields {
+            size = align_to(size, self.align_of(field));
+            offsets.push(size);
+            size += self.size_of(field);
+        }
+        return offsets;
+    }
+    /// The variant of the enum `t` named `variant`, with its index and its
+    /// payload types.
+    pub fn variant(&self, t: &Type, variant: &String) -> Option<(usize, Vec<Type>)> {
+        let (name, arguments) = match t {
+            Type::Custom(name) => (name, &[][..]),
+            Type::Generic(name, arguments) => (name, arguments.as_slice()),
+            _ => return None,
+        };
+        let layout = match self.enums.get(name) {
+            Some(layout) => layout,
+            None => return None,
+        };
+        return match layout.variants.iter().position(|(name, _)| name == variant) {
+            Some(index) => Some((index, layout.instantiate(arguments).swap_remove(index))),
+            None => None,
+        };
+    }
+    /// The payload types of every variant when `t` is an enum.
+    pub fn enum_variants(&self, t: &Type) -> Option<Vec<Vec<Type>>> {
+        return match t {
+            Type::Custom(name) => match self.enums.get(name) {
+                Some(layout) => Some(layout.instantiate(&[])),
+                None => None,
+            },
+            Type::Generic(name, arguments) => match self.enums.get(name) {
+                Some(layout) => Some(layout.instantiate(arguments)),
+                None => None,
+            },
+            _ => None,
+        };
+    }
+    fn enum_size(&self, variants: &Vec<Vec<Type>>) -> usize {
+        let payload = variants
+            .iter()
+            .map(|fields| self.fields_size(fields))
+            .max()
+            .unwrap_or(0);
+        return align_to(8 + payload, 8);
+    }
+    fn fields_size(&self, fields: &Vec<Type>) -> usize {
+        let mut size = 0;
+        let mut align = 1;
+        for field in fields {
+            let field_align = self.align_of(field);
+            size = align_to(size, field_align) + self.size_of(field);
+            align = align.max(field_align);
+        }
+        return align_to(size, align);
+    }
+}
+
+pub fn align_to(size: usize, align: usize) -> usize {
+    return match size % align {
+        0 => size,
+        rest => size + align - rest,
+    };
+}
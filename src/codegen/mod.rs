@@ -1,3 +1,10 @@
-// mod codegen;
-// mod action;
-// mod writer;
+mod codegen;
+mod function;
+mod labels;
+mod layout;
+mod runtime;
+mod statics;
+mod vtable;
+mod writer;
+
+pub use codegen::{generate, RESERVED_PREFIX};
@@ -0,0 +1,488 @@
+use super::writer::Writer;
+
+/// Functions the runtime imports from the C library and the Windows API.
+pub const RUNTIME_EXTERNS: [&str; 3] = ["realloc", "GetStdHandle", "WriteFile"];
+
+/// The formatting runtime behind `print`, `println` and `format`, together
+/// with the 128-bit division that has no instruction of its own and the
+/// UTF-8 decoding behind the chars of a `&str`.
+///
+/// Every value is appended as text to a growable buffer on the heap, which
+/// `eclipse_flush` writes to standard output and `eclipse_take_string` hands
+/// over as a `&str`. Integers are written in any base up to 16, floats with
+/// up to six decimal places and chars encoded as UTF-8.
+const RUNTIME: &str = "
+section .rdata
+eclipse_digits: db \"0123456789abcdef\"
+eclipse_upper_digits: db \"0123456789ABCDEF\"
+eclipse_true: db \"true\"
+eclipse_false: db \"false\"
+eclipse_nan: db \"NaN\"
+eclipse_infinity: db \"inf\"
+eclipse_symbols: db \"-.0\", 10
+
+section .bss
+eclipse_buffer: resq 1
+eclipse_length: resq 1
+eclipse_capacity: resq 1
+
+section .text
+; rcx: bytes the buffer needs room for
+eclipse_reserve:
+	push rbp
+	mov rbp, rsp
+	sub rsp, 32
+	mov rax, [rel eclipse_length]
+	add rax, rcx
+	cmp rax, [rel eclipse_capacity]
+	jbe .done
+	mov rdx, [rel eclipse_capacity]
+	shl rdx, 1
+	cmp rdx, rax
+	cmovb rdx, rax
+	mov eax, 64
+	cmp rdx, rax
+	cmovb rdx, rax
+	mov [rel eclipse_capacity], rdx
+	mov rcx, [rel eclipse_buffer]
+	call realloc
+	mov [rel eclipse_buffer], rax
+.done:
+	leave
+	ret
+
+; rcx: address of the bytes, rdx: their length
+eclipse_write_str:
+	push rbp
+	mov rbp, rsp
+	push rsi
+	push rdi
+	sub rsp, 32
+	mov rsi, rcx
+	mov rdi, rdx
+	mov rcx, rdx
+	call eclipse_reserve
+	mov rcx, rdi
+	mov rdi, [rel eclipse_buffer]
+	add rdi, [rel eclipse_length]
+	add [rel eclipse_length], rcx
+	rep movsb
+	add rsp, 32
+	pop rdi
+	pop rsi
+	pop rbp
+	ret
+
+; rcx: the bool
+eclipse_write_bool:
+	test cl, cl
+	jz .false
+	lea rcx, [rel eclipse_true]
+	mov edx, 4
+	jmp eclipse_write_str
+.false:
+	lea rcx, [rel eclipse_false]
+	mov edx, 5
+	jmp eclipse_write_str
+
+; rcx: the char
+eclipse_write_char:
+	push rbp
+	mov rbp, rsp
+	sub rsp, 48
+	mov eax, ecx
+	mov r8d, 1
+	xor r9d, r9d
+	cmp eax, 0x80
+	jb .lead
+	mov r8d, 2
+	mov r9d, 0xc0
+	cmp eax, 0x800
+	jb .encode
+	mov r8d, 3
+	mov r9d, 0xe0
+	cmp eax, 0x10000
+	jb .encode
+	mov r8d, 4
+	mov r9d, 0xf0
+.encode:
+	lea r10, [rbp+r8-8]
+	mov r11, r8
+.continuation:
+	dec r10
+	mov edx, eax
+	and edx, 0x3f
+	or edx, 0x80
+	mov [r10], dl
+	shr eax, 6
+	dec r11
+	cmp r11, 1
+	jne .continuation
+.lead:
+	or eax, r9d
+	mov [rbp-8], al
+	lea rcx, [rbp-8]
+	mov rdx, r8
+	call eclipse_write_str
+	leave
+	ret
+
+; rcx: the integer, rdx: the base, r8: non-zero when signed, r9: non-zero for upper case digits
+eclipse_write_int:
+	push rbp
+	mov rbp, rsp
+	sub rsp, 112
+	mov rax, rcx
+	mov rcx, rdx
+	lea r10, [rel eclipse_digits]
+	lea r11, [rel eclipse_upper_digits]
+	test r9, r9
+	cmovnz r10, r11
+	xor r11d, r11d
+	test r8, r8
+	jz .convert
+	test rax, rax
+	jns .convert
+	neg rax
+	mov r11d, 1
+.convert:
+	mov r8, rbp
+.digit:
+	xor edx, edx
+	div rcx
+	dec r8
+	mov r9b, [r10+rdx]
+	mov [r8], r9b
+	test rax, rax
+	jnz .digit
+	test r11, r11
+	jz .write
+	dec r8
+	mov byte [r8], '-'
+.write:
+	mov rcx, r8
+	mov rdx, rbp
+	sub rdx, r8
+	call eclipse_write_str
+	leave
+	ret
+
+; rcx: the low half, rdx: the high half, r8: the base, r9: 1 when signed plus 2 for upper case digits
+eclipse_write_int128:
+	push rbp
+	mov rbp, rsp
+	sub rsp, 192
+	mov r10, rcx
+	mov r11, rdx
+	mov rcx, r8
+	mov [rbp-8], r9
+	lea rax, [rel eclipse_digits]
+	lea rdx, [rel eclipse_upper_digits]
+	test r9, 2
+	cmovnz rax, rdx
+	mov [rbp-16], rax
+	and qword [rbp-8], 1
+	jz .convert
+	test r11, r11
+	jns .positive
+	neg r10
+	adc r11, 0
+	neg r11
+	jmp .convert
+.positive:
+	mov qword [rbp-8], 0
+.convert:
+	lea r8, [rbp-16]
+.digit:
+	xor edx, edx
+	mov rax, r11
+	div rcx
+	mov r11, rax
+	mov rax, r10
+	div rcx
+	mov r10, rax
+	mov r9, [rbp-16]
+	mov al, [r9+rdx]
+	dec r8
+	mov [r8], al
+	mov rax, r10
+	or rax, r11
+	jnz .digit
+	cmp qword [rbp-8], 0
+	je .write
+	dec r8
+	mov byte [r8], '-'
+.write:
+	mov rcx, r8
+	lea rdx, [rbp-16]
+	sub rdx, r8
+	call eclipse_write_str
+	leave
+	ret
+
+; rcx:rdx divided by r8:r9 as unsigned 128-bit integers, low halves first, quotient in rax:rdx
+eclipse_udiv128:
+	mov rax, rcx
+	xor r10d, r10d
+	xor r11d, r11d
+	mov ecx, 128
+.bit:
+	shl rax, 1
+	rcl rdx, 1
+	rcl r10, 1
+	rcl r11, 1
+	jc .subtract
+	sub r10, r8
+	sbb r11, r9
+	jnc .set
+	add r10, r8
+	adc r11, r9
+	jmp .next
+.subtract:
+	sub r10, r8
+	sbb r11, r9
+.set:
+	or rax, 1
+.next:
+	dec ecx
+	jnz .bit
+	ret
+
+; rcx:rdx divided by r8:r9 as signed 128-bit integers, rounding toward zero, quotient in rax:rdx
+eclipse_div128:
+	push rbp
+	mov rbp, rsp
+	sub rsp, 16
+	mov r10, rdx
+	xor r10, r9
+	mov [rbp-8], r10
+	test rdx, rdx
+	jns .dividend
+	neg rcx
+	adc rdx, 0
+	neg rdx
+.dividend:
+	test r9, r9
+	jns .divisor
+	neg r8
+	adc r9, 0
+	neg r9
+.divisor:
+	call eclipse_udiv128
+	cmp qword [rbp-8], 0
+	jge .done
+	neg rax
+	adc rdx, 0
+	neg rdx
+.done:
+	leave
+	ret
+
+; rcx: the bits of an f64
+eclipse_write_float:
+	push rbp
+	mov rbp, rsp
+	sub rsp, 80
+	movq xmm0, rcx
+	ucomisd xmm0, xmm0
+	jp .nan
+	btr rcx, 63
+	mov [rbp-8], rcx
+	jnc .positive
+	lea rcx, [rel eclipse_symbols]
+	mov edx, 1
+	call eclipse_write_str
+.positive:
+	mov rax, 0x7FF0000000000000
+	cmp [rbp-8], rax
+	je .infinity
+	movq xmm0, [rbp-8]
+	mov rax, 0x43E0000000000000
+	movq xmm1, rax
+	ucomisd xmm0, xmm1
+	jae .large
+	cvttsd2si rax, xmm0
+	mov [rbp-16], rax
+	cvtsi2sd xmm1, rax
+	subsd xmm0, xmm1
+	mov rax, 0x412E848000000000
+	movq xmm1, rax
+	mulsd xmm0, xmm1
+	cvtsd2si rax, xmm0
+	cmp rax, 1000000
+	jb .split
+	inc qword [rbp-16]
+	xor eax, eax
+.split:
+	mov [rbp-24], rax
+	mov rcx, [rbp-16]
+	mov edx, 10
+	xor r8d, r8d
+	xor r9d, r9d
+	call eclipse_write_int
+	mov rax, [rbp-24]
+	test rax, rax
+	jz .done
+	mov ecx, 6
+	mov r9d, 10
+.trim:
+	mov r8, rax
+	xor edx, edx
+	div r9
+	test rdx, rdx
+	jnz .fraction
+	dec ecx
+	jmp .trim
+.fraction:
+	mov [rbp-24], r8
+	mov [rbp-32], rcx
+	lea rcx, [rel eclipse_symbols+1]
+	mov edx, 1
+	call eclipse_write_str
+	mov rax, [rbp-24]
+	mov rcx, [rbp-32]
+	lea r8, [rbp-40]
+	mov r9d, 10
+.fraction_digit:
+	xor edx, edx
+	div r9
+	add dl, '0'
+	dec r8
+	mov [r8], dl
+	dec rcx
+	jnz .fraction_digit
+	mov rcx, r8
+	lea rdx, [rbp-40]
+	sub rdx, r8
+	call eclipse_write_str
+	jmp .done
+.large:
+	mov rax, 0x4024000000000000
+	movq xmm2, rax
+	xor ecx, ecx
+.shrink:
+	divsd xmm0, xmm2
+	inc rcx
+	ucomisd xmm0, xmm1
+	jae .shrink
+	mov [rbp-16], rcx
+	cvtsd2si rcx, xmm0
+	mov edx, 10
+	xor r8d, r8d
+	xor r9d, r9d
+	call eclipse_write_int
+.zeros:
+	lea rcx, [rel eclipse_symbols+2]
+	mov edx, 1
+	call eclipse_write_str
+	dec qword [rbp-16]
+	jnz .zeros
+	jmp .done
+.nan:
+	lea rcx, [rel eclipse_nan]
+	mov edx, 3
+	call eclipse_write_str
+	jmp .done
+.infinity:
+	lea rcx, [rel eclipse_infinity]
+	mov edx, 3
+	call eclipse_write_str
+.done:
+	leave
+	ret
+
+; rcx: non-zero to end the line first
+eclipse_flush:
+	push rbp
+	mov rbp, rsp
+	sub rsp, 48
+	test rcx, rcx
+	jz .write
+	lea rcx, [rel eclipse_symbols+3]
+	mov edx, 1
+	call eclipse_write_str
+.write:
+	mov ecx, -11
+	call GetStdHandle
+	mov rcx, rax
+	mov rdx, [rel eclipse_buffer]
+	mov r8, [rel eclipse_length]
+	lea r9, [rbp-8]
+	mov qword [rsp+32], 0
+	call WriteFile
+	mov qword [rel eclipse_length], 0
+	leave
+	ret
+
+; rcx: address of the `&str` left to decode, returns 1 in rax and the next char in rdx,
+; or 0 in rax at the end of the string
+eclipse_next_char:
+	mov r8, [rcx]
+	mov r9, [rcx+8]
+	xor eax, eax
+	test r9, r9
+	jz .done
+	movzx edx, byte [r8]
+	mov r10d, 1
+	cmp edx, 0x80
+	jb .advance
+	mov r10d, 2
+	and edx, 0x1f
+	cmp byte [r8], 0xe0
+	jb .continuation
+	mov r10d, 3
+	movzx edx, byte [r8]
+	and edx, 0x0f
+	cmp byte [r8], 0xf0
+	jb .continuation
+	mov r10d, 4
+	movzx edx, byte [r8]
+	and edx, 0x07
+.continuation:
+	mov r11d, 1
+.byte:
+	shl edx, 6
+	movzx eax, byte [r8+r11]
+	and eax, 0x3f
+	or edx, eax
+	inc r11
+	cmp r11, r10
+	jne .byte
+.advance:
+	add [rcx], r10
+	sub [rcx+8], r10
+	mov eax, 1
+.done:
+	ret
+
+; rcx: address of the bytes, rdx: their length, returns the number of chars in rax
+eclipse_count_chars:
+	xor eax, eax
+	test rdx, rdx
+	jz .done
+.byte:
+	movzx r8d, byte [rcx]
+	and r8d, 0xc0
+	cmp r8d, 0x80
+	je .next
+	inc rax
+.next:
+	inc rcx
+	dec rdx
+	jnz .byte
+.done:
+	ret
+
+; returns the buffer as a `&str` in rax:rdx and starts a new one
+eclipse_take_string:
+	mov rax, [rel eclipse_buffer]
+	mov rdx, [rel eclipse_length]
+	mov qword [rel eclipse_buffer], 0
+	mov qword [rel eclipse_length], 0
+	mov qword [rel eclipse_capacity], 0
+	ret
+";
+
+pub fn write_runtime(writer: &mut Writer) {
+    writer.push_str(RUNTIME);
+}
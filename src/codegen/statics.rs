@@ -0,0 +1,51 @@
+use super::{codegen::Context, writer::Writer};
+
+pub fn static_key(name: &String) -> String {
+    return format!("static {}", name);
+}
+
+/// The `nasm` data and reservation directives for a value of `size` bytes.
+pub fn directives(size: usize) -> (&'static str, &'static str) {
+    return match size {
+        1 => ("db", "resb"),
+        2 => ("dw", "resw"),
+        4 => ("dd", "resd"),
+        _ => ("dq", "resq"),
+    };
+}
+
+/// Writes statics with a non-zero initializer into `data` and the remaining
+/// ones into `bss`, so zeroed statics take no space in the executable.
+pub fn write_statics(context: &Context, data: &mut Writer, bss: &mut Writer) {
+    let mut names: Vec<&String> = context.analysis.constants.statics.keys().collect();
+    names.sort();
+
+    for name in names {
+        let definition = context.analysis.constants.statics.get(name).unwrap();
+        let label = match context.static_label(name) {
+            Some(label) => label,
+            None => continue,
+        };
+        let size = context.layouts.size_of(&definition.static_type);
+        let (define, reserve) = directives(size);
+        match (definition.value, size) {
+            (0, 16) => {
+                bss.label(&label);
+                bss.add_operation(format!("{} 2", reserve));
+            }
+            (0, _) => {
+                bss.label(&label);
+                bss.add_operation(format!("{} 1", reserve));
+            }
+            // 128-bit integers are stored low half first.
+            (value, 16) => {
+                data.label(&label);
+                data.add_operation(format!("{} {}, {}", define, value as i64, (value >> 64) as i64));
+            }
+            (value, _) => {
+                data.label(&label);
+                data.add_operation(format!("{} {}", define, value));
+            }
+        }
+    }
+}
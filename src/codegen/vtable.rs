@@ -0,0 +1,56 @@
+use crate::parser::Type;
+
+use super::{codegen::Context, writer::Writer};
+
+/// Number of `dq` slots in front of the method addresses of a vtable.
+pub const VTABLE_HEADER: usize = 2;
+
+/// Writes one vtable per (type, trait) pair into the data section.
+///
+/// Every vtable starts with the size and alignment of the concrete type,
+/// followed by the method addresses in trait declaration order, so method `n`
+/// lives at `[vtable + 8 * (VTABLE_HEADER + n)]`. Methods that cannot be
+/// called through a reference, like generic ones, leave their slot empty.
+pub fn write_vtables(context: &Context, writer: &mut Writer) {
+    let mut implementations: Vec<_> = context.analysis.traits.implementations.iter().collect();
+    implementations
+        .sort_by_key(|implementation| context.vtable_label(&implementation.target, &implementation.trait_name));
+    for implementation in implementations {
+        let (target, trait_name) = (&implementation.target, &implementation.trait_name);
+        let definition = match context.analysis.traits.traits.get(trait_name) {
+            Some(definition) => definition,
+            None => continue,
+        };
+        let label = match context.vtable_label(target, trait_name) {
+            Some(label) => label,
+            None => continue,
+        };
+        writer.push_str(&format!("; <{} as {}>\n", target, trait_name));
+        writer.label(&label);
+        writer.add_operation(format!(
+            "dq {}, {}",
+            context.layouts.size_of(target),
+            context.layouts.align_of(target)
+        ));
+        for method in &definition.methods {
+            let by_reference = match method.parameters.first() {
+                Some((name, t)) => name == "self" && matches!(t, Type::Reference(_, _)),
+                None => false,
+            };
+            let entry = match (
+                by_reference && method.generics.is_empty(),
+                context.method(target, trait_name, &method.name),
+            ) {
+                (true, Some(id)) => match context.defaults.contains_key(&id) {
+                    true => context.thunk_label(target, trait_name, &method.name),
+                    false => context.body_label(id),
+                },
+                _ => None,
+            };
+            match entry {
+                Some(entry) => writer.add_operation(format!("dq {}", entry)),
+                None => writer.add_operation_str("dq 0"),
+            }
+        }
+    }
+}
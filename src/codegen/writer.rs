@@ -7,9 +7,6 @@ impl Writer {
             body: String::new(),
         }
     }
-    pub fn push(&mut self, body: &String) {
-        self.body.push_str(&body);
-    }
     pub fn push_str(&mut self, body: &str) {
         self.body.push_str(body);
     }
@@ -0,0 +1,386 @@
+use crate::{BuildError, BuildProblem, BuildWarning, CompileError, Warning};
+
+use super::{
+    cfg::Cfg,
+    ir::{AsmOperand, Binding, BlockId, Function, Instruction, IrProgram, Place},
+};
+
+/// What is known about whether a local has been given a value at some
+/// point of the function.
+#[derive(Debug, Clone, PartialEq)]
+struct Assignment {
+    /// The lines of the assignments that may have happened.
+    assigned: Vec<usize>,
+    /// Whether a path gets here without an assignment, with the lines of
+    /// the jumps that path took where other paths had assigned the local.
+    unassigned: Option<Vec<usize>>,
+}
+
+fn merge(lines: &mut Vec<usize>, more: &Vec<usize>) {
+    for line in more {
+        if !lines.contains(line) {
+            lines.push(*line);
+        }
+    }
+    lines.sort();
+}
+
+struct Assignments<'a> {
+    function: &'a Function,
+    cfg: Cfg,
+    /// Which captures every function before this one assigns to or borrows
+    /// mutably, by function index.
+    closures: &'a Vec<Vec<bool>>,
+    /// The state of every slot at the end of every block, none for blocks
+    /// not reached yet.
+    exits: Vec<Option<Vec<Assignment>>>,
+    /// Which slots and captures are assigned again or borrowed mutably,
+    /// which is what `mut` allows.
+    mutated: Vec<bool>,
+    mutated_captures: Vec<bool>,
+}
+impl<'a> Assignments<'a> {
+    fn problem(&self, error: BuildError, line: usize) -> CompileError {
+        return CompileError::BuildProblem(BuildProblem::new(error, self.function.relative_path.clone(), line));
+    }
+
+    /// The state of a local no value has been stored to yet.
+    fn declared() -> Assignment {
+        return Assignment {
+            assigned: Vec::new(),
+            unassigned: Some(Vec::new()),
+        };
+    }
+
+    /// The state of every slot where the block starts, joining the paths into it.
+    fn entry(&self, block: BlockId) -> Vec<Assignment> {
+        let mut states = vec![Self::declared(); self.function.slots.len()];
+        if block.0 == 0 {
+            return states;
+        }
+        let incoming: Vec<(BlockId, &Vec<Assignment>)> = self.cfg.predecessors[block.0]
+            .iter()
+            .filter_map(|predecessor| self.exits[predecessor.0].as_ref().map(|exit| (*predecessor, exit)))
+            .collect();
+        for (slot, state) in states.iter_mut().enumerate() {
+            let mixed = incoming.iter().any(|(_, exit)| !exit[slot].assigned.is_empty());
+            let mut unassigned = None;
+            for (predecessor, exit) in &incoming {
+                merge(&mut state.assigned, &exit[slot].assigned);
+                let mut lines = match &exit[slot].unassigned {
+                    Some(lines) => lines.clone(),
+                    None => continue,
+                };
+                if mixed && lines.is_empty() {
+                    lines.push(self.function.blocks[predecessor.0].line);
+                }
+                let mut joined = unassigned.unwrap_or_else(Vec::new);
+                merge(&mut joined, &lines);
+                unassigned = Some(joined);
+            }
+            state.unassigned = unassigned;
+        }
+        return states;
+    }
+
+    /// Only locals declared without a value can be read before they have one.
+    fn read(&self, states: &Vec<Assignment>, place: &Place, line: usize) -> Result<(), CompileError> {
+        let slot = match place {
+            Place::Slot(slot) if self.function.slots[slot.0].binding == Binding::Deferred => *slot,
+            _ => return Ok(()),
+        };
+        let local = &self.function.slots[slot.0];
+        let paths = match (&states[slot.0].unassigned, states[slot.0].assigned.is_empty()) {
+            (None, _) => return Ok(()),
+            (Some(_), true) => Vec::new(),
+            (Some(paths), false) => paths.clone(),
+        };
+        return Err(self.problem(
+            BuildError::UsedUninitialized(
+                local.name.clone(),
+                self.function.relative_path.clone(),
+                local.line,
+                paths,
+            ),
+            line,
+        ));
+    }
+
+    fn write(&mut self, states: &mut Vec<Assignment>, place: &Place, line: usize) -> Result<(), CompileError> {
+        let slot = match place {
+            Place::Slot(slot) => *slot,
+            Place::Capture(index) => {
+                let capture = &self.function.captures[*index];
+                if !capture.mutable {
+                    return Err(self.problem(BuildError::AssignToImmutable(capture.name.clone()), line));
+                }
+                self.mutated_captures[*index] = true;
+                return Ok(());
+            }
+            Place::Static(_) => return Ok(()),
+        };
+        let local = &self.function.slots[slot.0];
+        match (states[slot.0].assigned.first(), local.binding) {
+            (_, Binding::Temporary) | (None, _) => {}
+            (Some(_), _) if local.mutable => self.mutated[slot.0] = true,
+            (Some(_), Binding::Parameter) => {
+                return Err(self.problem(BuildError::AssignToImmutableArgument(local.name.clone()), line))
+            }
+            (Some(first), _) => {
+                return Err(self.problem(
+                    BuildError::AssignTwice(local.name.clone(), self.function.relative_path.clone(), *first),
+                    line,
+                ))
+            }
+        }
+        states[slot.0] = Assignment {
+            assigned: vec![line],
+            unassigned: None,
+        };
+        return Ok(());
+    }
+
+    fn borrow_mutably(&mut self, place: &Place, line: usize) -> Result<(), CompileError> {
+        let (local, mutated) = match place {
+            Place::Slot(slot) => (&self.function.slots[slot.0], &mut self.mutated[slot.0]),
+            Place::Capture(index) => (&self.function.captures[*index], &mut self.mutated_captures[*index]),
+            Place::Static(_) => return Ok(()),
+        };
+        if local.binding == Binding::Temporary {
+            return Ok(());
+        }
+        if !local.mutable {
+            let name = local.name.clone();
+            return Err(self.problem(BuildError::BorrowImmutable(name), line));
+        }
+        *mutated = true;
+        return Ok(());
+    }
+
+    /// Runs the block from the state at its start, reporting the first read
+    /// of a local that may have no value and the first assignment or
+    /// mutable borrow `mut` is missing for.
+    fn block(&mut self, block: BlockId, states: &mut Vec<Assignment>) -> Result<(), CompileError> {
+        let function = self.function;
+        for node in &function.blocks[block.0].nodes {
+            let mut reads = Vec::new();
+            let mut writes = Vec::new();
+            let mut borrows = Vec::new();
+            match &node.instruction {
+                Instruction::Declare(Place::Slot(slot)) => states[slot.0] = Self::declared(),
+                Instruction::Store(place, _) => writes.push(*place),
+                Instruction::Address(_, true, place) => {
+                    reads.push(*place);
+                    borrows.push(*place);
+                }
+                Instruction::Asm(_, operands) => {
+                    for operand in operands {
+                        match operand {
+                            AsmOperand::Out(_, Some(place)) => writes.push(*place),
+                            AsmOperand::InOut(_, place) => {
+                                reads.push(*place);
+                                writes.push(*place);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                // What the closure assigns to or borrows mutably is checked
+                // in its body, against the same mutability.
+                Instruction::Closure(_, index, places) => {
+                    for (place, mutated) in places.iter().zip(&self.closures[*index]) {
+                        match (place, mutated) {
+                            (Place::Slot(slot), true) => self.mutated[slot.0] = true,
+                            (Place::Capture(index), true) => self.mutated_captures[*index] = true,
+                            _ => {}
+                        }
+                        reads.push(*place);
+                    }
+                }
+                instruction => reads.extend(instruction.places()),
+            }
+            for place in &reads {
+                match self.read(states, place, node.line) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            for place in &borrows {
+                match self.borrow_mutably(place, node.line) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            for place in &writes {
+                match self.write(states, place, node.line) {
+                    Ok(()) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    fn check(&mut self) -> Result<Vec<Warning>, CompileError> {
+        // Where every block leaves the slots, until going around the loops
+        // once more changes nothing. Errors are reported once it is known.
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in self.cfg.order.clone() {
+                let mut states = self.entry(block);
+                for node in &self.function.blocks[block.0].nodes {
+                    for place in node.instruction.places() {
+                        let slot = match place {
+                            Place::Slot(slot) => slot,
+                            _ => continue,
+                        };
+                        match &node.instruction {
+                            Instruction::Declare(_) => states[slot.0] = Self::declared(),
+                            Instruction::Store(_, _) | Instruction::Asm(_, _) => {
+                                states[slot.0] = Assignment {
+                                    assigned: vec![node.line],
+                                    unassigned: None,
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                if self.exits[block.0].as_ref() != Some(&states) {
+                    self.exits[block.0] = Some(states);
+                    changed = true;
+                }
+            }
+        }
+
+        for block in self.cfg.order.clone() {
+            let mut states = self.entry(block);
+            match self.block(block, &mut states) {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        let mut warnings = Vec::new();
+        for (local, mutated) in self.function.slots.iter().zip(&self.mutated) {
+            if local.mutable && !mutated && local.binding != Binding::Temporary {
+                warnings.push(Warning::new(
+                    BuildWarning::UnusedMut(local.name.clone()),
+                    self.function.relative_path.clone(),
+                    local.line,
+                ));
+            }
+        }
+        return Ok(warnings);
+    }
+}
+
+/// Checks that locals declared without a value, like `let x: i32;`, are
+/// assigned on every path before they are read and that only `mut` locals
+/// are assigned again or borrowed mutably, and warns about `mut` locals
+/// that are neither.
+pub fn check_assignments(program: &IrProgram) -> Result<Vec<Warning>, CompileError> {
+    let mut closures: Vec<Vec<bool>> = Vec::new();
+    let mut warnings = Vec::new();
+    for function in &program.functions {
+        let mut assignments = Assignments {
+            function,
+            cfg: Cfg::new(function),
+            closures: &closures,
+            exits: vec![None; function.blocks.len()],
+            mutated: vec![false; function.slots.len()],
+            mutated_captures: vec![false; function.captures.len()],
+        };
+        match assignments.check() {
+            Ok(mut found) => warnings.append(&mut found),
+            Err(error) => return Err(error),
+        }
+        let mutated = assignments.mutated_captures;
+        closures.push(mutated);
+    }
+    return Ok(warnings);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        builder::{compile_error, compile_source},
+        BuildError, BuildWarning,
+    };
+
+    const COUNTER: &str = "struct C(i32);\n\nimpl C {\n    fn bump(&mut self) {}\n}\n\n";
+
+    #[test]
+    fn reads_before_any_assignment() {
+        let error = compile_error("fn main() {\n    let x: i32;\n    let y = x + 1;\n}\n");
+        assert!(matches!(error, BuildError::UsedUninitialized(name, _, 2, paths) if name == "x" && paths.is_empty()));
+    }
+
+    #[test]
+    fn reads_on_some_paths_point_at_them() {
+        let error = compile_error("fn main() {\n    let x: i32;\n    loop {\n        let y = x;\n        x = 1;\n    }\n}\n");
+        assert!(matches!(error, BuildError::UsedUninitialized(name, _, 2, paths) if name == "x" && paths.len() > 0));
+    }
+
+    #[test]
+    fn deferred_initialization_happens_once() {
+        assert!(compile_source("fn main() {\n    let x;\n    x = 5;\n    let y: i64 = x;\n}\n").is_ok());
+        let error = compile_error("fn main() {\n    let x: i32;\n    x = 1;\n    x = 2;\n}\n");
+        assert!(matches!(error, BuildError::AssignTwice(name, _, 3) if name == "x"));
+    }
+
+    #[test]
+    fn paths_that_return_early_need_no_value() {
+        let source = "fn f(): Option<i32> {\n    let x: i32;\n    let v = Some(2);\n    x = v?;\n    return Some(x);\n}\n\nfn main() {\n    let c = f();\n}\n";
+        assert!(compile_source(source).is_ok());
+    }
+
+    #[test]
+    fn mutable_locals_are_assigned() {
+        let compiled = compile_source("fn main() {\n    let mut x = 1;\n    x = x + 1;\n}\n").unwrap();
+        assert!(compiled.warnings.is_empty());
+    }
+
+    #[test]
+    fn parameters_are_immutable_without_mut() {
+        let error = compile_error("fn f(x: i32): i32 {\n    x = 3;\n    return x;\n}\n\nfn main() {\n    f(1);\n}\n");
+        assert!(matches!(error, BuildError::AssignToImmutableArgument(name) if name == "x"));
+        assert!(compile_source("fn f(mut x: i32): i32 {\n    x = x + 3;\n    return x;\n}\n\nfn main() {\n    f(1);\n}\n").is_ok());
+    }
+
+    #[test]
+    fn mutable_receivers_need_mutable_locals() {
+        let error = compile_error(&format!("{}fn main() {{\n    let c = C(1);\n    c.bump();\n}}\n", COUNTER));
+        assert!(matches!(error, BuildError::BorrowImmutable(name) if name == "c"));
+        assert!(compile_source(&format!("{}fn main() {{\n    let mut c = C(1);\n    c.bump();\n}}\n", COUNTER)).is_ok());
+    }
+
+    #[test]
+    fn mutable_receivers_are_not_reached_through_shared_references() {
+        let error = compile_error(&format!("{}fn take(c: &C) {{\n    c.bump();\n}}\n\nfn main() {{}}\n", COUNTER));
+        assert!(matches!(error, BuildError::BorrowBehindReference(Some(place)) if place == "*c"));
+        let error = compile_error(&format!("{}fn main() {{\n    let c = C(1);\n    let r = &c;\n    r.bump();\n}}\n", COUNTER));
+        assert!(matches!(error, BuildError::BorrowBehindReference(Some(place)) if place == "*r"));
+        let source = format!(
+            "{}fn take(c: &mut C) {{\n    c.bump();\n}}\n\nfn main() {{\n    let mut c = C(1);\n    let r = &mut c;\n    r.bump();\n}}\n",
+            COUNTER
+        );
+        assert!(compile_source(&source).is_ok());
+    }
+
+    #[test]
+    fn closures_assign_only_mutable_captures() {
+        let error = compile_error("fn main() {\n    let k = 0;\n    let set = || {\n        k = 1;\n    };\n}\n");
+        assert!(matches!(error, BuildError::AssignToImmutable(name) if name == "k"));
+    }
+
+    #[test]
+    fn unneeded_mut_is_warned_about() {
+        let compiled = compile_source("fn main() {\n    let mut x = 1;\n    let y = x;\n}\n").unwrap();
+        assert!(compiled
+            .warnings
+            .iter()
+            .any(|warning| matches!(&warning.warning, BuildWarning::UnusedMut(name) if name == "x")));
+    }
+}
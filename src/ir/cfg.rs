@@ -0,0 +1,217 @@
+use super::ir::{BlockId, Function, Terminator};
+
+/// The control-flow graph of a function, with its dominator tree.
+#[derive(Debug)]
+pub struct Cfg {
+    pub successors: Vec<Vec<BlockId>>,
+    pub predecessors: Vec<Vec<BlockId>>,
+    /// The blocks reachable from the entry in reverse postorder, so every
+    /// block comes before the blocks it dominates.
+    pub order: Vec<BlockId>,
+    /// The immediate dominator of every reachable block but the entry.
+    pub dominators: Vec<Option<BlockId>>,
+}
+impl Cfg {
+    pub fn new(function: &Function) -> Self {
+        let successors: Vec<Vec<BlockId>> = function
+            .blocks
+            .iter()
+            .map(|block| block.terminator.successors())
+            .collect();
+        let mut predecessors = vec![Vec::new(); function.blocks.len()];
+        for (index, targets) in successors.iter().enumerate() {
+            for target in targets {
+                if !predecessors[target.0].contains(&BlockId(index)) {
+                    predecessors[target.0].push(BlockId(index));
+                }
+            }
+        }
+
+        // Postorder from the entry, without recursing as deep as the function is long.
+        let mut visited = vec![false; function.blocks.len()];
+        let mut postorder = Vec::new();
+        let mut stack = vec![(BlockId(0), 0)];
+        visited[0] = true;
+        loop {
+            let (block, next) = match stack.pop() {
+                Some(top) => top,
+                None => break,
+            };
+            match successors[block.0].get(next) {
+                Some(target) => {
+                    stack.push((block, next + 1));
+                    if !visited[target.0] {
+                        visited[target.0] = true;
+                        stack.push((*target, 0));
+                    }
+                }
+                None => postorder.push(block),
+            }
+        }
+        let order: Vec<BlockId> = postorder.into_iter().rev().collect();
+
+        let mut cfg = Self {
+            successors,
+            predecessors,
+            order,
+            dominators: vec![None; function.blocks.len()],
+        };
+        cfg.dominators = cfg.immediate_dominators();
+        return cfg;
+    }
+
+    /// "A Simple, Fast Dominance Algorithm" by Cooper, Harvey and Kennedy.
+    fn immediate_dominators(&self) -> Vec<Option<BlockId>> {
+        let mut position = vec![usize::MAX; self.successors.len()];
+        for (index, block) in self.order.iter().enumerate() {
+            position[block.0] = index;
+        }
+        let mut dominators: Vec<Option<BlockId>> = vec![None; self.successors.len()];
+        dominators[0] = Some(BlockId(0));
+
+        let intersect = |dominators: &Vec<Option<BlockId>>, a: BlockId, b: BlockId| {
+            let (mut a, mut b) = (a, b);
+            while a != b {
+                while position[a.0] > position[b.0] {
+                    a = dominators[a.0].unwrap();
+                }
+                while position[b.0] > position[a.0] {
+                    b = dominators[b.0].unwrap();
+                }
+            }
+            return a;
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in self.order.iter().skip(1) {
+                let mut dominator = None;
+                for predecessor in &self.predecessors[block.0] {
+                    if dominators[predecessor.0].is_none() {
+                        continue;
+                    }
+                    dominator = match dominator {
+                        None => Some(*predecessor),
+                        Some(dominator) => Some(intersect(&dominators, *predecessor, dominator)),
+                    };
+                }
+                if dominator != dominators[block.0] {
+                    dominators[block.0] = dominator;
+                    changed = true;
+                }
+            }
+        }
+        // The entry has no dominator, it only pointed to itself to end the walks up.
+        dominators[0] = None;
+        return dominators;
+    }
+
+    pub fn is_reachable(&self, block: BlockId) -> bool {
+        return block.0 == 0 || self.dominators[block.0].is_some();
+    }
+
+    /// Whether every path from the entry to `b` goes through `a`.
+    pub fn dominates(&self, a: BlockId, b: BlockId) -> bool {
+        let mut block = b;
+        loop {
+            if block == a {
+                return true;
+            }
+            block = match self.dominators[block.0] {
+                Some(dominator) => dominator,
+                None => return false,
+            };
+        }
+    }
+
+    /// The blocks each block immediately dominates, the edges of the dominator tree.
+    pub fn children(&self) -> Vec<Vec<BlockId>> {
+        let mut children = vec![Vec::new(); self.successors.len()];
+        for block in &self.order {
+            match self.dominators[block.0] {
+                Some(dominator) => children[dominator.0].push(*block),
+                None => {}
+            }
+        }
+        return children;
+    }
+
+    /// The blocks where the dominance of each block ends, where a value
+    /// defined in it meets values from other paths.
+    pub fn frontiers(&self) -> Vec<Vec<BlockId>> {
+        let mut frontiers = vec![Vec::new(); self.successors.len()];
+        for block in &self.order {
+            let predecessors: Vec<&BlockId> = self
+                .predecessors[block.0]
+                .iter()
+                .filter(|predecessor| self.is_reachable(**predecessor))
+                .collect();
+            if predecessors.len() < 2 {
+                continue;
+            }
+            for predecessor in predecessors {
+                let mut runner = *predecessor;
+                while Some(runner) != self.dominators[block.0] {
+                    if !frontiers[runner.0].contains(block) {
+                        frontiers[runner.0].push(*block);
+                    }
+                    runner = match self.dominators[runner.0] {
+                        Some(dominator) => dominator,
+                        None => break,
+                    };
+                }
+            }
+        }
+        return frontiers;
+    }
+}
+
+/// Drops the blocks no path from the entry reaches, keeping the others in order.
+pub fn remove_unreachable(function: &mut Function) {
+    let cfg = Cfg::new(function);
+    let mut renamed = vec![None; function.blocks.len()];
+    let mut kept = 0;
+    for index in 0..function.blocks.len() {
+        if cfg.is_reachable(BlockId(index)) {
+            renamed[index] = Some(BlockId(kept));
+            kept += 1;
+        }
+    }
+    let blocks = std::mem::take(&mut function.blocks);
+    for (index, mut block) in blocks.into_iter().enumerate() {
+        if renamed[index].is_none() {
+            continue;
+        }
+        block.terminator = match block.terminator {
+            Terminator::Jump(target) => Terminator::Jump(renamed[target.0].unwrap()),
+            Terminator::Branch(condition, then, otherwise) => {
+                Terminator::Branch(condition, renamed[then.0].unwrap(), renamed[otherwise.0].unwrap())
+            }
+            terminator => terminator,
+        };
+        function.blocks.push(block);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cfg;
+    use crate::{builder::compile_source, ir::BlockId};
+
+    #[test]
+    fn loops_are_dominated_by_their_header() {
+        let source = "fn step(i: i32): Option<i32> {\n    return Some(i);\n}\n\nfn count(): Option<i32> {\n    let mut i = 0;\n    loop {\n        i = step(i + 1)?;\n    }\n}\n\nfn main() {\n    let c = count();\n}\n";
+        let compiled = compile_source(source).unwrap();
+        let function = compiled.ir.functions.iter().find(|function| function.name == "count").unwrap();
+        let cfg = Cfg::new(function);
+        assert_eq!(cfg.dominators, vec![None, Some(BlockId(0)), Some(BlockId(1)), Some(BlockId(1))]);
+        assert!(cfg.dominates(BlockId(1), BlockId(2)));
+        assert!(!cfg.dominates(BlockId(2), BlockId(3)));
+        let mut children = cfg.children()[1].clone();
+        children.sort_by_key(|block| block.0);
+        assert_eq!(children, vec![BlockId(2), BlockId(3)]);
+        // The back edge from the body ends its dominance at the header.
+        assert_eq!(cfg.frontiers()[2], vec![BlockId(1)]);
+    }
+}
@@ -0,0 +1,87 @@
+use super::{
+    cfg::Cfg,
+    ir::{BlockId, Function, Terminator},
+};
+
+/// Escapes text for a Graphviz label, ending every line left aligned.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for ch in text.chars() {
+        match ch {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            '\n' => escaped.push_str("\\l"),
+            ch => escaped.push(ch),
+        }
+    }
+    return escaped;
+}
+
+/// The control-flow graph of the function in the Graphviz dot language, one
+/// box per block with its instructions. The dashed edges are the dominator tree.
+pub fn cfg_dot(function: &Function) -> String {
+    let cfg = Cfg::new(function);
+    let mut dot = format!("digraph \"{}\" {{\n", escape(&function.name));
+    dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+    dot.push_str(&format!("    label=\"{}\";\n", escape(&format!("fn {}", function.name))));
+
+    for (index, block) in function.blocks.iter().enumerate() {
+        let id = BlockId(index);
+        let mut label = format!("{}:\n", id);
+        for node in &block.nodes {
+            label.push_str(&function.instruction(&node.instruction));
+            label.push('\n');
+        }
+        label.push_str(&block.terminator.to_string());
+        label.push('\n');
+        let style = match cfg.is_reachable(id) {
+            true => "",
+            false => ", style=dashed, color=gray",
+        };
+        dot.push_str(&format!("    {} [label=\"{}\"{}];\n", id, escape(&label), style));
+    }
+
+    for (index, block) in function.blocks.iter().enumerate() {
+        let id = BlockId(index);
+        match &block.terminator {
+            Terminator::Branch(_, then, otherwise) => {
+                dot.push_str(&format!("    {} -> {} [label=\"true\"];\n", id, then));
+                dot.push_str(&format!("    {} -> {} [label=\"false\"];\n", id, otherwise));
+            }
+            terminator => {
+                for target in terminator.successors() {
+                    dot.push_str(&format!("    {} -> {};\n", id, target));
+                }
+            }
+        }
+        match cfg.dominators[index] {
+            Some(dominator) => dot.push_str(&format!(
+                "    {} -> {} [style=dashed, color=gray, constraint=false];\n",
+                dominator, id
+            )),
+            None => {}
+        }
+    }
+    dot.push_str("}\n");
+    return dot;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cfg_dot;
+    use crate::builder::compile_source;
+
+    #[test]
+    fn branches_and_dominators_are_drawn() {
+        let source = "fn count(): Option<i32> {\n    let mut i = 0;\n    loop {\n        i = Some(i + 1)?;\n    }\n}\n\nfn main() {\n    let c = count();\n}\n";
+        let compiled = compile_source(source).unwrap();
+        let dot = cfg_dot(&compiled.ir.functions[0]);
+        assert!(dot.starts_with("digraph \"count\" {"));
+        assert!(dot.contains("bb1 -> bb2 [label=\"true\"];"));
+        assert!(dot.contains("bb1 -> bb3 [label=\"false\"];"));
+        assert!(dot.contains("bb2 -> bb1;"));
+        assert!(dot.contains("bb1 -> bb3 [style=dashed, color=gray, constraint=false];"));
+    }
+}
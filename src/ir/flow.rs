@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use crate::{parser::Value, BuildError, BuildProblem, BuildWarning, CompileError, Warning};
+
+use super::{
+    cfg::Cfg,
+    ir::{BlockId, Function, Instruction, IrProgram, Place, Register, Terminator},
+};
+
+/// The blocks of the loop closed by the edge from `latch` back to `header`:
+/// the header and every block that reaches the latch without going through it.
+fn natural_loop(cfg: &Cfg, header: BlockId, latch: BlockId) -> Vec<BlockId> {
+    let mut body = vec![header];
+    let mut stack = vec![latch];
+    loop {
+        let block = match stack.pop() {
+            Some(block) => block,
+            None => break,
+        };
+        if body.contains(&block) {
+            continue;
+        }
+        body.push(block);
+        for predecessor in &cfg.predecessors[block.0] {
+            if cfg.is_reachable(*predecessor) {
+                stack.push(*predecessor);
+            }
+        }
+    }
+    return body;
+}
+
+struct Flow<'a> {
+    function: &'a Function,
+    cfg: Cfg,
+    /// The instruction defining each register.
+    definitions: HashMap<Register, &'a Instruction>,
+    /// The only value stored to each slot stored to once.
+    stored: HashMap<usize, Option<Register>>,
+    warnings: Vec<Warning>,
+}
+impl<'a> Flow<'a> {
+    fn new(function: &'a Function) -> Self {
+        let mut definitions = HashMap::new();
+        let mut stored: HashMap<usize, Option<Register>> = HashMap::new();
+        for block in &function.blocks {
+            for node in &block.nodes {
+                match node.instruction.definition() {
+                    Some(register) => {
+                        definitions.insert(register, &node.instruction);
+                    }
+                    None => {}
+                }
+                match &node.instruction {
+                    Instruction::Store(Place::Slot(slot), register) => match stored.get(&slot.0) {
+                        Some(_) => {
+                            stored.insert(slot.0, None);
+                        }
+                        None => {
+                            stored.insert(slot.0, Some(*register));
+                        }
+                    },
+                    _ => {}
+                }
+            }
+        }
+        return Self {
+            function,
+            cfg: Cfg::new(function),
+            definitions,
+            stored,
+            warnings: Vec::new(),
+        };
+    }
+
+    fn warn(&mut self, warning: BuildWarning, line: usize) {
+        self.warnings
+            .push(Warning::new(warning, self.function.relative_path.clone(), line));
+    }
+
+    /// The instruction that computes the value of a register, looking
+    /// through locals that are only ever given one value.
+    fn defining(&self, register: Register) -> Option<&'a Instruction> {
+        let mut register = register;
+        loop {
+            match self.definitions.get(&register) {
+                Some(Instruction::Load(_, Place::Slot(slot))) => match self.stored.get(&slot.0) {
+                    Some(Some(value)) => register = *value,
+                    _ => return None,
+                },
+                Some(instruction) => return Some(*instruction),
+                None => return None,
+            }
+        }
+    }
+
+    /// Why a branch always goes one way, if it is known without running it.
+    fn constant_condition(&self, condition: Register) -> Option<String> {
+        return match self.defining(condition) {
+            Some(Instruction::Value(_, Value::Boolean(value))) => {
+                Some(format!("its condition is always `{}`", value))
+            }
+            Some(Instruction::IsVariant(_, value, _)) => match self.defining(*value) {
+                Some(Instruction::Construct(_, variant, _)) => {
+                    Some(format!("the value is always `{}`", variant.name))
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+    }
+
+    fn check(&mut self) -> Result<(), CompileError> {
+        let function = self.function;
+
+        // Falling off the end only returns in functions without a value to return.
+        for (index, block) in function.blocks.iter().enumerate() {
+            let return_type = match (&block.terminator, &function.return_type) {
+                (Terminator::Unreachable, Some(t)) if self.cfg.is_reachable(BlockId(index)) => t,
+                _ => continue,
+            };
+            return Err(CompileError::BuildProblem(BuildProblem::new(
+                BuildError::MissingReturn(function.name.clone(), return_type.to_string()),
+                function.relative_path.clone(),
+                function.line,
+            )));
+        }
+
+        // Blocks are made in the order of the code they hold, so the first
+        // dead block not entered from other dead code starts a region of its
+        // own, which is warned about at its first statement.
+        let mut seen = vec![false; function.blocks.len()];
+        for index in 0..function.blocks.len() {
+            if seen[index] || self.cfg.is_reachable(BlockId(index)) {
+                continue;
+            }
+            let mut region = Vec::new();
+            let mut stack = vec![BlockId(index)];
+            loop {
+                let block = match stack.pop() {
+                    Some(block) => block,
+                    None => break,
+                };
+                if seen[block.0] || self.cfg.is_reachable(block) {
+                    continue;
+                }
+                seen[block.0] = true;
+                region.push(block);
+                stack.extend(self.cfg.successors[block.0].iter().copied());
+            }
+            region.sort_by_key(|block| block.0);
+            let line = region.iter().find_map(|block| {
+                let block = &function.blocks[block.0];
+                return match (block.nodes.first(), &block.terminator) {
+                    (Some(node), _) => Some(node.line),
+                    (None, Terminator::Return(Some(_))) => Some(block.line),
+                    _ => None,
+                };
+            });
+            match line {
+                Some(line) => self.warn(BuildWarning::UnreachableStatement, line),
+                None => {}
+            }
+        }
+
+        // A loop is an edge back to a block that dominates where it comes from.
+        let mut headers: Vec<BlockId> = Vec::new();
+        for latch in self.cfg.order.clone() {
+            for header in self.cfg.successors[latch.0].clone() {
+                if !self.cfg.dominates(header, latch) || headers.contains(&header) {
+                    continue;
+                }
+                headers.push(header);
+                let body = natural_loop(&self.cfg, header, latch);
+                let exits = body.iter().any(|block| {
+                    let terminator = &function.blocks[block.0].terminator;
+                    match terminator {
+                        Terminator::Return(_) => true,
+                        _ => terminator.successors().iter().any(|target| !body.contains(target)),
+                    }
+                });
+                if !exits {
+                    self.warn(BuildWarning::InfiniteLoop, function.blocks[latch.0].line);
+                }
+            }
+        }
+
+        for block in &function.blocks {
+            let condition = match &block.terminator {
+                Terminator::Branch(condition, _, _) => *condition,
+                _ => continue,
+            };
+            match self.constant_condition(condition) {
+                Some(reason) => self.warn(BuildWarning::UnreachableBranch(reason), block.line),
+                None => {}
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Checks that every path through a function that returns a value returns
+/// one, and warns about code that never runs and loops that never end.
+pub fn check_flow(program: &IrProgram) -> Result<Vec<Warning>, CompileError> {
+    let mut warnings = Vec::new();
+    for function in &program.functions {
+        let mut flow = Flow::new(function);
+        match flow.check() {
+            Ok(()) => warnings.append(&mut flow.warnings),
+            Err(error) => return Err(error),
+        }
+    }
+    return Ok(warnings);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{builder::compile_source, BuildWarning};
+
+    /// The lines warned about as unreachable.
+    fn unreachable(source: &str) -> Vec<usize> {
+        let compiled = match compile_source(source) {
+            Ok(compiled) => compiled,
+            Err(error) => panic!("{:?}", error),
+        };
+        return compiled
+            .warnings
+            .iter()
+            .filter(|warning| matches!(warning.warning, BuildWarning::UnreachableStatement))
+            .map(|warning| warning.line)
+            .collect();
+    }
+
+    #[test]
+    fn every_unreachable_region_is_warned_about() {
+        let source = "fn f(): i32 {\n    loop {\n        return 1;\n        let x = 2;\n    }\n    let y = 3;\n    return y;\n}\n\nfn main() {\n    let a = f();\n}\n";
+        assert_eq!(unreachable(source), vec![4, 6]);
+    }
+
+    #[test]
+    fn one_region_is_warned_about_once() {
+        let source = "fn main() {\n    return;\n    let a = 1;\n    loop {\n        let b = 2;\n    }\n}\n";
+        assert_eq!(unreachable(source), vec![3]);
+    }
+}
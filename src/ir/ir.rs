@@ -0,0 +1,485 @@
+use std::{collections::HashMap, fmt::Display, path::PathBuf};
+
+use crate::{
+    analyzer::is_unknown,
+    parser::{DefId, Operator, Type, Value},
+};
+
+/// A virtual register, assigned exactly once. Its type is kept by the function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Register(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockId(pub usize);
+
+/// A stack slot holding a local or a parameter, which loads and stores go through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Slot(pub usize);
+
+/// The typed intermediate representation every backend consumes, lowered from
+/// the bodies the type checker leaves behind.
+#[derive(Debug)]
+pub struct IrProgram {
+    /// Functions and methods in the order they were checked, each after the
+    /// closures inside it.
+    pub functions: Vec<Function>,
+    /// The type of every static.
+    pub statics: HashMap<DefId, Type>,
+}
+
+#[derive(Debug)]
+pub struct Function {
+    /// The name of a free function, `Target::method` for a method and
+    /// `outer::{closure#0}` for a closure.
+    pub name: String,
+    /// `None` for closures.
+    pub definition: Option<DefId>,
+    pub relative_path: PathBuf,
+    pub line: usize,
+    pub parameters: Vec<Register>,
+    pub return_type: Option<Type>,
+    /// The type of every register, at its index.
+    pub registers: Vec<Type>,
+    pub slots: Vec<Local>,
+    /// The locals of the enclosing function a closure uses.
+    pub captures: Vec<Local>,
+    /// Whether a closure holds copies of its captures instead of their
+    /// addresses, as `move` closures do.
+    pub is_move: bool,
+    /// The entry block comes first.
+    pub blocks: Vec<Block>,
+}
+impl Function {
+    pub fn register_type(&self, register: Register) -> &Type {
+        return &self.registers[register.0];
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Local {
+    pub name: String,
+    pub t: Type,
+    pub mutable: bool,
+    pub definition: Option<DefId>,
+    pub binding: Binding,
+    /// Where the local is declared.
+    pub line: usize,
+}
+
+/// How a local gets its first value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+    /// A value lowering keeps in memory, like a receiver that is borrowed.
+    Temporary,
+    Parameter,
+    /// A `let` with a value.
+    Let,
+    /// A `let` without a value, like `let x: i32;`, assigned later.
+    Deferred,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub nodes: Vec<IRNode>,
+    pub terminator: Terminator,
+    /// The line of the terminator.
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct IRNode {
+    pub line: usize,
+    pub instruction: Instruction,
+}
+
+/// A function, constant, static, struct or variant, with its name for the dump.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub definition: DefId,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Place {
+    Slot(Slot),
+    Static(DefId),
+    /// A local of the enclosing function, inside a closure.
+    Capture(usize),
+}
+
+#[derive(Debug, Clone)]
+pub enum Instruction {
+    Value(Register, Value),
+    /// A function named as a value, or the value of a constant.
+    Item(Register, Item),
+    /// Where a `let` runs, after which its local holds no value until one
+    /// is stored, even when the `let` is in a loop.
+    Declare(Place),
+    Load(Register, Place),
+    Store(Place, Register),
+    /// `&place` or `&mut place`.
+    Address(Register, bool, Place),
+    /// Reads through a reference or a pointer.
+    Deref(Register, Register),
+    /// The address of a field of the struct a reference points to.
+    FieldAddress(Register, Register, String),
+    Field(Register, Register, String),
+    Binary(Register, Operator, Register, Register),
+    /// Calls without a value to return have no register.
+    Call(Option<Register>, Callee, Vec<Register>),
+    /// A tuple struct or an enum variant built from its fields.
+    Construct(Register, Item, Vec<Register>),
+    Cast(Register, Register),
+    /// Turns a value into the type it is used as, like `&T` into `&dyn Trait`.
+    Coerce(Register, Register),
+    /// Whether an enum value is the given variant.
+    IsVariant(Register, Register, Item),
+    /// The field at the index of a value known to be the given variant.
+    Payload(Register, Register, Item, usize),
+    /// A closure over places of the current function, lowered into the
+    /// function at the index.
+    Closure(Register, usize, Vec<Place>),
+    Asm(Vec<String>, Vec<AsmOperand>),
+    /// The value of a local from whichever predecessor control came from.
+    /// Phis only start blocks.
+    Phi(Register, Vec<(BlockId, Register)>),
+    /// The value of a local read before anything was stored to it.
+    Undefined(Register),
+}
+impl Instruction {
+    /// The register the instruction defines.
+    pub fn definition(&self) -> Option<Register> {
+        return match self {
+            Instruction::Value(dest, _)
+            | Instruction::Item(dest, _)
+            | Instruction::Load(dest, _)
+            | Instruction::Address(dest, _, _)
+            | Instruction::Deref(dest, _)
+            | Instruction::FieldAddress(dest, _, _)
+            | Instruction::Field(dest, _, _)
+            | Instruction::Binary(dest, _, _, _)
+            | Instruction::Construct(dest, _, _)
+            | Instruction::Cast(dest, _)
+            | Instruction::Coerce(dest, _)
+            | Instruction::IsVariant(dest, _, _)
+            | Instruction::Payload(dest, _, _, _)
+            | Instruction::Closure(dest, _, _)
+            | Instruction::Phi(dest, _)
+            | Instruction::Undefined(dest) => Some(*dest),
+            Instruction::Call(dest, _, _) => *dest,
+            Instruction::Declare(_) | Instruction::Store(_, _) | Instruction::Asm(_, _) => None,
+        };
+    }
+
+    /// The registers the instruction reads, which can be renamed through them.
+    pub fn uses_mut(&mut self) -> Vec<&mut Register> {
+        return match self {
+            Instruction::Value(_, _)
+            | Instruction::Item(_, _)
+            | Instruction::Declare(_)
+            | Instruction::Load(_, _)
+            | Instruction::Address(_, _, _)
+            | Instruction::Closure(_, _, _)
+            | Instruction::Undefined(_) => Vec::new(),
+            Instruction::Store(_, register)
+            | Instruction::Deref(_, register)
+            | Instruction::FieldAddress(_, register, _)
+            | Instruction::Field(_, register, _)
+            | Instruction::Cast(_, register)
+            | Instruction::Coerce(_, register)
+            | Instruction::IsVariant(_, register, _)
+            | Instruction::Payload(_, register, _, _) => vec![register],
+            Instruction::Binary(_, _, a, b) => vec![a, b],
+            Instruction::Call(_, callee, arguments) => {
+                let mut registers = match callee {
+                    Callee::Register(register) => vec![register],
+                    _ => Vec::new(),
+                };
+                registers.extend(arguments.iter_mut());
+                registers
+            }
+            Instruction::Construct(_, _, fields) => fields.iter_mut().collect(),
+            Instruction::Asm(_, operands) => operands
+                .iter_mut()
+                .filter_map(|operand| match operand {
+                    AsmOperand::In(_, register) => Some(register),
+                    _ => None,
+                })
+                .collect(),
+            Instruction::Phi(_, incoming) => incoming.iter_mut().map(|(_, register)| register).collect(),
+        };
+    }
+
+    pub fn uses(&self) -> Vec<Register> {
+        return self.clone().uses_mut().into_iter().map(|register| *register).collect();
+    }
+
+    /// The places the instruction reads or writes, which can be renamed through them.
+    pub fn places_mut(&mut self) -> Vec<&mut Place> {
+        return match self {
+            Instruction::Declare(place)
+            | Instruction::Load(_, place)
+            | Instruction::Store(place, _)
+            | Instruction::Address(_, _, place) => vec![place],
+            Instruction::Closure(_, _, places) => places.iter_mut().collect(),
+            Instruction::Asm(_, operands) => operands
+                .iter_mut()
+                .filter_map(|operand| match operand {
+                    AsmOperand::Out(_, Some(place)) | AsmOperand::InOut(_, place) => Some(place),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+    }
+
+    pub fn places(&self) -> Vec<Place> {
+        return self.clone().places_mut().into_iter().map(|place| *place).collect();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Callee {
+    Function(Item),
+    /// A method of the trait object passed as the first argument.
+    Virtual(Item),
+    /// A closure or function pointer.
+    Register(Register),
+    /// `print`, `println`, `format` and the methods of `&str` and `Chars`.
+    Builtin(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum AsmOperand {
+    In(String, Register),
+    Out(String, Option<Place>),
+    InOut(String, Place),
+    Clobber(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum Terminator {
+    Jump(BlockId),
+    /// Goes to the first block if the condition holds, else to the second.
+    Branch(Register, BlockId, BlockId),
+    Return(Option<Register>),
+    /// Ends blocks control never leaves, like the rest of a value-returning
+    /// function nothing returns from.
+    Unreachable,
+}
+impl Terminator {
+    /// The register the terminator reads.
+    pub fn use_mut(&mut self) -> Option<&mut Register> {
+        return match self {
+            Terminator::Branch(condition, _, _) => Some(condition),
+            Terminator::Return(register) => register.as_mut(),
+            Terminator::Jump(_) | Terminator::Unreachable => None,
+        };
+    }
+
+    pub fn successors(&self) -> Vec<BlockId> {
+        return match self {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch(_, then, otherwise) => vec![*then, *otherwise],
+            Terminator::Return(_) | Terminator::Unreachable => Vec::new(),
+        };
+    }
+}
+
+/// Whether values of one type can be used as the other, `_` standing for any type.
+pub fn same_type(a: &Type, b: &Type) -> bool {
+    let all_same = |a: &Vec<Type>, b: &Vec<Type>| a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_type(a, b));
+    return match (a, b) {
+        (a, b) if is_unknown(a) || is_unknown(b) => true,
+        (Type::Tuple(a), Type::Tuple(b)) => all_same(a, b),
+        (Type::Generic(x, a), Type::Generic(y, b)) => x == y && all_same(a, b),
+        (Type::Reference(x, a), Type::Reference(y, b)) | (Type::Pointer(x, a), Type::Pointer(y, b)) => {
+            x == y && same_type(a, b)
+        }
+        (Type::Function(a, x), Type::Function(b, y)) => {
+            all_same(a, b)
+                && match (x, y) {
+                    (Some(x), Some(y)) => same_type(x, y),
+                    (None, None) => true,
+                    _ => false,
+                }
+        }
+        (a, b) => a == b,
+    };
+}
+
+fn value(value: &Value) -> String {
+    return match value {
+        Value::Integer(integer) => integer.to_string(),
+        Value::UInteger(integer) => integer.to_string(),
+        Value::String(string) => format!("{:?}", string),
+        Value::Boolean(boolean) => boolean.to_string(),
+        Value::Char(char) => format!("{:?}", char),
+    };
+}
+
+fn registers(registers: &Vec<Register>) -> String {
+    let registers: Vec<String> = registers.iter().map(|register| register.to_string()).collect();
+    return registers.join(", ");
+}
+
+impl Display for Register {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+impl Display for BlockId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+impl Display for Place {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Place::Slot(slot) => write!(f, "${}", slot.0),
+            Place::Static(id) => write!(f, "static#{}", id.0),
+            Place::Capture(index) => write!(f, "capture.{}", index),
+        }
+    }
+}
+impl Display for Callee {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Callee::Function(item) => write!(f, "{}", item.name),
+            Callee::Virtual(item) => write!(f, "virtual {}", item.name),
+            Callee::Register(register) => write!(f, "{}", register),
+            Callee::Builtin(name) => write!(f, "builtin {}", name),
+        }
+    }
+}
+impl Display for Terminator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Terminator::Jump(target) => write!(f, "jump {}", target),
+            Terminator::Branch(condition, then, otherwise) => {
+                write!(f, "branch {}, {}, {}", condition, then, otherwise)
+            }
+            Terminator::Return(Some(register)) => write!(f, "return {}", register),
+            Terminator::Return(None) => write!(f, "return"),
+            Terminator::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+impl Function {
+    pub fn instruction(&self, instruction: &Instruction) -> String {
+        let defined = |register: &Register| format!("{}: {} = ", register, self.register_type(*register));
+        return match instruction {
+            Instruction::Value(dest, v) => format!("{}const {}", defined(dest), value(v)),
+            Instruction::Item(dest, item) => format!("{}item {}", defined(dest), item.name),
+            Instruction::Load(dest, place) => format!("{}load {}", defined(dest), place),
+            Instruction::Declare(place) => format!("declare {}", place),
+            Instruction::Store(place, register) => format!("store {}, {}", place, register),
+            Instruction::Address(dest, mutable, place) => match mutable {
+                true => format!("{}&mut {}", defined(dest), place),
+                false => format!("{}&{}", defined(dest), place),
+            },
+            Instruction::Deref(dest, register) => format!("{}deref {}", defined(dest), register),
+            Instruction::FieldAddress(dest, register, field) => {
+                format!("{}&{}.{}", defined(dest), register, field)
+            }
+            Instruction::Field(dest, register, field) => format!("{}{}.{}", defined(dest), register, field),
+            Instruction::Binary(dest, operator, a, b) => {
+                format!("{}{} {} {}", defined(dest), a, operator.symbol(), b)
+            }
+            Instruction::Call(Some(dest), callee, arguments) => {
+                format!("{}call {}({})", defined(dest), callee, registers(arguments))
+            }
+            Instruction::Call(None, callee, arguments) => format!("call {}({})", callee, registers(arguments)),
+            Instruction::Construct(dest, item, fields) => {
+                format!("{}{}({})", defined(dest), item.name, registers(fields))
+            }
+            Instruction::Cast(dest, register) => format!("{}cast {}", defined(dest), register),
+            Instruction::Coerce(dest, register) => format!("{}coerce {}", defined(dest), register),
+            Instruction::IsVariant(dest, register, item) => {
+                format!("{}is {} {}", defined(dest), register, item.name)
+            }
+            Instruction::Payload(dest, register, item, index) => {
+                format!("{}({} as {}).{}", defined(dest), register, item.name, index)
+            }
+            Instruction::Closure(dest, function, captures) => {
+                let captures: Vec<String> = captures.iter().map(|place| place.to_string()).collect();
+                format!("{}closure fn#{} [{}]", defined(dest), function, captures.join(", "))
+            }
+            Instruction::Phi(dest, incoming) => {
+                let incoming: Vec<String> = incoming
+                    .iter()
+                    .map(|(block, register)| format!("{}: {}", block, register))
+                    .collect();
+                format!("{}phi [{}]", defined(dest), incoming.join(", "))
+            }
+            Instruction::Undefined(dest) => format!("{}undefined", defined(dest)),
+            Instruction::Asm(template, operands) => {
+                let mut line = format!("asm {:?}", template.join("; "));
+                for operand in operands {
+                    let operand = match operand {
+                        AsmOperand::In(register, value) => format!("in({}) {}", register, value),
+                        AsmOperand::Out(register, Some(place)) => format!("out({}) {}", register, place),
+                        AsmOperand::Out(register, None) => format!("out({}) _", register),
+                        AsmOperand::InOut(register, place) => format!("inout({}) {}", register, place),
+                        AsmOperand::Clobber(register) => format!("clobber({})", register),
+                    };
+                    line.push_str(", ");
+                    line.push_str(&operand);
+                }
+                line
+            }
+        };
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parameters: Vec<String> = self
+            .parameters
+            .iter()
+            .map(|register| format!("{}: {}", register, self.register_type(*register)))
+            .collect();
+        let mut text = match self.is_move {
+            true => format!("move fn {}({})", self.name, parameters.join(", ")),
+            false => format!("fn {}({})", self.name, parameters.join(", ")),
+        };
+        match &self.return_type {
+            Some(t) => text.push_str(&format!(": {}", t)),
+            None => {}
+        }
+        text.push_str(" {\n");
+        for (index, capture) in self.captures.iter().enumerate() {
+            text.push_str(&format!("    capture.{}: {} // {}\n", index, capture.t, capture.name));
+        }
+        for (index, slot) in self.slots.iter().enumerate() {
+            let mutable = match slot.mutable {
+                true => "mut ",
+                false => "",
+            };
+            text.push_str(&format!("    let {}${}: {} // {}\n", mutable, index, slot.t, slot.name));
+        }
+        for (index, block) in self.blocks.iter().enumerate() {
+            text.push_str(&format!("  {}:\n", BlockId(index)));
+            for node in &block.nodes {
+                let instruction = self.instruction(&node.instruction);
+                text.push_str(&format!("    {} // line {}\n", instruction, node.line));
+            }
+            text.push_str(&format!("    {}\n", block.terminator));
+        }
+        text.push_str("}\n");
+        write!(f, "{}", text)
+    }
+}
+
+impl Display for IrProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let functions: Vec<String> = self
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(index, function)| format!("// fn#{}\n{}", index, function))
+            .collect();
+        write!(f, "{}", functions.join("\n"))
+    }
+}
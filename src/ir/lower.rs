@@ -0,0 +1,781 @@
+use std::collections::HashMap;
+
+use crate::{
+    analyzer::{
+        unknown, Analysis, Callee as TypedCallee, DefKind, ExpressionKind, Statement, Symbols,
+        TypedAsmOperand, TypedExpression, TypedFunction, TypedNode,
+    },
+    builder::ModulePath,
+    parser::{ASTNode, DefId, Node, Program, Type},
+};
+
+use super::ir::{
+    same_type, AsmOperand, Binding, Block, BlockId, Callee, Function, IRNode, Instruction, IrProgram, Item, Local, Place,
+    Register, Slot, Terminator,
+};
+
+fn unit() -> Type {
+    return Type::Tuple(Vec::new());
+}
+
+/// What a reference or pointer of the type points to.
+fn pointee(t: &Type) -> Option<&Type> {
+    return match t {
+        Type::Reference(_, inner) | Type::Pointer(_, inner) => Some(inner),
+        _ => None,
+    };
+}
+
+/// Every local of an enclosing function the body names, in the order they
+/// are first named.
+fn named_locals(nodes: &Vec<TypedNode>, found: &mut Vec<DefId>) {
+    let mut expressions = Vec::new();
+    for node in nodes {
+        match &node.statement {
+            Statement::DefineVariable { expression, .. } => expressions.extend(expression.iter()),
+            Statement::SetVariable(id, expression) => {
+                found.push(*id);
+                expressions.push(expression);
+            }
+            Statement::Expression(expression) => expressions.push(expression),
+            Statement::Return(expression) => expressions.extend(expression.iter()),
+            Statement::Scope(body) | Statement::Loop(body) => named_locals(body, found),
+            Statement::InlineAsm { operands, .. } => {
+                for operand in operands {
+                    match operand {
+                        TypedAsmOperand::In(_, expression) => expressions.push(expression),
+                        TypedAsmOperand::Out(_, Some(id)) | TypedAsmOperand::InOut(_, id) => found.push(*id),
+                        TypedAsmOperand::Out(_, None) | TypedAsmOperand::Clobber(_) => {}
+                    }
+                }
+            }
+        }
+    }
+    for expression in expressions {
+        expression_locals(expression, found);
+    }
+}
+
+fn expression_locals(expression: &TypedExpression, found: &mut Vec<DefId>) {
+    match &expression.kind {
+        ExpressionKind::Value(_) | ExpressionKind::Item(_) => {}
+        ExpressionKind::Local(id) => found.push(*id),
+        ExpressionKind::Call(callee, arguments) => {
+            match callee {
+                TypedCallee::Local(id) => found.push(*id),
+                _ => {}
+            }
+            for argument in arguments {
+                expression_locals(argument, found);
+            }
+        }
+        ExpressionKind::MethodCall { receiver, arguments, .. } => {
+            expression_locals(receiver, found);
+            for argument in arguments {
+                expression_locals(argument, found);
+            }
+        }
+        ExpressionKind::BinaryOperation(a, _, b) => {
+            expression_locals(a, found);
+            expression_locals(b, found);
+        }
+        ExpressionKind::Reference(_, inner)
+        | ExpressionKind::Field(inner, _)
+        | ExpressionKind::Cast(inner)
+        | ExpressionKind::Try(inner) => expression_locals(inner, found),
+        ExpressionKind::Closure { body, .. } => named_locals(body, found),
+    }
+}
+
+/// Lowers the body of one function, and the closures inside it.
+struct Builder<'a> {
+    symbols: &'a Symbols,
+    items: &'a Items,
+    /// The functions lowered so far, which closures are added to.
+    functions: &'a mut Vec<Function>,
+
+    function: Function,
+    /// The instructions of the current block, until it is terminated.
+    nodes: Vec<IRNode>,
+    current: BlockId,
+    places: HashMap<DefId, Place>,
+    closures: usize,
+    line: usize,
+}
+impl<'a> Builder<'a> {
+    fn new(
+        symbols: &'a Symbols,
+        items: &'a Items,
+        functions: &'a mut Vec<Function>,
+        function: Function,
+    ) -> Self {
+        let line = function.line;
+        let mut builder = Self {
+            symbols,
+            items,
+            functions,
+            function,
+            nodes: Vec::new(),
+            current: BlockId(0),
+            places: HashMap::new(),
+            closures: 0,
+            line,
+        };
+        builder.current = builder.block();
+        return builder;
+    }
+
+    fn block(&mut self) -> BlockId {
+        self.function.blocks.push(Block {
+            nodes: Vec::new(),
+            terminator: Terminator::Unreachable,
+            line: self.line,
+        });
+        return BlockId(self.function.blocks.len() - 1);
+    }
+
+    fn terminate(&mut self, terminator: Terminator) {
+        let block = &mut self.function.blocks[self.current.0];
+        block.nodes = std::mem::take(&mut self.nodes);
+        block.terminator = terminator;
+        block.line = self.line;
+    }
+
+    fn register(&mut self, t: Type) -> Register {
+        self.function.registers.push(t);
+        return Register(self.function.registers.len() - 1);
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        self.nodes.push(IRNode {
+            line: self.line,
+            instruction,
+        });
+    }
+
+    fn item(&self, id: DefId) -> Item {
+        return Item {
+            definition: id,
+            name: self.symbols.definition(id).name.clone(),
+        };
+    }
+
+    /// The prelude variant of `Option` or `Result` with the name.
+    fn variant(&self, name: &str) -> Option<Item> {
+        return self.symbols.value(&ModulePath::prelude(), name).map(|id| self.item(id));
+    }
+
+    fn slot(&mut self, id: Option<DefId>, t: Type, mutable: bool, binding: Binding) -> Place {
+        let name = match id {
+            Some(id) => self.symbols.definition(id).name.clone(),
+            None => String::from("_"),
+        };
+        self.function.slots.push(Local {
+            name,
+            t,
+            mutable,
+            definition: id,
+            binding,
+            line: self.line,
+        });
+        let place = Place::Slot(Slot(self.function.slots.len() - 1));
+        match id {
+            Some(id) => {
+                self.places.insert(id, place);
+            }
+            None => {}
+        }
+        return place;
+    }
+
+    fn place(&self, id: DefId) -> Place {
+        return match self.places.get(&id) {
+            Some(place) => *place,
+            None => Place::Static(id),
+        };
+    }
+
+    fn place_type(&self, place: Place) -> Type {
+        return match place {
+            Place::Slot(slot) => self.function.slots[slot.0].t.clone(),
+            Place::Static(id) => self.items.statics.get(&id).cloned().unwrap_or_else(unknown),
+            Place::Capture(index) => self.function.captures[index].t.clone(),
+        };
+    }
+
+    /// The register as a value of the type, coerced if it has another type.
+    fn coerce(&mut self, register: Register, t: &Type) -> Register {
+        if same_type(self.function.register_type(register), t) {
+            return register;
+        }
+        let coerced = self.register(t.clone());
+        self.emit(Instruction::Coerce(coerced, register));
+        return coerced;
+    }
+
+    fn deref(&mut self, register: Register) -> Register {
+        let t = match pointee(self.function.register_type(register)) {
+            Some(t) => t.clone(),
+            None => return register,
+        };
+        let dest = self.register(t);
+        self.emit(Instruction::Deref(dest, register));
+        return dest;
+    }
+
+    /// Reads through every reference until the value is reached, as fields and
+    /// methods taking `self` are used on references too.
+    fn deref_all(&mut self, register: Register) -> Register {
+        let mut register = register;
+        while pointee(self.function.register_type(register)).is_some() {
+            register = self.deref(register);
+        }
+        return register;
+    }
+
+    /// Reads through every reference but the last.
+    fn deref_to_reference(&mut self, register: Register) -> Register {
+        let mut register = register;
+        loop {
+            match pointee(self.function.register_type(register)).map(pointee) {
+                Some(Some(_)) => register = self.deref(register),
+                _ => return register,
+            }
+        }
+    }
+
+    fn store(&mut self, place: Place, register: Option<Register>) {
+        let register = match register {
+            Some(register) => register,
+            None => return,
+        };
+        let t = self.place_type(place);
+        let register = self.coerce(register, &t);
+        self.emit(Instruction::Store(place, register));
+    }
+
+    fn nodes(&mut self, nodes: &Vec<TypedNode>) {
+        for node in nodes {
+            self.line = node.line;
+            match &node.statement {
+                Statement::DefineVariable {
+                    local,
+                    mutable,
+                    local_type,
+                    expression,
+                } => {
+                    let value = match expression {
+                        Some(expression) => self.expression(expression),
+                        None => None,
+                    };
+                    let binding = match expression {
+                        Some(_) => Binding::Let,
+                        None => Binding::Deferred,
+                    };
+                    let place = self.slot(Some(*local), local_type.clone(), *mutable, binding);
+                    self.emit(Instruction::Declare(place));
+                    self.store(place, value);
+                }
+                Statement::SetVariable(id, expression) => {
+                    let value = self.expression(expression);
+                    let place = self.place(*id);
+                    self.store(place, value);
+                }
+                Statement::Expression(expression) => {
+                    self.expression(expression);
+                }
+                Statement::Return(expression) => {
+                    let value = match expression {
+                        Some(expression) => self.expression(expression),
+                        None => None,
+                    };
+                    let value = match (value, self.function.return_type.clone()) {
+                        (Some(value), Some(t)) => Some(self.coerce(value, &t)),
+                        (value, _) => value,
+                    };
+                    self.terminate(Terminator::Return(value));
+                    // Whatever follows a return is never run.
+                    self.current = self.block();
+                }
+                Statement::Scope(body) => self.nodes(body),
+                Statement::Loop(body) => {
+                    let header = self.block();
+                    self.terminate(Terminator::Jump(header));
+                    self.current = header;
+                    self.nodes(body);
+                    self.line = node.line;
+                    self.terminate(Terminator::Jump(header));
+                    // Loops are only left by returning.
+                    self.current = self.block();
+                }
+                Statement::InlineAsm { template, operands } => {
+                    let mut lowered = Vec::new();
+                    for operand in operands {
+                        let operand = match operand {
+                            TypedAsmOperand::In(register, expression) => match self.expression(expression) {
+                                Some(value) => AsmOperand::In(register.clone(), value),
+                                None => continue,
+                            },
+                            TypedAsmOperand::Out(register, id) => {
+                                AsmOperand::Out(register.clone(), id.map(|id| self.place(id)))
+                            }
+                            TypedAsmOperand::InOut(register, id) => {
+                                AsmOperand::InOut(register.clone(), self.place(*id))
+                            }
+                            TypedAsmOperand::Clobber(register) => AsmOperand::Clobber(register.clone()),
+                        };
+                        lowered.push(operand);
+                    }
+                    self.emit(Instruction::Asm(template.clone(), lowered));
+                }
+            }
+        }
+    }
+
+    fn arguments(&mut self, arguments: &Vec<TypedExpression>) -> Vec<Register> {
+        let mut registers = Vec::new();
+        for argument in arguments {
+            match self.expression(argument) {
+                Some(register) => registers.push(register),
+                None => {}
+            }
+        }
+        return registers;
+    }
+
+    /// The address of the value of the expression. Values that are not kept
+    /// in a place are stored in a new slot first.
+    fn address(&mut self, expression: &TypedExpression, mutable: bool) -> Register {
+        let t = expression.t.clone().unwrap_or_else(unit);
+        let place = match &expression.kind {
+            ExpressionKind::Local(id) => Some(self.place(*id)),
+            ExpressionKind::Item(id) if self.symbols.definition(*id).kind == DefKind::Static => {
+                Some(Place::Static(*id))
+            }
+            _ => None,
+        };
+        match place {
+            Some(place) => {
+                let dest = self.register(Type::Reference(mutable, Box::new(t)));
+                self.emit(Instruction::Address(dest, mutable, place));
+                return dest;
+            }
+            None => {}
+        }
+        match &expression.kind {
+            ExpressionKind::Field(inner, field) => {
+                let base = match inner.t.as_ref().and_then(pointee) {
+                    Some(_) => match self.expression(inner) {
+                        Some(base) => self.deref_to_reference(base),
+                        None => self.address(inner, mutable),
+                    },
+                    None => self.address(inner, mutable),
+                };
+                let dest = self.register(Type::Reference(mutable, Box::new(t)));
+                self.emit(Instruction::FieldAddress(dest, base, field.clone()));
+                return dest;
+            }
+            _ => {}
+        }
+        let place = self.slot(None, t.clone(), mutable, Binding::Temporary);
+        let value = self.expression(expression);
+        self.store(place, value);
+        let dest = self.register(Type::Reference(mutable, Box::new(t)));
+        self.emit(Instruction::Address(dest, mutable, place));
+        return dest;
+    }
+
+    fn expression(&mut self, expression: &TypedExpression) -> Option<Register> {
+        let t = expression.t.clone();
+        match &expression.kind {
+            ExpressionKind::Value(value) => {
+                let dest = self.register(t.unwrap_or_else(unit));
+                self.emit(Instruction::Value(dest, value.clone()));
+                return Some(dest);
+            }
+            ExpressionKind::Local(id) => {
+                let place = self.place(*id);
+                let dest = self.register(t.unwrap_or_else(|| self.place_type(place)));
+                self.emit(Instruction::Load(dest, place));
+                return Some(dest);
+            }
+            ExpressionKind::Item(id) => {
+                let t = t.unwrap_or_else(unknown);
+                let dest = self.register(t.clone());
+                let instruction = match (self.symbols.definition(*id).kind, &t) {
+                    (DefKind::Static, _) => Instruction::Load(dest, Place::Static(*id)),
+                    // Unit variants are values, others are named as constructors.
+                    (DefKind::Variant, Type::Function(_, _)) => Instruction::Item(dest, self.item(*id)),
+                    (DefKind::Variant, _) => Instruction::Construct(dest, self.item(*id), Vec::new()),
+                    _ => Instruction::Item(dest, self.item(*id)),
+                };
+                self.emit(instruction);
+                return Some(dest);
+            }
+            ExpressionKind::Call(callee, arguments) => {
+                let callee = match callee {
+                    TypedCallee::Item(id) => match self.symbols.definition(*id).kind {
+                        DefKind::Struct | DefKind::Variant => {
+                            let fields = self.arguments(arguments);
+                            let dest = self.register(t.unwrap_or_else(unknown));
+                            self.emit(Instruction::Construct(dest, self.item(*id), fields));
+                            return Some(dest);
+                        }
+                        _ => Callee::Function(self.item(*id)),
+                    },
+                    TypedCallee::Local(id) => {
+                        let place = self.place(*id);
+                        let function = self.register(self.place_type(place));
+                        self.emit(Instruction::Load(function, place));
+                        Callee::Register(function)
+                    }
+                    TypedCallee::Builtin(name) => Callee::Builtin(name.clone()),
+                };
+                let mut arguments = self.arguments(arguments);
+                match &callee {
+                    Callee::Function(item) => match self.items.parameters.get(&item.definition) {
+                        Some(parameters) => {
+                            for (argument, t) in arguments.iter_mut().zip(parameters) {
+                                *argument = self.coerce(*argument, t);
+                            }
+                        }
+                        None => {}
+                    },
+                    _ => {}
+                }
+                let dest = t.map(|t| self.register(t));
+                self.emit(Instruction::Call(dest, callee, arguments));
+                return dest;
+            }
+            ExpressionKind::MethodCall {
+                receiver,
+                method,
+                definition,
+                arguments,
+            } => {
+                let receiver_type = receiver.t.clone().unwrap_or_else(unit);
+                let (callee, receiver) = match definition {
+                    Some(id) if receiver_type.trait_object().is_some() => {
+                        (Callee::Virtual(self.item(*id)), self.expression(receiver))
+                    }
+                    Some(id) => {
+                        let receiver = match (self.items.receivers.get(id), pointee(&receiver_type)) {
+                            // `&self` methods called on a value borrow it.
+                            (Some(Type::Reference(mutable, _)), None) => Some(self.address(receiver, *mutable)),
+                            (Some(Type::Reference(_, _)), Some(_)) => match self.expression(receiver) {
+                                Some(register) => Some(self.deref_to_reference(register)),
+                                None => None,
+                            },
+                            (Some(_), Some(_)) => match self.expression(receiver) {
+                                Some(register) => Some(self.deref_all(register)),
+                                None => None,
+                            },
+                            _ => self.expression(receiver),
+                        };
+                        (Callee::Function(self.item(*id)), receiver)
+                    }
+                    None => {
+                        // `next` advances the `Chars` in place, as if it took `&mut self`.
+                        let receiver = match (method.as_str(), pointee(&receiver_type)) {
+                            ("next", None) => Some(self.address(receiver, true)),
+                            _ => self.expression(receiver),
+                        };
+                        (Callee::Builtin(method.clone()), receiver)
+                    }
+                };
+                let mut registers: Vec<Register> = receiver.into_iter().collect();
+                registers.extend(self.arguments(arguments));
+                let dest = t.map(|t| self.register(t));
+                self.emit(Instruction::Call(dest, callee, registers));
+                return dest;
+            }
+            ExpressionKind::BinaryOperation(a, operator, b) => {
+                let a = self.expression(a);
+                let b = self.expression(b);
+                let (a, b) = match (a, b) {
+                    (Some(a), Some(b)) => (a, b),
+                    _ => return None,
+                };
+                // Pointer arithmetic adds an integer to a pointer, otherwise
+                // both operands have one type.
+                let b = match self.function.register_type(a) {
+                    Type::Pointer(_, _) => b,
+                    t => {
+                        let t = t.clone();
+                        self.coerce(b, &t)
+                    }
+                };
+                let dest = self.register(t.unwrap_or_else(unknown));
+                self.emit(Instruction::Binary(dest, *operator, a, b));
+                return Some(dest);
+            }
+            ExpressionKind::Reference(mutable, inner) => return Some(self.address(inner, *mutable)),
+            ExpressionKind::Field(inner, field) => {
+                let inner = match self.expression(inner) {
+                    Some(inner) => self.deref_all(inner),
+                    None => return None,
+                };
+                let dest = self.register(t.unwrap_or_else(unknown));
+                self.emit(Instruction::Field(dest, inner, field.clone()));
+                return Some(dest);
+            }
+            ExpressionKind::Cast(inner) => {
+                let inner = match self.expression(inner) {
+                    Some(inner) => inner,
+                    None => return None,
+                };
+                let dest = self.register(t.unwrap_or_else(unknown));
+                self.emit(Instruction::Cast(dest, inner));
+                return Some(dest);
+            }
+            ExpressionKind::Try(inner) => {
+                let inner = match self.expression(inner) {
+                    Some(inner) => inner,
+                    None => return None,
+                };
+                return Some(self.try_value(inner, t.unwrap_or_else(unknown)));
+            }
+            ExpressionKind::Closure {
+                is_move,
+                parameters,
+                body,
+            } => {
+                let return_type = match &t {
+                    Some(Type::Function(_, return_type)) => return_type.as_ref().map(|t| t.as_ref().clone()),
+                    _ => None,
+                };
+                let index = self.closure(*is_move, parameters, return_type, body);
+                let mut captures = Vec::new();
+                for capture in &self.functions[index].captures {
+                    match capture.definition {
+                        Some(id) => captures.push(self.place(id)),
+                        None => {}
+                    }
+                }
+                let dest = self.register(t.unwrap_or_else(unknown));
+                self.emit(Instruction::Closure(dest, index, captures));
+                return Some(dest);
+            }
+        }
+    }
+
+    /// `value?`, which returns the `None` or the `Err` and otherwise goes on
+    /// with what is inside the `Some` or the `Ok`.
+    fn try_value(&mut self, inner: Register, t: Type) -> Register {
+        let is_result = match self.function.register_type(inner) {
+            Type::Generic(name, _) => name == "Result",
+            _ => false,
+        };
+        let (success, failure) = match is_result {
+            true => (self.variant("Ok"), self.variant("Err")),
+            false => (self.variant("Some"), self.variant("None")),
+        };
+        let (success, failure) = match (success, failure) {
+            (Some(success), Some(failure)) => (success, failure),
+            _ => return inner,
+        };
+
+        let condition = self.register(Type::Base(crate::parser::BaseType::Boolean));
+        self.emit(Instruction::IsVariant(condition, inner, success.clone()));
+        let then = self.block();
+        let otherwise = self.block();
+        self.terminate(Terminator::Branch(condition, then, otherwise));
+
+        self.current = otherwise;
+        let return_type = self.function.return_type.clone().unwrap_or_else(unknown);
+        let returned = self.register(return_type);
+        let fields = match is_result {
+            true => {
+                let error_type = match self.function.register_type(inner) {
+                    Type::Generic(_, types) if types.len() == 2 => types[1].clone(),
+                    _ => unknown(),
+                };
+                let error = self.register(error_type);
+                self.emit(Instruction::Payload(error, inner, failure.clone(), 0));
+                vec![error]
+            }
+            false => Vec::new(),
+        };
+        self.emit(Instruction::Construct(returned, failure, fields));
+        self.terminate(Terminator::Return(Some(returned)));
+
+        self.current = then;
+        let dest = self.register(t);
+        self.emit(Instruction::Payload(dest, inner, success, 0));
+        return dest;
+    }
+
+    /// Lowers a closure into a function of its own, returning its index.
+    fn closure(
+        &mut self,
+        is_move: bool,
+        parameters: &Vec<(DefId, Type)>,
+        return_type: Option<Type>,
+        body: &Vec<TypedNode>,
+    ) -> usize {
+        let mut named = Vec::new();
+        named_locals(body, &mut named);
+        let mut captures: Vec<DefId> = Vec::new();
+        for id in named {
+            match self.places.get(&id) {
+                Some(Place::Slot(_) | Place::Capture(_)) if !captures.contains(&id) => captures.push(id),
+                _ => {}
+            }
+        }
+
+        let function = Function {
+            name: format!("{}::{{closure#{}}}", self.function.name, self.closures),
+            definition: None,
+            relative_path: self.function.relative_path.clone(),
+            line: self.line,
+            parameters: Vec::new(),
+            return_type,
+            registers: Vec::new(),
+            slots: Vec::new(),
+            captures: Vec::new(),
+            is_move,
+            blocks: Vec::new(),
+        };
+        self.closures += 1;
+        let mut locals = Vec::new();
+        for id in &captures {
+            let place = self.place(*id);
+            let outer = match place {
+                Place::Slot(slot) => &self.function.slots[slot.0],
+                Place::Capture(index) => &self.function.captures[index],
+                Place::Static(_) => continue,
+            };
+            locals.push(Local {
+                name: outer.name.clone(),
+                t: outer.t.clone(),
+                mutable: outer.mutable,
+                definition: Some(*id),
+                binding: outer.binding,
+                line: outer.line,
+            });
+        }
+
+        let mut builder = Builder::new(self.symbols, self.items, self.functions, function);
+        for (index, local) in locals.into_iter().enumerate() {
+            match local.definition {
+                Some(id) => {
+                    builder.places.insert(id, Place::Capture(index));
+                }
+                None => {}
+            }
+            builder.function.captures.push(local);
+        }
+        let function = builder.lower(parameters, &vec![false; parameters.len()], body);
+        self.functions.push(function);
+        return self.functions.len() - 1;
+    }
+
+    /// Stores the parameters into slots and lowers the body.
+    fn lower(mut self, parameters: &Vec<(DefId, Type)>, mutable: &Vec<bool>, body: &Vec<TypedNode>) -> Function {
+        for ((id, t), mutable) in parameters.iter().zip(mutable) {
+            let register = self.register(t.clone());
+            self.function.parameters.push(register);
+            let place = self.slot(Some(*id), t.clone(), *mutable, Binding::Parameter);
+            self.emit(Instruction::Store(place, register));
+        }
+        self.nodes(body);
+        self.line = self.function.line;
+        // Falling off the end returns nothing, which only functions without
+        // a return type may do.
+        match self.function.return_type {
+            Some(_) => self.terminate(Terminator::Unreachable),
+            None => self.terminate(Terminator::Return(None)),
+        }
+        return self.function;
+    }
+}
+
+/// What lowering needs to know about items besides their checked bodies.
+struct Items {
+    /// The type of the `self` parameter of every method.
+    receivers: HashMap<DefId, Type>,
+    /// The parameter types of free and foreign functions without generics,
+    /// which arguments are coerced to.
+    parameters: HashMap<DefId, Vec<Type>>,
+    statics: HashMap<DefId, Type>,
+}
+
+fn collect(items: &mut Items, nodes: &Vec<ASTNode>, symbols: &Symbols, is_member: bool) {
+    for ast_node in nodes {
+        match &ast_node.node {
+            Node::Function {
+                generics,
+                parameters,
+                definition: Some(id),
+                ..
+            } => {
+                match parameters.first() {
+                    Some((name, t)) if name == "self" => {
+                        items.receivers.insert(*id, t.clone());
+                    }
+                    _ => {}
+                }
+                if generics.is_empty() && !is_member {
+                    items.parameters.insert(*id, parameters.iter().map(|(_, t)| t.clone()).collect());
+                }
+            }
+            Node::ForeignFunction { name, parameters, .. } => match symbols.values.get(name) {
+                Some(id) => {
+                    items.parameters.insert(*id, parameters.iter().map(|(_, t)| t.clone()).collect());
+                }
+                None => {}
+            },
+            Node::Impl { body, .. } | Node::Trait { body, .. } => collect(items, body, symbols, true),
+            Node::Extern { body, .. } => collect(items, body, symbols, is_member),
+            Node::Static { name, static_type, .. } => match symbols.values.get(name) {
+                Some(id) => {
+                    items.statics.insert(*id, static_type.clone());
+                }
+                None => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+fn function(typed: &TypedFunction, symbols: &Symbols, items: &Items, functions: &mut Vec<Function>) {
+    let function = Function {
+        name: typed.name.clone(),
+        definition: Some(typed.definition),
+        relative_path: typed.relative_path.clone(),
+        line: typed.line,
+        parameters: Vec::new(),
+        return_type: typed.return_type.clone(),
+        registers: Vec::new(),
+        slots: Vec::new(),
+        captures: Vec::new(),
+        is_move: false,
+        blocks: Vec::new(),
+    };
+    let builder = Builder::new(symbols, items, functions, function);
+    let function = builder.lower(&typed.parameters, &typed.mutable_parameters, &typed.body);
+    functions.push(function);
+}
+
+/// Lowers every checked function into the IR.
+pub fn lower(program: &Program, analysis: &Analysis) -> IrProgram {
+    let mut items = Items {
+        receivers: HashMap::new(),
+        parameters: HashMap::new(),
+        statics: HashMap::new(),
+    };
+    let mut paths: Vec<_> = program.modules.keys().collect();
+    paths.sort();
+    for path in paths {
+        collect(&mut items, &program.modules[path].body, &analysis.symbols, false);
+    }
+
+    let mut functions = Vec::new();
+    for typed in &analysis.functions {
+        function(typed, &analysis.symbols, &items, &mut functions);
+    }
+    return IrProgram {
+        functions,
+        statics: items.statics,
+    };
+}
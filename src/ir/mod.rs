@@ -0,0 +1,17 @@
+mod assign;
+mod cfg;
+mod dot;
+mod flow;
+mod ir;
+mod lower;
+mod ssa;
+mod verify;
+
+pub use assign::check_assignments;
+pub use cfg::Cfg;
+pub use dot::cfg_dot;
+pub use flow::check_flow;
+pub use ir::{AsmOperand, BlockId, Callee, Function, Instruction, IrProgram, Item, Place, Register, Terminator};
+pub use lower::lower;
+pub use ssa::to_ssa;
+pub use verify::verify;
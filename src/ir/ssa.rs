@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+
+use super::{
+    cfg::{remove_unreachable, Cfg},
+    ir::{BlockId, Function, IRNode, Instruction, IrProgram, Place, Register, Slot},
+};
+
+/// The slots only ever declared, loaded and stored, whose values can live in registers.
+/// Slots whose address is taken, that a closure captures or that `asm!` writes
+/// to can change behind the back of the function and stay in memory.
+fn promotable(function: &Function) -> Vec<bool> {
+    let mut promotable = vec![true; function.slots.len()];
+    for block in &function.blocks {
+        for node in &block.nodes {
+            match &node.instruction {
+                Instruction::Declare(_) | Instruction::Load(_, _) | Instruction::Store(_, _) => continue,
+                _ => {}
+            }
+            for place in node.instruction.places() {
+                match place {
+                    Place::Slot(slot) => promotable[slot.0] = false,
+                    _ => {}
+                }
+            }
+        }
+    }
+    return promotable;
+}
+
+fn promoted_slot(promotable: &Vec<bool>, place: &Place) -> Option<usize> {
+    return match place {
+        Place::Slot(slot) if promotable[slot.0] => Some(slot.0),
+        _ => None,
+    };
+}
+
+struct Renamer<'a> {
+    function: &'a mut Function,
+    cfg: Cfg,
+    children: Vec<Vec<BlockId>>,
+    promotable: Vec<bool>,
+    /// The slot each phi stands for, by the register it defines.
+    phis: HashMap<Register, usize>,
+    /// The value of each slot at the current point of the walk, innermost last.
+    values: Vec<Vec<Register>>,
+    /// What every load of a promoted slot is replaced by.
+    replaced: HashMap<Register, Register>,
+    /// The value of each slot read before anything was stored to it.
+    undefined: Vec<Option<Register>>,
+}
+impl<'a> Renamer<'a> {
+    fn replacement(&self, register: Register) -> Register {
+        let mut register = register;
+        loop {
+            register = match self.replaced.get(&register) {
+                Some(replacement) => *replacement,
+                None => return register,
+            };
+        }
+    }
+
+    fn value(&mut self, slot: usize) -> Register {
+        match self.values[slot].last() {
+            Some(register) => return *register,
+            None => {}
+        }
+        match self.undefined[slot] {
+            Some(register) => return register,
+            None => {}
+        }
+        self.function.registers.push(self.function.slots[slot].t.clone());
+        let register = Register(self.function.registers.len() - 1);
+        self.undefined[slot] = Some(register);
+        return register;
+    }
+
+    /// Renames the block and the blocks it dominates, following the dominator tree.
+    fn rename(&mut self, block: BlockId) {
+        let mut pushed = Vec::new();
+        let nodes = std::mem::take(&mut self.function.blocks[block.0].nodes);
+        let mut kept = Vec::new();
+        for mut node in nodes {
+            for register in node.instruction.uses_mut() {
+                *register = self.replacement(*register);
+            }
+            match &node.instruction {
+                Instruction::Phi(dest, _) => match self.phis.get(dest) {
+                    Some(slot) => {
+                        self.values[*slot].push(*dest);
+                        pushed.push(*slot);
+                    }
+                    None => {}
+                },
+                Instruction::Load(dest, place) => match promoted_slot(&self.promotable, place) {
+                    Some(slot) => {
+                        let value = self.value(slot);
+                        self.replaced.insert(*dest, value);
+                        continue;
+                    }
+                    None => {}
+                },
+                Instruction::Declare(place) => match promoted_slot(&self.promotable, place) {
+                    Some(_) => continue,
+                    None => {}
+                },
+                Instruction::Store(place, register) => match promoted_slot(&self.promotable, place) {
+                    Some(slot) => {
+                        self.values[slot].push(*register);
+                        pushed.push(slot);
+                        continue;
+                    }
+                    None => {}
+                },
+                _ => {}
+            }
+            kept.push(node);
+        }
+        self.function.blocks[block.0].nodes = kept;
+        let mut terminator = self.function.blocks[block.0].terminator.clone();
+        match terminator.use_mut() {
+            Some(register) => *register = self.replacement(*register),
+            None => {}
+        }
+        self.function.blocks[block.0].terminator = terminator;
+
+        for successor in self.cfg.successors[block.0].clone() {
+            let count = self.function.blocks[successor.0].nodes.len();
+            for index in 0..count {
+                let slot = match &self.function.blocks[successor.0].nodes[index].instruction {
+                    Instruction::Phi(dest, _) => match self.phis.get(dest) {
+                        Some(slot) => *slot,
+                        None => continue,
+                    },
+                    _ => break,
+                };
+                let value = self.value(slot);
+                match &mut self.function.blocks[successor.0].nodes[index].instruction {
+                    Instruction::Phi(_, incoming) => incoming.push((block, value)),
+                    _ => {}
+                }
+            }
+        }
+
+        for child in self.children[block.0].clone() {
+            self.rename(child);
+        }
+        for slot in pushed {
+            self.values[slot].pop();
+        }
+    }
+}
+
+/// Places a phi for every promoted slot where values stored to it on
+/// different paths meet, at the iterated dominance frontier of its stores.
+fn place_phis(function: &mut Function, cfg: &Cfg, promotable: &Vec<bool>) -> HashMap<Register, usize> {
+    let frontiers = cfg.frontiers();
+    let mut phis = HashMap::new();
+    for slot in 0..function.slots.len() {
+        if !promotable[slot] {
+            continue;
+        }
+        let mut stores: Vec<BlockId> = Vec::new();
+        for (index, block) in function.blocks.iter().enumerate() {
+            let stored = block.nodes.iter().any(|node| match &node.instruction {
+                Instruction::Store(Place::Slot(Slot(stored)), _) => *stored == slot,
+                _ => false,
+            });
+            if stored {
+                stores.push(BlockId(index));
+            }
+        }
+        let mut placed: Vec<BlockId> = Vec::new();
+        loop {
+            let block = match stores.pop() {
+                Some(block) => block,
+                None => break,
+            };
+            for frontier in &frontiers[block.0] {
+                if placed.contains(frontier) {
+                    continue;
+                }
+                placed.push(*frontier);
+                function.registers.push(function.slots[slot].t.clone());
+                let dest = Register(function.registers.len() - 1);
+                let block = &function.blocks[frontier.0];
+                let line = block.nodes.first().map(|node| node.line).unwrap_or(block.line);
+                function.blocks[frontier.0].nodes.insert(
+                    0,
+                    IRNode {
+                        line,
+                        instruction: Instruction::Phi(dest, Vec::new()),
+                    },
+                );
+                phis.insert(dest, slot);
+                // A phi stores to the slot too.
+                if !stores.contains(frontier) {
+                    stores.push(*frontier);
+                }
+            }
+        }
+    }
+    return phis;
+}
+
+/// Renames every use of a register in the function.
+fn replace(function: &mut Function, register: Register, replacement: Register) {
+    for block in &mut function.blocks {
+        for node in &mut block.nodes {
+            for used in node.instruction.uses_mut() {
+                if *used == register {
+                    *used = replacement;
+                }
+            }
+        }
+        match block.terminator.use_mut() {
+            Some(used) if *used == register => *used = replacement,
+            _ => {}
+        }
+    }
+}
+
+/// The one value a phi has on every path, not counting the paths where it
+/// keeps its own value.
+fn trivial_value(instruction: &Instruction) -> Option<(Register, Register)> {
+    let (dest, incoming) = match instruction {
+        Instruction::Phi(dest, incoming) => (dest, incoming),
+        _ => return None,
+    };
+    let mut values: Vec<Register> = Vec::new();
+    for (_, register) in incoming {
+        if register != dest && !values.contains(register) {
+            values.push(*register);
+        }
+    }
+    return match values.as_slice() {
+        [value] => Some((*dest, *value)),
+        _ => None,
+    };
+}
+
+/// Removes phis whose value is the same on every path, one at a time as
+/// removing one can make another trivial, then the phis and undefined
+/// values nothing reads.
+fn remove_phis(function: &mut Function) {
+    loop {
+        let mut trivial = None;
+        for (index, block) in function.blocks.iter().enumerate() {
+            for (position, node) in block.nodes.iter().enumerate() {
+                match trivial_value(&node.instruction) {
+                    Some(found) => {
+                        trivial = Some((index, position, found));
+                        break;
+                    }
+                    None => {}
+                }
+            }
+            if trivial.is_some() {
+                break;
+            }
+        }
+        match trivial {
+            Some((index, position, (dest, value))) => {
+                function.blocks[index].nodes.remove(position);
+                replace(function, dest, value);
+            }
+            None => break,
+        }
+    }
+
+    loop {
+        let mut used: Vec<Register> = Vec::new();
+        for block in &function.blocks {
+            for node in &block.nodes {
+                let dest = node.instruction.definition();
+                used.extend(node.instruction.uses().into_iter().filter(|register| Some(*register) != dest));
+            }
+            match block.terminator.clone().use_mut() {
+                Some(register) => used.push(*register),
+                None => {}
+            }
+        }
+        let mut changed = false;
+        for block in &mut function.blocks {
+            let count = block.nodes.len();
+            block.nodes.retain(|node| match &node.instruction {
+                Instruction::Phi(dest, _) | Instruction::Undefined(dest) => used.contains(dest),
+                _ => true,
+            });
+            changed = changed || count != block.nodes.len();
+        }
+        if !changed {
+            return;
+        }
+    }
+}
+
+/// Drops the promoted slots, numbering the others from zero again.
+fn remove_slots(function: &mut Function, promotable: &Vec<bool>) {
+    let mut renamed = Vec::new();
+    let mut kept = Vec::new();
+    for (index, slot) in function.slots.iter().enumerate() {
+        match promotable[index] {
+            true => renamed.push(None),
+            false => {
+                renamed.push(Some(Slot(kept.len())));
+                kept.push(slot.clone());
+            }
+        }
+    }
+    function.slots = kept;
+    for block in &mut function.blocks {
+        for node in &mut block.nodes {
+            for place in node.instruction.places_mut() {
+                match place {
+                    Place::Slot(slot) => *slot = renamed[slot.0].unwrap(),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Turns the locals of the function that live in slots into registers
+/// assigned once, joined by phis where control flow meets.
+fn promote(function: &mut Function) {
+    remove_unreachable(function);
+    let cfg = Cfg::new(function);
+    let promotable = promotable(function);
+    let phis = place_phis(function, &cfg, &promotable);
+
+    let children = cfg.children();
+    let slots = function.slots.len();
+    let mut renamer = Renamer {
+        function,
+        cfg,
+        children,
+        promotable: promotable.clone(),
+        phis,
+        values: vec![Vec::new(); slots],
+        replaced: HashMap::new(),
+        undefined: vec![None; slots],
+    };
+    renamer.rename(BlockId(0));
+
+    let undefined: Vec<Register> = renamer.undefined.iter().filter_map(|register| *register).collect();
+    let line = function.line;
+    for register in undefined.into_iter().rev() {
+        function.blocks[0].nodes.insert(
+            0,
+            IRNode {
+                line,
+                instruction: Instruction::Undefined(register),
+            },
+        );
+    }
+    remove_phis(function);
+    remove_slots(function, &promotable);
+}
+
+/// Converts every function to SSA form.
+pub fn to_ssa(program: &mut IrProgram) {
+    for function in &mut program.functions {
+        promote(function);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::compile_source;
+
+    fn ir(source: &str) -> String {
+        return match compile_source(source) {
+            Ok(compiled) => compiled.ir.to_string(),
+            Err(error) => panic!("{:?}", error),
+        };
+    }
+
+    #[test]
+    fn loops_join_values_with_phis() {
+        let ir = ir("fn step(i: i32): Option<i32> {\n    return Some(i);\n}\n\nfn count(): Option<i32> {\n    let mut i = 0;\n    loop {\n        i = step(i + 1)?;\n    }\n}\n\nfn main() {\n    let c = count();\n}\n");
+        assert!(ir.contains("phi [bb0: %0, bb2: %7]"), "{}", ir);
+        assert!(!ir.contains("load $"), "{}", ir);
+    }
+
+    #[test]
+    fn borrowed_locals_stay_in_memory() {
+        let ir = ir("fn main(): i32 {\n    let mut x = 1;\n    let r = &mut x;\n    x = 2;\n    return x;\n}\n");
+        assert!(ir.contains("store $0, %2"), "{}", ir);
+        assert!(ir.contains("load $0"), "{}", ir);
+        assert!(!ir.contains("phi"), "{}", ir);
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+
+use crate::{
+    analyzer::{is_unknown, unknown},
+    parser::{BaseType, Type},
+};
+
+use super::{
+    cfg::Cfg,
+    ir::{same_type, AsmOperand, BlockId, Function, Instruction, IrProgram, Place, Register, Terminator},
+};
+
+fn boolean() -> Type {
+    return Type::Base(BaseType::Boolean);
+}
+
+struct Verifier<'a> {
+    program: &'a IrProgram,
+    function: &'a Function,
+    block: BlockId,
+}
+impl<'a> Verifier<'a> {
+    fn error(&self, message: String) -> String {
+        return format!("fn {}, {}: {}", self.function.name, self.block, message);
+    }
+
+    fn register(&self, register: Register) -> Result<&'a Type, String> {
+        return match self.function.registers.get(register.0) {
+            Some(t) => Ok(t),
+            None => Err(self.error(format!("{} has no type", register))),
+        };
+    }
+
+    fn place(&self, place: &Place) -> Result<Type, String> {
+        return match place {
+            Place::Slot(slot) => match self.function.slots.get(slot.0) {
+                Some(local) => Ok(local.t.clone()),
+                None => Err(self.error(format!("{} is not a slot", place))),
+            },
+            Place::Capture(index) => match self.function.captures.get(*index) {
+                Some(local) => Ok(local.t.clone()),
+                None => Err(self.error(format!("{} is not a capture", place))),
+            },
+            Place::Static(id) => Ok(self.program.statics.get(id).cloned().unwrap_or_else(unknown)),
+        };
+    }
+
+    fn expect(&self, expected: &Type, register: Register) -> Result<(), String> {
+        let found = match self.register(register) {
+            Ok(t) => t,
+            Err(error) => return Err(error),
+        };
+        return match same_type(expected, found) {
+            true => Ok(()),
+            false => Err(self.error(format!("{} is a `{}`, not a `{}`", register, found, expected))),
+        };
+    }
+
+    fn instruction(&self, instruction: &Instruction) -> Result<(), String> {
+        return match instruction {
+            Instruction::Declare(place) => match self.place(place) {
+                Ok(_) => Ok(()),
+                Err(error) => Err(error),
+            },
+            Instruction::Load(dest, place) => match self.place(place) {
+                Ok(t) => self.expect(&t, *dest),
+                Err(error) => Err(error),
+            },
+            Instruction::Store(place, register) => match self.place(place) {
+                Ok(t) => self.expect(&t, *register),
+                Err(error) => Err(error),
+            },
+            Instruction::Address(dest, mutable, place) => match self.place(place) {
+                Ok(t) => self.expect(&Type::Reference(*mutable, Box::new(t)), *dest),
+                Err(error) => Err(error),
+            },
+            Instruction::Deref(dest, register) | Instruction::FieldAddress(dest, register, _) => {
+                let pointer = match self.register(*register) {
+                    Ok(t) => t,
+                    Err(error) => return Err(error),
+                };
+                let pointee = match pointer {
+                    Type::Reference(_, inner) | Type::Pointer(_, inner) => inner.as_ref().clone(),
+                    t if is_unknown(t) => unknown(),
+                    t => return Err(self.error(format!("{} is a `{}`, which can't be read through", register, t))),
+                };
+                match instruction {
+                    Instruction::Deref(_, _) => self.expect(&pointee, *dest),
+                    _ => Ok(()),
+                }
+            }
+            Instruction::Binary(dest, operator, a, b) => {
+                let a_type = match self.register(*a) {
+                    Ok(t) => t,
+                    Err(error) => return Err(error),
+                };
+                match a_type {
+                    // Pointer arithmetic takes an integer of any type.
+                    Type::Pointer(_, _) => {}
+                    t => match self.expect(t, *b) {
+                        Ok(()) => {}
+                        Err(error) => return Err(error),
+                    },
+                }
+                match operator.is_comparison() {
+                    true => self.expect(&boolean(), *dest),
+                    false => self.expect(a_type, *dest),
+                }
+            }
+            Instruction::IsVariant(dest, _, _) => self.expect(&boolean(), *dest),
+            Instruction::Closure(_, index, captures) => {
+                let closure = match self.program.functions.get(*index) {
+                    Some(closure) => closure,
+                    None => return Err(self.error(format!("fn#{} does not exist", index))),
+                };
+                if closure.captures.len() != captures.len() {
+                    return Err(self.error(format!(
+                        "{} captures {} places, not {}",
+                        closure.name,
+                        closure.captures.len(),
+                        captures.len()
+                    )));
+                }
+                for (place, capture) in captures.iter().zip(&closure.captures) {
+                    match self.place(place) {
+                        Ok(t) if same_type(&t, &capture.t) => {}
+                        Ok(t) => {
+                            return Err(self.error(format!(
+                                "{} is a `{}`, but {} captures a `{}`",
+                                place, t, closure.name, capture.t
+                            )))
+                        }
+                        Err(error) => return Err(error),
+                    }
+                }
+                Ok(())
+            }
+            Instruction::Asm(_, operands) => {
+                for operand in operands {
+                    let place = match operand {
+                        AsmOperand::Out(_, Some(place)) | AsmOperand::InOut(_, place) => place,
+                        _ => continue,
+                    };
+                    match self.place(place) {
+                        Ok(_) => {}
+                        Err(error) => return Err(error),
+                    }
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+    }
+
+    fn terminator(&self, terminator: &Terminator) -> Result<(), String> {
+        return match (terminator, &self.function.return_type) {
+            (Terminator::Branch(condition, _, _), _) => self.expect(&boolean(), *condition),
+            (Terminator::Return(Some(register)), Some(t)) => self.expect(t, *register),
+            (Terminator::Return(Some(register)), None) => {
+                Err(self.error(format!("{} is returned from a function without a return type", register)))
+            }
+            (Terminator::Return(None), Some(t)) => Err(self.error(format!("returns nothing instead of a `{}`", t))),
+            _ => Ok(()),
+        };
+    }
+
+    /// Whether the definition of a register comes before a use at the
+    /// position of the block on every path.
+    fn is_defined_before(cfg: &Cfg, defined: (BlockId, usize), block: BlockId, position: usize) -> bool {
+        return match defined.0 == block {
+            true => defined.1 <= position,
+            false => cfg.dominates(defined.0, block),
+        };
+    }
+
+    fn function(&mut self) -> Result<(), String> {
+        if self.function.blocks.is_empty() {
+            return Err(format!("fn {} has no blocks", self.function.name));
+        }
+        // The graph of the blocks can only be built from jumps to blocks that exist.
+        for (index, block) in self.function.blocks.iter().enumerate() {
+            self.block = BlockId(index);
+            for target in block.terminator.successors() {
+                if target.0 >= self.function.blocks.len() {
+                    return Err(self.error(format!("{} does not exist", target)));
+                }
+            }
+        }
+        let cfg = Cfg::new(self.function);
+
+        // Where every register is defined, by block and the position after it.
+        let mut definitions: HashMap<Register, (BlockId, usize)> = HashMap::new();
+        for register in &self.function.parameters {
+            definitions.insert(*register, (BlockId(0), 0));
+        }
+        for (index, block) in self.function.blocks.iter().enumerate() {
+            self.block = BlockId(index);
+            for (position, node) in block.nodes.iter().enumerate() {
+                let register = match node.instruction.definition() {
+                    Some(register) => register,
+                    None => continue,
+                };
+                match self.register(register) {
+                    Ok(_) => {}
+                    Err(error) => return Err(error),
+                }
+                match definitions.insert(register, (BlockId(index), position + 1)) {
+                    Some(_) => return Err(self.error(format!("{} is defined twice", register))),
+                    None => {}
+                }
+            }
+        }
+
+        for (index, block) in self.function.blocks.iter().enumerate() {
+            self.block = BlockId(index);
+            // Code no path reaches may use anything, it is never run.
+            let is_reachable = cfg.is_reachable(self.block);
+            let mut in_phis = true;
+            for (position, node) in block.nodes.iter().enumerate() {
+                match &node.instruction {
+                    Instruction::Phi(dest, incoming) => {
+                        if !in_phis {
+                            return Err(self.error(format!("phi {} comes after other instructions", dest)));
+                        }
+                        let mut predecessors: Vec<BlockId> = incoming.iter().map(|(block, _)| *block).collect();
+                        let mut expected = cfg.predecessors[index].clone();
+                        predecessors.sort_by_key(|block| block.0);
+                        expected.sort_by_key(|block| block.0);
+                        if is_reachable && predecessors != expected {
+                            return Err(self.error(format!("phi {} does not take a value from every predecessor", dest)));
+                        }
+                        let t = match self.register(*dest) {
+                            Ok(t) => t.clone(),
+                            Err(error) => return Err(error),
+                        };
+                        for (predecessor, register) in incoming {
+                            match definitions.get(register) {
+                                Some(defined)
+                                    if !is_reachable
+                                        || Self::is_defined_before(&cfg, *defined, *predecessor, usize::MAX) => {}
+                                Some(_) => {
+                                    return Err(self.error(format!(
+                                        "{} is not defined on the way from {}",
+                                        register, predecessor
+                                    )))
+                                }
+                                None => return Err(self.error(format!("{} is never defined", register))),
+                            }
+                            match self.expect(&t, *register) {
+                                Ok(()) => {}
+                                Err(error) => return Err(error),
+                            }
+                        }
+                        continue;
+                    }
+                    _ => in_phis = false,
+                }
+                for register in node.instruction.uses() {
+                    match definitions.get(&register) {
+                        Some(defined) if !is_reachable || Self::is_defined_before(&cfg, *defined, self.block, position) => {}
+                        Some(_) => return Err(self.error(format!("{} is used before it is defined", register))),
+                        None => return Err(self.error(format!("{} is never defined", register))),
+                    }
+                }
+                match self.instruction(&node.instruction) {
+                    Ok(()) => {}
+                    Err(error) => return Err(format!("{} (line {})", error, node.line)),
+                }
+            }
+            match block.terminator.clone().use_mut() {
+                Some(register) => match definitions.get(register) {
+                    Some(defined)
+                        if !is_reachable || Self::is_defined_before(&cfg, *defined, self.block, usize::MAX) => {}
+                    Some(_) => return Err(self.error(format!("{} is used before it is defined", register))),
+                    None => return Err(self.error(format!("{} is never defined", register))),
+                },
+                None => {}
+            }
+            match self.terminator(&block.terminator) {
+                Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Checks that the IR is well formed: registers are defined once and on
+/// every path to their uses, jumps go to blocks that exist and values have
+/// the types the instructions using them expect.
+pub fn verify(program: &IrProgram) -> Result<(), String> {
+    for function in &program.functions {
+        let mut verifier = Verifier {
+            program,
+            function,
+            block: BlockId(0),
+        };
+        match verifier.function() {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use super::verify;
+    use crate::{
+        ir::ir::{Block, BlockId, Function, IRNode, Instruction, IrProgram, Register, Terminator},
+        parser::{BaseType, Operator, Type, Value},
+    };
+
+    /// A program of one function `f` returning an `i32`, with `registers`
+    /// registers of that type.
+    fn program(registers: usize, blocks: Vec<Block>) -> IrProgram {
+        let function = Function {
+            name: String::from("f"),
+            definition: None,
+            relative_path: PathBuf::from("src/main.eclipse"),
+            line: 1,
+            parameters: Vec::new(),
+            return_type: Some(Type::Base(BaseType::Int32)),
+            registers: vec![Type::Base(BaseType::Int32); registers],
+            slots: Vec::new(),
+            captures: Vec::new(),
+            is_move: false,
+            blocks,
+        };
+        return IrProgram {
+            functions: vec![function],
+            statics: HashMap::new(),
+        };
+    }
+
+    fn block(instructions: Vec<Instruction>, terminator: Terminator) -> Block {
+        return Block {
+            nodes: instructions
+                .into_iter()
+                .map(|instruction| IRNode { line: 2, instruction })
+                .collect(),
+            terminator,
+            line: 3,
+        };
+    }
+
+    #[test]
+    fn registers_are_defined_before_their_use() {
+        let add = Instruction::Binary(Register(1), Operator::Plus, Register(0), Register(0));
+        let one = Instruction::Value(Register(0), Value::Integer(1));
+        let valid = program(2, vec![block(vec![one.clone(), add.clone()], Terminator::Return(Some(Register(1))))]);
+        assert_eq!(verify(&valid), Ok(()));
+        let swapped = program(2, vec![block(vec![add, one], Terminator::Return(Some(Register(1))))]);
+        assert_eq!(verify(&swapped), Err(String::from("fn f, bb0: %0 is used before it is defined")));
+        let missing = program(1, vec![block(Vec::new(), Terminator::Return(Some(Register(0))))]);
+        assert_eq!(verify(&missing), Err(String::from("fn f, bb0: %0 is never defined")));
+    }
+
+    #[test]
+    fn blocks_end_in_a_terminator_that_fits() {
+        let one = Instruction::Value(Register(0), Value::Integer(1));
+        let nothing = program(1, vec![block(vec![one.clone()], Terminator::Return(None))]);
+        assert_eq!(verify(&nothing), Err(String::from("fn f, bb0: returns nothing instead of a `i32`")));
+        let nowhere = program(1, vec![block(vec![one], Terminator::Jump(BlockId(1)))]);
+        assert_eq!(verify(&nowhere), Err(String::from("fn f, bb0: bb1 does not exist")));
+        assert_eq!(verify(&program(0, Vec::new())), Err(String::from("fn f has no blocks")));
+    }
+}
@@ -5,17 +5,7 @@ use std::{path::PathBuf, str::Chars};
 use super::{reader::Reader, token::Token, TokensGroup};
 
 pub fn tokenize(source: String, relative_path: PathBuf) -> Result<TokensGroup, (String, Reader)> {
-    let mut reader;
-    {
-        let split = source.chars();
-        let mut vec: Vec<char> = Vec::new();
-        for char in split {
-            vec.push(char);
-        }
-
-        reader = Reader::new(vec);
-    }
-    // let mut word = tokens.next().unwrap();
+    let mut reader = Reader::new(source.chars().collect());
 
     loop {
         let mut string = match reader.next() {
@@ -24,122 +14,203 @@ pub fn tokenize(source: String, relative_path: PathBuf) -> Result<TokensGroup, (
         };
 
         loop {
-            let length = string.len();
+            match string.as_str() {
+                "" | "\n" | "\t" | "\r" | " " => break,
+                _ => {}
+            }
+            let position = reader.position(string.chars().count());
 
-            if length == 0 {
+            if string.starts_with("//") {
+                // The word already holds the line break when the comment ends with it.
+                if !string.ends_with('\n') {
+                    skip_line(&mut reader);
+                }
                 break;
             }
-            if string == "//" {
-                loop {
-                    match reader.next() {
-                        Some(str) => {
-                            if str == "\n" {
-                                break;
-                            }
+
+            match string.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let (text, rest) = match read_quoted(&mut reader, &string[1..], quote) {
+                        Ok(literal) => literal,
+                        Err(error) => {
+                            // Point at the literal, not at the end of the file.
+                            reader.line = position.0;
+                            return Err((error, reader));
                         }
-                        None => break,
+                    };
+                    let token = match quote {
+                        '"' => Token::String(text),
+                        _ => match is_char(&text) {
+                            Ok(token) => token,
+                            Err(error) => return Err((error, reader)),
+                        },
+                    };
+                    reader.push(token, position);
+                    string = rest;
+                    continue;
+                }
+                _ => {}
+            }
+
+            // The longest prefix that is a token wins, so `==` is not read as two `=`.
+            let ends: Vec<usize> = string
+                .char_indices()
+                .map(|(index, char)| index + char.len_utf8())
+                .collect();
+            let mut found = None;
+            for end in ends.into_iter().rev() {
+                let match_string = &string[..end];
+                match match_word(match_string) {
+                    Some(token) => {
+                        found = Some((token, end));
+                        break;
                     }
+                    None => {}
+                }
+                match is_identifier(match_string) {
+                    Ok(Some(token)) => {
+                        found = Some((token, end));
+                        break;
+                    }
+                    Ok(None) => {}
+                    Err(error) => return Err((error, reader)),
                 }
-                break;
             }
 
-            match string.as_str() {
-                "\n" => break,
-                "\t" => break,
-                "\r" => break,
-                " " => break,
-                _ => {}
+            match found {
+                Some((token, end)) => {
+                    reader.push(token, position);
+                    string = string[end..].to_string();
+                }
+                None => {
+                    let char = string.chars().next().unwrap();
+                    return Err((format!("unknown start of token: `{}`", char), reader));
+                }
             }
+        }
+    }
 
-            for offset in 0..length {
-                let match_string = string.get(..length - offset).unwrap();
-                let token;
+    let position = reader.position(0);
+    reader.push(Token::EndOfFile, position);
+    return Ok(TokensGroup::new(reader.tokens, relative_path));
+}
 
-                if match_string == "//" {
-                    string = match_string.to_string();
+fn skip_line(reader: &mut Reader) {
+    loop {
+        match reader.next() {
+            Some(word) => {
+                if word.ends_with('\n') {
                     break;
                 }
+            }
+            None => break,
+        }
+    }
+}
 
-                match match_word(match_string) {
-                    Some(t) => token = Some(t),
-                    None => match is_identifier(match_string) {
-                        Ok(t) => match t {
-                            Some(t) => token = Some(t),
-                            None => match match_string {
-                                "\"" => {
-                                    let mut token_string = String::new();
-                                    string = string.get(1..).unwrap().to_string();
-
-                                    let mut offset: usize = 0;
-                                    let mut chars: Chars = string.chars();
-
-                                    loop {
-                                        let ch = match chars.next() {
-                                            Some(ch) => ch,
-                                            None => {
-                                                offset = 0;
-                                                string = reader.next().unwrap();
-                                                chars = string.chars();
-                                                continue;
-                                            }
-                                        };
-
-                                        if ch == '"' {
-                                            break;
-                                        } else if ch == '\\' {
-                                            offset += 2;
-                                            match chars.next() {
-                                                Some(chr) => match chr {
-                                                    'n' => token_string.push('\n'),
-                                                    't' => token_string.push('\t'),
-                                                    'r' => token_string.push('\r'),
-                                                    '\\' => token_string.push('\\'),
-                                                    '"' => token_string.push('"'),
-                                                    _ => todo!(),
-                                                },
-                                                None => {
-                                                    return Err((
-                                                        String::from("Expected n,t"),
-                                                        reader,
-                                                    ))
-                                                }
-                                            }
-                                            continue;
-                                        }
-                                        offset += 1;
-                                        token_string.push(ch);
-                                    }
-                                    token = Some(Token::String(token_string));
-                                    string = string.get(offset..).unwrap().to_string();
-                                }
-                                _ => {
-                                    assert!(match_string.len() > 1);
-                                    continue;
-                                }
-                            },
-                        },
-                        Err(error) => return Err((error, reader)),
-                    },
-                }
-
-                string = match string.get(length - offset..) {
-                    Some(s) => s.to_string(),
-                    None => return Err((String::from("Unsuported token"), reader)),
-                };
+/// Reads a string or char literal up to its closing `quote`, pulling in the
+/// following words when the literal contains whitespace. Returns the unescaped
+/// text and what is left of the word after the literal.
+fn read_quoted(reader: &mut Reader, string: &str, quote: char) -> Result<(String, String), String> {
+    let mut text = String::new();
+    let mut word = string.to_string();
+    loop {
+        let mut chars = word.chars();
+        loop {
+            let char = match chars.next() {
+                Some(char) => char,
+                None => break,
+            };
+            if char == quote {
+                return Ok((text, chars.as_str().to_string()));
+            }
+            if char != '\\' {
+                text.push(char);
+                continue;
+            }
+            match escape(&mut chars) {
+                Ok(char) => text.push(char),
+                Err(error) => return Err(error),
+            }
+        }
+        word = match reader.next() {
+            Some(word) => word,
+            None => {
+                return Err(match quote {
+                    '"' => String::from("unterminated double quote string"),
+                    _ => String::from("unterminated character literal"),
+                })
+            }
+        };
+    }
+}
 
-                match token {
-                    Some(t) => {
-                        reader.push(t);
-                        break;
-                    }
-                    None => panic!(),
+/// Unescapes the sequence after a `\`. Only ASCII can be written with `\x`
+/// and `\u{...}` has to name a Unicode scalar value, so every literal is
+/// valid UTF-8.
+fn escape(chars: &mut Chars) -> Result<char, String> {
+    let char = match chars.next() {
+        Some(char) => char,
+        None => return Err(String::from("unterminated escape sequence")),
+    };
+    return match char {
+        'n' => Ok('\n'),
+        't' => Ok('\t'),
+        'r' => Ok('\r'),
+        '0' => Ok('\0'),
+        '\\' | '"' | '\'' => Ok(char),
+        'x' => {
+            let digits: String = chars.by_ref().take(2).collect();
+            if digits.len() != 2 || !digits.chars().all(|char| char.is_ascii_hexdigit()) {
+                return Err(format!("invalid hex escape `\\x{}`", digits));
+            }
+            match u8::from_str_radix(&digits, 16) {
+                Ok(byte) if byte <= 0x7f => Ok(byte as char),
+                _ => Err(format!(
+                    "out of range hex escape `\\x{}`, it must be at most `\\x7f`",
+                    digits
+                )),
+            }
+        }
+        'u' => {
+            if chars.next() != Some('{') {
+                return Err(String::from("incorrect unicode escape sequence, expected `\\u{...}`"));
+            }
+            let mut digits = String::new();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(char) => digits.push(char),
+                    None => return Err(String::from("unterminated unicode escape")),
                 }
             }
+            if digits.len() == 0
+                || digits.len() > 6
+                || !digits.chars().all(|char| char.is_ascii_hexdigit())
+            {
+                return Err(format!("invalid unicode escape `\\u{{{}}}`", digits));
+            }
+            match char::from_u32(u32::from_str_radix(&digits, 16).unwrap()) {
+                Some(char) => Ok(char),
+                None => Err(format!(
+                    "invalid unicode character escape `\\u{{{}}}`, it must be a Unicode scalar value",
+                    digits
+                )),
+            }
         }
-    }
+        char => Err(format!("unknown character escape: `\\{}`", char)),
+    };
+}
 
-    reader.push(Token::EndOfFile);
-    return Ok(TokensGroup::new(reader.tokens, relative_path));
+fn is_char(text: &String) -> Result<Token, String> {
+    let mut chars = text.chars();
+    return match (chars.next(), chars.next()) {
+        (Some(char), None) => Ok(Token::Char(char)),
+        (None, _) => Err(String::from("empty character literal")),
+        (Some(_), Some(_)) => Err(String::from(
+            "character literal may only contain one codepoint",
+        )),
+    };
 }
 
 fn is_integer(source: &String) -> Result<Option<Token>, String> {
@@ -150,9 +221,12 @@ fn is_integer(source: &String) -> Result<Option<Token>, String> {
         return Ok(None);
     }
     let string = source.to_string();
-    return match string.parse::<usize>() {
+    return match string.parse::<u128>() {
         Ok(integer) => Ok(Some(Token::Integer(integer))),
-        Err(a) => Err(format!("{:?}", a)),
+        Err(_) => Err(format!(
+            "integer literal is too large\n   = note: value exceeds limit of `{}`",
+            u128::MAX
+        )),
     };
 }
 
@@ -200,6 +274,9 @@ fn match_word(word: &str) -> Option<Token> {
         ";" => Token::SemiColon,
         "=" => Token::Equals,
         "==" => Token::Compare,
+        "!=" => Token::NotEqual,
+        "<=" => Token::LessEqual,
+        ">=" => Token::GreaterEqual,
         "mut" => Token::Mutable,
         "&" => Token::Reference,
         "_" => Token::Underscore,
@@ -224,8 +301,95 @@ fn match_word(word: &str) -> Option<Token> {
         "give" => Token::Give,
         "loop" => Token::Loop,
         "while" => Token::While,
+        "trait" => Token::Trait,
+        "impl" => Token::Impl,
+        "for" => Token::For,
+        "dyn" => Token::Dyn,
+        "<" => Token::LessThan,
+        ">" => Token::GreaterThan,
+        "|" => Token::Pipe,
+        "move" => Token::Move,
+        "const" => Token::Const,
+        "static" => Token::Static,
+        "type" => Token::Type,
+        "#" => Token::Hash,
+        "extern" => Token::Extern,
+        "..." => Token::Ellipsis,
+        "as" => Token::As,
+        "?" => Token::Question,
+        "!" => Token::Exclamation,
         _ => return None,
     };
 
     return Some(token);
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::tokenize;
+    use crate::lexer::Token;
+
+    /// The tokens of the source with their line and column, without the end of file.
+    fn tokens(source: &str) -> Vec<(Token, usize, usize)> {
+        let mut group = match tokenize(String::from(source), PathBuf::from("main.eclipse")) {
+            Ok(group) => group,
+            Err((error, _)) => panic!("{}", error),
+        };
+        let mut tokens = Vec::new();
+        loop {
+            let info = group.advance().unwrap();
+            if info.token == Token::EndOfFile {
+                return tokens;
+            }
+            tokens.push((info.token, info.line, info.column));
+        }
+    }
+
+    fn error(source: &str) -> (String, usize) {
+        return match tokenize(String::from(source), PathBuf::from("main.eclipse")) {
+            Ok(_) => panic!("`{}` should not tokenize", source),
+            Err((error, reader)) => (error, reader.line),
+        };
+    }
+
+    #[test]
+    fn columns_count_chars_not_bytes() {
+        let tokens = tokens("let s = \"ünï cödé\"; x\n\tlet c = 'ß';");
+        assert_eq!(tokens, [
+            (Token::Variable, 1, 1),
+            (Token::Identifier(String::from("s")), 1, 5),
+            (Token::Equals, 1, 7),
+            (Token::String(String::from("ünï cödé")), 1, 9),
+            (Token::SemiColon, 1, 19),
+            (Token::Identifier(String::from("x")), 1, 21),
+            (Token::Variable, 2, 2),
+            (Token::Identifier(String::from("c")), 2, 6),
+            (Token::Equals, 2, 8),
+            (Token::Char('ß'), 2, 10),
+            (Token::SemiColon, 2, 13),
+        ]);
+        assert_eq!(error("let é = 1;").0, "unknown start of token: `é`");
+    }
+
+    #[test]
+    fn char_literals_hold_one_codepoint() {
+        let tokens = tokens("'a' '\\n' '\\'' ' ' '\\u{1F600}' '\\x41'");
+        let chars: Vec<Token> = tokens.into_iter().map(|(token, _, _)| token).collect();
+        assert_eq!(chars, ['a', '\n', '\'', ' ', '😀', 'A'].map(Token::Char));
+        assert_eq!(error("''").0, "empty character literal");
+        assert_eq!(error("'ab'").0, "character literal may only contain one codepoint");
+        assert_eq!(error("'\\x80'").0, "out of range hex escape `\\x80`, it must be at most `\\x7f`");
+        assert_eq!(
+            error("'\\u{D800}'").0,
+            "invalid unicode character escape `\\u{D800}`, it must be a Unicode scalar value"
+        );
+    }
+
+    #[test]
+    fn unterminated_literals_point_at_their_start() {
+        assert_eq!(error("let a = 1;\nlet s = \"open\n\n\n"), (String::from("unterminated double quote string"), 2));
+        assert_eq!(error("\n\nlet c = 'a"), (String::from("unterminated character literal"), 3));
+    }
+}
@@ -6,8 +6,14 @@ pub struct Reader {
     chars: Vec<char>,
     index: usize,
 
-    pub column: usize,
+    /// Where the word last returned by `next` starts, counted in chars from 1.
     pub line: usize,
+    pub column: usize,
+    /// Number of chars in that word.
+    length: usize,
+
+    next_line: usize,
+    next_column: usize,
 }
 impl Reader {
     pub fn new(chars: Vec<char>) -> Self {
@@ -15,24 +21,32 @@ impl Reader {
             tokens: Vec::new(),
             chars,
             index: 0,
-            column: 0,
-            line: 1
+            line: 1,
+            column: 1,
+            length: 0,
+            next_line: 1,
+            next_column: 1,
         }
     }
+    /// Returns the next word, which ends with the first whitespace char after it.
     pub fn next(&mut self) -> Option<String> {
         let mut string = String::new();
+        self.line = self.next_line;
+        self.column = self.next_column;
+        self.length = 0;
         loop {
             match self.chars.get(self.index) {
                 Some(schar) => {
                     string.push(schar.clone());
 
                     self.index += 1;
-                    self.column += 1;
+                    self.length += 1;
+                    self.next_column += 1;
 
                     match schar {
                         '\n' => {
-                            self.line += 1;
-                            self.column = 0;
+                            self.next_line += 1;
+                            self.next_column = 1;
                             break
                         },
                         '\r' => break,
@@ -49,8 +63,12 @@ impl Reader {
         }
         return Some(string);
     }
-    pub fn push(&mut self, token: Token) {
-        // let len = token.len();
-        self.tokens.push(TokenInfo::new(token, self.line, self.column))
+    /// Line and column of the char `remaining` chars before the end of the
+    /// current word.
+    pub fn position(&self, remaining: usize) -> (usize, usize) {
+        return (self.line, self.column + self.length - remaining);
+    }
+    pub fn push(&mut self, token: Token, (line, column): (usize, usize)) {
+        self.tokens.push(TokenInfo::new(token, line, column))
     }
 }
@@ -7,11 +7,15 @@ mod lexer;
 mod parser;
 mod codegen;
 mod analyzer;
+mod ir;
 
-pub use builder::build;
+pub use builder::{build, compile, Compiled, Emit};
 
 pub const FILE_EXTENSION: &str = "eclipse";
 
+/// Size in bytes of pointers, `isize` and `usize` on the target.
+pub const POINTER_SIZE: usize = 8;
+
 pub fn open_file(path: &PathBuf) -> Result<std::fs::File, CompileError> {
     let file = match std::fs::File::open(path) {
         Ok(file) => file,
@@ -26,14 +30,25 @@ pub fn read_file(path: &PathBuf) -> Result<String, CompileError> {
         Err(error) => return Err(error),
     };
 
-    let mut buf = String::new();
+    let mut buf = Vec::new();
 
-    match file.read_to_string(&mut buf) {
+    match file.read_to_end(&mut buf) {
         Ok(_) => {}
         Err(error) => return Err(CompileError::OpenFile(error)),
     }
 
-    return Ok(buf);
+    return match String::from_utf8(buf) {
+        Ok(source) => Ok(source),
+        Err(error) => {
+            let valid = &error.as_bytes()[..error.utf8_error().valid_up_to()];
+            let line = valid.iter().filter(|byte| **byte == b'\n').count() + 1;
+            Err(CompileError::BuildProblem(BuildProblem::new(
+                BuildError::InvalidUtf8,
+                path.clone(),
+                line,
+            )))
+        }
+    };
 }
 
 pub fn execute(command: String) -> Result<String, String> {
@@ -92,6 +107,94 @@ pub enum BuildError {
     ExpressionExpected,
     Peekfail,
     NoTokenFound,
+    AlreadyDefined(String),
+    /// A name defined again, with where it was defined first.
+    Redefined(String, PathBuf, usize),
+    NotDefined(String),
+    MissingFunctionBody(String),
+    UndefinedTrait(String),
+    NotATraitMethod(String, String),
+    IncompatibleTraitMethod(String, String, String, String),
+    MissingTraitMethod(String, String, String),
+    NotObjectSafe(String, String, String, PathBuf, usize),
+    ClosureMayOutlive(String),
+    NotConstant(String),
+    CyclicConstant(Vec<String>),
+    OutOfRange(String, String),
+    DivisionByZero,
+    ConstantOverflow,
+    NotMutable(String),
+    MutableStaticOutsideUnsafe(String),
+    CyclicTypeAlias(Vec<String>),
+    ExpectedItemAfterAttributes,
+    UnknownAttribute(String),
+    MisplacedAttribute(String, &'static str),
+    MalformedAttribute(String, &'static str),
+    UnsupportedAbi(String),
+    NotFfiSafe(String, String),
+    InvalidCast(String, String),
+    UnsafeCast(String, String),
+    TryInIncompatibleFunction(String),
+    TryOnIncompatibleType(String),
+    TryMismatch(String, String),
+    FormatStringExpected(String),
+    InvalidFormatString(String),
+    FormatArgumentCount(usize, usize),
+    NotFormattable(String, &'static str),
+    RequiredAfterDefault(String),
+    DefaultOnMethod(String),
+    DefaultUsesParameter(String, String),
+    NamedArgumentsUnsupported,
+    /// Code the backend cannot generate yet.
+    Unsupported(String),
+    UnknownArgument(String, String),
+    DuplicateArgument(String),
+    PositionalAfterNamed,
+    MissingArgument(String, String),
+    TooManyArguments(String, usize, usize),
+    TooFewArguments(String, usize, usize),
+    AsmOutsideUnsafe,
+    ForeignCallOutsideUnsafe(String),
+    ReservedName(String),
+    UnknownRegister(String),
+    ReservedRegister(String),
+    DuplicateAsmRegister(String),
+    AsmOperandType(String),
+    MissingOperatorImpl(&'static str, String, &'static str),
+    InvalidOperands(&'static str, String),
+    InvalidUtf8,
+    CaptureInItem(String),
+    /// The files of an import cycle, starting and ending with the same one.
+    CyclicImport(Vec<String>),
+    /// What is private, and where it is declared.
+    Private(String, PathBuf, usize),
+    UndefinedType(String),
+    StatementOutsideFunction,
+    /// The type that was expected, and the one that was found.
+    MismatchedTypes(String, String),
+    /// The field, and the type that does not have it.
+    NoField(String, String),
+    /// The method, and the type that does not have it.
+    NoMethod(String, String),
+    /// What is called, and its type.
+    NotAFunction(String, String),
+    /// The type that could not be inferred, and the local that has it.
+    TypeAnnotationsNeeded(String, Option<String>),
+    /// The method called on an integer literal of no known type.
+    AmbiguousNumeric(String),
+    /// The function, and the type it returns.
+    MissingReturn(String, String),
+    /// The local, where it is declared, and the lines of the paths on which
+    /// it is not assigned if it is on others.
+    UsedUninitialized(String, PathBuf, usize, Vec<usize>),
+    /// The local, and where it is first assigned.
+    AssignTwice(String, PathBuf, usize),
+    AssignToImmutableArgument(String),
+    /// A local of the enclosing function a closure assigns to.
+    AssignToImmutable(String),
+    BorrowImmutable(String),
+    /// The place a `&mut self` method is called on through a `&`, if it has a name.
+    BorrowBehindReference(Option<String>),
 }
 impl BuildError {
     fn stringify(self) -> String {
@@ -100,6 +203,310 @@ impl BuildError {
                 "expected: {:?} got: {:?}:{}:{}",
                 expected, got.token, got.line, got.column
             ),
+            BuildError::AlreadyDefined(name) => {
+                format!("the name `{}` is defined multiple times", name)
+            }
+            BuildError::Redefined(name, path, line) => format!(
+                "the name `{}` is defined multiple times\n   = note: previous definition of `{}` at {}:{}",
+                name,
+                name,
+                path.to_string_lossy(),
+                line
+            ),
+            BuildError::NotATraitMethod(trait_name, method) => {
+                format!("method `{}` is not a member of trait `{}`", method, trait_name)
+            }
+            BuildError::IncompatibleTraitMethod(trait_name, method, expected, found) => format!(
+                "method `{}` has an incompatible type for trait `{}`\n   = note: expected signature `{}`\n   = note:    found signature `{}`",
+                method, trait_name, expected, found
+            ),
+            BuildError::MissingTraitMethod(trait_name, method, target) => format!(
+                "not all trait items implemented, missing `{}` from `{}` in impl for `{}`",
+                method, trait_name, target
+            ),
+            BuildError::NotObjectSafe(trait_name, method, reason, path, line) => format!(
+                "the trait `{}` cannot be made into an object\n   = note: method `{}` {}, `{}` is defined at {}:{}",
+                trait_name,
+                method,
+                reason,
+                trait_name,
+                path.to_string_lossy(),
+                line
+            ),
+            BuildError::ClosureMayOutlive(name) => format!(
+                "closure may outlive the current function, but it borrows `{}`\n   = help: use `move` to capture `{}` by value",
+                name, name
+            ),
+            BuildError::NotConstant(what) => {
+                format!("{} cannot be evaluated at compile time", what)
+            }
+            BuildError::CyclicConstant(cycle) => format!(
+                "cycle detected when evaluating constant `{}`\n   = note: {}",
+                cycle[0],
+                cycle.join(" -> ")
+            ),
+            BuildError::OutOfRange(value, value_type) => {
+                format!("literal out of range for `{}`: `{}`", value_type, value)
+            }
+            BuildError::DivisionByZero => String::from("attempt to divide by zero"),
+            BuildError::ConstantOverflow => {
+                String::from("this arithmetic operation will overflow")
+            }
+            BuildError::NotMutable(name) => format!("cannot assign to immutable `{}`", name),
+            BuildError::MutableStaticOutsideUnsafe(name) => format!(
+                "use of mutable static `{}` is unsafe and requires unsafe function or block",
+                name
+            ),
+            BuildError::CyclicTypeAlias(cycle) => format!(
+                "cycle detected when expanding type alias `{}`\n   = note: {}",
+                cycle[0],
+                cycle.join(" -> ")
+            ),
+            BuildError::ExpectedItemAfterAttributes => {
+                String::from("expected item after attributes")
+            }
+            BuildError::UnknownAttribute(name) => {
+                format!("cannot find attribute `{}` in this scope", name)
+            }
+            BuildError::MisplacedAttribute(name, target) => {
+                format!("attribute `#[{}]` cannot be applied to a {}", name, target)
+            }
+            BuildError::MalformedAttribute(name, template) => format!(
+                "malformed `{}` attribute input\n   = help: must be of the form `{}`",
+                name, template
+            ),
+            BuildError::UnsupportedAbi(abi) => format!("invalid ABI: found `{}`, expected `C`", abi),
+            BuildError::NotFfiSafe(t, name) => format!(
+                "`extern` fn `{}` uses type `{}`, which is not FFI-safe",
+                name, t
+            ),
+            BuildError::InvalidCast(from, to) => {
+                format!("casting `{}` as `{}` is invalid", from, to)
+            }
+            BuildError::UnsafeCast(from, to) => format!(
+                "casting `{}` as `{}` is unsafe and requires unsafe function or block",
+                from, to
+            ),
+            BuildError::TryInIncompatibleFunction(return_type) => format!(
+                "the `?` operator can only be used in a function that returns `Result` or `Option`, found `{}`",
+                return_type
+            ),
+            BuildError::TryOnIncompatibleType(t) => format!(
+                "the `?` operator can only be applied to values of type `Result` or `Option`, found `{}`",
+                t
+            ),
+            BuildError::TryMismatch(operand, return_type) => format!(
+                "`?` cannot convert `{}` into the return type `{}`",
+                operand, return_type
+            ),
+            BuildError::FormatStringExpected(name) => {
+                format!("`{}` expects a string literal as its first argument", name)
+            }
+            BuildError::InvalidFormatString(reason) => format!("invalid format string: {}", reason),
+            BuildError::FormatArgumentCount(placeholders, arguments) => format!(
+                "{} {} in format string, but {} {} given",
+                placeholders,
+                match placeholders {
+                    1 => "placeholder",
+                    _ => "placeholders",
+                },
+                arguments,
+                match arguments {
+                    1 => "argument was",
+                    _ => "arguments were",
+                }
+            ),
+            BuildError::NotFormattable(t, placeholder) => {
+                format!("`{}` cannot be formatted with `{}`", t, placeholder)
+            }
+            BuildError::RequiredAfterDefault(parameter) => format!(
+                "parameter `{}` needs a default value because an earlier parameter has one",
+                parameter
+            ),
+            BuildError::DefaultOnMethod(name) => format!(
+                "default values are not supported on parameters of the method `{}`",
+                name
+            ),
+            BuildError::DefaultUsesParameter(parameter, used) => format!(
+                "the default value of `{}` cannot use the parameter `{}`",
+                parameter, used
+            ),
+            BuildError::Unsupported(what) => format!("{} is not supported by the backend yet", what),
+            BuildError::NamedArgumentsUnsupported => {
+                String::from("named arguments can only be passed to functions")
+            }
+            BuildError::UnknownArgument(function, argument) => {
+                format!("function `{}` has no parameter named `{}`", function, argument)
+            }
+            BuildError::DuplicateArgument(argument) => {
+                format!("argument `{}` is given more than once", argument)
+            }
+            BuildError::PositionalAfterNamed => {
+                String::from("positional arguments cannot follow named arguments")
+            }
+            BuildError::MissingArgument(function, parameter) => {
+                format!("missing argument `{}` in call to `{}`", parameter, function)
+            }
+            BuildError::TooManyArguments(function, expected, found) => format!(
+                "function `{}` takes {} {} but {} were given",
+                function,
+                expected,
+                match expected {
+                    1 => "argument",
+                    _ => "arguments",
+                },
+                found
+            ),
+            BuildError::TooFewArguments(function, expected, found) => format!(
+                "function `{}` takes {} {} but {} were given\n   = note: default arguments are only filled in when a function is called by its name",
+                function,
+                expected,
+                match expected {
+                    1 => "argument",
+                    _ => "arguments",
+                },
+                found
+            ),
+            BuildError::AsmOutsideUnsafe => {
+                String::from("use of inline assembly is unsafe and requires an unsafe block")
+            }
+            BuildError::ForeignCallOutsideUnsafe(name) => format!(
+                "call to unsafe function `{}` is unsafe and requires unsafe function or block\n   = note: functions declared in an `extern` block are not checked by the compiler",
+                name
+            ),
+            BuildError::ReservedName(name) => format!(
+                "the name `{}` is reserved\n   = note: names starting with `{}` are used by the compiler",
+                name,
+                codegen::RESERVED_PREFIX
+            ),
+            BuildError::UnknownRegister(register) => format!(
+                "invalid register `{}`, operands take a 64-bit general purpose register",
+                register
+            ),
+            BuildError::ReservedRegister(register) => format!(
+                "register `{}` is used by the stack frame and cannot be an operand",
+                register
+            ),
+            BuildError::DuplicateAsmRegister(register) => {
+                format!("register `{}` is allocated to more than one operand", register)
+            }
+            BuildError::AsmOperandType(t) => format!(
+                "cannot pass a value of type `{}` in a register, operands must be integers, bools or pointers",
+                t
+            ),
+            BuildError::MissingOperatorImpl(operator, t, trait_name) => format!(
+                "binary operation `{}` cannot be applied to type `{}`\n   = note: an implementation of `{}` is missing for `{}`",
+                operator, t, trait_name, t
+            ),
+            BuildError::InvalidOperands(operator, t) => {
+                format!("binary operation `{}` cannot be applied to type `{}`", operator, t)
+            }
+            BuildError::Tokenize(message) => message,
+            BuildError::CaptureInItem(name) => format!(
+                "can't capture dynamic environment in a nested item, `{}` is a local of the enclosing function\n   = help: use the closure form `|| {{ ... }}` instead",
+                name
+            ),
+            BuildError::InvalidUtf8 => String::from("source file is not valid UTF-8"),
+            BuildError::AlreadyImported(name) => {
+                format!("the module `{}` is imported multiple times", name)
+            }
+            BuildError::CannotFindModules([file, module]) => format!(
+                "file not found for module\n   = help: create `{}` or `{}`",
+                file.to_string_lossy(),
+                module.to_string_lossy()
+            ),
+            BuildError::NotDefined(name) => format!("cannot find `{}` in this scope", name),
+            BuildError::UndefinedType(name) => format!("cannot find type `{}` in this scope", name),
+            BuildError::UndefinedTrait(name) => format!("cannot find trait `{}` in this scope", name),
+            BuildError::MismatchedTypes(expected, found) => {
+                format!("mismatched types: expected `{}`, found `{}`", expected, found)
+            }
+            BuildError::NoField(field, t) => format!("no field `{}` on type `{}`", field, t),
+            BuildError::NoMethod(method, t) => format!("no method named `{}` found for `{}`", method, t),
+            BuildError::NotAFunction(name, t) => {
+                format!("expected function, found `{}` of type `{}`", name, t)
+            }
+            BuildError::TypeAnnotationsNeeded(t, local) => match local {
+                Some(local) => format!(
+                    "type annotations needed for `{}`\n   = help: consider giving `{}` an explicit type",
+                    t, local
+                ),
+                None => format!("type annotations needed for `{}`", t),
+            },
+            BuildError::AmbiguousNumeric(method) => format!(
+                "can't call method `{}` on ambiguous numeric type `{{integer}}`\n   = help: give the value a type, like `let x: i32 = 1;`",
+                method
+            ),
+            BuildError::MissingReturn(function, t) => format!(
+                "function `{}` can reach its end without returning a `{}`\n   = help: add a `return` at the end of every path",
+                function, t
+            ),
+            BuildError::UsedUninitialized(local, path, line, paths) => match paths.is_empty() {
+                true => format!(
+                    "used binding `{}` isn't initialized\n   = note: `{}` declared here but left uninitialized: {}:{}\n   = help: assign `{}` a value before reading it",
+                    local,
+                    local,
+                    path.to_string_lossy(),
+                    line,
+                    local
+                ),
+                false => {
+                    let mut message = format!(
+                        "used binding `{}` is possibly-uninitialized\n   = note: `{}` declared here without a value: {}:{}",
+                        local,
+                        local,
+                        path.to_string_lossy(),
+                        line
+                    );
+                    for unassigned in paths {
+                        message.push_str(&format!(
+                            "\n   = note: it is not assigned on the path through {}:{}",
+                            path.to_string_lossy(),
+                            unassigned
+                        ));
+                    }
+                    message
+                }
+            },
+            BuildError::AssignTwice(local, path, line) => format!(
+                "cannot assign twice to immutable variable `{}`\n   = note: first assignment at {}:{}\n   = help: consider making this binding mutable: `mut {}`",
+                local,
+                path.to_string_lossy(),
+                line,
+                local
+            ),
+            BuildError::AssignToImmutableArgument(parameter) => format!(
+                "cannot assign to immutable argument `{}`\n   = help: consider making this binding mutable: `mut {}`",
+                parameter, parameter
+            ),
+            BuildError::AssignToImmutable(local) => format!(
+                "cannot assign to `{}`, as it is not declared as mutable\n   = help: consider changing this to be mutable: `mut {}`",
+                local, local
+            ),
+            BuildError::BorrowImmutable(local) => format!(
+                "cannot borrow `{}` as mutable, as it is not declared as mutable\n   = help: consider changing this to be mutable: `mut {}`",
+                local, local
+            ),
+            BuildError::BorrowBehindReference(Some(place)) => {
+                format!("cannot borrow `{}` as mutable, as it is behind a `&` reference", place)
+            }
+            BuildError::BorrowBehindReference(None) => {
+                String::from("cannot borrow data in a `&` reference as mutable")
+            }
+            BuildError::StatementOutsideFunction => String::from(
+                "expected an item, found a statement\n   = help: statements can only appear inside a function body",
+            ),
+            BuildError::Private(what, path, line) => format!(
+                "{} is private\n   = note: declared private at {}:{}",
+                what,
+                path.to_string_lossy(),
+                line
+            ),
+            BuildError::CyclicImport(cycle) => format!(
+                "cycle detected when importing `{}`\n   = note: {}",
+                cycle[0],
+                cycle.join(" -> ")
+            ),
             token => format!("{:?}", token),
         };
     }
@@ -130,6 +537,66 @@ impl BuildProblem {
     }
 }
 
+/// Something that compiles, but is most likely not what was meant.
+#[derive(Debug)]
+pub enum BuildWarning {
+    UnreachableStatement,
+    /// A `loop` nothing returns from.
+    InfiniteLoop,
+    /// What the branch always depends on.
+    UnreachableBranch(String),
+    /// A `mut` local that is never assigned again or borrowed mutably.
+    UnusedMut(String),
+    /// The kind and name of a `#[deprecated]` item that is used, with the
+    /// note the attribute gives.
+    Deprecated(&'static str, String, Option<String>),
+}
+impl BuildWarning {
+    fn stringify(&self) -> String {
+        return match self {
+            BuildWarning::UnreachableStatement => String::from(
+                "unreachable statement\n   = note: any code following a `return` or an endless `loop` is never run",
+            ),
+            BuildWarning::InfiniteLoop => String::from(
+                "this loop never ends\n   = note: nothing inside it returns, so it runs until the program is stopped",
+            ),
+            BuildWarning::UnreachableBranch(reason) => format!("this branch can never run, as {}", reason),
+            BuildWarning::UnusedMut(local) => format!(
+                "variable `{}` does not need to be mutable\n   = help: remove this `mut`",
+                local
+            ),
+            BuildWarning::Deprecated(kind, name, note) => match note {
+                Some(note) => format!("use of deprecated {} `{}`: {}", kind, name, note),
+                None => format!("use of deprecated {} `{}`", kind, name),
+            },
+        };
+    }
+}
+
+#[derive(Debug)]
+pub struct Warning {
+    relative_path: PathBuf,
+    line: usize,
+    warning: BuildWarning,
+}
+impl Warning {
+    pub fn new(warning: BuildWarning, relative_path: PathBuf, line: usize) -> Self {
+        Self {
+            relative_path,
+            line,
+            warning,
+        }
+    }
+    pub fn print(&self) {
+        println!("warning: {}", self.warning.stringify());
+        println!(
+            "   --> {}:{}",
+            self.relative_path.to_string_lossy(),
+            self.line
+        );
+    }
+}
+
 #[derive(Debug)]
 pub enum CompileError {
     OpenFile(std::io::Error),
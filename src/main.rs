@@ -1,4 +1,4 @@
-use eclipse::build;
+use eclipse::{build, Emit};
 use std::{
     env,
     io::{BufRead, BufReader},
@@ -28,8 +28,17 @@ fn main() {
         _ => return println!("{:?} is not a valid argument", action),
     };
 
+    let mut emit = Vec::new();
+    for argument in arguments {
+        match argument.as_str() {
+            "--emit=ir" => emit.push(Emit::Ir),
+            "--emit=cfg-dot" => emit.push(Emit::CfgDot),
+            _ => return println!("{:?} is not a valid argument", argument),
+        }
+    }
+
     if action == Action::Build || action == Action::BuildAndRun {
-        let executable = match build(project_dir) {
+        let executable = match build(project_dir, &emit) {
             Ok(path) => path,
             Err(a) => {
                 a.print();
@@ -72,7 +81,7 @@ mod tests {
         const SOURCE: &str = "C:/Users/Gebruiker/Documents/eclipse/first_project/";
         // const NAME: &str = "app";
 
-        let executable_path = match build(PathBuf::from(SOURCE)) {
+        let executable_path = match build(PathBuf::from(SOURCE), &Vec::new()) {
             Ok(path) => path,
             Err(a) => {
                 a.print();
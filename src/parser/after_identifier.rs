@@ -4,7 +4,7 @@ use crate::{
 };
 
 use super::{
-    arguments::parse_arguments, expression::parse_expression, path::parse_path,
+    arguments::parse_arguments, asm::parse_asm, expression::parse_expression, path::parse_path,
     tokens_expected_got, ASTNode, Node,
 };
 
@@ -48,10 +48,38 @@ pub fn parse_after_identifier(
 
             ASTNode::new(tokens.current.line, Node::Call(path, arguments))
         }
+        Token::Dot => {
+            let method = match parse_identifer_string(tokens) {
+                Ok(name) => name,
+                Err(error) => return Err(error),
+            };
+            match tokens.advance() {
+                Ok(info) => match info.token {
+                    Token::OpenParen => {}
+                    _ => return Err(tokens_expected_got(tokens, vec![Token::OpenParen], info)),
+                },
+                Err(error) => return Err(error),
+            }
+            let arguments = match parse_arguments(tokens) {
+                Ok(args) => args,
+                Err(error) => return Err(error),
+            };
+
+            ASTNode::new(
+                tokens.current.line,
+                Node::MethodCall(path, method, arguments),
+            )
+        }
+        Token::Exclamation if path.root == "asm" && path.location.len() == 0 => {
+            match parse_asm(tokens) {
+                Ok(node) => node,
+                Err(error) => return Err(error),
+            }
+        }
         _ => {
             return Err(tokens_expected_got(
                 tokens,
-                vec![Token::Equals, Token::OpenParen],
+                vec![Token::Equals, Token::OpenParen, Token::Dot],
                 info,
             ))
         }
@@ -0,0 +1,47 @@
+use crate::{
+    lexer::{Token, TokensGroup},
+    CompileError,
+};
+
+use super::{
+    after_identifier::parse_identifer_string, tokens_expected_got, types::parse_type, ASTNode, Node,
+};
+
+/// Parses `type Name = Type;` after the `type` keyword.
+pub fn parse_type_alias(tokens: &mut TokensGroup, export: bool) -> Result<ASTNode, CompileError> {
+    let line = tokens.current.line;
+
+    let name = match parse_identifer_string(tokens) {
+        Ok(name) => name,
+        Err(error) => return Err(error),
+    };
+
+    match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::Equals => {}
+            _ => return Err(tokens_expected_got(tokens, vec![Token::Equals], info)),
+        },
+        Err(error) => return Err(error),
+    }
+    let aliased = match parse_type(tokens) {
+        Ok(t) => t,
+        Err(error) => return Err(error),
+    };
+
+    match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::SemiColon => {}
+            _ => return Err(tokens_expected_got(tokens, vec![Token::SemiColon], info)),
+        },
+        Err(error) => return Err(error),
+    }
+
+    return Ok(ASTNode::new(
+        line,
+        Node::TypeAlias {
+            export,
+            name,
+            aliased,
+        },
+    ));
+}
@@ -1,20 +1,44 @@
 use crate::{
     lexer::{Token, TokensGroup},
-    CompileError,
+    BuildError, BuildProblem, CompileError,
 };
 
 use super::{expression::parse_expression, tokens_expected_got, Expression};
 
+/// Parses the arguments of a call up to the closing parenthesis. Arguments
+/// written as `name: expression` become named arguments.
 pub fn parse_arguments(tokens: &mut TokensGroup) -> Result<Vec<Expression>, CompileError> {
     let mut expressions = Vec::new();
     loop {
-        match parse_expression(tokens) {
+        let expression = match parse_expression(tokens) {
             Ok(expression) => match expression {
-                Some(expression) => expressions.push(expression),
+                Some(expression) => expression,
                 None => break,
             },
             Err(error) => return Err(error),
         };
+        let info = match tokens.peek() {
+            Ok(info) => info,
+            Err(error) => return Err(error),
+        };
+        match (info.token, expression) {
+            (Token::Colon, Expression::GetVariable(path)) if path.location.len() == 0 => {
+                tokens.advance().unwrap();
+                let value = match parse_expression(tokens) {
+                    Ok(Some(value)) => value,
+                    Ok(None) => {
+                        return Err(CompileError::BuildProblem(BuildProblem::new(
+                            BuildError::ExpressionExpected,
+                            tokens.relative_path.clone(),
+                            tokens.current.line,
+                        )))
+                    }
+                    Err(error) => return Err(error),
+                };
+                expressions.push(Expression::NamedArgument(path.root, Box::new(value)));
+            }
+            (_, expression) => expressions.push(expression),
+        }
         match tokens.advance() {
             Ok(info) => match info.token {
                 Token::Comma => continue,
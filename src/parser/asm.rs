@@ -0,0 +1,170 @@
+use crate::{
+    lexer::{Token, TokensGroup},
+    BuildError, BuildProblem, CompileError,
+};
+
+use super::{
+    expression::parse_expression, path::parse_path, tokens_expected_got, ASTNode, AsmOperand,
+    Node,
+};
+
+/// Parses `("template", ..., operand, ...)` after `asm!`. Operands are
+/// `in("reg") expression`, `out("reg") variable`, `out("reg") _`,
+/// `inout("reg") variable` and `clobber("reg", ...)`.
+pub fn parse_asm(tokens: &mut TokensGroup) -> Result<ASTNode, CompileError> {
+    let line = tokens.current.line;
+
+    match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::OpenParen => {}
+            _ => return Err(tokens_expected_got(tokens, vec![Token::OpenParen], info)),
+        },
+        Err(error) => return Err(error),
+    }
+
+    let mut template = Vec::new();
+    let mut operands = Vec::new();
+    loop {
+        let info = match tokens.advance() {
+            Ok(info) => info,
+            Err(error) => return Err(error),
+        };
+        match info.token.clone() {
+            Token::CloseParen => break,
+            Token::String(string) if operands.len() == 0 => template.push(string),
+            Token::Identifier(kind) => {
+                let registers = match parse_registers(tokens) {
+                    Ok(registers) => registers,
+                    Err(error) => return Err(error),
+                };
+                let register = registers[0].clone();
+                match kind.as_str() {
+                    "clobber" => {
+                        for register in registers {
+                            operands.push(AsmOperand::Clobber(register));
+                        }
+                    }
+                    _ if registers.len() > 1 => {
+                        return Err(tokens_expected_got(tokens, vec![Token::CloseParen], info))
+                    }
+                    "in" => match parse_expression(tokens) {
+                        Ok(Some(expression)) => operands.push(AsmOperand::In(register, expression)),
+                        Ok(None) => {
+                            return Err(CompileError::BuildProblem(BuildProblem::new(
+                                BuildError::ExpressionExpected,
+                                tokens.relative_path.clone(),
+                                tokens.current.line,
+                            )))
+                        }
+                        Err(error) => return Err(error),
+                    },
+                    "out" | "inout" => {
+                        let info = match tokens.advance() {
+                            Ok(info) => info,
+                            Err(error) => return Err(error),
+                        };
+                        let place = match info.token {
+                            Token::Identifier(name) => match parse_path(tokens, name) {
+                                Ok(path) => Some(path),
+                                Err(error) => return Err(error),
+                            },
+                            Token::Underscore if kind == "out" => None,
+                            _ => {
+                                return Err(tokens_expected_got(
+                                    tokens,
+                                    vec![Token::Identifier(String::from("variable"))],
+                                    info,
+                                ))
+                            }
+                        };
+                        operands.push(match place {
+                            Some(path) if kind == "inout" => AsmOperand::InOut(register, path),
+                            place => AsmOperand::Out(register, place),
+                        });
+                    }
+                    _ => {
+                        return Err(tokens_expected_got(
+                            tokens,
+                            vec![
+                                Token::Identifier(String::from("in")),
+                                Token::Identifier(String::from("out")),
+                                Token::Identifier(String::from("inout")),
+                                Token::Identifier(String::from("clobber")),
+                            ],
+                            info,
+                        ))
+                    }
+                }
+            }
+            _ => {
+                return Err(tokens_expected_got(
+                    tokens,
+                    vec![
+                        Token::String(String::from("template")),
+                        Token::Identifier(String::from("operand")),
+                    ],
+                    info,
+                ))
+            }
+        }
+
+        match tokens.advance() {
+            Ok(info) => match info.token {
+                Token::Comma => {}
+                Token::CloseParen => break,
+                _ => {
+                    return Err(tokens_expected_got(
+                        tokens,
+                        vec![Token::Comma, Token::CloseParen],
+                        info,
+                    ))
+                }
+            },
+            Err(error) => return Err(error),
+        }
+    }
+
+    return Ok(ASTNode::new(line, Node::InlineAsm { template, operands }));
+}
+
+/// Parses the `("reg", ...)` after an operand kind.
+fn parse_registers(tokens: &mut TokensGroup) -> Result<Vec<String>, CompileError> {
+    match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::OpenParen => {}
+            _ => return Err(tokens_expected_got(tokens, vec![Token::OpenParen], info)),
+        },
+        Err(error) => return Err(error),
+    }
+    let mut registers = Vec::new();
+    loop {
+        match tokens.advance() {
+            Ok(info) => match info.token {
+                Token::String(register) => registers.push(register),
+                _ => {
+                    return Err(tokens_expected_got(
+                        tokens,
+                        vec![Token::String(String::from("register"))],
+                        info,
+                    ))
+                }
+            },
+            Err(error) => return Err(error),
+        }
+        match tokens.advance() {
+            Ok(info) => match info.token {
+                Token::Comma => {}
+                Token::CloseParen => break,
+                _ => {
+                    return Err(tokens_expected_got(
+                        tokens,
+                        vec![Token::Comma, Token::CloseParen],
+                        info,
+                    ))
+                }
+            },
+            Err(error) => return Err(error),
+        }
+    }
+    return Ok(registers);
+}
@@ -0,0 +1,112 @@
+use crate::{
+    lexer::{Token, TokensGroup},
+    CompileError,
+};
+
+use super::{tokens_expected_got, Attribute};
+
+/// Parses `[name(arguments)]` after the `#` of an attribute.
+pub fn parse_attribute(tokens: &mut TokensGroup) -> Result<Attribute, CompileError> {
+    match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::OpenBracket => {}
+            _ => return Err(tokens_expected_got(tokens, vec![Token::OpenBracket], info)),
+        },
+        Err(error) => return Err(error),
+    }
+
+    let attribute = match parse_meta(tokens) {
+        Ok(attribute) => attribute,
+        Err(error) => return Err(error),
+    };
+
+    match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::CloseBracket => {}
+            _ => return Err(tokens_expected_got(tokens, vec![Token::CloseBracket], info)),
+        },
+        Err(error) => return Err(error),
+    }
+
+    return Ok(attribute);
+}
+
+/// Parses `name`, `name = "value"` or `name(meta, ...)`.
+fn parse_meta(tokens: &mut TokensGroup) -> Result<Attribute, CompileError> {
+    let (line, name) = match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::Identifier(name) => (info.line, name),
+            // Keywords are valid attribute names, as in `#[type = "..."]`.
+            Token::Type => (info.line, String::from("type")),
+            Token::Unsafe => (info.line, String::from("unsafe")),
+            _ => {
+                return Err(tokens_expected_got(
+                    tokens,
+                    vec![Token::Identifier(String::from("attribute"))],
+                    info,
+                ))
+            }
+        },
+        Err(error) => return Err(error),
+    };
+
+    let info = match tokens.peek() {
+        Ok(info) => info,
+        Err(error) => return Err(error),
+    };
+    let mut attribute = Attribute {
+        line,
+        name,
+        value: None,
+        arguments: None,
+    };
+
+    match info.token {
+        Token::Equals => {
+            tokens.advance().unwrap();
+            attribute.value = match tokens.advance() {
+                Ok(info) => match info.token {
+                    Token::String(value) | Token::Identifier(value) => Some(value),
+                    Token::Integer(value) => Some(value.to_string()),
+                    Token::Boolean(value) => Some(value.to_string()),
+                    _ => {
+                        return Err(tokens_expected_got(
+                            tokens,
+                            vec![Token::String(String::from("value"))],
+                            info,
+                        ))
+                    }
+                },
+                Err(error) => return Err(error),
+            };
+        }
+        Token::OpenParen => {
+            tokens.advance().unwrap();
+            let mut arguments = Vec::new();
+            loop {
+                match tokens.peek() {
+                    Ok(info) => match info.token {
+                        Token::CloseParen => {
+                            tokens.advance().unwrap();
+                            break;
+                        }
+                        Token::Comma => {
+                            tokens.advance().unwrap();
+                            continue;
+                        }
+                        _ => {}
+                    },
+                    Err(error) => return Err(error),
+                }
+                match parse_meta(tokens) {
+                    Ok(argument) => arguments.push(argument),
+                    Err(error) => return Err(error),
+                }
+            }
+            attribute.arguments = Some(arguments);
+        }
+        _ => {}
+    }
+
+    return Ok(attribute);
+}
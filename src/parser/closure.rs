@@ -0,0 +1,116 @@
+use crate::{
+    lexer::{Token, TokensGroup},
+    BuildError, BuildProblem, CompileError,
+};
+
+use super::{
+    expression::parse_expression, scope::parse_scope, tokens_expected_got, types::parse_type,
+    ASTNode, Expression, Node,
+};
+
+/// Parses a closure after its opening `|`, e.g. `|x| x + offset` or
+/// `move |a i64, b: i64|: i64 { return a * b; }`.
+pub fn parse_closure(tokens: &mut TokensGroup, is_move: bool) -> Result<Expression, CompileError> {
+    let mut parameters = Vec::new();
+
+    loop {
+        let name = match tokens.advance() {
+            Ok(info) => match info.token {
+                Token::Pipe => break,
+                Token::Identifier(name) => name,
+                _ => {
+                    return Err(tokens_expected_got(
+                        tokens,
+                        vec![Token::Identifier(String::from("parameter")), Token::Pipe],
+                        info,
+                    ))
+                }
+            },
+            Err(error) => return Err(error),
+        };
+
+        let parameter_type = match tokens.peek() {
+            Ok(info) => match info.token {
+                Token::Comma | Token::Pipe => None,
+                // `name: type` is accepted next to the shorter `name type`.
+                Token::Colon => {
+                    tokens.advance().unwrap();
+                    match parse_type(tokens) {
+                        Ok(t) => Some(t),
+                        Err(error) => return Err(error),
+                    }
+                }
+                _ => match parse_type(tokens) {
+                    Ok(t) => Some(t),
+                    Err(error) => return Err(error),
+                },
+            },
+            Err(error) => return Err(error),
+        };
+        parameters.push((name, parameter_type));
+
+        match tokens.advance() {
+            Ok(info) => match info.token {
+                Token::Comma => continue,
+                Token::Pipe => break,
+                _ => {
+                    return Err(tokens_expected_got(
+                        tokens,
+                        vec![Token::Comma, Token::Pipe],
+                        info,
+                    ))
+                }
+            },
+            Err(error) => return Err(error),
+        }
+    }
+
+    let return_type = match tokens.peek() {
+        Ok(info) => match info.token {
+            Token::Colon => {
+                tokens.advance().unwrap();
+                match parse_type(tokens) {
+                    Ok(t) => Some(t),
+                    Err(error) => return Err(error),
+                }
+            }
+            _ => None,
+        },
+        Err(error) => return Err(error),
+    };
+
+    let body = match tokens.peek() {
+        Ok(info) => match info.token {
+            Token::StartScope => {
+                tokens.advance().unwrap();
+                match parse_scope(tokens) {
+                    Ok(body) => body,
+                    Err(error) => return Err(error),
+                }
+            }
+            _ => match parse_expression(tokens) {
+                Ok(Some(expression)) => vec![ASTNode::new(
+                    tokens.current.line,
+                    Node::Return(Some(expression)),
+                )],
+                Ok(None) => {
+                    return Err(CompileError::BuildProblem(BuildProblem::new(
+                        BuildError::ExpressionExpected,
+                        tokens.relative_path.clone(),
+                        tokens.current.line,
+                    )))
+                }
+                Err(error) => return Err(error),
+            },
+        },
+        Err(error) => return Err(error),
+    };
+
+    return Ok(Expression::Closure {
+        is_move,
+        parameters,
+        return_type,
+        body,
+        parameter_definitions: Vec::new(),
+    });
+}
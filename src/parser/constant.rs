@@ -0,0 +1,104 @@
+use crate::{
+    lexer::{Token, TokensGroup},
+    BuildError, BuildProblem, CompileError,
+};
+
+use super::{
+    after_identifier::parse_identifer_string, expression::parse_expression, tokens_expected_got,
+    types::parse_type, ASTNode, Expression, Node, Type,
+};
+
+/// Parses the `NAME: Type = expression;` part shared by `const` and `static`.
+fn parse_item(tokens: &mut TokensGroup) -> Result<(String, Type, Expression), CompileError> {
+    let name = match parse_identifer_string(tokens) {
+        Ok(name) => name,
+        Err(error) => return Err(error),
+    };
+
+    match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::Colon => {}
+            _ => return Err(tokens_expected_got(tokens, vec![Token::Colon], info)),
+        },
+        Err(error) => return Err(error),
+    }
+    let item_type = match parse_type(tokens) {
+        Ok(t) => t,
+        Err(error) => return Err(error),
+    };
+
+    match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::Equals => {}
+            _ => return Err(tokens_expected_got(tokens, vec![Token::Equals], info)),
+        },
+        Err(error) => return Err(error),
+    }
+    let expression = match parse_expression(tokens) {
+        Ok(Some(expression)) => expression,
+        Ok(None) => {
+            return Err(CompileError::BuildProblem(BuildProblem::new(
+                BuildError::ExpressionExpected,
+                tokens.relative_path.clone(),
+                tokens.current.line,
+            )))
+        }
+        Err(error) => return Err(error),
+    };
+
+    match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::SemiColon => {}
+            _ => return Err(tokens_expected_got(tokens, vec![Token::SemiColon], info)),
+        },
+        Err(error) => return Err(error),
+    }
+
+    return Ok((name, item_type, expression));
+}
+
+pub fn parse_constant(tokens: &mut TokensGroup, export: bool) -> Result<ASTNode, CompileError> {
+    let line = tokens.current.line;
+
+    return match parse_item(tokens) {
+        Ok((name, constant_type, expression)) => Ok(ASTNode::new(
+            line,
+            Node::Constant {
+                export,
+                name,
+                constant_type,
+                expression,
+            },
+        )),
+        Err(error) => Err(error),
+    };
+}
+
+pub fn parse_static(tokens: &mut TokensGroup, export: bool) -> Result<ASTNode, CompileError> {
+    let line = tokens.current.line;
+
+    let mutable = match tokens.peek() {
+        Ok(info) => match info.token {
+            Token::Mutable => {
+                tokens.advance().unwrap();
+                true
+            }
+            _ => false,
+        },
+        Err(error) => return Err(error),
+    };
+
+    return match parse_item(tokens) {
+        Ok((name, static_type, expression)) => Ok(ASTNode::new(
+            line,
+            Node::Static {
+                export,
+                mutable,
+                name,
+                static_type,
+                expression,
+            },
+        )),
+        Err(error) => Err(error),
+    };
+}
@@ -4,7 +4,8 @@ use crate::{
 };
 
 use super::{
-    after_identifier::parse_identifer_string, tokens_expected_got, types::parse_type, ASTNode, Node,
+    after_identifier::parse_identifer_string, generics::parse_generics, tokens_expected_got,
+    types::parse_type, ASTNode, Node,
 };
 
 pub fn parse_enum(tokens: &mut TokensGroup, export: bool) -> Result<ASTNode, CompileError> {
@@ -15,6 +16,11 @@ pub fn parse_enum(tokens: &mut TokensGroup, export: bool) -> Result<ASTNode, Com
         Err(error) => return Err(error),
     };
 
+    let generics = match parse_generics(tokens) {
+        Ok(generics) => generics,
+        Err(error) => return Err(error),
+    };
+
     match tokens.advance() {
         Ok(info) => match info.token {
             Token::StartScope => {}
@@ -85,7 +91,7 @@ pub fn parse_enum(tokens: &mut TokensGroup, export: bool) -> Result<ASTNode, Com
         Node::Enum {
             export,
             name,
-            generics: Vec::new(),
+            generics,
             body: body,
         },
     ));
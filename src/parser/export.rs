@@ -4,8 +4,12 @@ use crate::{
 };
 
 use super::{
-    after_identifier::parse_identifer_string, enums::parse_enum, function::parse_function,
-    structs::parse_struct, tokens_expected_got, ASTNode, Node,
+    after_identifier::parse_identifer_string,
+    alias::parse_type_alias,
+    constant::{parse_constant, parse_static},
+    enums::parse_enum,
+    function::parse_function,
+    structs::parse_struct, tokens_expected_got, traits::parse_trait, ASTNode, Node,
 };
 
 pub fn parse_export(tokens: &mut TokensGroup) -> Result<ASTNode, CompileError> {
@@ -19,6 +23,22 @@ pub fn parse_export(tokens: &mut TokensGroup) -> Result<ASTNode, CompileError> {
                 tokens.advance().unwrap();
                 return parse_struct(tokens, true);
             }
+            Token::Trait => {
+                tokens.advance().unwrap();
+                return parse_trait(tokens, true);
+            }
+            Token::Const => {
+                tokens.advance().unwrap();
+                return parse_constant(tokens, true);
+            }
+            Token::Static => {
+                tokens.advance().unwrap();
+                return parse_static(tokens, true);
+            }
+            Token::Type => {
+                tokens.advance().unwrap();
+                return parse_type_alias(tokens, true);
+            }
             Token::Import => {
                 tokens.advance().unwrap();
                 let name = match parse_identifer_string(tokens) {
@@ -5,12 +5,72 @@ use crate::{
 
 use super::{
     arguments::parse_arguments,
+    closure::parse_closure,
     node::{Expression, Value},
     path::parse_path,
-    tokens_expected_got, Operator,
+    tokens_expected_got,
+    types::parse_type,
+    Operator,
 };
 
 pub fn parse_expression(tokens: &mut TokensGroup) -> Result<Option<Expression>, CompileError> {
+    return parse_binary(tokens, 0);
+}
+
+/// Parses operands joined by operators that bind tighter than `precedence`,
+/// grouping operators of the same precedence from the left.
+fn parse_binary(tokens: &mut TokensGroup, precedence: usize) -> Result<Option<Expression>, CompileError> {
+    let mut expression = match parse_operand(tokens) {
+        Ok(Some(expression)) => expression,
+        Ok(None) => return Ok(None),
+        Err(error) => return Err(error),
+    };
+
+    loop {
+        let info = match tokens.peek() {
+            Ok(info) => info,
+            Err(error) => return Err(error),
+        };
+        let operator = match info.token {
+            // A colon ends the name of a named argument.
+            Token::SemiColon | Token::Comma | Token::CloseParen | Token::Colon => {
+                return Ok(Some(expression))
+            }
+            Token::Plus => Operator::Plus,
+            Token::Minus => Operator::Minus,
+            Token::Asterisk => Operator::Multiply,
+            Token::Slash => Operator::Division,
+            Token::Compare => Operator::Equal,
+            Token::NotEqual => Operator::NotEqual,
+            Token::LessThan => Operator::Less,
+            Token::LessEqual => Operator::LessEqual,
+            Token::GreaterThan => Operator::Greater,
+            Token::GreaterEqual => Operator::GreaterEqual,
+            _ => return Err(tokens_expected_got(tokens, vec![Token::SemiColon], info)),
+        };
+        if operator.precedence() <= precedence {
+            return Ok(Some(expression));
+        }
+        tokens.advance().unwrap();
+
+        let second = match parse_binary(tokens, operator.precedence()) {
+            Ok(Some(expression)) => expression,
+            Ok(None) => {
+                return Err(CompileError::BuildProblem(BuildProblem::new(
+                    BuildError::ExpressionExpected,
+                    tokens.relative_path.clone(),
+                    tokens.current.line,
+                )))
+            }
+            Err(error) => return Err(error),
+        };
+        expression = Expression::BinaryOperation(Box::new(expression), operator, Box::new(second));
+    }
+}
+
+/// Parses a single operand together with its method calls, field accesses,
+/// `?` and `as` casts.
+fn parse_operand(tokens: &mut TokensGroup) -> Result<Option<Expression>, CompileError> {
     match tokens.peek() {
         Ok(info) => match info.token {
             Token::SemiColon => return Ok(None),
@@ -35,11 +95,24 @@ pub fn parse_expression(tokens: &mut TokensGroup) -> Result<Option<Expression>,
         Err(error) => return Err(error),
     };
 
-    let expression = match info.token {
+    let mut expression = match info.token {
         Token::Integer(integer) => Expression::Value(match minus {
-            true => Value::Integer(-(integer as isize)),
+            // No integer type goes lower than `i128`.
+            true => match integer <= i128::MIN.unsigned_abs() {
+                true => Value::Integer((integer as i128).wrapping_neg()),
+                false => {
+                    return Err(CompileError::BuildProblem(BuildProblem::new(
+                        BuildError::OutOfRange(format!("-{}", integer), String::from("i128")),
+                        tokens.relative_path.clone(),
+                        tokens.current.line,
+                    )))
+                }
+            },
             false => Value::UInteger(integer),
         }),
+        Token::String(string) => Expression::Value(Value::String(string)),
+        Token::Boolean(boolean) => Expression::Value(Value::Boolean(boolean)),
+        Token::Char(char) => Expression::Value(Value::Char(char)),
         Token::Identifier(name) => {
             let path = match parse_path(tokens, name) {
                 Ok(path) => path,
@@ -64,48 +137,127 @@ pub fn parse_expression(tokens: &mut TokensGroup) -> Result<Option<Expression>,
                 Err(error) => return Err(error),
             }
         }
+        Token::Reference => {
+            let mutable = match tokens.peek() {
+                Ok(info) => match info.token {
+                    Token::Mutable => {
+                        tokens.advance().unwrap();
+                        true
+                    }
+                    _ => false,
+                },
+                Err(error) => return Err(error),
+            };
+            let path = match tokens.advance() {
+                Ok(info) => match info.token {
+                    Token::Identifier(name) => match parse_path(tokens, name) {
+                        Ok(path) => path,
+                        Err(error) => return Err(error),
+                    },
+                    _ => {
+                        return Err(tokens_expected_got(
+                            tokens,
+                            vec![Token::Identifier(String::from("variable"))],
+                            info,
+                        ))
+                    }
+                },
+                Err(error) => return Err(error),
+            };
+
+            Expression::Reference(mutable, Box::new(Expression::GetVariable(path)))
+        }
+        Token::Pipe => match parse_closure(tokens, false) {
+            Ok(closure) => closure,
+            Err(error) => return Err(error),
+        },
+        Token::Move => {
+            match tokens.advance() {
+                Ok(info) => match info.token {
+                    Token::Pipe => {}
+                    _ => return Err(tokens_expected_got(tokens, vec![Token::Pipe], info)),
+                },
+                Err(error) => return Err(error),
+            }
+            match parse_closure(tokens, true) {
+                Ok(closure) => closure,
+                Err(error) => return Err(error),
+            }
+        }
         _ => return Ok(None),
     };
 
-    match tokens.peek() {
-        Ok(info) => match info.token {
-            Token::SemiColon | Token::Comma | Token::CloseParen => return Ok(Some(expression)),
-            Token::Plus | Token::Minus | Token::Asterisk | Token::Slash => {
-                tokens.advance().unwrap();
-
-                let operator = match info.token {
-                    Token::Plus => Operator::Plus,
-                    Token::Minus => Operator::Minus,
-                    Token::Asterisk => Operator::Multiply,
-                    Token::Slash => Operator::Division,
-                    _ => panic!(),
-                };
-
-                let second = match parse_expression(tokens) {
-                    Ok(expression) => match expression {
-                        Some(expression) => expression,
-                        None => {
-                            return Err(CompileError::BuildProblem(BuildProblem::new(
-                                BuildError::ExpressionExpected,
-                                tokens.relative_path.clone(),
-                                tokens.current.line,
-                            )))
-                        }
-                    },
-                    Err(error) => return Err(error),
-                };
-                return Ok(Some(Expression::BinaryOperation(
-                    Box::new(expression),
-                    operator,
-                    Box::new(second),
-                )));
-            }
-            _ => return Err(tokens_expected_got(tokens, vec![Token::SemiColon], info)),
-        },
-        Err(error) => return Err(error),
+    loop {
+        match tokens.peek() {
+            Ok(info) => match info.token {
+                Token::Dot => {
+                    tokens.advance().unwrap();
+                }
+                Token::Question => {
+                    tokens.advance().unwrap();
+                    expression = Expression::Try(Box::new(expression));
+                    continue;
+                }
+                _ => break,
+            },
+            Err(error) => return Err(error),
+        }
+
+        let method = match tokens.advance() {
+            Ok(info) => match info.token {
+                Token::Identifier(name) => name,
+                Token::Integer(index) => {
+                    expression = Expression::Field(Box::new(expression), index.to_string());
+                    continue;
+                }
+                _ => {
+                    return Err(tokens_expected_got(
+                        tokens,
+                        vec![Token::Identifier(String::from("field"))],
+                        info,
+                    ))
+                }
+            },
+            Err(error) => return Err(error),
+        };
+        match tokens.peek() {
+            Ok(info) => match info.token {
+                Token::OpenParen => {
+                    tokens.advance().unwrap();
+                }
+                _ => {
+                    expression = Expression::Field(Box::new(expression), method);
+                    continue;
+                }
+            },
+            Err(error) => return Err(error),
+        }
+        let arguments = match parse_arguments(tokens) {
+            Ok(args) => args,
+            Err(error) => return Err(error),
+        };
+
+        expression = Expression::MethodCall(Box::new(expression), method, arguments);
+    }
+
+    loop {
+        match tokens.peek() {
+            Ok(info) => match info.token {
+                Token::As => {
+                    tokens.advance().unwrap();
+                }
+                _ => break,
+            },
+            Err(error) => return Err(error),
+        }
+        let t = match parse_type(tokens) {
+            Ok(t) => t,
+            Err(error) => return Err(error),
+        };
+        expression = Expression::Cast(Box::new(expression), t);
     }
 
-    // return Ok(Some(expression));
+    return Ok(Some(expression));
 }
 
 // let expression = match info.token {
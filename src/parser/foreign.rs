@@ -0,0 +1,187 @@
+use crate::{
+    codegen::RESERVED_PREFIX,
+    lexer::{Token, TokensGroup},
+    BuildError, BuildProblem, CompileError,
+};
+
+use super::{
+    after_identifier::parse_identifer_string, tokens_expected_got, types::parse_type, ASTNode,
+    Node, Type,
+};
+
+/// Parses `"C" { fn name(parameters): type; ... }` after the `extern` keyword.
+pub fn parse_extern(tokens: &mut TokensGroup) -> Result<ASTNode, CompileError> {
+    let line = tokens.current.line;
+
+    let abi = match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::String(abi) => abi,
+            _ => {
+                return Err(tokens_expected_got(
+                    tokens,
+                    vec![Token::String(String::from("C"))],
+                    info,
+                ))
+            }
+        },
+        Err(error) => return Err(error),
+    };
+
+    match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::StartScope => {}
+            _ => return Err(tokens_expected_got(tokens, vec![Token::StartScope], info)),
+        },
+        Err(error) => return Err(error),
+    }
+
+    let mut body = Vec::new();
+    loop {
+        let info = match tokens.advance() {
+            Ok(info) => info,
+            Err(error) => return Err(error),
+        };
+        let export = match info.token {
+            Token::EndScope => break,
+            Token::Function => false,
+            Token::Pub => match tokens.advance() {
+                Ok(info) => match info.token {
+                    Token::Function => true,
+                    _ => return Err(tokens_expected_got(tokens, vec![Token::Function], info)),
+                },
+                Err(error) => return Err(error),
+            },
+            _ => {
+                return Err(tokens_expected_got(
+                    tokens,
+                    vec![Token::Function, Token::EndScope],
+                    info,
+                ))
+            }
+        };
+        match parse_foreign_function(tokens, export) {
+            Ok(node) => body.push(node),
+            Err(error) => return Err(error),
+        }
+    }
+
+    return Ok(ASTNode::new(line, Node::Extern { abi, body }));
+}
+
+fn parse_foreign_function(tokens: &mut TokensGroup, export: bool) -> Result<ASTNode, CompileError> {
+    let line = tokens.current.line;
+
+    let name = match parse_identifer_string(tokens) {
+        Ok(name) => name,
+        Err(error) => return Err(error),
+    };
+    if name.starts_with(RESERVED_PREFIX) {
+        return Err(CompileError::BuildProblem(BuildProblem::new(
+            BuildError::ReservedName(name),
+            tokens.relative_path.clone(),
+            line,
+        )));
+    }
+
+    match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::OpenParen => {}
+            _ => return Err(tokens_expected_got(tokens, vec![Token::OpenParen], info)),
+        },
+        Err(error) => return Err(error),
+    }
+
+    let mut parameters = Vec::new();
+    let mut variadic = false;
+    loop {
+        let info = match tokens.advance() {
+            Ok(info) => info,
+            Err(error) => return Err(error),
+        };
+        match info.token {
+            Token::CloseParen => break,
+            // `...` has to be the last parameter.
+            Token::Ellipsis => {
+                variadic = true;
+                match tokens.advance() {
+                    Ok(info) => match info.token {
+                        Token::CloseParen => break,
+                        _ => return Err(tokens_expected_got(tokens, vec![Token::CloseParen], info)),
+                    },
+                    Err(error) => return Err(error),
+                }
+            }
+            Token::Identifier(name) => {
+                match tokens.peek() {
+                    Ok(info) => match info.token {
+                        Token::Colon => {
+                            tokens.advance().unwrap();
+                        }
+                        _ => {}
+                    },
+                    Err(error) => return Err(error),
+                }
+                let t = match parse_type(tokens) {
+                    Ok(t) => t,
+                    Err(error) => return Err(error),
+                };
+                parameters.push((name, t));
+
+                match tokens.advance() {
+                    Ok(info) => match info.token {
+                        Token::Comma => {}
+                        Token::CloseParen => break,
+                        _ => {
+                            return Err(tokens_expected_got(
+                                tokens,
+                                vec![Token::Comma, Token::CloseParen],
+                                info,
+                            ))
+                        }
+                    },
+                    Err(error) => return Err(error),
+                }
+            }
+            _ => {
+                return Err(tokens_expected_got(
+                    tokens,
+                    vec![Token::Identifier(String::from("parameter")), Token::Ellipsis],
+                    info,
+                ))
+            }
+        }
+    }
+
+    let return_type: Option<Type> = match tokens.peek() {
+        Ok(info) => match info.token {
+            Token::Colon => {
+                tokens.advance().unwrap();
+                match parse_type(tokens) {
+                    Ok(t) => Some(t),
+                    Err(error) => return Err(error),
+                }
+            }
+            _ => None,
+        },
+        Err(error) => return Err(error),
+    };
+
+    match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::SemiColon => {}
+            _ => return Err(tokens_expected_got(tokens, vec![Token::SemiColon], info)),
+        },
+        Err(error) => return Err(error),
+    }
+
+    return Ok(ASTNode::new(
+        line,
+        Node::ForeignFunction {
+            export,
+            name,
+            parameters,
+            variadic,
+            return_type,
+        },
+    ));
+}
@@ -1,9 +1,11 @@
 use crate::{
     lexer::{Token, TokensGroup},
-    CompileError,
+    BuildError, BuildProblem, CompileError,
 };
 
 use super::{
+    expression::parse_expression,
+    generics::parse_generics,
     node::{ASTNode, Node},
     scope::parse_scope,
     tokens_expected_got,
@@ -16,6 +18,8 @@ pub fn parse_function(
     export: bool,
     is_unsafe: bool,
 ) -> Result<ASTNode, CompileError> {
+    let line = tokens.current.line;
+
     let name = match tokens.advance() {
         Ok(info) => match info.token {
             Token::Identifier(name) => name,
@@ -30,6 +34,11 @@ pub fn parse_function(
         Err(error) => return Err(error),
     };
 
+    let generics = match parse_generics(tokens) {
+        Ok(generics) => generics,
+        Err(error) => return Err(error),
+    };
+
     match tokens.advance() {
         Ok(info) => match info.token {
             Token::OpenParen => {}
@@ -39,17 +48,106 @@ pub fn parse_function(
     }
 
     let mut parameters = Vec::new();
+    let mut defaults = Vec::new();
+    let mut mutable_parameters = Vec::new();
     loop {
+        // `mut name type` lets the body assign to the parameter.
+        let is_mutable = match tokens.peek() {
+            Ok(info) => match info.token {
+                Token::Mutable => {
+                    tokens.advance().unwrap();
+                    true
+                }
+                _ => false,
+            },
+            Err(error) => return Err(error),
+        };
         match tokens.advance() {
             Ok(info) => match info.token {
+                Token::Reference => {
+                    let mutable = match tokens.peek() {
+                        Ok(info) => match info.token {
+                            Token::Mutable => {
+                                tokens.advance().unwrap();
+                                true
+                            }
+                            _ => false,
+                        },
+                        Err(error) => return Err(error),
+                    };
+                    match tokens.advance() {
+                        Ok(info) => match info.token {
+                            Token::Identifier(name) if name == "self" => {}
+                            _ => {
+                                return Err(tokens_expected_got(
+                                    tokens,
+                                    vec![Token::Identifier(String::from("self"))],
+                                    info,
+                                ))
+                            }
+                        },
+                        Err(error) => return Err(error),
+                    };
+
+                    parameters.push((
+                        String::from("self"),
+                        Type::Reference(mutable, Box::new(Type::Custom(String::from("Self")))),
+                    ));
+                    defaults.push(None);
+                    mutable_parameters.push(is_mutable);
+                    match tokens.advance() {
+                        Ok(info) => match info.token {
+                            Token::Comma => {}
+                            Token::CloseParen => break,
+                            _ => return Err(tokens_expected_got(tokens, vec![Token::Comma], info)),
+                        },
+                        Err(error) => return Err(error),
+                    }
+                }
                 Token::Identifier(name) => {
+                    let is_self = match tokens.peek() {
+                        Ok(info) => match info.token {
+                            Token::Comma | Token::CloseParen => name == "self",
+                            // `name: type` is accepted next to the shorter `name type`.
+                            Token::Colon => {
+                                tokens.advance().unwrap();
+                                false
+                            }
+                            _ => false,
+                        },
+                        Err(error) => return Err(error),
+                    };
                     parameters.push((
                         name,
-                        match parse_type(tokens) {
-                            Ok(t) => t,
-                            Err(error) => return Err(error),
+                        match is_self {
+                            true => Type::Custom(String::from("Self")),
+                            false => match parse_type(tokens) {
+                                Ok(t) => t,
+                                Err(error) => return Err(error),
+                            },
                         },
                     ));
+                    mutable_parameters.push(is_mutable);
+                    defaults.push(match tokens.peek() {
+                        Ok(info) => match info.token {
+                            Token::Equals => {
+                                tokens.advance().unwrap();
+                                match parse_expression(tokens) {
+                                    Ok(Some(expression)) => Some(expression),
+                                    Ok(None) => {
+                                        return Err(CompileError::BuildProblem(BuildProblem::new(
+                                            BuildError::ExpressionExpected,
+                                            tokens.relative_path.clone(),
+                                            tokens.current.line,
+                                        )))
+                                    }
+                                    Err(error) => return Err(error),
+                                }
+                            }
+                            _ => None,
+                        },
+                        Err(error) => return Err(error),
+                    });
                     match tokens.advance() {
                         Ok(info) => match info.token {
                             Token::Comma => {}
@@ -66,48 +164,54 @@ pub fn parse_function(
         }
     }
 
-    let return_type: Option<Type> = match tokens.advance() {
+    let return_type: Option<Type> = match tokens.peek() {
         Ok(info) => match info.token {
-            Token::StartScope => None,
-            Token::Colon => match parse_type(tokens) {
-                Ok(t) => {
-                    match tokens.advance() {
-                        Ok(info) => match info.token {
-                            Token::StartScope => {}
-                            _ => {
-                                return Err(tokens_expected_got(
-                                    tokens,
-                                    vec![Token::StartScope],
-                                    info,
-                                ))
-                            }
-                        },
-                        Err(error) => return Err(error),
-                    };
-
-                    Some(t)
+            Token::Colon => {
+                tokens.advance().unwrap();
+                match parse_type(tokens) {
+                    Ok(t) => Some(t),
+                    Err(error) => return Err(error),
                 }
-                Err(error) => return Err(error),
-            },
-            _ => return Err(tokens_expected_got(tokens, vec![Token::StartScope], info)),
+            }
+            _ => None,
         },
         Err(error) => return Err(error),
     };
 
-    let body = match parse_scope(tokens) {
-        Ok(body) => body,
+    // A signature followed by `;` declares a function without defining it,
+    // which is how trait methods without a default body are written.
+    let body = match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::StartScope => match parse_scope(tokens) {
+                Ok(body) => Some(body),
+                Err(error) => return Err(error),
+            },
+            Token::SemiColon => None,
+            _ => {
+                return Err(tokens_expected_got(
+                    tokens,
+                    vec![Token::StartScope, Token::SemiColon],
+                    info,
+                ))
+            }
+        },
         Err(error) => return Err(error),
     };
 
     return Ok(ASTNode::new(
-        tokens.current.line,
+        line,
         Node::Function {
             export,
             is_unsafe,
             name,
+            generics,
             parameters,
+            defaults,
+            mutable_parameters,
             return_type: return_type,
             body: body,
+            definition: None,
+            parameter_definitions: Vec::new(),
         },
     ));
 }
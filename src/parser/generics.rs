@@ -0,0 +1,45 @@
+use crate::{
+    lexer::{Token, TokensGroup},
+    CompileError,
+};
+
+use super::{after_identifier::parse_identifer_string, tokens_expected_got};
+
+pub fn parse_generics(tokens: &mut TokensGroup) -> Result<Vec<String>, CompileError> {
+    let mut generics = Vec::new();
+
+    match tokens.peek() {
+        Ok(info) => match info.token {
+            Token::LessThan => {
+                tokens.advance().unwrap();
+            }
+            _ => return Ok(generics),
+        },
+        Err(error) => return Err(error),
+    };
+
+    loop {
+        let name = match parse_identifer_string(tokens) {
+            Ok(name) => name,
+            Err(error) => return Err(error),
+        };
+        generics.push(name);
+
+        match tokens.advance() {
+            Ok(info) => match info.token {
+                Token::Comma => continue,
+                Token::GreaterThan => break,
+                _ => {
+                    return Err(tokens_expected_got(
+                        tokens,
+                        vec![Token::Comma, Token::GreaterThan],
+                        info,
+                    ))
+                }
+            },
+            Err(error) => return Err(error),
+        }
+    }
+
+    return Ok(generics);
+}
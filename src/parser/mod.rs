@@ -8,10 +8,18 @@ mod expression;
 mod arguments;
 mod path;
 mod after_identifier;
+mod alias;
+mod asm;
+mod attribute;
 mod program;
 mod structs;
 mod enums;
 mod export;
+mod foreign;
+mod generics;
+mod traits;
+mod closure;
+mod constant;
 
 pub use program::Program;
 pub use node::*;
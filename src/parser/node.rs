@@ -1,5 +1,12 @@
-#[derive(Debug, PartialEq, Eq)]
+use std::fmt::Display;
+
+use crate::POINTER_SIZE;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum BaseType {
+    Int128,
+    UInt128,
+
     Int64,
     UInt64,
 
@@ -14,49 +21,231 @@ pub enum BaseType {
 
     Boolean,
 
+    /// A Unicode scalar value, stored in 4 bytes.
+    Char,
+
     Float64,
     Float32,
 
-    // Intsize,
-    // UIntsize,
+    /// String slices, only used behind a reference as `&str`.
+    Str,
+
+    /// Pointer sized integers, as wide as `POINTER_SIZE`.
+    IntSize,
+    UIntSize,
+}
+impl BaseType {
+    /// `isize` and `usize` as the fixed width integer of the same size on the target.
+    pub fn sized(&self) -> BaseType {
+        return match (self, POINTER_SIZE) {
+            (BaseType::IntSize, 4) => BaseType::Int32,
+            (BaseType::IntSize, _) => BaseType::Int64,
+            (BaseType::UIntSize, 4) => BaseType::UInt32,
+            (BaseType::UIntSize, _) => BaseType::UInt64,
+            (base, _) => base.clone(),
+        };
+    }
+    /// 128-bit integers, which take two registers.
+    pub fn is_wide(&self) -> bool {
+        return match self {
+            BaseType::Int128 | BaseType::UInt128 => true,
+            _ => false,
+        };
+    }
+    pub fn is_signed(&self) -> bool {
+        return match self.sized() {
+            BaseType::Int128 | BaseType::Int64 | BaseType::Int32 | BaseType::Int16 | BaseType::Int8 => true,
+            _ => false,
+        };
+    }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Type {
     Custom(String),
     Base(BaseType),
     // StaticString,
     Tuple(Vec<Type>),
+    Generic(String, Vec<Type>),
+    Reference(bool, Box<Type>),
+    /// Raw `*const T` and `*mut T` pointers, mostly used to talk to C.
+    Pointer(bool, Box<Type>),
+    Dyn(String),
+    Function(Vec<Type>, Option<Box<Type>>),
+}
+impl Type {
+    /// Name of the trait behind a `&dyn Trait` or `Box<dyn Trait>` fat pointer.
+    pub fn trait_object(&self) -> Option<&String> {
+        return match self {
+            Type::Reference(_, inner) => match inner.as_ref() {
+                Type::Dyn(name) => Some(name),
+                _ => None,
+            },
+            Type::Generic(name, types) => match (name.as_str(), types.as_slice()) {
+                ("Box", [Type::Dyn(name)]) => Some(name),
+                _ => None,
+            },
+            _ => None,
+        };
+    }
+    /// `&str`, a fat pointer to the bytes of a string and their length.
+    pub fn is_str_slice(&self) -> bool {
+        return match self {
+            Type::Reference(_, inner) => inner.as_ref() == &Type::Base(BaseType::Str),
+            _ => false,
+        };
+    }
+}
+impl Display for BaseType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            BaseType::Int128 => "i128",
+            BaseType::UInt128 => "u128",
+            BaseType::Int64 => "i64",
+            BaseType::UInt64 => "u64",
+            BaseType::Int32 => "i32",
+            BaseType::UInt32 => "u32",
+            BaseType::Int16 => "i16",
+            BaseType::UInt16 => "u16",
+            BaseType::Int8 => "i8",
+            BaseType::UInt8 => "u8",
+            BaseType::Boolean => "bool",
+            BaseType::Char => "char",
+            BaseType::Float64 => "f64",
+            BaseType::Float32 => "f32",
+            BaseType::Str => "str",
+            BaseType::IntSize => "isize",
+            BaseType::UIntSize => "usize",
+        };
+        write!(f, "{}", name)
+    }
+}
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Custom(name) => write!(f, "{}", name),
+            Type::Base(base) => write!(f, "{}", base),
+            Type::Tuple(types) => {
+                let types: Vec<String> = types.iter().map(|t| t.to_string()).collect();
+                write!(f, "({})", types.join(", "))
+            }
+            Type::Generic(name, types) => {
+                let types: Vec<String> = types.iter().map(|t| t.to_string()).collect();
+                write!(f, "{}<{}>", name, types.join(", "))
+            }
+            Type::Reference(mutable, inner) => match mutable {
+                true => write!(f, "&mut {}", inner),
+                false => write!(f, "&{}", inner),
+            },
+            Type::Pointer(mutable, inner) => match mutable {
+                true => write!(f, "*mut {}", inner),
+                false => write!(f, "*const {}", inner),
+            },
+            Type::Dyn(name) => write!(f, "dyn {}", name),
+            Type::Function(parameters, return_type) => {
+                let parameters: Vec<String> = parameters.iter().map(|t| t.to_string()).collect();
+                match return_type {
+                    Some(return_type) => write!(f, "fn({}): {}", parameters.join(", "), return_type),
+                    None => write!(f, "fn({})", parameters.join(", ")),
+                }
+            }
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Value {
-    Integer(isize),
-    UInteger(usize),
-    // String(String),
-    // Boolean(bool),
+    Integer(i128),
+    UInteger(u128),
+    String(String),
+    Boolean(bool),
+    Char(char),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Operator {
     Plus,
     Minus,
     Multiply,
     Division,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+impl Operator {
+    pub fn symbol(&self) -> &'static str {
+        return match self {
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Multiply => "*",
+            Operator::Division => "/",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::Less => "<",
+            Operator::LessEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterEqual => ">=",
+        };
+    }
+    /// Operators with a higher precedence bind tighter.
+    pub fn precedence(&self) -> usize {
+        return match self {
+            Operator::Multiply | Operator::Division => 3,
+            Operator::Plus | Operator::Minus => 2,
+            _ => 1,
+        };
+    }
+    pub fn is_comparison(&self) -> bool {
+        return self.precedence() == 1;
+    }
+    /// The prelude trait and method that implement the operator for types
+    /// other than numbers, bools and pointers. `!=` negates `Eq::eq` and the
+    /// ordering operators look at the `Ordering` returned by `Ord::cmp`.
+    pub fn overload(&self) -> (&'static str, &'static str) {
+        return match self {
+            Operator::Plus => ("Add", "add"),
+            Operator::Minus => ("Sub", "sub"),
+            Operator::Multiply => ("Mul", "mul"),
+            Operator::Division => ("Div", "div"),
+            Operator::Equal | Operator::NotEqual => ("Eq", "eq"),
+            _ => ("Ord", "cmp"),
+        };
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Expression {
     Value(Value),
     GetVariable(Path),
     Call(Path, Vec<Expression>),
     BinaryOperation(Box<Expression>, Operator, Box<Expression>),
+    Reference(bool, Box<Expression>),
+    Field(Box<Expression>, String),
+    Cast(Box<Expression>, Type),
+    /// The postfix `?` operator, returning early on `None` or `Err`.
+    Try(Box<Expression>),
+    /// A `name: value` argument of a call, which the analyzer turns into a
+    /// positional one.
+    NamedArgument(String, Box<Expression>),
+    MethodCall(Box<Expression>, String, Vec<Expression>),
+    Closure {
+        is_move: bool,
+        parameters: Vec<(String, Option<Type>)>,
+        return_type: Option<Type>,
+        body: Vec<ASTNode>,
+        /// Each parameter in the order of `parameters`, once names are resolved.
+        parameter_definitions: Vec<DefId>,
+    },
     // Tuple(Vec<Expression>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Node {
     Call(Path, Vec<Expression>),
+    MethodCall(Path, String, Vec<Expression>),
     Return(Option<Expression>),
     // Conditional((Expression, Expression), Vec<ASTNode>, Option<Vec<ASTNode>>),
     SetVariable(Path, Expression),
@@ -64,6 +253,8 @@ pub enum Node {
         export: bool,
         name: String,
         generics: Vec<String>,
+        /// Tuple structs like `struct UserId(u64);` name their fields `0`, `1`, ...
+        tuple: bool,
         body: Vec<(bool, String, Type)>,
     },
     Enum {
@@ -72,6 +263,47 @@ pub enum Node {
         generics: Vec<String>,
         body: Vec<(String, Vec<Type>)>,
     },
+    Trait {
+        export: bool,
+        name: String,
+        generics: Vec<String>,
+        body: Vec<ASTNode>,
+    },
+    Impl {
+        generics: Vec<String>,
+        trait_name: Option<String>,
+        target: Type,
+        body: Vec<ASTNode>,
+    },
+    Constant {
+        export: bool,
+        name: String,
+        constant_type: Type,
+        expression: Expression,
+    },
+    Static {
+        export: bool,
+        mutable: bool,
+        name: String,
+        static_type: Type,
+        expression: Expression,
+    },
+    TypeAlias {
+        export: bool,
+        name: String,
+        aliased: Type,
+    },
+    Extern {
+        abi: String,
+        body: Vec<ASTNode>,
+    },
+    ForeignFunction {
+        export: bool,
+        name: String,
+        parameters: Vec<(String, Type)>,
+        variadic: bool,
+        return_type: Option<Type>,
+    },
     Import(String, bool),
     Loop {
         // condition
@@ -81,44 +313,94 @@ pub enum Node {
         is_unsafe: bool,
         body: Vec<ASTNode>,
     },
+    /// `asm!("syscall", in("rax") n, out("rax") ret)`, one template line per string.
+    InlineAsm {
+        template: Vec<String>,
+        operands: Vec<AsmOperand>,
+    },
     Function {
         export: bool,
         is_unsafe: bool,
         name: String,
+        generics: Vec<String>,
         parameters: Vec<(String, Type)>,
+        /// The default value of each parameter, in the order of `parameters`.
+        defaults: Vec<Option<Expression>>,
+        /// Which parameters are declared `mut`, in the order of `parameters`.
+        mutable_parameters: Vec<bool>,
         return_type: Option<Type>,
-        body: Vec<ASTNode>,
+        body: Option<Vec<ASTNode>>,
+        /// The function and each of its parameters, in the order of
+        /// `parameters`, once names are resolved.
+        definition: Option<DefId>,
+        parameter_definitions: Vec<DefId>,
     },
     DefineVariable {
         mutable: bool,
         name: String,
         var_type: Option<Type>,
         expression: Option<Expression>,
+        /// The local, once names are resolved.
+        definition: Option<DefId>,
     },
 }
 
-#[derive(Debug)]
+/// An operand of `asm!`, bound to an explicit register.
+#[derive(Debug, Clone)]
+pub enum AsmOperand {
+    In(String, Expression),
+    /// Writing to `_` discards the output, which only marks the register as clobbered.
+    Out(String, Option<Path>),
+    InOut(String, Path),
+    Clobber(String),
+}
+
+/// An attribute like `#[inline]`, `#[deprecated = "use bar"]` or `#[cfg(not(test))]`.
+/// Arguments are attributes themselves, so `repr(C)` has the argument `C`.
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    /// The line the attribute is written on, which may be above its node.
+    pub line: usize,
+    pub name: String,
+    pub value: Option<String>,
+    pub arguments: Option<Vec<Attribute>>,
+}
+
+#[derive(Debug, Clone)]
 pub struct ASTNode {
     // indent: usize,
     pub line: usize,
     pub node: Node,
+    pub attributes: Vec<Attribute>,
 }
 impl ASTNode {
     pub fn new(line: usize, node: Node) -> Self {
-        Self { node, line }
+        Self {
+            node,
+            line,
+            attributes: Vec::new(),
+        }
     }
 }
 
-#[derive(Debug)]
+/// Names one definition of the program: an item, a local or a parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DefId(pub usize);
+
+#[derive(Debug, Clone)]
 pub struct Path {
     pub root: String,
     pub location: Vec<String>,
+    /// What the path names, filled in by name resolution. Stays `None` for
+    /// the formatting builtins.
+    pub definition: Option<DefId>,
 }
 impl Path {
     pub fn new(root: String) -> Self {
         Self {
             root,
             location: Vec::new(),
+            definition: None,
         }
     }
     pub fn add(&mut self, name: String) {
@@ -1,14 +1,26 @@
 use crate::{lexer::TokenInfo, BuildError, BuildProblem, CompileError};
 
 use super::{
-    after_identifier::{parse_after_identifier, parse_identifer_string}, enums::parse_enum, export::parse_export,
-    expression::parse_expression, function::parse_function, node::*, scope::parse_scope,
-    structs::parse_struct, variable::parse_define_variable,
+    after_identifier::{parse_after_identifier, parse_identifer_string},
+    alias::parse_type_alias,
+    attribute::parse_attribute,
+    constant::{parse_constant, parse_static},
+    enums::parse_enum,
+    export::parse_export,
+    foreign::parse_extern,
+    expression::parse_expression,
+    function::parse_function,
+    node::*,
+    scope::parse_scope,
+    structs::parse_struct,
+    traits::{parse_impl, parse_trait},
+    variable::parse_define_variable,
 };
 use crate::lexer::{Token, TokensGroup};
 
 pub fn parse(tokens: &mut TokensGroup) -> Result<Vec<ASTNode>, CompileError> {
     let mut tree: Vec<ASTNode> = Vec::new();
+    let mut attributes = Vec::new();
 
     loop {
         let info = match tokens.peek() {
@@ -16,6 +28,9 @@ pub fn parse(tokens: &mut TokensGroup) -> Result<Vec<ASTNode>, CompileError> {
             Err(error) => return Err(error),
         };
         match info.token {
+            Token::EndScope | Token::EndOfFile if attributes.len() > 0 => {
+                return Err(expected_item(tokens))
+            }
             Token::EndScope => break,
             _ => {}
         }
@@ -28,6 +43,13 @@ pub fn parse(tokens: &mut TokensGroup) -> Result<Vec<ASTNode>, CompileError> {
         let node = match info.token {
             Token::EndOfFile => break,
             Token::SemiColon => continue,
+            Token::Hash => {
+                match parse_attribute(tokens) {
+                    Ok(attribute) => attributes.push(attribute),
+                    Err(error) => return Err(error),
+                }
+                continue;
+            }
             Token::Variable => parse_define_variable(tokens),
             Token::Identifier(name) => parse_after_identifier(tokens, name),
             Token::StartScope => Ok(ASTNode::new(
@@ -68,6 +90,12 @@ pub fn parse(tokens: &mut TokensGroup) -> Result<Vec<ASTNode>, CompileError> {
             },
             Token::Struct => parse_struct(tokens, false),
             Token::Enum => parse_enum(tokens, false),
+            Token::Trait => parse_trait(tokens, false),
+            Token::Const => parse_constant(tokens, false),
+            Token::Static => parse_static(tokens, false),
+            Token::Type => parse_type_alias(tokens, false),
+            Token::Extern => parse_extern(tokens),
+            Token::Impl => parse_impl(tokens),
             Token::Function => parse_function(tokens, false, false),
             Token::Return => {
                 let expression = match parse_expression(tokens) {
@@ -88,6 +116,8 @@ pub fn parse(tokens: &mut TokensGroup) -> Result<Vec<ASTNode>, CompileError> {
             //--------------[[FUNCTION-END]]--------------
             // Token::OpenParen
             Token::Loop => {
+                // The line of `loop` itself rather than of its closing brace.
+                let line = tokens.current.line;
                 match tokens.advance() {
                     Ok(info) => match info.token {
                         Token::StartScope => {}
@@ -103,7 +133,7 @@ pub fn parse(tokens: &mut TokensGroup) -> Result<Vec<ASTNode>, CompileError> {
                     Err(error) => return Err(error),
                 };
 
-                Ok(ASTNode::new(tokens.current.line, Node::Loop { body: body }))
+                Ok(ASTNode::new(line, Node::Loop { body: body }))
             }
             _ => {
                 return Err(tokens_expected_got(
@@ -119,7 +149,10 @@ pub fn parse(tokens: &mut TokensGroup) -> Result<Vec<ASTNode>, CompileError> {
             }
         };
         match node {
-            Ok(node) => tree.push(node),
+            Ok(mut node) => {
+                node.attributes = std::mem::take(&mut attributes);
+                tree.push(node)
+            }
             Err(error) => return Err(error),
         }
     }
@@ -127,6 +160,14 @@ pub fn parse(tokens: &mut TokensGroup) -> Result<Vec<ASTNode>, CompileError> {
     return Ok(tree);
 }
 
+pub fn expected_item(tokens: &TokensGroup) -> CompileError {
+    return CompileError::BuildProblem(BuildProblem::new(
+        BuildError::ExpectedItemAfterAttributes,
+        tokens.relative_path.clone(),
+        tokens.current.line,
+    ));
+}
+
 pub fn tokens_expected_got(
     tokens: &TokensGroup,
     expected: Vec<Token>,
@@ -1,12 +1,14 @@
 use std::{collections::HashMap, path::PathBuf};
 
 use crate::{
-    builder::Module, lexer::tokenize, read_file, BuildError, BuildProblem, CompileError,
+    builder::{Import, Module, ModulePath}, lexer::tokenize, read_file, BuildError, BuildProblem, CompileError,
     FILE_EXTENSION,
 };
 
 use super::{parse, Node};
 
+const PRELUDE: &str = include_str!("prelude.eclipse");
+
 fn get_path(project_path: &PathBuf, paths: [PathBuf; 2]) -> Result<PathBuf, ()> {
     for path in paths {
         if project_path.join(&path).exists() {
@@ -15,21 +17,74 @@ fn get_path(project_path: &PathBuf, paths: [PathBuf; 2]) -> Result<PathBuf, ()>
     }
     return Err(());
 }
+/// Falls back to the path as given when it can't be resolved, which only
+/// happens for a file that can't be read either.
+fn canonical(path: &PathBuf) -> PathBuf {
+    return match path.canonicalize() {
+        Ok(path) => path,
+        Err(_) => path.clone(),
+    };
+}
 #[derive(Debug)]
 pub struct Program {
-    // main: Module,
     pub project_path: PathBuf,
-    pub modules: HashMap<PathBuf, Module>,
+    pub modules: HashMap<ModulePath, Module>,
+    /// The module each file was parsed into, keyed by canonical path so a file
+    /// reached through several imports or links is only parsed once.
+    files: HashMap<PathBuf, ModulePath>,
 }
 impl Program {
     pub fn new(project_path: PathBuf) -> Self {
         Self {
             project_path,
             modules: HashMap::new(),
+            files: HashMap::new(),
         }
     }
+    /// Parses the prelude, whose items every module can use without importing them.
+    pub fn parse_prelude(&mut self) -> Result<(), CompileError> {
+        let relative_path = PathBuf::from("<prelude>");
+        let mut tokens = match tokenize(String::from(PRELUDE), relative_path.clone()) {
+            Ok(tokens) => tokens,
+            Err((message, reader)) => {
+                return Err(CompileError::BuildProblem(BuildProblem::new(
+                    BuildError::Tokenize(message),
+                    relative_path,
+                    reader.line,
+                )))
+            }
+        };
+        let nodes = match parse(&mut tokens) {
+            Ok(nodes) => nodes,
+            Err(error) => return Err(error),
+        };
+        self.modules.insert(
+            ModulePath::prelude(),
+            Module {
+                relative_path,
+                body: nodes,
+                imports: Vec::new(),
+            },
+        );
+
+        Ok(())
+    }
+    /// Parses the crate rooted at `relative_path` along with every module it
+    /// imports.
     pub fn parse(&mut self, relative_path: PathBuf) -> Result<(), CompileError> {
+        return self.parse_module(ModulePath::root(), relative_path, &mut Vec::new());
+    }
+    /// `chain` holds the canonical and relative paths of the files currently
+    /// being parsed, from the crate root down to the importer of this one, so an
+    /// import cycle can be reported in full.
+    fn parse_module(
+        &mut self,
+        module_path: ModulePath,
+        relative_path: PathBuf,
+        chain: &mut Vec<(PathBuf, PathBuf)>,
+    ) -> Result<(), CompileError> {
         let full_path = self.project_path.join(&relative_path);
+        let canonical_path = canonical(&full_path);
         let file_name = relative_path.file_stem().unwrap().to_str().unwrap();
         let is_module_root = match file_name {
             "main" => true,
@@ -39,6 +94,14 @@ impl Program {
 
         let source = match read_file(&full_path) {
             Ok(source) => source,
+            // Report invalid UTF-8 relative to the project like every other problem.
+            Err(CompileError::BuildProblem(problem)) => {
+                return Err(CompileError::BuildProblem(BuildProblem::new(
+                    problem.error,
+                    relative_path,
+                    problem.line,
+                )))
+            }
             Err(error) => return Err(error),
         };
         let mut tokens = match tokenize(source, relative_path.clone()) {
@@ -56,10 +119,21 @@ impl Program {
             Err(error) => return Err(error),
         };
 
+        chain.push((canonical_path.clone(), relative_path.clone()));
+        let mut imports: Vec<Import> = Vec::new();
+
         for ast in &nodes {
             let node = &ast.node;
             match node {
-                Node::Import(module, _) => {
+                Node::Import(module, export) => {
+                    if imports.iter().any(|import| &import.name == module) {
+                        return Err(CompileError::BuildProblem(BuildProblem::new(
+                            BuildError::AlreadyImported(module.clone()),
+                            relative_path,
+                            ast.line,
+                        )));
+                    }
+
                     let mut parent =
                         String::from(relative_path.parent().unwrap().to_str().unwrap());
                     parent.push('/');
@@ -95,16 +169,143 @@ impl Program {
                         }
                     };
 
-                    match self.parse(path.clone()) {
-                        Ok(()) => break,
-                        Err(error) => return Err(error),
+                    let file = canonical(&self.project_path.join(&path));
+                    if chain.iter().any(|(canonical, _)| *canonical == file) {
+                        let mut cycle: Vec<String> = chain
+                            .iter()
+                            .skip_while(|(canonical, _)| *canonical != file)
+                            .map(|(_, relative)| relative.display().to_string())
+                            .collect();
+                        cycle.push(path.display().to_string());
+                        return Err(CompileError::BuildProblem(BuildProblem::new(
+                            BuildError::CyclicImport(cycle),
+                            relative_path,
+                            ast.line,
+                        )));
+                    }
+
+                    let child = match self.files.get(&file) {
+                        Some(existing) => existing.clone(),
+                        None => {
+                            let child = module_path.child(module);
+                            match self.parse_module(child.clone(), path, chain) {
+                                Ok(()) => {}
+                                Err(error) => return Err(error),
+                            };
+                            child
+                        }
                     };
+                    imports.push(Import {
+                        name: module.clone(),
+                        module: child,
+                        export: *export,
+                        line: ast.line,
+                    });
                 }
                 _ => continue,
             }
         }
-        self.modules.insert(relative_path, Module { body: nodes });
+        chain.pop();
+
+        self.files.insert(canonical_path, module_path.clone());
+        self.modules.insert(
+            module_path,
+            Module {
+                relative_path,
+                body: nodes,
+                imports,
+            },
+        );
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{builder::ModulePath, BuildError, CompileError};
+
+    use super::Program;
+
+    /// Writes the files of a project named after the test and parses it from
+    /// `src/main.eclipse`.
+    fn parse(name: &str, files: &[(&str, &str)], links: &[(&str, &str)]) -> Result<Program, CompileError> {
+        let project = std::env::temp_dir().join(format!("eclipse-{}-{}", std::process::id(), name));
+        for (path, source) in files {
+            let path = project.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+        // Files can only import files below their own directory, so a cycle
+        // needs a link back up.
+        for (link, target) in links {
+            let link = project.join(link);
+            std::fs::create_dir_all(link.parent().unwrap()).unwrap();
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(target, link).unwrap();
+            #[cfg(windows)]
+            std::os::windows::fs::symlink_file(target, link).unwrap();
+        }
+        let mut program = Program::new(project.clone());
+        let result = program.parse(PathBuf::from("src/main.eclipse"));
+        std::fs::remove_dir_all(&project).unwrap_or_default();
+        return match result {
+            Ok(()) => Ok(program),
+            Err(error) => Err(error),
+        };
+    }
+
+    fn module(names: &[&str]) -> ModulePath {
+        return ModulePath(names.iter().map(|name| name.to_string()).collect());
+    }
+
+    #[test]
+    fn import_cycles_report_the_chain() {
+        let files = [
+            ("src/main.eclipse", "import a;\n\nfn main() {}\n"),
+            ("src/a.eclipse", "import b;\n"),
+            ("src/b/mod.eclipse", "import c;\n"),
+        ];
+        let error = match parse("cycle", &files, &[("src/b/c.eclipse", "../a.eclipse")]) {
+            Err(CompileError::BuildProblem(problem)) => problem,
+            _ => panic!("the cycle was not reported"),
+        };
+        assert_eq!(error.relative_path, PathBuf::from("src/b/mod.eclipse"));
+        assert!(matches!(
+            error.error,
+            BuildError::CyclicImport(chain) if chain == ["src/a.eclipse", "src/b/mod.eclipse", "src/b/c.eclipse"]
+        ));
+    }
+
+    #[test]
+    fn diamond_imports_parse_the_shared_module_once() {
+        let files = [
+            ("src/main.eclipse", "import a;\nimport b;\n\nfn main() {}\n"),
+            ("src/a.eclipse", "import c;\n"),
+            ("src/b.eclipse", "import c;\n"),
+            ("src/c/mod.eclipse", "pub fn f() {}\n"),
+        ];
+        let program = parse("diamond", &files, &[]).unwrap();
+        assert_eq!(program.modules.len(), 4);
+        let shared = module(&["crate", "a", "c"]);
+        assert!(program.modules.contains_key(&shared));
+        let b = &program.modules[&module(&["crate", "b"])];
+        assert_eq!(b.imports[0].module, shared);
+    }
+
+    #[test]
+    fn modules_are_imported_once_per_file() {
+        let files = [
+            ("src/main.eclipse", "import a;\n\nimport a;\n\nfn main() {}\n"),
+            ("src/a.eclipse", "pub fn f() {}\n"),
+        ];
+        let error = match parse("repeated", &files, &[]) {
+            Err(CompileError::BuildProblem(problem)) => problem,
+            _ => panic!("the repeated import was not reported"),
+        };
+        assert_eq!(error.line, 3);
+        assert!(matches!(error.error, BuildError::AlreadyImported(name) if name == "a"));
+    }
+}
@@ -3,7 +3,7 @@ use crate::{
     CompileError,
 };
 
-use super::{after_identifier::parse_identifer_string, tokens_expected_got, types::parse_type, ASTNode, Node};
+use super::{after_identifier::parse_identifer_string, tokens_expected_got, types::parse_type, ASTNode, Node, Type};
 
 pub fn parse_struct(tokens: &mut TokensGroup, export: bool) -> Result<ASTNode, CompileError> {
     let line = tokens.current.line;
@@ -16,7 +16,23 @@ pub fn parse_struct(tokens: &mut TokensGroup, export: bool) -> Result<ASTNode, C
     match tokens.advance() {
         Ok(info) => match info.token {
             Token::StartScope => {}
-            _ => return Err(tokens_expected_got(tokens, vec![Token::StartScope], info)),
+            Token::OpenParen => {
+                let body = match parse_tuple_fields(tokens) {
+                    Ok(body) => body,
+                    Err(error) => return Err(error),
+                };
+                return Ok(ASTNode::new(
+                    line,
+                    Node::Struct {
+                        export,
+                        name,
+                        generics: Vec::new(),
+                        tuple: true,
+                        body,
+                    },
+                ));
+            }
+            _ => return Err(tokens_expected_got(tokens, vec![Token::StartScope, Token::OpenParen], info)),
         },
         Err(error) => return Err(error),
     };
@@ -36,7 +52,7 @@ pub fn parse_struct(tokens: &mut TokensGroup, export: bool) -> Result<ASTNode, C
                     Ok(str) => str,
                     Err(error) => return Err(error),
                 };
-                let t = match parse_type(tokens) {
+                let t = match parse_field_type(tokens) {
                     Ok(t) => t,
                     Err(error) => return Err(error)
                 };
@@ -44,7 +60,7 @@ pub fn parse_struct(tokens: &mut TokensGroup, export: bool) -> Result<ASTNode, C
                 body.push((true, name, t))
             },
             Token::Identifier(name) => {
-                let t = match parse_type(tokens) {
+                let t = match parse_field_type(tokens) {
                     Ok(t) => t,
                     Err(error) => return Err(error)
                 };
@@ -67,7 +83,87 @@ pub fn parse_struct(tokens: &mut TokensGroup, export: bool) -> Result<ASTNode, C
             export,
             name,
             generics: Vec::new(),
+            tuple: false,
             body: body,
         },
     ));
 }
+
+/// Parses the type after the name of a field, as `name type` or `name: type`
+/// like parameters.
+fn parse_field_type(tokens: &mut TokensGroup) -> Result<Type, CompileError> {
+    match tokens.peek() {
+        Ok(info) => match info.token {
+            Token::Colon => {
+                tokens.advance().unwrap();
+            }
+            _ => {}
+        },
+        Err(error) => return Err(error),
+    }
+    return parse_type(tokens);
+}
+
+/// Parses the `(pub Type, Type);` fields of a tuple struct, naming them by position.
+fn parse_tuple_fields(tokens: &mut TokensGroup) -> Result<Vec<(bool, String, Type)>, CompileError> {
+    let mut body = Vec::new();
+
+    loop {
+        let info = match tokens.peek() {
+            Ok(info) => info,
+            Err(error) => return Err(error),
+        };
+        match info.token {
+            Token::CloseParen => {
+                tokens.advance().unwrap();
+                break;
+            }
+            Token::Comma => {
+                tokens.advance().unwrap();
+                continue;
+            }
+            _ => {}
+        }
+
+        let export = match info.token {
+            Token::Pub => {
+                tokens.advance().unwrap();
+                true
+            }
+            _ => false,
+        };
+        let t = match parse_type(tokens) {
+            Ok(t) => t,
+            Err(error) => return Err(error),
+        };
+        body.push((export, body.len().to_string(), t));
+    }
+
+    match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::SemiColon => {}
+            _ => return Err(tokens_expected_got(tokens, vec![Token::SemiColon], info)),
+        },
+        Err(error) => return Err(error),
+    }
+
+    return Ok(body);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::compile_source;
+
+    #[test]
+    fn names_and_types_are_separated_by_an_optional_colon() {
+        let source = "struct P {\n    x: i32,\n    pub y i64,\n}\n\nfn get(p: &P, c i64): i64 {\n    let f = |a: i64, b i64|: i64 {\n        return a * b;\n    };\n    let x: i32 = p.x;\n    return f(p.y, c);\n}\n\nfn main() {}\n";
+        let ir = match compile_source(source) {
+            Ok(compiled) => compiled.ir.to_string(),
+            Err(error) => panic!("{:?}", error),
+        };
+        assert!(ir.contains("fn get::{closure#0}(%0: i64, %1: i64): i64 {"));
+        assert!(ir.contains("fn get(%0: &P, %1: i64): i64 {"));
+        assert!(ir.contains("%5: i32 = %4.x // line 10\n"));
+        assert!(ir.contains("%9: i64 = %8.y // line 11\n"));
+    }
+}
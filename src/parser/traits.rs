@@ -0,0 +1,156 @@
+use crate::{
+    lexer::{Token, TokensGroup},
+    CompileError,
+};
+
+use super::{
+    after_identifier::parse_identifer_string, attribute::parse_attribute, expected_item,
+    export::parse_export, function::parse_function, generics::parse_generics,
+    tokens_expected_got, types::parse_type, ASTNode, Node, Type,
+};
+
+pub fn parse_trait(tokens: &mut TokensGroup, export: bool) -> Result<ASTNode, CompileError> {
+    let line = tokens.current.line;
+
+    let name = match parse_identifer_string(tokens) {
+        Ok(str) => str,
+        Err(error) => return Err(error),
+    };
+
+    let generics = match parse_generics(tokens) {
+        Ok(generics) => generics,
+        Err(error) => return Err(error),
+    };
+
+    match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::StartScope => {}
+            _ => return Err(tokens_expected_got(tokens, vec![Token::StartScope], info)),
+        },
+        Err(error) => return Err(error),
+    };
+
+    let body = match parse_methods(tokens, false) {
+        Ok(body) => body,
+        Err(error) => return Err(error),
+    };
+
+    return Ok(ASTNode::new(
+        line,
+        Node::Trait {
+            export,
+            name,
+            generics,
+            body,
+        },
+    ));
+}
+
+pub fn parse_impl(tokens: &mut TokensGroup) -> Result<ASTNode, CompileError> {
+    let line = tokens.current.line;
+
+    let generics = match parse_generics(tokens) {
+        Ok(generics) => generics,
+        Err(error) => return Err(error),
+    };
+
+    let first = match parse_type(tokens) {
+        Ok(t) => t,
+        Err(error) => return Err(error),
+    };
+
+    let (trait_name, target) = match tokens.peek() {
+        Ok(info) => match info.token {
+            Token::For => {
+                tokens.advance().unwrap();
+                let trait_name = match first {
+                    Type::Custom(name) => name,
+                    _ => {
+                        return Err(tokens_expected_got(
+                            tokens,
+                            vec![Token::Identifier(String::from("trait"))],
+                            info,
+                        ))
+                    }
+                };
+                match parse_type(tokens) {
+                    Ok(target) => (Some(trait_name), target),
+                    Err(error) => return Err(error),
+                }
+            }
+            _ => (None, first),
+        },
+        Err(error) => return Err(error),
+    };
+
+    match tokens.advance() {
+        Ok(info) => match info.token {
+            Token::StartScope => {}
+            _ => return Err(tokens_expected_got(tokens, vec![Token::StartScope], info)),
+        },
+        Err(error) => return Err(error),
+    };
+
+    let body = match parse_methods(tokens, true) {
+        Ok(body) => body,
+        Err(error) => return Err(error),
+    };
+
+    return Ok(ASTNode::new(
+        line,
+        Node::Impl {
+            generics,
+            trait_name,
+            target,
+            body,
+        },
+    ));
+}
+
+fn parse_methods(tokens: &mut TokensGroup, allow_pub: bool) -> Result<Vec<ASTNode>, CompileError> {
+    let mut body = Vec::new();
+    let mut attributes = Vec::new();
+
+    loop {
+        let info = match tokens.advance() {
+            Ok(info) => info,
+            Err(error) => return Err(error),
+        };
+        let node = match info.token {
+            Token::EndScope if attributes.len() > 0 => return Err(expected_item(tokens)),
+            Token::EndScope => break,
+            Token::Hash => {
+                match parse_attribute(tokens) {
+                    Ok(attribute) => attributes.push(attribute),
+                    Err(error) => return Err(error),
+                }
+                continue;
+            }
+            Token::Function => parse_function(tokens, false, false),
+            Token::Pub if allow_pub => parse_export(tokens),
+            Token::Unsafe => match tokens.advance() {
+                Ok(info) => match info.token {
+                    Token::Function => parse_function(tokens, false, true),
+                    _ => return Err(tokens_expected_got(tokens, vec![Token::Function], info)),
+                },
+                Err(error) => return Err(error),
+            },
+            _ => {
+                return Err(tokens_expected_got(
+                    tokens,
+                    vec![Token::Function, Token::EndScope],
+                    info,
+                ))
+            }
+        };
+        match node {
+            Ok(mut node) => {
+                node.attributes = std::mem::take(&mut attributes);
+                body.push(node)
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    return Ok(body);
+}
@@ -3,7 +3,7 @@ use crate::{
     CompileError,
 };
 
-use super::{node::Type, tokens_expected_got, BaseType};
+use super::{after_identifier::parse_identifer_string, node::Type, tokens_expected_got, BaseType};
 
 pub fn parse_type(tokens: &mut TokensGroup) -> Result<Type, CompileError> {
     let name = match tokens.advance() {
@@ -46,6 +46,109 @@ pub fn parse_type(tokens: &mut TokensGroup) -> Result<Type, CompileError> {
 
                 return Ok(Type::Tuple(types));
             }
+            Token::Reference => {
+                let mutable = match tokens.peek() {
+                    Ok(info) => match info.token {
+                        Token::Mutable => {
+                            tokens.advance().unwrap();
+                            true
+                        }
+                        _ => false,
+                    },
+                    Err(error) => return Err(error),
+                };
+
+                return match parse_type(tokens) {
+                    Ok(t) => Ok(Type::Reference(mutable, Box::new(t))),
+                    Err(error) => Err(error),
+                };
+            }
+            Token::Function => {
+                match tokens.advance() {
+                    Ok(info) => match info.token {
+                        Token::OpenParen => {}
+                        _ => return Err(tokens_expected_got(tokens, vec![Token::OpenParen], info)),
+                    },
+                    Err(error) => return Err(error),
+                }
+
+                let mut parameters = Vec::new();
+                loop {
+                    match tokens.peek() {
+                        Ok(info) => match info.token {
+                            Token::CloseParen => {
+                                tokens.advance().unwrap();
+                                break;
+                            }
+                            _ => {}
+                        },
+                        Err(error) => return Err(error),
+                    };
+
+                    parameters.push(match parse_type(tokens) {
+                        Ok(t) => t,
+                        Err(error) => return Err(error),
+                    });
+
+                    match tokens.advance() {
+                        Ok(info) => match info.token {
+                            Token::CloseParen => break,
+                            Token::Comma => continue,
+                            _ => {
+                                return Err(tokens_expected_got(
+                                    tokens,
+                                    vec![Token::CloseParen, Token::Comma],
+                                    info,
+                                ))
+                            }
+                        },
+                        Err(error) => return Err(error),
+                    }
+                }
+
+                let return_type = match tokens.peek() {
+                    Ok(info) => match info.token {
+                        Token::Colon => {
+                            tokens.advance().unwrap();
+                            match parse_type(tokens) {
+                                Ok(t) => Some(Box::new(t)),
+                                Err(error) => return Err(error),
+                            }
+                        }
+                        _ => None,
+                    },
+                    Err(error) => return Err(error),
+                };
+
+                return Ok(Type::Function(parameters, return_type));
+            }
+            Token::Asterisk => {
+                let mutable = match tokens.advance() {
+                    Ok(info) => match info.token {
+                        Token::Const => false,
+                        Token::Mutable => true,
+                        _ => {
+                            return Err(tokens_expected_got(
+                                tokens,
+                                vec![Token::Const, Token::Mutable],
+                                info,
+                            ))
+                        }
+                    },
+                    Err(error) => return Err(error),
+                };
+
+                return match parse_type(tokens) {
+                    Ok(t) => Ok(Type::Pointer(mutable, Box::new(t))),
+                    Err(error) => Err(error),
+                };
+            }
+            Token::Dyn => {
+                return match parse_identifer_string(tokens) {
+                    Ok(name) => Ok(Type::Dyn(name)),
+                    Err(error) => Err(error),
+                };
+            }
             Token::Identifier(name) => name,
             _ => {
                 return Err(tokens_expected_got(
@@ -58,7 +161,44 @@ pub fn parse_type(tokens: &mut TokensGroup) -> Result<Type, CompileError> {
         Err(error) => return Err(error),
     };
 
+    match tokens.peek() {
+        Ok(info) => match info.token {
+            Token::LessThan => {
+                tokens.advance().unwrap();
+                let mut types = Vec::new();
+
+                loop {
+                    types.push(match parse_type(tokens) {
+                        Ok(t) => t,
+                        Err(error) => return Err(error),
+                    });
+
+                    match tokens.advance() {
+                        Ok(info) => match info.token {
+                            Token::GreaterThan => break,
+                            Token::Comma => continue,
+                            _ => {
+                                return Err(tokens_expected_got(
+                                    tokens,
+                                    vec![Token::GreaterThan, Token::Comma],
+                                    info,
+                                ))
+                            }
+                        },
+                        Err(error) => return Err(error),
+                    }
+                }
+
+                return Ok(Type::Generic(name, types));
+            }
+            _ => {}
+        },
+        Err(error) => return Err(error),
+    }
+
     return Ok(match name.as_str() {
+        "i128" => Type::Base(BaseType::Int128),
+        "u128" => Type::Base(BaseType::UInt128),
         "i64" => Type::Base(BaseType::Int64),
         "u64" => Type::Base(BaseType::UInt64),
         "i32" => Type::Base(BaseType::Int32),
@@ -70,6 +210,10 @@ pub fn parse_type(tokens: &mut TokensGroup) -> Result<Type, CompileError> {
         "f64" => Type::Base(BaseType::Float64),
         "f32" => Type::Base(BaseType::Float32),
         "bool" => Type::Base(BaseType::Boolean),
+        "str" => Type::Base(BaseType::Str),
+        "char" => Type::Base(BaseType::Char),
+        "isize" => Type::Base(BaseType::IntSize),
+        "usize" => Type::Base(BaseType::UIntSize),
         a => Type::Custom(a.to_string()),
     });
 }
@@ -14,7 +14,10 @@ use super::{
 pub fn parse_define_variable(tokens: &mut TokensGroup) -> Result<ASTNode, CompileError> {
     let mutable = match tokens.peek() {
         Ok(info) => match info.token {
-            Token::Mutable => true,
+            Token::Mutable => {
+                tokens.advance().unwrap();
+                true
+            }
             _ => false,
         },
         Err(error) => return Err(error),
@@ -36,7 +39,7 @@ pub fn parse_define_variable(tokens: &mut TokensGroup) -> Result<ASTNode, Compil
 
     let var_type = match tokens.peek() {
         Ok(info) => match info.token {
-            Token::Equals => None,
+            Token::Equals | Token::SemiColon => None,
             Token::Colon => {
                 tokens.advance().unwrap();
                 match parse_type(tokens) {
@@ -47,7 +50,7 @@ pub fn parse_define_variable(tokens: &mut TokensGroup) -> Result<ASTNode, Compil
             _ => {
                 return Err(tokens_expected_got(
                     tokens,
-                    vec![Token::Colon, Token::Equals],
+                    vec![Token::Colon, Token::Equals, Token::SemiColon],
                     info,
                 ))
             }
@@ -55,21 +58,25 @@ pub fn parse_define_variable(tokens: &mut TokensGroup) -> Result<ASTNode, Compil
         Err(error) => return Err(error),
     };
 
-    let expression: Option<Expression> = match tokens.advance() {
+    // `let x: i32;` declares a local that is given its value later.
+    let expression: Option<Expression> = match tokens.peek() {
         Ok(info) => match info.token {
-            Token::Equals => match parse_expression(tokens) {
-                Ok(expression) => match expression {
-                    Some(expression) => Some(expression),
-                    None => {
-                        return Err(CompileError::BuildProblem(BuildProblem::new(
-                            BuildError::ExpressionExpected,
-                            tokens.relative_path.clone(),
-                            tokens.current.line,
-                        )))
-                    }
-                },
-                Err(error) => return Err(error),
-            },
+            Token::Equals => {
+                tokens.advance().unwrap();
+                match parse_expression(tokens) {
+                    Ok(expression) => match expression {
+                        Some(expression) => Some(expression),
+                        None => {
+                            return Err(CompileError::BuildProblem(BuildProblem::new(
+                                BuildError::ExpressionExpected,
+                                tokens.relative_path.clone(),
+                                tokens.current.line,
+                            )))
+                        }
+                    },
+                    Err(error) => return Err(error),
+                }
+            }
             _ => None,
         },
         Err(error) => return Err(error),
@@ -90,6 +97,7 @@ pub fn parse_define_variable(tokens: &mut TokensGroup) -> Result<ASTNode, Compil
             name,
             var_type,
             expression,
+            definition: None,
         },
     ));
 }